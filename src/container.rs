@@ -44,6 +44,55 @@ impl Container {
         })
     }
 
+    // BTX0 container (.nsbtx) with a single TEX0 subfile
+    pub fn new_btx(tex: Tex) -> Container {
+        Self::new_single(*b"BTX0", Type::TEX, Files { tex: vec![tex], ..Files::default() })
+    }
+
+    // BCA0 container (.nsbca) with a single JNT0 subfile
+    pub fn new_bca(jnt: Jnt) -> Container {
+        Self::new_single(*b"BCA0", Type::JNT, Files { jnt: vec![jnt], ..Files::default() })
+    }
+
+    // BTA0 container (.nsbta) with a single SRT0 subfile
+    pub fn new_bta(srt: Srt) -> Container {
+        Self::new_single(*b"BTA0", Type::SRT, Files { srt: vec![srt], ..Files::default() })
+    }
+
+    // BTP0 container (.nsbtp) with a single PAT0 subfile
+    pub fn new_btp(pat: Pat) -> Container {
+        Self::new_single(*b"BTP0", Type::PAT, Files { pat: vec![pat], ..Files::default() })
+    }
+
+    // Container with the only subfile in files, which is of the given kind
+    fn new_single(stamp: [u8; 4], kind: Type, mut files: Files) -> Container {
+        files.sorted_indices = vec![(kind, 0)];
+
+        let mut container = Container {
+            header: Header {
+                stamp,
+                bom: 0xFEFF,
+                version: 1,
+                filesize: 0,
+                header_size: Header::SIZE as u16,
+                num_subfiles: 1
+            },
+            subfile_offsets: vec![0],
+            files
+        };
+
+        container.rebase();
+        container
+    }
+
+    // Serializes a TEX0 subfile as a standalone .nsbtx file
+    pub fn tex_to_btx_bytes(&self, tex_index: usize) -> Result<Vec<u8>, AppError> {
+        let tex = self.files.tex.get(tex_index)
+            .ok_or_else(|| AppError::new(&format!("TEX0 subfile {} not found", tex_index)))?;
+
+        Container::new_btx(tex.clone()).to_bytes()
+    }
+
     pub fn to_bytes(&self) -> Result<Vec<u8>, AppError> {
         let mut bytes = vec![0u8; self.header.filesize as usize];
 
@@ -64,9 +113,15 @@ impl Container {
                 Type::TEX => {
                     self.files.tex[local_index].write_bytes(&mut bytes[file_offset..])?;
                 },
-                Type::JNT => todo!(),
-                Type::PAT => todo!(),
-                Type::SRT => todo!(),
+                Type::JNT => {
+                    self.files.jnt[local_index].write_bytes(&mut bytes[file_offset..])?;
+                },
+                Type::PAT => {
+                    self.files.pat[local_index].write_bytes(&mut bytes[file_offset..])?;
+                },
+                Type::SRT => {
+                    self.files.srt[local_index].write_bytes(&mut bytes[file_offset..])?;
+                },
             }
         }
 
@@ -139,19 +194,19 @@ impl Container {
                     tex.push(tex_file);
                 },
                 Type::JNT => {
-                    let jnt_file = Jnt::from_bytes(&bytes[offset..])?;
+                    let jnt_file = Jnt::from_bytes(&bytes[offset..], debug_info)?;
 
                     sorted_indices.push((Type::JNT, jnt.len()));
                     jnt.push(jnt_file);
                 },
                 Type::PAT => {
-                    let pat_file = Pat::from_bytes(&bytes[offset..])?;
+                    let pat_file = Pat::from_bytes(&bytes[offset..], debug_info)?;
 
                     sorted_indices.push((Type::PAT, pat.len()));
                     pat.push(pat_file);
                 },
                 Type::SRT => {
-                    let srt_file = Srt::from_bytes(&bytes[offset..])?;
+                    let srt_file = Srt::from_bytes(&bytes[offset..], debug_info)?;
 
                     sorted_indices.push((Type::SRT, srt.len()));
                     srt.push(srt_file);
@@ -183,12 +238,21 @@ impl Container {
                     self.files.mdl[local_index].size() as u32
                 },
                 Type::TEX => {
-                    // self.files.tex[local_index].rebase();
+                    self.files.tex[local_index].rebase();
                     self.files.tex[local_index].size() as u32
                 },
-                Type::JNT => todo!(),
-                Type::PAT => todo!(),
-                Type::SRT => todo!(),
+                Type::JNT => {
+                    self.files.jnt[local_index].rebase();
+                    self.files.jnt[local_index].size() as u32
+                },
+                Type::PAT => {
+                    self.files.pat[local_index].rebase();
+                    self.files.pat[local_index].size() as u32
+                },
+                Type::SRT => {
+                    self.files.srt[local_index].rebase();
+                    self.files.srt[local_index].size() as u32
+                },
             };
 
             prev_offset = offset;
@@ -205,6 +269,14 @@ impl Container {
         self.files.mdl.get_mut(index)
     }
 
+    pub fn mdl_iter(&self) -> impl Iterator<Item = &Mdl> {
+        self.files.mdl.iter()
+    }
+
+    pub fn mdl_iter_mut(&mut self) -> impl Iterator<Item = &mut Mdl> {
+        self.files.mdl.iter_mut()
+    }
+
     pub fn get_tex(&self, index: usize) -> Option<&Tex> {
         self.files.tex.get(index)
     }
@@ -212,6 +284,85 @@ impl Container {
     pub fn get_tex_mut(&mut self, index: usize) -> Option<&mut Tex> {
         self.files.tex.get_mut(index)
     }
+
+    pub fn tex_iter(&self) -> impl Iterator<Item = &Tex> {
+        self.files.tex.iter()
+    }
+
+    pub fn get_jnt(&self, index: usize) -> Option<&Jnt> {
+        self.files.jnt.get(index)
+    }
+
+    pub fn get_jnt_mut(&mut self, index: usize) -> Option<&mut Jnt> {
+        self.files.jnt.get_mut(index)
+    }
+
+    pub fn jnt_iter(&self) -> impl Iterator<Item = &Jnt> {
+        self.files.jnt.iter()
+    }
+
+    pub fn get_srt(&self, index: usize) -> Option<&Srt> {
+        self.files.srt.get(index)
+    }
+
+    pub fn get_srt_mut(&mut self, index: usize) -> Option<&mut Srt> {
+        self.files.srt.get_mut(index)
+    }
+
+    pub fn srt_iter(&self) -> impl Iterator<Item = &Srt> {
+        self.files.srt.iter()
+    }
+
+    pub fn get_pat(&self, index: usize) -> Option<&Pat> {
+        self.files.pat.get(index)
+    }
+
+    pub fn get_pat_mut(&mut self, index: usize) -> Option<&mut Pat> {
+        self.files.pat.get_mut(index)
+    }
+
+    pub fn pat_iter(&self) -> impl Iterator<Item = &Pat> {
+        self.files.pat.iter()
+    }
+
+    // Renames a texture of a TEX0 subfile and the texture pairings of every model, so materials keep their texture.
+    // The container must be rebased afterwards
+    pub fn rename_texture(&mut self, tex_index: usize, name: &str, new_name: &str) -> Result<(), AppError> {
+        let tex = self.files.tex.get_mut(tex_index)
+            .ok_or_else(|| AppError::new(&format!("TEX0 subfile {} not found", tex_index)))?;
+        let texture_index = tex.texture_list().index_of_name(name)
+            .ok_or_else(|| AppError::new(&format!("Texture \"{}\" not found in TEX0 subfile {}", name, tex_index)))?;
+
+        // Fails if the new name is invalid or taken, before anything is changed
+        tex.texture_list_mut().rename(texture_index, new_name)?;
+
+        for mdl in self.files.mdl.iter_mut() {
+            for model in mdl.models_iter_mut() {
+                model.get_material_list_mut().rename_texture_pairing(name, new_name)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Renames a palette of a TEX0 subfile and the palette pairings of every model, so materials keep their palette.
+    // The container must be rebased afterwards
+    pub fn rename_palette(&mut self, tex_index: usize, name: &str, new_name: &str) -> Result<(), AppError> {
+        let tex = self.files.tex.get_mut(tex_index)
+            .ok_or_else(|| AppError::new(&format!("TEX0 subfile {} not found", tex_index)))?;
+        let palette_index = tex.palette_list().index_of_name(name)
+            .ok_or_else(|| AppError::new(&format!("Palette \"{}\" not found in TEX0 subfile {}", name, tex_index)))?;
+
+        tex.palette_list_mut().rename(palette_index, new_name)?;
+
+        for mdl in self.files.mdl.iter_mut() {
+            for model in mdl.models_iter_mut() {
+                model.get_material_list_mut().rename_palette_pairing(name, new_name)?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -268,7 +419,7 @@ impl Header {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 struct Files {
     mdl: Vec<Mdl>,
     tex: Vec<Tex>,
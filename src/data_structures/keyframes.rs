@@ -0,0 +1,311 @@
+use crate::{error::AppError, util::number::{alignment::get_4_byte_alignment, fixed_point::fixed_1_19_12::Fixed1_19_12}};
+
+// Keyframe storage shared by the animation subfiles (JNT0, SRT0, PAT0)
+
+// A value for the whole animation, or one per frame (or every 2 or 4 frames)
+#[derive(Debug, Clone)]
+pub enum Channel<T> {
+    Constant(T),
+    Keyed(Keyframes<T>)
+}
+
+#[derive(Debug, Clone)]
+pub struct Keyframes<T> {
+    pub start_frame: u16,
+    pub last_interp_frame: u16, // Last frame sampled with the step. The frames after it have a value each
+    pub step: KeyframeStep,
+    pub compact: bool, // Stored as fx16 instead of fx32
+    pub values: Vec<T>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyframeStep {
+    Every1,
+    Every2,
+    Every4
+}
+
+impl KeyframeStep {
+    pub fn from_shift(shift: u32) -> Result<KeyframeStep, AppError> {
+        match shift {
+            0 => Ok(KeyframeStep::Every1),
+            1 => Ok(KeyframeStep::Every2),
+            2 => Ok(KeyframeStep::Every4),
+            _ => Err(AppError::new(&format!("Invalid keyframe step {}", shift)))
+        }
+    }
+
+    pub fn shift(&self) -> u32 {
+        match self {
+            KeyframeStep::Every1 => 0,
+            KeyframeStep::Every2 => 1,
+            KeyframeStep::Every4 => 2
+        }
+    }
+
+    pub fn frames(&self) -> u16 {
+        1 << self.shift()
+    }
+}
+
+impl<T> Keyframes<T> {
+    // Frames from start_frame to last_interp_frame every step, then every frame until the end
+    pub fn count(num_frames: u16, start_frame: u16, last_interp_frame: u16, step: KeyframeStep) -> usize {
+        let frames = num_frames.saturating_sub(start_frame) as usize;
+        if step == KeyframeStep::Every1 || frames == 0 {
+            return frames;
+        }
+
+        let last_interp = (last_interp_frame.saturating_sub(start_frame) as usize).min(frames - 1);
+        (last_interp >> step.shift()) + 1 + (frames - 1 - last_interp)
+    }
+}
+
+impl<T> Channel<T> {
+    // Value at a frame, interpolating between keyframes with lerp. Past the last keyframe the value is held or, with
+    // end_to_start, blended towards the first one
+    pub fn sample<V: Copy>(
+        &self,
+        frame: f32,
+        end_to_start: bool,
+        value: impl Fn(&T) -> Result<V, AppError>,
+        lerp: impl Fn(V, V, f32) -> V
+    ) -> Result<V, AppError> {
+        let keyframes = match self {
+            Channel::Constant(constant) => return value(constant),
+            Channel::Keyed(keyframes) => keyframes
+        };
+
+        if keyframes.values.is_empty() {
+            return Err(AppError::new("Keyed channel has no values"));
+        }
+
+        // Continuous index into the values: every step frames up to the last interpolated frame, then every frame
+        let local = (frame - keyframes.start_frame as f32).max(0.0);
+        let step = keyframes.step.frames() as f32;
+        let stepped_end = ((keyframes.last_interp_frame.saturating_sub(keyframes.start_frame)) >> keyframes.step.shift()) as f32;
+        let position = if local <= stepped_end * step {
+            local / step
+        } else {
+            stepped_end + (local - stepped_end * step)
+        };
+
+        let last = keyframes.values.len() - 1;
+        let index = (position.floor() as usize).min(last);
+        let t = if index == last && position > last as f32 { (position - last as f32).min(1.0) } else { position.fract() };
+
+        let current = value(&keyframes.values[index])?;
+        if t == 0.0 {
+            return Ok(current);
+        }
+
+        let next = if index < last {
+            value(&keyframes.values[index + 1])?
+        } else if end_to_start {
+            value(&keyframes.values[0])?
+        } else {
+            return Ok(current);
+        };
+
+        Ok(lerp(current, next, t))
+    }
+
+    // Values at each of the frames (see sample)
+    pub fn sample_frames<V: Copy>(
+        &self,
+        frames: &[f32],
+        end_to_start: bool,
+        value: impl Fn(&T) -> Result<V, AppError>,
+        lerp: impl Fn(V, V, f32) -> V
+    ) -> Result<Vec<V>, AppError> {
+        frames.iter()
+            .map(|&frame| self.sample(frame, end_to_start, &value, &lerp))
+            .collect()
+    }
+
+    // Constant if every frame has the same value, else keyed every frame (compact if every value fits in fx16)
+    pub fn from_values(values: Vec<T>, fits_compact: impl Fn(&T) -> bool) -> Result<Channel<T>, AppError>
+    where T: PartialEq
+    {
+        let Some(first) = values.first() else {
+            return Err(AppError::new("Cannot build a channel from 0 values, it needs one per frame"));
+        };
+
+        if values.iter().all(|value| value == first) {
+            return Ok(Channel::Constant(values.into_iter().next().unwrap()));
+        }
+
+        let compact = values.iter().all(fits_compact);
+        Ok(Channel::Keyed(Keyframes {
+            start_frame: 0,
+            last_interp_frame: values.len() as u16 - 1,
+            step: KeyframeStep::Every1,
+            compact,
+            values
+        }))
+    }
+}
+
+impl<T: ChannelValue> Channel<T> {
+    // Size of the keyframe values, 4 byte aligned. None for constants
+    pub fn keyframe_data_size(&self) -> Option<usize> {
+        match self {
+            Channel::Constant(_) => None,
+            Channel::Keyed(keyframes) => Some(get_4_byte_alignment(keyframes.values.len() * T::keyframe_size(keyframes.compact)))
+        }
+    }
+}
+
+// How the values of a channel are stored, as a constant next to the channel info or as keyframes
+pub trait ChannelValue: Sized {
+    const CONSTANT_SIZE: usize;
+
+    fn keyframe_size(compact: bool) -> usize;
+    fn read(bytes: &[u8], offset: usize, compact: bool) -> Result<Self, AppError>;
+    fn write(&self, buffer: &mut [u8], offset: usize, compact: bool);
+
+    fn read_constant(bytes: &[u8], offset: usize) -> Result<Self, AppError> {
+        Self::read(bytes, offset, false)
+    }
+
+    fn write_constant(&self, buffer: &mut [u8], offset: usize) {
+        self.write(buffer, offset, false);
+    }
+}
+
+impl ChannelValue for Fixed1_19_12 {
+    const CONSTANT_SIZE: usize = 4;
+
+    fn keyframe_size(compact: bool) -> usize {
+        if compact { 2 } else { 4 }
+    }
+
+    fn read(bytes: &[u8], offset: usize, compact: bool) -> Result<Self, AppError> {
+        if compact {
+            Ok(Fixed1_19_12::from_i32(read_u16(bytes, offset)? as i16 as i32))
+        }
+        else {
+            Ok(Fixed1_19_12::from_i32(read_u32(bytes, offset)? as i32))
+        }
+    }
+
+    fn write(&self, buffer: &mut [u8], offset: usize, compact: bool) {
+        if compact {
+            buffer[offset..offset + 2].copy_from_slice(&(self.to_i32() as i16).to_le_bytes());
+        }
+        else {
+            buffer[offset..offset + 4].copy_from_slice(&self.to_le_bytes());
+        }
+    }
+}
+
+// Keeps the keys sorted by frame, replacing the key of the same frame
+pub fn insert_key<T>(keys: &mut Vec<(u16, T)>, frame: u16, value: T) {
+    match keys.binary_search_by_key(&frame, |key| key.0) {
+        Ok(index) => keys[index].1 = value,
+        Err(index) => keys.insert(index, (frame, value))
+    }
+}
+
+// Frames of an animation to sample so it lasts new_num_frames instead of num_frames
+pub fn resampled_frames(num_frames: u16, new_num_frames: u16) -> Result<Vec<f32>, AppError> {
+    if new_num_frames == 0 {
+        return Err(AppError::new("Cannot resample an animation to 0 frames, it needs at least one"));
+    }
+
+    let ratio = num_frames as f32 / new_num_frames as f32;
+    Ok((0..new_num_frames).map(|frame| frame as f32 * ratio).collect())
+}
+
+// Frames of an animation to sample to convert it from fps to new_fps (e.g. 60 to 30 fps halves the frames)
+pub fn frame_rate_frames(num_frames: u16, fps: f32, new_fps: f32) -> Result<Vec<f32>, AppError> {
+    resampled_frames(num_frames, frame_count_at_rate(num_frames, fps, new_fps)?)
+}
+
+// Frames start_frame to end_frame (exclusive) of an animation, and whether they are the whole animation
+// (a slice only interpolates end to start if they are)
+pub fn sliced_frames(num_frames: u16, start_frame: u16, end_frame: u16) -> Result<(Vec<f32>, bool), AppError> {
+    if start_frame >= end_frame || end_frame > num_frames {
+        return Err(AppError::new(&format!("Invalid frame range {}..{}. Animation has {} frames", start_frame, end_frame, num_frames)));
+    }
+
+    let frames = (start_frame..end_frame).map(|frame| frame as f32).collect();
+    Ok((frames, end_frame - start_frame == num_frames))
+}
+
+// Number of frames an animation of num_frames at fps takes at new_fps
+pub fn frame_count_at_rate(num_frames: u16, fps: f32, new_fps: f32) -> Result<u16, AppError> {
+    if !(fps > 0.0 && new_fps > 0.0) {
+        return Err(AppError::new(&format!("Invalid frame rates {} and {}", fps, new_fps)));
+    }
+
+    let frames = (num_frames as f32 * new_fps / fps).round().max(1.0);
+    if frames > u16::MAX as f32 {
+        return Err(AppError::new(&format!("Animation would have {} frames, up to {} are supported", frames, u16::MAX)));
+    }
+
+    Ok(frames as u16)
+}
+
+// One value per frame from keys sorted by frame, holding the first and last ones. None without keys
+pub fn resample_keys<T: Copy>(keys: &[(u16, T)], num_frames: u16, lerp: impl Fn(&T, &T, f32) -> T) -> Option<Vec<T>> {
+    let (first, last) = (keys.first()?, keys.last()?);
+
+    Some((0..num_frames).map(|frame| {
+        if frame <= first.0 {
+            return first.1;
+        }
+        if frame >= last.0 {
+            return last.1;
+        }
+
+        let next = keys.partition_point(|key| key.0 <= frame);
+        let (from, to) = (&keys[next - 1], &keys[next]);
+        lerp(&from.1, &to.1, (frame - from.0) as f32 / (to.0 - from.0) as f32)
+    }).collect())
+}
+
+pub(crate) fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, AppError> {
+    bytes.get(offset..offset + 2)
+        .map(|value| u16::from_le_bytes([value[0], value[1]]))
+        .ok_or_else(|| AppError::new(&format!("Animation offset 0x{:X} is out of bounds (0x{:X} bytes)", offset, bytes.len())))
+}
+
+pub(crate) fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, AppError> {
+    bytes.get(offset..offset + 4)
+        .map(|value| u32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+        .ok_or_else(|| AppError::new(&format!("Animation offset 0x{:X} is out of bounds (0x{:X} bytes)", offset, bytes.len())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resampling_to_zero_frames_is_an_error() {
+        assert!(resampled_frames(10, 0).is_err());
+        assert_eq!(resampled_frames(10, 5).unwrap(), vec![0.0, 2.0, 4.0, 6.0, 8.0]);
+    }
+
+    #[test]
+    fn frame_rate_conversion_scales_the_frame_count() {
+        assert_eq!(frame_rate_frames(60, 60.0, 30.0).unwrap().len(), 30);
+        assert_eq!(frame_rate_frames(1, 60.0, 30.0).unwrap(), vec![0.0]);
+        assert!(frame_rate_frames(60, 0.0, 30.0).is_err());
+    }
+
+    #[test]
+    fn slices_know_if_they_are_the_whole_animation() {
+        assert_eq!(sliced_frames(4, 1, 3).unwrap(), (vec![1.0, 2.0], false));
+        assert!(sliced_frames(4, 0, 4).unwrap().1);
+        assert!(sliced_frames(4, 3, 3).is_err());
+        assert!(sliced_frames(4, 2, 5).is_err());
+    }
+
+    #[test]
+    fn channels_need_at_least_one_value() {
+        assert!(Channel::<u32>::from_values(Vec::new(), |_| true).is_err());
+        assert!(matches!(Channel::from_values(vec![3u32, 3], |_| true), Ok(Channel::Constant(3))));
+        assert!(matches!(Channel::from_values(vec![3u32, 4], |_| true), Ok(Channel::Keyed(_))));
+    }
+}
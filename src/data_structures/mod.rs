@@ -1,2 +1,3 @@
 pub mod name;
 pub mod name_list;
+pub mod keyframes;
@@ -11,7 +11,7 @@ impl Name {
     pub const SIZE: usize = 16;
 
     pub fn from_bytes(bytes: &[u8]) -> Result<Name, AppError> {
-        if bytes.len() > 16 {
+        if bytes.len() < 16 {
             return Err(AppError::new("Name needs at least 16 bytes"))
         }
 
@@ -25,7 +25,15 @@ impl Name {
 
     pub fn from_string(name: &str) -> Result<Name, AppError> {
         let bytes = name.as_bytes();
-        Self::from_bytes(bytes)
+        if bytes.len() > Self::SIZE {
+            return Err(AppError::new(&format!("Name '{}' is longer than {} bytes", name, Self::SIZE)))
+        }
+
+        // Names are null padded up to 16 bytes
+        let mut padded = [0; 16];
+        padded[..bytes.len()].copy_from_slice(bytes);
+
+        Self::from_bytes(&padded)
     }
 
     pub fn to_string(&self) -> Result<String, AppError> {
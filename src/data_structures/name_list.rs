@@ -17,6 +17,23 @@ pub struct NameList<T> {
 impl<T> NameList<T>
 where T: BinarySerializable
 {
+    pub fn new(element_size: u16) -> NameList<T> {
+        let mut name_list = NameList {
+            dummy: 0,
+            count: 0,
+            size: 0,
+            unknown: Unknown::new(),
+            element_size,
+            data_section_size: 0,
+            data: Vec::new(),
+            names: Vec::new()
+        };
+
+        name_list.rebase();
+
+        name_list
+    }
+
     pub fn from_bytes(bytes: &[u8]) -> Result<NameList<T>, AppError> {
         if bytes.len() < 4 {
             return Err(AppError::new("NameList needs at least 4 bytes"));
@@ -107,7 +124,59 @@ where T: BinarySerializable
         self.size as usize
     }
 
+    pub fn push(&mut self, name: Name, element: T) -> Result<(), AppError> {
+        // The count is a u8, so a list holds up to 255 elements
+        if self.data.len() + 1 > u8::MAX as usize {
+            return Err(AppError::new(&format!("NameList cannot hold more than {} elements", u8::MAX)));
+        }
+
+        self.data.push(element);
+        self.names.push(name);
+        self.unknown.unknown.push(0);
+
+        self.rebase();
+
+        Ok(())
+    }
+
+    pub fn insert(&mut self, index: usize, name: Name, element: T) -> Result<(), AppError> {
+        if index > self.data.len() {
+            return Err(AppError::new(&format!("NameList insert index out of bounds. Expected 0-{}, got {}", self.data.len(), index)));
+        }
+
+        // The count is a u8, so a list holds up to 255 elements
+        if self.data.len() + 1 > u8::MAX as usize {
+            return Err(AppError::new(&format!("NameList cannot hold more than {} elements", u8::MAX)));
+        }
+
+        self.data.insert(index, element);
+        self.names.insert(index, name);
+        self.unknown.unknown.insert(index, 0);
+
+        self.rebase();
+
+        Ok(())
+    }
+
+    pub fn remove(&mut self, index: usize) -> Result<(Name, T), AppError> {
+        if index >= self.data.len() {
+            return Err(AppError::new(&format!("NameList remove index out of bounds. Expected less than {}, got {}", self.data.len(), index)));
+        }
+
+        let element = self.data.remove(index);
+        let name = self.names.remove(index);
+        self.unknown.unknown.remove(index);
+
+        self.rebase();
+
+        Ok((name, element))
+    }
+
     pub fn rebase(&mut self) {
+        self.count = self.data.len() as u8;
+        self.unknown.header.unknown_size = (4 + self.unknown.size()) as u16; // Offset from the start of the list to the data section
+        self.data_section_size = (4 + self.data.len() * self.element_size as usize) as u16;
+
         self.size = (
             4 + // dummy + count + size
             self.unknown.size() +
@@ -164,6 +233,17 @@ struct Unknown {
 }
 
 impl Unknown {
+    fn new() -> Unknown {
+        Unknown {
+            header: UnknownHeader {
+                subheader_size: UnknownHeader::SIZE as u16,
+                unknown_size: 0, // Set on rebase
+                unknown: UnknownHeader::DEFAULT_UNKNOWN
+            },
+            unknown: Vec::new()
+        }
+    }
+
     fn from_bytes(bytes: &[u8], count: u8) -> Result<Unknown, AppError> {
         let header = UnknownHeader::from_bytes(bytes)?;
         let mut unknown = Vec::with_capacity(count as usize);
@@ -206,6 +286,7 @@ struct UnknownHeader {
 
 impl UnknownHeader {
     const SIZE: usize = 8;
+    const DEFAULT_UNKNOWN: u32 = 0x17F; // Value found in every list of retail files
 
     fn from_bytes(bytes: &[u8]) -> Result<UnknownHeader, AppError> {
         Self::check_size(bytes.len())?;
@@ -239,3 +320,21 @@ impl UnknownHeader {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holds_up_to_255_elements() {
+        let mut list: NameList<u32> = NameList::new(4);
+        for i in 0..u8::MAX as u32 {
+            list.push(Name::from_string(&format!("n{}", i)).unwrap(), i).unwrap();
+        }
+
+        assert_eq!(list.len(), 255);
+        assert!(list.push(Name::from_string("full").unwrap(), 0).is_err());
+        assert!(list.insert(0, Name::from_string("full").unwrap(), 0).is_err());
+        assert_eq!(list.len(), 255);
+    }
+}
@@ -0,0 +1,284 @@
+use crate::{error::AppError, subfiles::mdl::model::mesh_list::gpu_command_list::{GpuCommand, GpuCommandList, MtxModeParams}, util::{math::{fixed_matrix::FixedMatrix, matrix::Matrix}, number::fixed_point::fixed_1_19_12::Fixed1_19_12}};
+
+use super::mesh_geometry_decoder::{DecodedPrimitive, MeshGeometryDecoder};
+
+// State machine to execute the GPU commands of a mesh, including its matrix commands.
+// Outputs the drawn primitives with positions (and normals) in model space
+pub struct GpuCommandExecutor<'a> {
+    render_cmds: &'a GpuCommandList,
+
+    // Internal state for the executor
+    matrix_mode: u8,
+    matrix_stack: Vec<Matrix>, // Visit https://problemkaputt.de/gbatek.htm#ds3dvideo (DS 3D Matrix Stack) for more info
+    vector_stack: Vec<Matrix>,
+    stack_pointer: usize, // Used by MtxPush and MtxPop
+    current_matrix: Matrix,
+    current_vector: Matrix,
+    fixed_point: bool, // See ModelRenderCmdExecutor::set_fixed_point
+
+    // Raw 20.12 position matrices, only kept up to date in fixed point mode. The vector matrix stays in f32,
+    // as normals are renormalized anyway
+    fixed_matrix_stack: Vec<FixedMatrix>,
+    fixed_current_matrix: FixedMatrix,
+
+    decoder: MeshGeometryDecoder<'a>
+}
+
+impl GpuCommandExecutor<'_> {
+    const STACK_SIZE: usize = 31; // 0..30 (31 entries)
+
+    // The matrix stack is usually the one left by ModelRenderCmdExecutor before the mesh is drawn
+    pub fn new<'a>(render_cmds: &'a GpuCommandList, matrix_stack: &[Matrix]) -> Result<GpuCommandExecutor<'a>, AppError> {
+        if matrix_stack.len() > Self::STACK_SIZE {
+            return Err(AppError::new(&format!("Matrix stack too big. Expected at most {} entries, got {}", Self::STACK_SIZE, matrix_stack.len())));
+        }
+
+        if matrix_stack.iter().any(|matrix| matrix.width() != 4 || matrix.height() != 4) {
+            return Err(AppError::new("Matrix stack entries must be 4x4."));
+        }
+
+        let mut stack = matrix_stack.to_vec();
+        stack.resize(Self::STACK_SIZE, Matrix::identity(4));
+
+        Ok(GpuCommandExecutor {
+            render_cmds,
+            matrix_mode: MtxModeParams::POSITION_VECTOR,
+            vector_stack: stack.clone(),
+            matrix_stack: stack,
+            stack_pointer: 0,
+            current_matrix: Matrix::identity(4),
+            current_vector: Matrix::identity(4),
+            fixed_point: false,
+            fixed_matrix_stack: vec![FixedMatrix::identity(); Self::STACK_SIZE],
+            fixed_current_matrix: FixedMatrix::identity(),
+            decoder: MeshGeometryDecoder::new(render_cmds)
+        })
+    }
+
+    // Matrix the mesh starts with, usually the current matrix left by ModelRenderCmdExecutor
+    pub fn set_current_matrix(&mut self, matrix: Matrix) -> Result<(), AppError> {
+        if matrix.width() != 4 || matrix.height() != 4 {
+            return Err(AppError::new("Current matrix must be 4x4."));
+        }
+
+        self.current_vector = matrix.clone();
+        self.current_matrix = matrix;
+
+        Ok(())
+    }
+
+    // Positions are transformed in 20.12 fixed point too, so they match the ones the DS computes.
+    // The current matrix and stack are truncated, use set_fixed_matrices to start from exact ones
+    pub fn set_fixed_point(&mut self, fixed_point: bool) -> Result<(), AppError> {
+        if fixed_point && !self.fixed_point {
+            self.fixed_matrix_stack = self.matrix_stack.iter()
+                .map(FixedMatrix::from_matrix)
+                .collect::<Result<Vec<FixedMatrix>, AppError>>()?;
+            for (matrix, fixed_matrix) in self.matrix_stack.iter_mut().zip(self.fixed_matrix_stack.iter()) {
+                *matrix = fixed_matrix.to_matrix();
+            }
+
+            self.set_fixed_current_matrix(FixedMatrix::from_matrix(&self.current_matrix)?);
+        }
+
+        self.fixed_point = fixed_point;
+
+        Ok(())
+    }
+
+    // Enables fixed point mode with the raw matrices left by ModelRenderCmdExecutor (see DrawMeshContext::fixed_matrices)
+    pub fn set_fixed_matrices(&mut self, current_matrix: FixedMatrix, matrix_stack: &[FixedMatrix]) -> Result<(), AppError> {
+        if matrix_stack.len() > Self::STACK_SIZE {
+            return Err(AppError::new(&format!("Matrix stack too big. Expected at most {} entries, got {}", Self::STACK_SIZE, matrix_stack.len())));
+        }
+
+        self.fixed_matrix_stack = matrix_stack.to_vec();
+        self.fixed_matrix_stack.resize(Self::STACK_SIZE, FixedMatrix::identity());
+        for (matrix, fixed_matrix) in self.matrix_stack.iter_mut().zip(self.fixed_matrix_stack.iter()) {
+            *matrix = fixed_matrix.to_matrix();
+        }
+
+        self.set_fixed_current_matrix(current_matrix);
+        self.current_vector = self.current_matrix.clone();
+        self.fixed_point = true;
+
+        Ok(())
+    }
+
+    pub fn execute(&mut self) -> Result<(), AppError> {
+        for (index, cmd) in self.render_cmds.iter().enumerate() {
+            self.execute_command(&cmd)
+                .map_err(|err| AppError::new(&format!("GPU command {}: {}", index, err.message())))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn primitives(&self) -> &Vec<DecodedPrimitive> {
+        self.decoder.primitives()
+    }
+
+    pub fn into_primitives(self) -> Vec<DecodedPrimitive> {
+        self.decoder.into_primitives()
+    }
+
+    pub fn matrix_stack(&self) -> &Vec<Matrix> {
+        &self.matrix_stack
+    }
+
+    pub fn current_matrix(&self) -> &Matrix {
+        &self.current_matrix
+    }
+
+    fn execute_command(&mut self, cmd: &GpuCommand) -> Result<(), AppError> {
+        // Projection and texture matrices do not affect model space vertices
+        let affects_model = self.matrix_mode == MtxModeParams::POSITION || self.matrix_mode == MtxModeParams::POSITION_VECTOR;
+        let affects_vector = self.matrix_mode == MtxModeParams::POSITION_VECTOR;
+
+        match cmd {
+            GpuCommand::MtxMode(mtx_mode_params) => {
+                self.matrix_mode = mtx_mode_params.mode;
+            },
+            GpuCommand::MtxPush if affects_model => {
+                if self.stack_pointer >= Self::STACK_SIZE {
+                    return Err(AppError::new("MtxPush::Matrix stack overflow."));
+                }
+
+                self.store(self.stack_pointer);
+                self.stack_pointer += 1;
+            },
+            GpuCommand::MtxPop(mtx_pop_params) if affects_model => {
+                let stack_pointer = self.stack_pointer as i32 - mtx_pop_params.offset as i32;
+                if stack_pointer < 0 || stack_pointer as usize >= Self::STACK_SIZE {
+                    return Err(AppError::new(&format!("MtxPop::Invalid stack pointer {} after popping {}", stack_pointer, mtx_pop_params.offset)));
+                }
+
+                self.stack_pointer = stack_pointer as usize;
+                self.restore(self.stack_pointer);
+            },
+            GpuCommand::MtxStore(mtx_store_params) if affects_model => {
+                let index = self.stack_index(mtx_store_params.index, "MtxStore")?;
+                self.store(index);
+            },
+            GpuCommand::MtxRestore(mtx_restore_params) if affects_model => {
+                let index = self.stack_index(mtx_restore_params.index, "MtxRestore")?;
+                self.restore(index);
+            },
+            GpuCommand::MtxIdentity if affects_model => {
+                self.load(FixedMatrix::identity(), affects_vector);
+            },
+            GpuCommand::MtxLoad4x4(mtx_params) if affects_model => {
+                self.load(matrix_from_4x4(&mtx_params.m), affects_vector);
+            },
+            GpuCommand::MtxLoad4x3(mtx_params) if affects_model => {
+                self.load(matrix_from_4x3(&mtx_params.m), affects_vector);
+            },
+            GpuCommand::MtxMult4x4(mtx_params) if affects_model => {
+                self.multiply(matrix_from_4x4(&mtx_params.m), affects_vector);
+            },
+            GpuCommand::MtxMult4x3(mtx_params) if affects_model => {
+                self.multiply(matrix_from_4x3(&mtx_params.m), affects_vector);
+            },
+            GpuCommand::MtxMult3x3(mtx_params) if affects_model => {
+                self.multiply(matrix_from_3x3(&mtx_params.m), affects_vector);
+            },
+            GpuCommand::MtxScale(mtx_scale_params) if affects_model => {
+                // Scaling never affects the vector matrix
+                self.multiply(FixedMatrix::scale(mtx_scale_params.x, mtx_scale_params.y, mtx_scale_params.z), false);
+            },
+            GpuCommand::MtxTrans(mtx_trans_params) if affects_model => {
+                self.multiply(FixedMatrix::translation(mtx_trans_params.x, mtx_trans_params.y, mtx_trans_params.z), affects_vector);
+            },
+            _ => {}
+        }
+
+        self.decoder.set_matrices(Some(self.current_matrix.clone()), Some(self.current_vector.clone()));
+        self.decoder.set_fixed_position_matrix(self.fixed_point.then_some(self.fixed_current_matrix));
+        self.decoder.execute_command(cmd)
+    }
+
+    fn stack_index(&self, index: u32, command_name: &str) -> Result<usize, AppError> {
+        if index as usize >= Self::STACK_SIZE {
+            return Err(AppError::new(&format!("{}::Invalid stack index. Expected 0-{}, got {}", command_name, Self::STACK_SIZE - 1, index)));
+        }
+
+        Ok(index as usize)
+    }
+
+    fn store(&mut self, index: usize) {
+        self.matrix_stack[index] = self.current_matrix.clone();
+        self.vector_stack[index] = self.current_vector.clone();
+        self.fixed_matrix_stack[index] = self.fixed_current_matrix;
+    }
+
+    fn restore(&mut self, index: usize) {
+        self.current_matrix = self.matrix_stack[index].clone();
+        self.current_vector = self.vector_stack[index].clone();
+        self.fixed_current_matrix = self.fixed_matrix_stack[index];
+    }
+
+    fn set_fixed_current_matrix(&mut self, matrix: FixedMatrix) {
+        self.fixed_current_matrix = matrix;
+        self.current_matrix = matrix.to_matrix();
+    }
+
+    fn load(&mut self, matrix: FixedMatrix, affects_vector: bool) {
+        if affects_vector {
+            self.current_vector = matrix.to_matrix();
+        }
+
+        if self.fixed_point {
+            self.set_fixed_current_matrix(matrix);
+        } else {
+            self.current_matrix = matrix.to_matrix();
+        }
+    }
+
+    fn multiply(&mut self, matrix: FixedMatrix, affects_vector: bool) {
+        if affects_vector {
+            self.current_vector = self.current_vector.clone() * matrix.to_matrix();
+        }
+
+        if self.fixed_point {
+            self.set_fixed_current_matrix(self.fixed_current_matrix * matrix);
+        } else {
+            self.current_matrix = self.current_matrix.clone() * matrix.to_matrix();
+        }
+    }
+}
+
+// GPU matrices are sent row by row with the translation in the last row (row vectors),
+// while Matrix works with column vectors, so they get transposed
+
+fn matrix_from_4x4(m: &[Fixed1_19_12; 16]) -> FixedMatrix {
+    let mut matrix = FixedMatrix::identity();
+    for row in 0..4 {
+        for column in 0..4 {
+            matrix.set(column, row, m[row * 4 + column]);
+        }
+    }
+
+    matrix
+}
+
+fn matrix_from_4x3(m: &[Fixed1_19_12; 12]) -> FixedMatrix {
+    let mut matrix = FixedMatrix::identity();
+    for row in 0..4 {
+        for column in 0..3 {
+            matrix.set(column, row, m[row * 3 + column]);
+        }
+    }
+
+    matrix
+}
+
+fn matrix_from_3x3(m: &[Fixed1_19_12; 9]) -> FixedMatrix {
+    let mut matrix = FixedMatrix::identity();
+    for row in 0..3 {
+        for column in 0..3 {
+            matrix.set(column, row, m[row * 3 + column]);
+        }
+    }
+
+    matrix
+}
@@ -0,0 +1,283 @@
+use crate::{error::AppError, subfiles::mdl::model::mesh_list::gpu_command_list::{BeginVtxsParams, GpuCommand, GpuCommandList}, tools::models::vertex::{Position, TexCoord}, util::{math::{fixed_matrix::FixedMatrix, matrix::Matrix}, number::fixed_point::fixed_1_19_12::Fixed1_19_12}};
+
+#[derive(Debug, Clone)]
+pub struct DecodedVertex {
+    pub position: Position,
+    pub tex_coord: Option<TexCoord>, // In texels, as sent to the GPU
+    pub normal: Option<[f32; 3]>,
+    pub color: Option<[f32; 3]>,
+    pub matrix_index: Option<u32> // Last MtxRestore index, if any
+}
+
+#[derive(Debug, Clone)]
+pub enum DecodedPrimitive {
+    Triangle([DecodedVertex; 3]),
+    Quad([DecodedVertex; 4])
+}
+
+impl DecodedPrimitive {
+    pub fn vertices(&self) -> &[DecodedVertex] {
+        match self {
+            DecodedPrimitive::Triangle(vertices) => vertices,
+            DecodedPrimitive::Quad(vertices) => vertices
+        }
+    }
+
+    // Quads are split along the 0-2 diagonal, keeping the winding
+    pub fn triangles(&self) -> Vec<[DecodedVertex; 3]> {
+        match self {
+            DecodedPrimitive::Triangle(vertices) => vec![vertices.clone()],
+            DecodedPrimitive::Quad([v0, v1, v2, v3]) => vec![
+                [v0.clone(), v1.clone(), v2.clone()],
+                [v0.clone(), v2.clone(), v3.clone()]
+            ]
+        }
+    }
+}
+
+// Walks the GPU commands of a mesh and assembles the primitives they draw
+#[derive(Debug, Clone)]
+pub struct MeshGeometryDecoder<'a> {
+    render_cmds: &'a GpuCommandList,
+
+    // Internal state for the decoder
+    current_position: Position,
+    current_tex_coord: Option<TexCoord>,
+    current_normal: Option<[f32; 3]>,
+    current_color: Option<[f32; 3]>,
+    current_matrix_index: Option<u32>,
+
+    // Transforms applied to the emitted positions and normals (see GpuCommandExecutor)
+    position_matrix: Option<Matrix>,
+    vector_matrix: Option<Matrix>,
+    fixed_position_matrix: Option<FixedMatrix>, // Replaces position_matrix in fixed point mode
+
+    primitive_type: Option<u8>, // None when not in a vertex group
+    group_vertices: Vec<DecodedVertex>,
+
+    primitives: Vec<DecodedPrimitive>
+}
+
+impl MeshGeometryDecoder<'_> {
+    const STACK_SIZE: u32 = 31; // 0..30 (31 entries)
+
+    pub fn new<'a>(render_cmds: &'a GpuCommandList) -> MeshGeometryDecoder<'a> {
+        MeshGeometryDecoder {
+            render_cmds,
+            current_position: Position { x: 0.0, y: 0.0, z: 0.0 },
+            current_tex_coord: None,
+            current_normal: None,
+            current_color: None,
+            current_matrix_index: None,
+            position_matrix: None,
+            vector_matrix: None,
+            fixed_position_matrix: None,
+            primitive_type: None,
+            group_vertices: Vec::new(),
+            primitives: Vec::new()
+        }
+    }
+
+    pub fn execute(&mut self) -> Result<(), AppError> {
+        for (index, cmd) in self.render_cmds.iter().enumerate() {
+            self.execute_command(&cmd)
+                .map_err(|err| AppError::new(&format!("GPU command {}: {}", index, err.message())))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn primitives(&self) -> &Vec<DecodedPrimitive> {
+        &self.primitives
+    }
+
+    pub fn into_primitives(self) -> Vec<DecodedPrimitive> {
+        self.primitives
+    }
+
+    pub(crate) fn set_matrices(&mut self, position_matrix: Option<Matrix>, vector_matrix: Option<Matrix>) {
+        self.position_matrix = position_matrix;
+        self.vector_matrix = vector_matrix;
+    }
+
+    pub(crate) fn set_fixed_position_matrix(&mut self, fixed_position_matrix: Option<FixedMatrix>) {
+        self.fixed_position_matrix = fixed_position_matrix;
+    }
+
+    pub(crate) fn execute_command(&mut self, cmd: &GpuCommand) -> Result<(), AppError> {
+        match cmd {
+            GpuCommand::MtxRestore(mtx_restore_params) => {
+                if mtx_restore_params.index >= Self::STACK_SIZE {
+                    return Err(AppError::new(&format!("MtxRestore::Invalid stack index. Expected 0-{}, got {}", Self::STACK_SIZE - 1, mtx_restore_params.index)));
+                }
+
+                self.current_matrix_index = Some(mtx_restore_params.index);
+            },
+            GpuCommand::Color(color_params) => {
+                self.current_color = Some([
+                    color_params.r as f32 / 31.0,
+                    color_params.g as f32 / 31.0,
+                    color_params.b as f32 / 31.0
+                ]);
+            },
+            GpuCommand::Normal(normal_params) => {
+                self.current_normal = Some([
+                    normal_params.x.to_f32(),
+                    normal_params.y.to_f32(),
+                    normal_params.z.to_f32()
+                ]);
+            },
+            GpuCommand::TexCoord(tex_coord_params) => {
+                self.current_tex_coord = Some(TexCoord {
+                    u: tex_coord_params.s.to_f32(),
+                    v: tex_coord_params.t.to_f32()
+                });
+            },
+            GpuCommand::Vtx16(vtx16_params) => {
+                self.push_vertex(Position {
+                    x: vtx16_params.x.to_f32(),
+                    y: vtx16_params.y.to_f32(),
+                    z: vtx16_params.z.to_f32()
+                })?;
+            },
+            GpuCommand::Vtx10(vtx10_params) => {
+                self.push_vertex(Position {
+                    x: vtx10_params.x.to_f32(),
+                    y: vtx10_params.y.to_f32(),
+                    z: vtx10_params.z.to_f32()
+                })?;
+            },
+            GpuCommand::VtxXY(vtx_xyparams) => {
+                self.push_vertex(Position {
+                    x: vtx_xyparams.x.to_f32(),
+                    y: vtx_xyparams.y.to_f32(),
+                    z: self.current_position.z
+                })?;
+            },
+            GpuCommand::VtxXZ(vtx_xzparams) => {
+                self.push_vertex(Position {
+                    x: vtx_xzparams.x.to_f32(),
+                    y: self.current_position.y,
+                    z: vtx_xzparams.z.to_f32()
+                })?;
+            },
+            GpuCommand::VtxYZ(vtx_yzparams) => {
+                self.push_vertex(Position {
+                    x: self.current_position.x,
+                    y: vtx_yzparams.y.to_f32(),
+                    z: vtx_yzparams.z.to_f32()
+                })?;
+            },
+            GpuCommand::VtxDiff(vtx_diff_params) => {
+                self.push_vertex(Position {
+                    x: self.current_position.x + vtx_diff_params.x.to_f32(),
+                    y: self.current_position.y + vtx_diff_params.y.to_f32(),
+                    z: self.current_position.z + vtx_diff_params.z.to_f32()
+                })?;
+            },
+            GpuCommand::BeginVtxs(begin_vtxs_params) => {
+                // The hardware does not need EndVtxs between groups, so a new BeginVtxs just starts a new group
+                if begin_vtxs_params.primitive_type > BeginVtxsParams::QUAD_STRIP {
+                    return Err(AppError::new(&format!("BeginVtxs::Invalid primitive type {}", begin_vtxs_params.primitive_type)));
+                }
+
+                self.primitive_type = Some(begin_vtxs_params.primitive_type);
+                self.group_vertices.clear();
+                self.current_position = Position { x: 0.0, y: 0.0, z: 0.0 };
+            },
+            GpuCommand::EndVtxs => {
+                if self.primitive_type.is_none() {
+                    return Err(AppError::new("EndVtxs called while not in a vertex group."));
+                }
+
+                self.primitive_type = None;
+                self.group_vertices.clear();
+            },
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn push_vertex(&mut self, position: Position) -> Result<(), AppError> {
+        let primitive_type = self.primitive_type
+            .ok_or_else(|| AppError::new("Vertex command called while not in a vertex group."))?;
+
+        self.current_position = position.clone();
+        let vertex = DecodedVertex {
+            position,
+            tex_coord: self.current_tex_coord.clone(),
+            normal: self.current_normal,
+            color: self.current_color,
+            matrix_index: self.current_matrix_index
+        };
+        let vertex = self.transform_vertex(vertex)?;
+        self.group_vertices.push(vertex);
+
+        let count = self.group_vertices.len();
+        let vertex = |i: usize| self.group_vertices[i].clone();
+
+        let primitive = match primitive_type {
+            BeginVtxsParams::TRIANGLE if count.is_multiple_of(3) => {
+                Some(DecodedPrimitive::Triangle([vertex(count - 3), vertex(count - 2), vertex(count - 1)]))
+            },
+            BeginVtxsParams::QUAD if count.is_multiple_of(4) => {
+                Some(DecodedPrimitive::Quad([vertex(count - 4), vertex(count - 3), vertex(count - 2), vertex(count - 1)]))
+            },
+            BeginVtxsParams::TRIANGLE_STRIP if count >= 3 => {
+                // Every other triangle has its first two vertices swapped to keep the winding
+                let first = count - 3;
+                if first.is_multiple_of(2) {
+                    Some(DecodedPrimitive::Triangle([vertex(first), vertex(first + 1), vertex(first + 2)]))
+                } else {
+                    Some(DecodedPrimitive::Triangle([vertex(first + 1), vertex(first), vertex(first + 2)]))
+                }
+            },
+            BeginVtxsParams::QUAD_STRIP if count >= 4 && count.is_multiple_of(2) => {
+                // Strip order is 0, 1, 2, 3 -> quad 0, 1, 3, 2
+                let first = count - 4;
+                Some(DecodedPrimitive::Quad([vertex(first), vertex(first + 1), vertex(first + 3), vertex(first + 2)]))
+            },
+            _ => None
+        };
+
+        if let Some(primitive) = primitive {
+            self.primitives.push(primitive);
+        }
+
+        Ok(())
+    }
+
+    fn transform_vertex(&self, mut vertex: DecodedVertex) -> Result<DecodedVertex, AppError> {
+        if let Some(fixed_position_matrix) = &self.fixed_position_matrix {
+            // Vertex positions are 4.12 values, which f32 holds exactly
+            let position = [vertex.position.x, vertex.position.y, vertex.position.z].map(Fixed1_19_12::from_f32);
+            let [x, y, z] = fixed_position_matrix.transform_point(position);
+            vertex.position = Position { x: x.to_f32(), y: y.to_f32(), z: z.to_f32() };
+        } else if let Some(position_matrix) = &self.position_matrix {
+            let pos = Matrix::new(1, 4, vec![vertex.position.x, vertex.position.y, vertex.position.z, 1.0])?;
+            let transformed = position_matrix.clone() * pos;
+            vertex.position = Position {
+                x: transformed.get(0, 0)?,
+                y: transformed.get(1, 0)?,
+                z: transformed.get(2, 0)?
+            };
+        }
+
+        if let (Some(vector_matrix), Some(normal)) = (&self.vector_matrix, vertex.normal) {
+            // Directions ignore the translation, so w is 0
+            let dir = Matrix::new(1, 4, vec![normal[0], normal[1], normal[2], 0.0])?;
+            let transformed = vector_matrix.clone() * dir;
+            let mut normal = [transformed.get(0, 0)?, transformed.get(1, 0)?, transformed.get(2, 0)?];
+
+            let length = (normal[0] * normal[0] + normal[1] * normal[1] + normal[2] * normal[2]).sqrt();
+            if length > 0.0 {
+                normal.iter_mut().for_each(|component| *component /= length);
+            }
+
+            vertex.normal = Some(normal);
+        }
+
+        Ok(vertex)
+    }
+}
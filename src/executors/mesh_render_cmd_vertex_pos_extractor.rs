@@ -21,7 +21,7 @@ impl MeshRenderCmdVertexPosExtractor<'_> {
 
     pub fn execute(&mut self) -> Result<(), AppError> {
         for cmd in self.render_cmds.iter() {
-            self.execute_command(cmd)?;
+            self.execute_command(&cmd)?;
         }
 
         Ok(())
@@ -36,7 +36,7 @@ impl MeshRenderCmdVertexPosExtractor<'_> {
             GpuCommand::Nop => {},
             GpuCommand::MtxRestore(_mtx_restore_params) => {},
             GpuCommand::MtxScale(_mtx_scale_params) => {},
-            GpuCommand::Unknown0x1C(_unknown0x1_cparams) => {},
+            GpuCommand::MtxTrans(_mtx_trans_params) => {},
             GpuCommand::Color(_color_params) => {},
             GpuCommand::Normal(_normal_params) => {},
             GpuCommand::TexCoord(_tex_coord_params) => {},
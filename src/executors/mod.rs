@@ -1,2 +1,5 @@
 pub mod model_render_cmd_executor;
 pub mod mesh_render_cmd_vertex_pos_extractor;
+pub mod mesh_geometry_decoder;
+pub mod gpu_command_executor;
+pub mod posed_mesh_extractor;
@@ -1,20 +1,80 @@
-use crate::{error::AppError, subfiles::mdl::model::{bone_list::BoneList, render_command_list::{RenderCommand, RenderCommandList}}, util::math::matrix::Matrix};
+use crate::{error::AppError, subfiles::mdl::model::{bone_list::{BoneList, BoneMatrix}, inv_bind_matrices::InvBindMatrices, render_command_list::{BillboardData, CalculateSkinningEquationData, RenderCommand, RenderCommandList}}, util::{math::{fixed_matrix::FixedMatrix, matrix::Matrix}, number::fixed_point::fixed_1_19_12::Fixed1_19_12}};
+
+// State in which a mesh is drawn, as left by the render commands before its DrawMesh
+#[derive(Debug, Clone)]
+pub struct DrawMeshContext {
+    pub mesh_index: usize,
+    pub material_index: Option<usize>, // None if no material was bound before
+    pub current_matrix: Matrix,
+    pub matrix_stack: Vec<Matrix>,
+    pub fixed_matrices: Option<(FixedMatrix, Vec<FixedMatrix>)> // Raw current matrix and stack, in fixed point mode only
+}
+
+// Executed command with the state it left, recorded in trace mode
+#[derive(Debug, Clone)]
+pub struct TraceEntry {
+    pub command_index: usize,
+    pub command: RenderCommand,
+    pub current_matrix: Matrix,
+    pub changed_slots: Vec<(usize, Matrix)> // Stack slots written by the command, with their new value
+}
+
+// Execution state saved by ModelRenderCmdExecutor::snapshot
+#[derive(Debug, Clone)]
+pub struct ExecutorSnapshot {
+    cursor: usize,
+    matrix_stack: Vec<Matrix>,
+    stored_slots: Vec<bool>,
+    skinned_slots: Vec<bool>,
+    current_matrix: Matrix,
+    fixed_matrix_stack: Vec<FixedMatrix>,
+    fixed_current_matrix: FixedMatrix,
+    current_material: Option<usize>,
+    loaded_bones_in_matrix: Vec<Option<String>>
+}
+
+impl ExecutorSnapshot {
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+}
 
 // State machine to execute model render commands
 pub struct ModelRenderCmdExecutor<'a> {
     render_cmds: &'a RenderCommandList,
     bone_list: &'a BoneList,
+    inv_bind_matrices: &'a InvBindMatrices,
+    upscale: f32,
+    downscale: f32,
+
+    // Local transforms replacing the rest pose of some bones, indexed like the bone list (e.g. sampled from an animation)
+    pose: Vec<Option<BoneMatrix>>,
+
+    // Whether matrix math is done in 20.12 fixed point like the DS, instead of f32
+    fixed_point: bool,
+
+    // Camera used to orient billboards, identity by default (camera looking down -Z from the model origin)
+    view_matrix: Matrix,
 
     // Internal state for the executor
+    cursor: usize, // Next command to execute
     matrix_stack: Vec<Matrix>, // Visit https://problemkaputt.de/gbatek.htm#ds3dvideo (DS 3D Matrix Stack) for more info
+    stored_slots: Vec<bool>, // Slots written by a command. Reading any other slot is an error, as its content is undefined on hardware
+    skinned_slots: Vec<bool>, // Slots currently holding a matrix computed by CalculateSkinningEquation
     current_matrix: Matrix,
+    current_material: Option<usize>,
+
+    // Raw 20.12 state, only kept up to date in fixed point mode. The matrices above are converted from it
+    fixed_matrix_stack: Vec<FixedMatrix>,
+    fixed_current_matrix: FixedMatrix,
 
     // Additional useful data
-    loaded_bones_in_matrix: Vec<Option<String>>
+    loaded_bones_in_matrix: Vec<Option<String>>,
+    trace: Option<Vec<TraceEntry>> // None when not tracing
 }
 
 impl ModelRenderCmdExecutor<'_> {
-    pub fn new<'a>(render_cmds: &'a RenderCommandList, bone_list: &'a BoneList) -> ModelRenderCmdExecutor<'a> {
+    pub fn new<'a>(render_cmds: &'a RenderCommandList, bone_list: &'a BoneList, inv_bind_matrices: &'a InvBindMatrices, upscale: f32, downscale: f32) -> ModelRenderCmdExecutor<'a> {
         let matrix_stack = vec![Matrix::identity(4); 31]; // 0..30 (31 entries)
         let current_matrix = Matrix::identity(4); // Initial current matrix
 
@@ -23,30 +83,198 @@ impl ModelRenderCmdExecutor<'_> {
         ModelRenderCmdExecutor {
             render_cmds,
             bone_list,
+            inv_bind_matrices,
+            upscale,
+            downscale,
+            pose: Vec::new(),
+            fixed_point: false,
+            view_matrix: Matrix::identity(4),
+            cursor: 0,
             matrix_stack,
+            stored_slots: vec![false; 31],
+            skinned_slots: vec![false; 31],
             current_matrix,
-            loaded_bones_in_matrix
+            current_material: None,
+            fixed_matrix_stack: vec![FixedMatrix::identity(); 31],
+            fixed_current_matrix: FixedMatrix::identity(),
+            loaded_bones_in_matrix,
+            trace: None
         }
     }
 
+    // Executes the remaining commands
     pub fn execute(&mut self) -> Result<(), AppError> {
-        for cmd in self.render_cmds.iter() {
-            self.execute_command(cmd)?;
-        }
+        while self.step()?.is_some() {}
 
         Ok(())
     }
 
+    // Executes until the next DrawMesh, which is left as the last executed command
     pub fn execute_until_next_mesh_draw(&mut self) -> Result<(), AppError> {
-        for cmd in self.render_cmds.iter() {
-            if let RenderCommand::DrawMesh(_) = cmd {
-                return Ok(()); // Stop execution when we reach a DrawMesh command
+        self.next_mesh_draw()?
+            .ok_or_else(|| AppError::new("No DrawMesh command found in the render command list."))?;
+
+        Ok(())
+    }
+
+    // Executes until the next DrawMesh and returns its context, or None when there are no more meshes to draw.
+    // Each call resumes where the previous one stopped
+    pub fn next_mesh_draw(&mut self) -> Result<Option<DrawMeshContext>, AppError> {
+        while let Some(cmd) = self.step()? {
+            if let RenderCommand::DrawMesh(data) = cmd {
+                return Ok(Some(DrawMeshContext {
+                    mesh_index: data.mesh_index as usize,
+                    material_index: self.current_material,
+                    current_matrix: self.current_matrix.clone(),
+                    matrix_stack: self.matrix_stack.clone(),
+                    fixed_matrices: self.fixed_point.then(|| (self.fixed_current_matrix, self.fixed_matrix_stack.clone()))
+                }));
             }
+        }
 
-            self.execute_command(cmd)?;
+        Ok(None)
+    }
+
+    // Executes the remaining commands and returns the context of each DrawMesh, in drawing order
+    pub fn execute_draw_contexts(&mut self) -> Result<Vec<DrawMeshContext>, AppError> {
+        let mut contexts = Vec::new();
+        while let Some(context) = self.next_mesh_draw()? {
+            contexts.push(context);
         }
 
-        Err(AppError::new("No DrawMesh command found in the render command list."))
+        Ok(contexts)
+    }
+
+    // Saves the execution state (cursor included), so execution can be branched and later resumed from this point
+    pub fn snapshot(&self) -> ExecutorSnapshot {
+        ExecutorSnapshot {
+            cursor: self.cursor,
+            matrix_stack: self.matrix_stack.clone(),
+            stored_slots: self.stored_slots.clone(),
+            skinned_slots: self.skinned_slots.clone(),
+            current_matrix: self.current_matrix.clone(),
+            fixed_matrix_stack: self.fixed_matrix_stack.clone(),
+            fixed_current_matrix: self.fixed_current_matrix,
+            current_material: self.current_material,
+            loaded_bones_in_matrix: self.loaded_bones_in_matrix.clone()
+        }
+    }
+
+    // The snapshot must come from an executor over the same command list. Trace entries are kept
+    pub fn restore(&mut self, snapshot: &ExecutorSnapshot) -> Result<(), AppError> {
+        if snapshot.cursor > self.render_cmds.len() {
+            return Err(AppError::new(&format!("Snapshot cursor {} is beyond the {} render commands", snapshot.cursor, self.render_cmds.len())));
+        }
+
+        self.cursor = snapshot.cursor;
+        self.matrix_stack = snapshot.matrix_stack.clone();
+        self.stored_slots = snapshot.stored_slots.clone();
+        self.skinned_slots = snapshot.skinned_slots.clone();
+        self.current_matrix = snapshot.current_matrix.clone();
+        self.fixed_matrix_stack = snapshot.fixed_matrix_stack.clone();
+        self.fixed_current_matrix = snapshot.fixed_current_matrix;
+        self.current_material = snapshot.current_material;
+        self.loaded_bones_in_matrix = snapshot.loaded_bones_in_matrix.clone();
+
+        Ok(())
+    }
+
+    // Starts (or stops) recording every executed command. Previous entries are discarded
+    pub fn set_trace(&mut self, enabled: bool) {
+        self.trace = if enabled { Some(Vec::new()) } else { None };
+    }
+
+    pub fn trace(&self) -> Option<&Vec<TraceEntry>> {
+        self.trace.as_ref()
+    }
+
+    // Restarts the execution from the first command with a clean state
+    pub fn reset(&mut self) {
+        self.cursor = 0;
+        self.matrix_stack = vec![Matrix::identity(4); 31];
+        self.stored_slots = vec![false; 31];
+        self.skinned_slots = vec![false; 31];
+        self.current_matrix = Matrix::identity(4);
+        self.fixed_matrix_stack = vec![FixedMatrix::identity(); 31];
+        self.fixed_current_matrix = FixedMatrix::identity();
+        self.current_material = None;
+        self.loaded_bones_in_matrix = vec![None; 31];
+        if let Some(trace) = self.trace.as_mut() {
+            trace.clear();
+        }
+    }
+
+    // Executes the command at the cursor, returning it, or None when the list has been fully executed
+    fn step(&mut self) -> Result<Option<&RenderCommand>, AppError> {
+        let render_cmds = self.render_cmds;
+        let Some(cmd) = render_cmds.get(self.cursor) else {
+            return Ok(None);
+        };
+
+        let previous_stack = self.trace.is_some().then(|| self.matrix_stack.clone());
+
+        self.execute_command(cmd)
+            .map_err(|err| AppError::new(&format!("Render command {}: {}", self.cursor, err.message())))?;
+
+        if let (Some(trace), Some(previous_stack)) = (self.trace.as_mut(), previous_stack) {
+            let changed_slots = self.matrix_stack.iter()
+                .zip(previous_stack.iter())
+                .enumerate()
+                .filter(|(_, (matrix, previous))| matrix != previous)
+                .map(|(slot, (matrix, _))| (slot, matrix.clone()))
+                .collect();
+
+            trace.push(TraceEntry {
+                command_index: self.cursor,
+                command: cmd.clone(),
+                current_matrix: self.current_matrix.clone(),
+                changed_slots
+            });
+        }
+
+        self.cursor += 1;
+
+        Ok(Some(cmd))
+    }
+
+    // Bones without a transform (or beyond the end of the pose) keep their rest pose.
+    // Use BoneMatrix::from_components to build the transforms from TRS values
+    pub fn set_pose(&mut self, pose: Vec<Option<BoneMatrix>>) -> Result<(), AppError> {
+        if pose.len() > self.bone_list.len() {
+            return Err(AppError::new(&format!("Pose has {} bone transforms, but the model only has {} bones", pose.len(), self.bone_list.len())));
+        }
+
+        self.pose = pose;
+
+        Ok(())
+    }
+
+    // In fixed point mode matrices are kept in raw 20.12 and only converted to f32 for the outputs. Bone matrices are
+    // applied component by component (translation, rotation, scale) and every product is truncated like on the
+    // geometry engine, so results match hardware. Enabling it mid execution truncates the current state
+    pub fn set_fixed_point(&mut self, fixed_point: bool) -> Result<(), AppError> {
+        if fixed_point && !self.fixed_point {
+            self.fixed_current_matrix = FixedMatrix::from_matrix(&self.current_matrix)?;
+            self.fixed_matrix_stack = self.matrix_stack.iter()
+                .map(FixedMatrix::from_matrix)
+                .collect::<Result<Vec<FixedMatrix>, AppError>>()?;
+        }
+
+        self.fixed_point = fixed_point;
+
+        Ok(())
+    }
+
+    pub fn set_view_matrix(&mut self, view_matrix: Matrix) -> Result<(), AppError> {
+        if view_matrix.width() != 4 || view_matrix.height() != 4 {
+            return Err(AppError::new("View matrix must be 4x4."));
+        }
+
+        // Checked here so billboards can always invert it
+        view_matrix.inverted()?;
+        self.view_matrix = view_matrix;
+
+        Ok(())
     }
 
     pub fn matrix_stack(&self) -> &Vec<Matrix> {
@@ -57,21 +285,33 @@ impl ModelRenderCmdExecutor<'_> {
         &self.loaded_bones_in_matrix
     }
 
+    // Blended matrices (sum of weighted stack matrices multiplied by their inverse binds) and the stack slot holding each.
+    // Vertices restoring one of these slots are already in model space after the transform, as the inverse binds are applied
+    pub fn skinning_matrices(&self) -> Vec<(usize, &Matrix)> {
+        self.skinned_slots.iter()
+            .enumerate()
+            .filter(|(_, &skinned)| skinned)
+            .map(|(slot, _)| (slot, &self.matrix_stack[slot]))
+            .collect()
+    }
+
+    pub fn skinning_matrix(&self, slot: usize) -> Option<&Matrix> {
+        match self.skinned_slots.get(slot) {
+            Some(true) => Some(&self.matrix_stack[slot]),
+            _ => None
+        }
+    }
+
     fn execute_command(&mut self, cmd: &RenderCommand) -> Result<(), AppError> {
         match cmd {
             RenderCommand::Nop(_nop_data) => {},
             RenderCommand::End => {},
-            RenderCommand::Unknown0x02(_unknown0x02_data) => { /* Unknown */},
+            RenderCommand::NodeVisibility(_node_visibility_data) => { /* Does not affect the matrices */ },
             RenderCommand::LoadMatrixFromStack(load_matrix_from_stack_data) => {
-                let index = load_matrix_from_stack_data.stack_index as usize;
-                if index >= self.matrix_stack.len() {
-                    return Err(AppError::new(&format!("LoadMatrixFromStack::Invalid stack index. Expected 0-{}, got {}", self.matrix_stack.len() - 1, index)));
-                }
-
-                self.current_matrix = self.matrix_stack[index].clone();
+                self.load_slot(load_matrix_from_stack_data.stack_index, "LoadMatrixFromStack")?;
             },
-            RenderCommand::BindMaterial(_bind_material_data) => {
-                // TODO: Implement material binding logic
+            RenderCommand::BindMaterial(bind_material_data) => {
+                self.current_material = Some(bind_material_data.material_index as usize);
             },
             RenderCommand::DrawMesh(_draw_mesh_data) => {
                 // Nothing to do at the moment
@@ -91,34 +331,211 @@ impl ModelRenderCmdExecutor<'_> {
                 };
 
                 if let Some(stack_index) = load_pos {
-                    self.current_matrix = self.matrix_stack[stack_index as usize].clone();
+                    self.load_slot(stack_index, "MulCurrentMatrixWithBoneMatrix")?;
                 }
 
-                let bone_matrix = self.bone_list.get_bone_matrix(bone_index)
-                    .ok_or_else(|| AppError::new(&format!("MulCurrentMatrixWithBoneMatrix::Could not find bone matrix at index {}", bone_index)))?
-                    .to_matrix();
-                self.current_matrix = self.current_matrix.clone() * bone_matrix;
+                let bone_matrix = match self.pose.get(bone_index) {
+                    Some(Some(posed_matrix)) => posed_matrix,
+                    _ => self.bone_list.get_bone_matrix(bone_index)
+                        .ok_or_else(|| AppError::new(&format!("MulCurrentMatrixWithBoneMatrix::Could not find bone matrix at index {}", bone_index)))?
+                };
+
+                if self.fixed_point {
+                    let matrix = bone_matrix.fixed_component_matrices()
+                        .into_iter()
+                        .fold(self.fixed_current_matrix, |matrix, component| matrix * component);
+                    self.set_fixed_current_matrix(matrix);
+                } else {
+                    self.current_matrix = self.current_matrix.clone() * bone_matrix.to_matrix();
+                }
 
                 if let Some(stack_index) = store_pos {
-                    let matrix_update_index = stack_index as usize;
-                    self.matrix_stack[matrix_update_index] = self.current_matrix.clone();
+                    let matrix_update_index = self.store_slot(stack_index, "MulCurrentMatrixWithBoneMatrix")?;
                     self.loaded_bones_in_matrix[matrix_update_index] = Some(self.bone_list.get_name(bone_index).unwrap().to_not_null_string().unwrap());
                 }
             },
-            RenderCommand::Unknown0x07(_unknown0x07_data) => { /* Unknown */ },
-            RenderCommand::Unknown0x08(_unknown0x08_data) => { /* Unknown */ },
-            RenderCommand::CalculateSkinningEquation(_calculate_skinning_equation_data) => {
-                // TODO: Implement skinning equation calculation logic
-                println!("WARNING: CalculateSkinningEquation command is not implemented yet.");
+            RenderCommand::Billboard(data) => {
+                self.execute_billboard(data, false)?;
+            },
+            RenderCommand::BillboardY(data) => {
+                self.execute_billboard(data, true)?;
             },
-            RenderCommand::Scale(_scale_data) => {
-                // TODO: Implement scaling logic
-                // It uses the model scale factor to scale the current matrix
+            RenderCommand::CalculateSkinningEquation(data) => {
+                self.execute_skinning_equation(data)?;
             },
-            RenderCommand::Unknown0x0C(_unknown0x0c_data) => { /* Unknown */ },
-            RenderCommand::Unknown0x0D(_unknown0x0d_data) => { /* Unknown */ },
+            RenderCommand::Scale(scale_data) => {
+                // Subtype 0x00 applies the model upscale and 0x20 the downscale
+                let factor = if scale_data.subtype == 0x20 { self.downscale } else { self.upscale };
+
+                if self.fixed_point {
+                    let factor = Fixed1_19_12::from_f32(factor);
+                    self.set_fixed_current_matrix(self.fixed_current_matrix * FixedMatrix::scale(factor, factor, factor));
+                } else {
+                    let mut scale = Matrix::identity(4);
+                    for i in 0..3 {
+                        scale.set(i, i, factor)?;
+                    }
+
+                    self.current_matrix = self.current_matrix.clone() * scale;
+                }
+            },
+            RenderCommand::EnvironmentMap(_tex_map_data) => { /* Only affects the texture matrix */ },
+            RenderCommand::ProjectionMap(_tex_map_data) => { /* Only affects the texture matrix */ },
+        }
+
+        Ok(())
+    }
+
+    // Replaces the rotation of the current matrix (as seen from the camera) so it faces the camera, keeping its scale.
+    // Y billboards keep their Y axis and only rotate around it
+    fn execute_billboard(&mut self, data: &BillboardData, y_only: bool) -> Result<(), AppError> {
+        if let Some(stack_index) = data.load_index {
+            self.load_slot(stack_index, "Billboard")?;
+        }
+
+        let view_space = self.view_matrix.clone() * self.current_matrix.clone();
+
+        let mut axes = [[0.0; 3]; 3];
+        for (column, axis) in axes.iter_mut().enumerate() {
+            for (row, value) in axis.iter_mut().enumerate() {
+                *value = view_space.get(row as u32, column as u32)?;
+            }
+        }
+        let scales = axes.map(length);
+
+        let billboard_axes = if y_only {
+            let y = normalize(axes[1]);
+            // Camera looks down -Z, so the facing axis is +Z made perpendicular to Y
+            let z = normalize([-y[0] * y[2], -y[1] * y[2], 1.0 - y[2] * y[2]]);
+            let x = cross(y, z);
+
+            [x, y, z]
+        } else {
+            [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]
+        };
+
+        let mut billboard = view_space;
+        for (column, axis) in billboard_axes.iter().enumerate() {
+            for (row, value) in axis.iter().enumerate() {
+                billboard.set(row as u32, column as u32, value * scales[column])?;
+            }
+        }
+
+        let matrix = self.view_matrix.inverted()? * billboard;
+        if self.fixed_point {
+            self.set_fixed_current_matrix(FixedMatrix::from_matrix(&matrix)?);
+        } else {
+            self.current_matrix = matrix;
+        }
+
+        if let Some(stack_index) = data.store_index {
+            let stack_index = self.store_slot(stack_index, "Billboard")?;
+            self.loaded_bones_in_matrix[stack_index] = self.bone_list.get_name(data.node_index as usize)
+                .and_then(|name| name.to_not_null_string().ok());
+        }
+
+        Ok(())
+    }
+
+    // Weighted sum of stack matrices multiplied by their inverse bind matrices. Weights are in 1/256 units
+    fn execute_skinning_equation(&mut self, data: &CalculateSkinningEquationData) -> Result<(), AppError> {
+        let mut result = Matrix::zeros(4, 4);
+        let mut fixed_result = FixedMatrix::from_raw([0; 16]);
+        for term in data.terms.iter() {
+            let slot = self.stored_slot(term.matrix_index, "CalculateSkinningEquation")?;
+            let inv_bind = self.inv_bind_matrices.get(term.inv_bind_index as usize)
+                .ok_or_else(|| AppError::new(&format!("CalculateSkinningEquation::Invalid inverse bind index. Expected 0-{}, got {}", self.inv_bind_matrices.len() as isize - 1, term.inv_bind_index)))?;
+
+            if self.fixed_point {
+                let weighted = (self.fixed_matrix_stack[slot] * inv_bind.to_fixed_matrix()).weighted(term.weight);
+                fixed_result = fixed_result.wrapping_add(&weighted);
+                continue;
+            }
+
+            let weighted = self.matrix_stack[slot].clone() * inv_bind.to_matrix();
+            let weight = term.weight as f32 / 256.0;
+            for row in 0..4 {
+                for column in 0..4 {
+                    result.set(row, column, result.get(row, column)? + weighted.get(row, column)? * weight)?;
+                }
+            }
+        }
+
+        if self.fixed_point {
+            self.set_fixed_current_matrix(fixed_result);
+        } else {
+            self.current_matrix = result;
+        }
+
+        let store_index = self.store_slot(data.store_index, "CalculateSkinningEquation")?;
+        self.skinned_slots[store_index] = true;
+        // The slot blends several bones, so it does not belong to a single one
+        self.loaded_bones_in_matrix[store_index] = None;
+
+        Ok(())
+    }
+
+    fn set_fixed_current_matrix(&mut self, matrix: FixedMatrix) {
+        self.fixed_current_matrix = matrix;
+        self.current_matrix = matrix.to_matrix();
+    }
+
+    // Index of a slot that can be read
+    fn stored_slot(&self, index: u8, command_name: &str) -> Result<usize, AppError> {
+        let index = self.stack_index(index, command_name)?;
+        if !self.stored_slots[index] {
+            return Err(AppError::new(&format!("{}::Stack slot {} is read before any command stores a matrix in it", command_name, index)));
         }
 
+        Ok(index)
+    }
+
+    // Makes the slot the current matrix
+    fn load_slot(&mut self, index: u8, command_name: &str) -> Result<(), AppError> {
+        let index = self.stored_slot(index, command_name)?;
+        self.current_matrix = self.matrix_stack[index].clone();
+        self.fixed_current_matrix = self.fixed_matrix_stack[index];
+
         Ok(())
     }
+
+    // Stores the current matrix in the slot
+    fn store_slot(&mut self, index: u8, command_name: &str) -> Result<usize, AppError> {
+        let index = self.stack_index(index, command_name)?;
+        self.matrix_stack[index] = self.current_matrix.clone();
+        self.fixed_matrix_stack[index] = self.fixed_current_matrix;
+        self.stored_slots[index] = true;
+        self.skinned_slots[index] = false;
+
+        Ok(index)
+    }
+
+    fn stack_index(&self, index: u8, command_name: &str) -> Result<usize, AppError> {
+        if index as usize >= self.matrix_stack.len() {
+            return Err(AppError::new(&format!("{}::Invalid stack index. Expected 0-{}, got {}", command_name, self.matrix_stack.len() - 1, index)));
+        }
+
+        Ok(index as usize)
+    }
+}
+
+fn length(v: [f32; 3]) -> f32 {
+    (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt()
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let length = length(v);
+    if length > 0.0 {
+        v.map(|component| component / length)
+    } else {
+        v
+    }
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0]
+    ]
 }
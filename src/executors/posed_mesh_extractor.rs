@@ -0,0 +1,74 @@
+use crate::{error::AppError, subfiles::mdl::model::{bone_list::BoneMatrix, Model}};
+
+use super::{gpu_command_executor::GpuCommandExecutor, mesh_geometry_decoder::DecodedVertex};
+
+// Mesh drawn by a DrawMesh command, with its triangles in model space (quads are split)
+#[derive(Debug, Clone)]
+pub struct PosedMesh {
+    pub mesh_index: usize,
+    pub material_index: Option<usize>,
+    pub triangles: Vec<[DecodedVertex; 3]>
+}
+
+// Executes the render commands of a model and draws every mesh with the matrices they leave,
+// so the result is the model in the pose described by its bones
+pub struct PosedMeshExtractor<'a> {
+    model: &'a Model,
+    pose: Vec<Option<BoneMatrix>>, // See ModelRenderCmdExecutor::set_pose
+    fixed_point: bool // See ModelRenderCmdExecutor::set_fixed_point
+}
+
+impl PosedMeshExtractor<'_> {
+    pub fn new<'a>(model: &'a Model) -> PosedMeshExtractor<'a> {
+        PosedMeshExtractor {
+            model,
+            pose: Vec::new(),
+            fixed_point: false
+        }
+    }
+
+    pub fn set_pose(&mut self, pose: Vec<Option<BoneMatrix>>) {
+        self.pose = pose;
+    }
+
+    pub fn set_fixed_point(&mut self, fixed_point: bool) {
+        self.fixed_point = fixed_point;
+    }
+
+    // One entry per DrawMesh, in drawing order (a mesh drawn twice appears twice)
+    pub fn extract(&self) -> Result<Vec<PosedMesh>, AppError> {
+        let mut executor = self.model.get_render_command_executor();
+        executor.set_pose(self.pose.clone())?;
+        executor.set_fixed_point(self.fixed_point)?;
+        let contexts = executor.execute_draw_contexts()?;
+
+        let mesh_list = self.model.get_mesh_list();
+
+        let mut posed_meshes = Vec::with_capacity(contexts.len());
+        for context in contexts {
+            let mesh = mesh_list.get_mesh(context.mesh_index)
+                .ok_or_else(|| AppError::new(&format!("DrawMesh references mesh {}, but there are only {}", context.mesh_index, mesh_list.len())))?;
+
+            let mut gpu_executor = GpuCommandExecutor::new(mesh.get_render_cmds_list(), &context.matrix_stack)?;
+            gpu_executor.set_current_matrix(context.current_matrix)?;
+            if let Some((current_matrix, matrix_stack)) = &context.fixed_matrices {
+                gpu_executor.set_fixed_matrices(*current_matrix, matrix_stack)?;
+            }
+            gpu_executor.execute()
+                .map_err(|err| AppError::new(&format!("Mesh {}: {}", context.mesh_index, err.message())))?;
+
+            let triangles = gpu_executor.into_primitives()
+                .iter()
+                .flat_map(|primitive| primitive.triangles())
+                .collect();
+
+            posed_meshes.push(PosedMesh {
+                mesh_index: context.mesh_index,
+                material_index: context.material_index,
+                triangles
+            });
+        }
+
+        Ok(posed_meshes)
+    }
+}
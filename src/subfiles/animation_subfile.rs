@@ -0,0 +1,191 @@
+use crate::{data_structures::{name::Name, name_list::NameList}, debug_info::DebugInfo, error::AppError, traits::SubfileAnimation, util::number::alignment::get_4_byte_alignment};
+
+// JNT0, PAT0 and SRT0 share their layout: stamp, filesize and a name list with the offset of each animation
+#[derive(Debug, Clone)]
+pub struct AnimationSubfile<T: SubfileAnimation> {
+    stamp: [u8; 4],
+    filesize: u32,
+    animations: NameList<u32>,
+
+    // Actual data
+    animations_data: Vec<T>,
+
+    // Debug info
+    _debug_info: DebugInfo
+}
+
+impl<T: SubfileAnimation> AnimationSubfile<T> {
+    // Empty subfile, to add animations to (see the builder of the animation type)
+    pub fn new() -> AnimationSubfile<T> {
+        let mut subfile = AnimationSubfile {
+            stamp: T::STAMP,
+            filesize: 0,
+            animations: NameList::new(4),
+            animations_data: Vec::new(),
+            _debug_info: DebugInfo { offset: 0 }
+        };
+
+        subfile.rebase();
+        subfile
+    }
+
+    pub fn from_bytes(bytes: &[u8], debug_info: DebugInfo) -> Result<AnimationSubfile<T>, AppError> {
+        if bytes.len() < 8 {
+            return Err(AppError::new(&format!("{} needs at least 8 bytes to start reading", Self::kind())))
+        }
+
+        let stamp = [
+            bytes[0],
+            bytes[1],
+            bytes[2],
+            bytes[3]
+        ];
+
+        let filesize = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+
+        if bytes.len() < filesize as usize {
+            return Err(AppError::new(&format!("{} needs at least {} bytes", Self::kind(), filesize)))
+        }
+
+        let bytes = &bytes[..filesize as usize];
+
+        let animations = NameList::from_bytes(&bytes[8..])?;
+
+        let mut animations_data = Vec::with_capacity(animations.len());
+        for &offset in animations.data_iter() {
+            let debug_info = DebugInfo {
+                offset: debug_info.offset + offset
+            };
+
+            let offset = offset as usize;
+            if offset >= bytes.len() {
+                return Err(AppError::new(&format!("{} animation offset 0x{:X} is out of bounds", Self::kind(), offset)));
+            }
+
+            let animation = T::from_bytes(&bytes[offset..], debug_info)?;
+            animations_data.push(animation);
+        }
+
+        Ok(AnimationSubfile {
+            stamp,
+            filesize,
+            animations,
+            animations_data,
+            _debug_info: debug_info
+        })
+    }
+
+    pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
+        if buffer.len() < self.filesize as usize {
+            return Err(AppError::new(&format!("Buffer is too small to write {}", Self::kind())));
+        }
+
+        buffer[0..4].copy_from_slice(&self.stamp); // Write stamp
+        buffer[4..8].copy_from_slice(&self.filesize.to_le_bytes()); // Write filesize
+        self.animations.write_bytes(&mut buffer[8..])?; // Write animations
+
+        for (i, &offset) in self.animations.data_iter().enumerate() {
+            let offset = offset as usize;
+            let animation = &self.animations_data[i];
+            animation.write_bytes(&mut buffer[offset..])?;
+        }
+
+        Ok(())
+    }
+
+    pub fn rebase(&mut self) {
+        if self.animations.len() != self.animations_data.len() {
+            // This should never happen
+            panic!("Unexpected mismatch between animations header and animations data");
+        }
+
+        let mut prev_offset = get_4_byte_alignment(8 + self.animations.size()) as u32;
+        let mut prev_size = 0u32;
+
+        let iter = self.animations.data_iter_mut().zip(self.animations_data.iter_mut());
+        for (offset, animation) in iter {
+            animation.rebase();
+
+            let size = animation.size() as u32;
+
+            let new_offset = prev_offset + prev_size;
+            *offset = new_offset;
+
+            prev_offset = new_offset;
+            prev_size = size;
+        }
+
+        // Update the filesize
+        self.filesize = prev_offset + prev_size;
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0u8; self.filesize as usize]; // write buffer
+
+        self.write_bytes(&mut bytes).unwrap();
+
+        bytes
+    }
+
+    // Returns the index of the new animation
+    pub fn add_animation(&mut self, name: &str, animation: T) -> Result<usize, AppError> {
+        if self.index_of_name(name).is_some() {
+            return Err(AppError::new(&format!("{} already has an animation named {}", Self::kind(), name)));
+        }
+
+        self.animations.push(Name::from_string(name)?, 0)?;
+        self.animations_data.push(animation);
+
+        self.rebase();
+
+        Ok(self.animations_data.len() - 1)
+    }
+
+    pub fn size(&self) -> usize {
+        self.filesize as usize
+    }
+
+    pub fn len(&self) -> usize {
+        self.animations_data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.animations_data.is_empty()
+    }
+
+    pub fn get_animation(&self, index: usize) -> Option<&T> {
+        self.animations_data.get(index)
+    }
+
+    pub fn get_animation_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.animations_data.get_mut(index)
+    }
+
+    pub fn get_animation_name(&self, index: usize) -> Option<&Name> {
+        self.animations.get_name(index)
+    }
+
+    pub fn index_of_name(&self, name: &str) -> Option<usize> {
+        self.animations.names_iter()
+            .position(|animation_name| animation_name.to_not_null_string().is_ok_and(|animation_name| animation_name == name))
+    }
+
+    pub fn animations_iter(&self) -> impl Iterator<Item = &T> {
+        self.animations_data.iter()
+    }
+
+    pub fn animations_iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.animations_data.iter_mut()
+    }
+
+    // "JNT", "PAT" or "SRT", for error messages
+    fn kind() -> String {
+        String::from_utf8_lossy(&T::STAMP[..3]).into_owned()
+    }
+}
+
+impl<T: SubfileAnimation> Default for AnimationSubfile<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,1205 @@
+use crate::{data_structures::keyframes::{frame_rate_frames, insert_key, read_u16, read_u32, resample_keys, resampled_frames, sliced_frames, Channel, ChannelValue, KeyframeStep, Keyframes}, debug_info::DebugInfo, error::AppError, subfiles::mdl::model::{bone_list::BoneMatrix, Model}, traits::SubfileAnimation, util::{math::{pivot_rotation::PivotRotation, trs::{lerp_array, lerp_rotation, Trs}}, number::{alignment::get_4_byte_alignment, fixed_point::{fixed_1_19_12::Fixed1_19_12, fixed_1_3_12::Fixed1_3_12}}}};
+
+// Joint animation of a JNT0 subfile: one track per animated node (bone) of the model, plus the rotation tables
+// the rotation channels index
+#[derive(Debug, Clone)]
+pub struct JntAnimation {
+    num_frames: u16,
+    flags: u32,
+    pivot_rotations_offset: u32, // Rotation tables, from the start of the animation
+    basis_rotations_offset: u32,
+    tag_offsets: Vec<u16>, // One per track
+    keyframe_offsets: Vec<u32>, // One per keyed channel, in the order of the tracks (translation, rotation, scale)
+    size: u32, // Not stored in the file
+
+    // Actual data
+    tracks: Vec<JointTrack>,
+    pivot_rotations: Vec<PivotRotation>,
+    basis_rotations: Vec<BasisRotation>,
+
+    // Debug info
+    _debug_info: DebugInfo
+}
+
+// How a node is animated. Components are read from the tag of the node, the top byte of which is the node id
+#[derive(Debug, Clone)]
+pub struct JointTrack {
+    pub node_id: u8,
+    pub translation: JointComponent<[Channel<Fixed1_19_12>; 3]>,
+    pub rotation: JointComponent<Channel<RotationRef>>,
+    pub scale: JointComponent<[Channel<ScaleValue>; 3]>
+}
+
+#[derive(Debug, Clone)]
+pub enum JointComponent<T> {
+    Identity,
+    Base, // The value of the bone in the model
+    Animated(T)
+}
+
+// Scales are stored along with their inverse
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScaleValue {
+    pub scale: Fixed1_19_12,
+    pub inverse: Fixed1_19_12
+}
+
+// Index into one of the rotation tables of the animation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RotationRef {
+    Pivot(u16),
+    Basis(u16)
+}
+
+impl RotationRef {
+    const PIVOT_FLAG: u16 = 0x8000;
+
+    pub fn from_u16(value: u16) -> RotationRef {
+        if value & Self::PIVOT_FLAG != 0 {
+            RotationRef::Pivot(value & !Self::PIVOT_FLAG)
+        }
+        else {
+            RotationRef::Basis(value)
+        }
+    }
+
+    pub fn to_u16(&self) -> u16 {
+        match *self {
+            RotationRef::Pivot(index) => index | Self::PIVOT_FLAG,
+            RotationRef::Basis(index) => index
+        }
+    }
+
+    fn pivot(index: usize) -> Result<RotationRef, AppError> {
+        if index >= Self::PIVOT_FLAG as usize {
+            return Err(AppError::new(&format!("Too many pivot rotations ({})", index + 1)));
+        }
+
+        Ok(RotationRef::Pivot(index as u16))
+    }
+
+    fn basis(index: usize) -> Result<RotationRef, AppError> {
+        if index >= Self::PIVOT_FLAG as usize {
+            return Err(AppError::new(&format!("Too many basis rotations ({})", index + 1)));
+        }
+
+        Ok(RotationRef::Basis(index as u16))
+    }
+}
+
+// Rotation matrix packed in 5 halfwords. The top 13 bits of each are 5 elements of the second and third rows, the low
+// 3 bits of all of them make the remaining one, and the first row is the cross product of the other two
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BasisRotation {
+    data: [u16; 5]
+}
+
+impl BasisRotation {
+    pub const SIZE: usize = 10;
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<BasisRotation, AppError> {
+        if bytes.len() < Self::SIZE {
+            return Err(AppError::new(&format!("Basis rotation needs {} bytes", Self::SIZE)));
+        }
+
+        let mut data = [0u16; 5];
+        for (i, value) in data.iter_mut().enumerate() {
+            *value = u16::from_le_bytes([bytes[i * 2], bytes[i * 2 + 1]]);
+        }
+
+        Ok(BasisRotation { data })
+    }
+
+    // Any rotation matrix (row-major) without ±1 elements, which do not fit the 13 bits of the stored elements
+    pub fn from_matrix(matrix: &[f32; 9]) -> BasisRotation {
+        let element = |value: f32| (Fixed1_3_12::from_f32(value).to_i16().clamp(-4096, 4095) << 3) as u16;
+        let packed = (Fixed1_3_12::from_f32(matrix[5]).to_i16().clamp(-16384, 16383) as u16) & 0x7FFF;
+
+        let elements = [matrix[3], matrix[4], matrix[6], matrix[7], matrix[8]];
+        let data = std::array::from_fn(|i| element(elements[i]) | ((packed >> (3 * (4 - i))) & 0x07));
+
+        BasisRotation { data }
+    }
+
+    pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
+        if buffer.len() < Self::SIZE {
+            return Err(AppError::new("Buffer is too small to write basis rotation"));
+        }
+
+        for (i, value) in self.data.iter().enumerate() {
+            buffer[i * 2..i * 2 + 2].copy_from_slice(&value.to_le_bytes());
+        }
+
+        Ok(())
+    }
+
+    // Row-major 3x3 matrix
+    pub fn to_matrix(&self) -> [f32; 9] {
+        let element = |value: u16| Fixed1_3_12::from_i16((value as i16) >> 3).to_f32();
+        let low_bits = self.data.iter().fold(0u16, |bits, &value| (bits << 3) | (value & 0x07));
+        // 15 bits, sign extended
+        let packed = Fixed1_3_12::from_i16(((low_bits << 1) as i16) >> 1).to_f32();
+
+        let row_1 = [element(self.data[0]), element(self.data[1]), packed];
+        let row_2 = [element(self.data[2]), element(self.data[3]), element(self.data[4])];
+        let row_0 = [
+            row_1[1] * row_2[2] - row_1[2] * row_2[1],
+            row_1[2] * row_2[0] - row_1[0] * row_2[2],
+            row_1[0] * row_2[1] - row_1[1] * row_2[0]
+        ];
+
+        [
+            row_0[0], row_0[1], row_0[2],
+            row_1[0], row_1[1], row_1[2],
+            row_2[0], row_2[1], row_2[2]
+        ]
+    }
+}
+
+impl JntAnimation {
+    const STAMP: &'static [u8; 4] = b"J\0AC";
+    const HEADER_SIZE: usize = 0x14; // Without the node tag offsets
+
+    // Node tag flags
+    const TAG_IDENTITY: u32 = 0x0001;
+    const TAG_IDENTITY_T: u32 = 0x0002;
+    const TAG_BASE_T: u32 = 0x0004;
+    const TAG_CONST_T: u32 = 0x0008; // X, then Y and Z in the next bits
+    const TAG_IDENTITY_R: u32 = 0x0040;
+    const TAG_BASE_R: u32 = 0x0080;
+    const TAG_CONST_R: u32 = 0x0100;
+    const TAG_IDENTITY_S: u32 = 0x0200;
+    const TAG_BASE_S: u32 = 0x0400;
+    const TAG_CONST_S: u32 = 0x0800; // X, then Y and Z in the next bits
+
+    // Keyframe info fields
+    const INFO_START_FRAME_MASK: u32 = 0x0000FFFF;
+    const INFO_LAST_INTERP_MASK: u32 = 0x1FFF0000;
+    const INFO_LAST_INTERP_SHIFT: u32 = 16;
+    const INFO_FX16: u32 = 0x20000000;
+    const INFO_STEP_SHIFT: u32 = 30;
+
+    // Option flags
+    const FLAG_END_TO_START_INTERPOLATION: u32 = 0x1;
+
+    pub fn from_bytes(bytes: &[u8], debug_info: DebugInfo) -> Result<JntAnimation, AppError> {
+        if bytes.len() < Self::HEADER_SIZE {
+            return Err(AppError::new(&format!("JNT animation needs at least (20 | 0x14) bytes to start reading, got {}", bytes.len())));
+        }
+
+        let stamp = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        if &stamp != Self::STAMP {
+            return Err(AppError::new(&format!("Invalid JNT animation stamp: {:?}", stamp)));
+        }
+
+        let num_frames = u16::from_le_bytes([bytes[4], bytes[5]]);
+        let num_nodes = u16::from_le_bytes([bytes[6], bytes[7]]) as usize;
+        let flags = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
+        let pivot_rotations_offset = u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]);
+        let basis_rotations_offset = u32::from_le_bytes([bytes[16], bytes[17], bytes[18], bytes[19]]);
+
+        let mut tag_offsets = Vec::with_capacity(num_nodes);
+        let mut keyframe_offsets = Vec::new();
+        let mut tracks = Vec::with_capacity(num_nodes);
+        for node in 0..num_nodes {
+            let tag_offset = read_u16(bytes, Self::HEADER_SIZE + node * 2)?;
+            tracks.push(Self::read_track(bytes, tag_offset as usize, num_frames, &mut keyframe_offsets)?);
+            tag_offsets.push(tag_offset);
+        }
+
+        // The tables have no count, so they span up to the last index used
+        let (mut num_pivot_rotations, mut num_basis_rotations) = (0usize, 0usize);
+        for track in &tracks {
+            let JointComponent::Animated(channel) = &track.rotation else {
+                continue;
+            };
+
+            let rotations = match channel {
+                Channel::Constant(rotation) => std::slice::from_ref(rotation),
+                Channel::Keyed(keyframes) => keyframes.values.as_slice()
+            };
+            for rotation in rotations {
+                match *rotation {
+                    RotationRef::Pivot(index) => num_pivot_rotations = num_pivot_rotations.max(index as usize + 1),
+                    RotationRef::Basis(index) => num_basis_rotations = num_basis_rotations.max(index as usize + 1)
+                }
+            }
+        }
+
+        let mut pivot_rotations = Vec::with_capacity(num_pivot_rotations);
+        for index in 0..num_pivot_rotations {
+            let offset = pivot_rotations_offset as usize + index * PivotRotation::SIZE;
+            pivot_rotations.push(PivotRotation::from_bytes(bytes.get(offset..).unwrap_or_default())?);
+        }
+
+        let mut basis_rotations = Vec::with_capacity(num_basis_rotations);
+        for index in 0..num_basis_rotations {
+            let offset = basis_rotations_offset as usize + index * BasisRotation::SIZE;
+            basis_rotations.push(BasisRotation::from_bytes(bytes.get(offset..).unwrap_or_default())?);
+        }
+
+        // The size is not stored, so it is where the last piece of data ends
+        let mut end = Self::HEADER_SIZE + num_nodes * 2;
+        for (track, &tag_offset) in tracks.iter().zip(&tag_offsets) {
+            end = end.max(tag_offset as usize + track.header_size());
+        }
+        let data_sizes = tracks.iter().flat_map(JointTrack::keyframe_data_sizes);
+        for (size, &offset) in data_sizes.zip(&keyframe_offsets) {
+            end = end.max(offset as usize + size);
+        }
+        if num_pivot_rotations > 0 {
+            end = end.max(pivot_rotations_offset as usize + num_pivot_rotations * PivotRotation::SIZE);
+        }
+        if num_basis_rotations > 0 {
+            end = end.max(basis_rotations_offset as usize + num_basis_rotations * BasisRotation::SIZE);
+        }
+
+        Ok(JntAnimation {
+            num_frames,
+            flags,
+            pivot_rotations_offset,
+            basis_rotations_offset,
+            tag_offsets,
+            keyframe_offsets,
+            size: get_4_byte_alignment(end) as u32,
+            tracks,
+            pivot_rotations,
+            basis_rotations,
+            _debug_info: debug_info
+        })
+    }
+
+    fn read_track(bytes: &[u8], offset: usize, num_frames: u16, keyframe_offsets: &mut Vec<u32>) -> Result<JointTrack, AppError> {
+        let tag = read_u32(bytes, offset)?;
+        let node_id = (tag >> 24) as u8;
+        let mut offset = offset + 4;
+
+        if tag & Self::TAG_IDENTITY != 0 {
+            return Ok(JointTrack {
+                node_id,
+                translation: JointComponent::Identity,
+                rotation: JointComponent::Identity,
+                scale: JointComponent::Identity
+            });
+        }
+
+        let translation = Self::read_component(tag, Self::TAG_IDENTITY_T, Self::TAG_BASE_T, || {
+            let x = Self::read_channel(bytes, &mut offset, tag & Self::TAG_CONST_T != 0, num_frames, keyframe_offsets)?;
+            let y = Self::read_channel(bytes, &mut offset, tag & (Self::TAG_CONST_T << 1) != 0, num_frames, keyframe_offsets)?;
+            let z = Self::read_channel(bytes, &mut offset, tag & (Self::TAG_CONST_T << 2) != 0, num_frames, keyframe_offsets)?;
+            Ok([x, y, z])
+        })?;
+
+        let rotation = Self::read_component(tag, Self::TAG_IDENTITY_R, Self::TAG_BASE_R, || {
+            Self::read_channel(bytes, &mut offset, tag & Self::TAG_CONST_R != 0, num_frames, keyframe_offsets)
+        })?;
+
+        let scale = Self::read_component(tag, Self::TAG_IDENTITY_S, Self::TAG_BASE_S, || {
+            let x = Self::read_channel(bytes, &mut offset, tag & Self::TAG_CONST_S != 0, num_frames, keyframe_offsets)?;
+            let y = Self::read_channel(bytes, &mut offset, tag & (Self::TAG_CONST_S << 1) != 0, num_frames, keyframe_offsets)?;
+            let z = Self::read_channel(bytes, &mut offset, tag & (Self::TAG_CONST_S << 2) != 0, num_frames, keyframe_offsets)?;
+            Ok([x, y, z])
+        })?;
+
+        Ok(JointTrack {
+            node_id,
+            translation,
+            rotation,
+            scale
+        })
+    }
+
+    fn read_component<T>(tag: u32, identity_flag: u32, base_flag: u32, read: impl FnOnce() -> Result<T, AppError>) -> Result<JointComponent<T>, AppError> {
+        if tag & identity_flag != 0 {
+            Ok(JointComponent::Identity)
+        }
+        else if tag & base_flag != 0 {
+            Ok(JointComponent::Base)
+        }
+        else {
+            Ok(JointComponent::Animated(read()?))
+        }
+    }
+
+    // Reads a constant, or the info and data offset of the keyframes and then their values. Advances offset past it
+    fn read_channel<T: ChannelValue>(bytes: &[u8], offset: &mut usize, constant: bool, num_frames: u16, keyframe_offsets: &mut Vec<u32>) -> Result<Channel<T>, AppError> {
+        if constant {
+            let value = T::read_constant(bytes, *offset)?;
+            *offset += T::CONSTANT_SIZE;
+            return Ok(Channel::Constant(value));
+        }
+
+        let info = read_u32(bytes, *offset)?;
+        let data_offset = read_u32(bytes, *offset + 4)?;
+        *offset += 8;
+
+        let start_frame = (info & Self::INFO_START_FRAME_MASK) as u16;
+        let last_interp_frame = ((info & Self::INFO_LAST_INTERP_MASK) >> Self::INFO_LAST_INTERP_SHIFT) as u16;
+        let step = KeyframeStep::from_shift(info >> Self::INFO_STEP_SHIFT)?;
+        let compact = info & Self::INFO_FX16 != 0;
+
+        let count = Keyframes::<T>::count(num_frames, start_frame, last_interp_frame, step);
+        let mut values = Vec::with_capacity(count);
+        for index in 0..count {
+            values.push(T::read(bytes, data_offset as usize + index * T::keyframe_size(compact), compact)?);
+        }
+
+        keyframe_offsets.push(data_offset);
+
+        Ok(Channel::Keyed(Keyframes {
+            start_frame,
+            last_interp_frame,
+            step,
+            compact,
+            values
+        }))
+    }
+
+    pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
+        if buffer.len() < self.size as usize {
+            return Err(AppError::new("Buffer is too small to write JNT animation"));
+        }
+
+        let num_keyed_channels: usize = self.tracks.iter().map(|track| track.keyframe_data_sizes().len()).sum();
+        if self.tag_offsets.len() != self.tracks.len() || self.keyframe_offsets.len() != num_keyed_channels {
+            return Err(AppError::new("JNT animation must be rebased before writing"));
+        }
+
+        buffer[0..4].copy_from_slice(Self::STAMP);
+        buffer[4..6].copy_from_slice(&self.num_frames.to_le_bytes());
+        buffer[6..8].copy_from_slice(&(self.tracks.len() as u16).to_le_bytes());
+        buffer[8..12].copy_from_slice(&self.flags.to_le_bytes());
+        buffer[12..16].copy_from_slice(&self.pivot_rotations_offset.to_le_bytes());
+        buffer[16..20].copy_from_slice(&self.basis_rotations_offset.to_le_bytes());
+
+        let mut keyframe_offsets = self.keyframe_offsets.iter().copied();
+        for (i, (track, &tag_offset)) in self.tracks.iter().zip(&self.tag_offsets).enumerate() {
+            let offset = Self::HEADER_SIZE + i * 2;
+            buffer[offset..offset + 2].copy_from_slice(&tag_offset.to_le_bytes());
+
+            let mut offset = tag_offset as usize;
+            buffer[offset..offset + 4].copy_from_slice(&self.track_tag(track).to_le_bytes());
+            offset += 4;
+
+            if let JointComponent::Animated(axes) = &track.translation {
+                for channel in axes {
+                    Self::write_channel(buffer, &mut offset, channel, &mut keyframe_offsets);
+                }
+            }
+            if let JointComponent::Animated(channel) = &track.rotation {
+                Self::write_channel(buffer, &mut offset, channel, &mut keyframe_offsets);
+            }
+            if let JointComponent::Animated(axes) = &track.scale {
+                for channel in axes {
+                    Self::write_channel(buffer, &mut offset, channel, &mut keyframe_offsets);
+                }
+            }
+        }
+
+        for (i, rotation) in self.pivot_rotations.iter().enumerate() {
+            let offset = self.pivot_rotations_offset as usize + i * PivotRotation::SIZE;
+            rotation.write_bytes(&mut buffer[offset..])?;
+        }
+
+        for (i, rotation) in self.basis_rotations.iter().enumerate() {
+            let offset = self.basis_rotations_offset as usize + i * BasisRotation::SIZE;
+            rotation.write_bytes(&mut buffer[offset..])?;
+        }
+
+        Ok(())
+    }
+
+    // Writes a constant, or the info and data offset of the keyframes and then their values. Advances offset past it
+    fn write_channel<T: ChannelValue>(buffer: &mut [u8], offset: &mut usize, channel: &Channel<T>, keyframe_offsets: &mut impl Iterator<Item = u32>) {
+        match channel {
+            Channel::Constant(value) => {
+                value.write_constant(buffer, *offset);
+                *offset += T::CONSTANT_SIZE;
+            },
+            Channel::Keyed(keyframes) => {
+                let data_offset = keyframe_offsets.next().unwrap();
+                let info = keyframes.start_frame as u32 |
+                    ((keyframes.last_interp_frame as u32) << Self::INFO_LAST_INTERP_SHIFT & Self::INFO_LAST_INTERP_MASK) |
+                    if keyframes.compact { Self::INFO_FX16 } else { 0 } |
+                    (keyframes.step.shift() << Self::INFO_STEP_SHIFT);
+
+                buffer[*offset..*offset + 4].copy_from_slice(&info.to_le_bytes());
+                buffer[*offset + 4..*offset + 8].copy_from_slice(&data_offset.to_le_bytes());
+                *offset += 8;
+
+                for (index, value) in keyframes.values.iter().enumerate() {
+                    value.write(buffer, data_offset as usize + index * T::keyframe_size(keyframes.compact), keyframes.compact);
+                }
+            }
+        }
+    }
+
+    fn track_tag(&self, track: &JointTrack) -> u32 {
+        let mut tag = (track.node_id as u32) << 24;
+
+        tag |= match &track.translation {
+            JointComponent::Identity => Self::TAG_IDENTITY_T,
+            JointComponent::Base => Self::TAG_BASE_T,
+            JointComponent::Animated(axes) => (0..3)
+                .filter(|&axis| matches!(axes[axis], Channel::Constant(_)))
+                .fold(0, |flags, axis| flags | Self::TAG_CONST_T << axis)
+        };
+
+        tag |= match &track.rotation {
+            JointComponent::Identity => Self::TAG_IDENTITY_R,
+            JointComponent::Base => Self::TAG_BASE_R,
+            JointComponent::Animated(Channel::Constant(_)) => Self::TAG_CONST_R,
+            JointComponent::Animated(Channel::Keyed(_)) => 0
+        };
+
+        tag |= match &track.scale {
+            JointComponent::Identity => Self::TAG_IDENTITY_S,
+            JointComponent::Base => Self::TAG_BASE_S,
+            JointComponent::Animated(axes) => (0..3)
+                .filter(|&axis| matches!(axes[axis], Channel::Constant(_)))
+                .fold(0, |flags, axis| flags | Self::TAG_CONST_S << axis)
+        };
+
+        let identity = Self::TAG_IDENTITY_T | Self::TAG_IDENTITY_R | Self::TAG_IDENTITY_S;
+        if tag & identity == identity {
+            tag |= Self::TAG_IDENTITY;
+        }
+
+        tag
+    }
+
+    // Lays out the header, the node tag offsets, the tags with their channels, the keyframe data and the rotation tables
+    pub fn rebase(&mut self) {
+        let mut offset = get_4_byte_alignment(Self::HEADER_SIZE + self.tracks.len() * 2);
+
+        self.tag_offsets.clear();
+        for track in &self.tracks {
+            self.tag_offsets.push(offset as u16);
+            offset += track.header_size();
+        }
+
+        self.keyframe_offsets.clear();
+        for size in self.tracks.iter().flat_map(JointTrack::keyframe_data_sizes) {
+            self.keyframe_offsets.push(offset as u32);
+            offset += size;
+        }
+
+        self.pivot_rotations_offset = offset as u32;
+        offset += get_4_byte_alignment(self.pivot_rotations.len() * PivotRotation::SIZE);
+
+        self.basis_rotations_offset = offset as u32;
+        offset += get_4_byte_alignment(self.basis_rotations.len() * BasisRotation::SIZE);
+
+        self.size = offset as u32;
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, AppError> {
+        let mut bytes = vec![0u8; self.size as usize]; // write buffer
+
+        self.write_bytes(&mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    pub fn size(&self) -> usize {
+        self.size as usize
+    }
+
+    pub fn builder(num_frames: u16) -> JntAnimationBuilder {
+        JntAnimationBuilder::new(num_frames)
+    }
+
+    pub fn num_frames(&self) -> u16 {
+        self.num_frames
+    }
+
+    // Whether the last frame is interpolated towards the first one, so the animation loops smoothly
+    pub fn interpolates_end_to_start(&self) -> bool {
+        self.flags & Self::FLAG_END_TO_START_INTERPOLATION != 0
+    }
+
+    pub fn tracks(&self) -> &[JointTrack] {
+        &self.tracks
+    }
+
+    // Tracks can be edited but not added or removed. The animation must be rebased afterwards if channels change
+    pub fn tracks_mut(&mut self) -> &mut [JointTrack] {
+        &mut self.tracks
+    }
+
+    pub fn track_for_node(&self, node_id: u8) -> Option<&JointTrack> {
+        self.tracks.iter().find(|track| track.node_id == node_id)
+    }
+
+    pub fn pivot_rotations(&self) -> &[PivotRotation] {
+        &self.pivot_rotations
+    }
+
+    pub fn basis_rotations(&self) -> &[BasisRotation] {
+        &self.basis_rotations
+    }
+
+    // Pose of a bone of the model at a frame, interpolating between keyframes. Base components and bones without a
+    // track take the pose of the bone in the model
+    pub fn sample(&self, model: &Model, bone_name: &str, frame: f32) -> Result<Trs, AppError> {
+        let bone_list = model.get_bone_list();
+        let node_id = (0..bone_list.len())
+            .find(|&index| bone_list.get_name(index).is_some_and(|name| name.to_not_null_string().is_ok_and(|name| name == bone_name)))
+            .ok_or_else(|| AppError::new(&format!("Model has no bone named {}", bone_name)))?;
+
+        let base = bone_list.get_bone_matrix(node_id).unwrap().to_trs();
+        self.sample_node(node_id as u8, &base, frame)
+    }
+
+    // Pose of a node at a frame, given the pose of its bone in the model. Frames are clamped to the animation; past
+    // the last one, the animation holds it or, if it interpolates end to start, blends it towards the first one
+    pub fn sample_node(&self, node_id: u8, base: &Trs, frame: f32) -> Result<Trs, AppError> {
+        let Some(track) = self.track_for_node(node_id) else {
+            return Ok(*base);
+        };
+
+        let frame = frame.clamp(0.0, self.num_frames as f32);
+        let mut trs = Trs::identity();
+
+        match &track.translation {
+            JointComponent::Identity => {},
+            JointComponent::Base => trs.translation = base.translation,
+            JointComponent::Animated(axes) => {
+                for (axis, channel) in axes.iter().enumerate() {
+                    trs.translation[axis] = channel.sample(frame, self.interpolates_end_to_start(), |value| Ok(value.to_f32()), |a, b, t| a + (b - a) * t)?;
+                }
+            }
+        }
+
+        match &track.rotation {
+            JointComponent::Identity => {},
+            JointComponent::Base => trs.rotation = base.rotation,
+            JointComponent::Animated(channel) => {
+                trs.rotation = channel.sample(frame, self.interpolates_end_to_start(), |&rotation| self.resolve_rotation(rotation), |a, b, t| {
+                    lerp_rotation(&a, &b, t)
+                })?;
+            }
+        }
+
+        match &track.scale {
+            JointComponent::Identity => {},
+            JointComponent::Base => trs.scale = base.scale,
+            JointComponent::Animated(axes) => {
+                for (axis, channel) in axes.iter().enumerate() {
+                    trs.scale[axis] = channel.sample(frame, self.interpolates_end_to_start(), |value| Ok(value.scale.to_f32()), |a, b, t| a + (b - a) * t)?;
+                }
+            }
+        }
+
+        Ok(trs)
+    }
+
+    // Posed bone matrices of the model at a frame, for ModelRenderCmdExecutor::set_pose (or PosedMeshExtractor).
+    // Bones without a track are None, so they keep their rest pose
+    pub fn pose(&self, model: &Model, frame: f32) -> Result<Vec<Option<BoneMatrix>>, AppError> {
+        let bone_list = model.get_bone_list();
+
+        let mut pose = Vec::with_capacity(bone_list.len());
+        for node_id in 0..bone_list.len() {
+            if self.track_for_node(node_id as u8).is_none() {
+                pose.push(None);
+                continue;
+            }
+
+            let base = bone_list.get_bone_matrix(node_id).unwrap().to_trs();
+            let trs = self.sample_node(node_id as u8, &base, frame)
+                .map_err(|err| AppError::new(&format!("Node {}: {}", node_id, err.message())))?;
+            pose.push(Some(BoneMatrix::from_trs(&trs)));
+        }
+
+        Ok(pose)
+    }
+
+    // Posed bone matrices (see pose) of a blend towards another animation: 0 is this one, 1 the other one, with rotations
+    // interpolated spherically (see Trs::blend). Both play over the same time, so the other one is sampled at the same
+    // point of its own frames
+    pub fn blend_pose(&self, other: &JntAnimation, model: &Model, frame: f32, weight: f32) -> Result<Vec<Option<BoneMatrix>>, AppError> {
+        let bone_list = model.get_bone_list();
+        let weight = weight.clamp(0.0, 1.0);
+
+        let mut pose = Vec::with_capacity(bone_list.len());
+        for node_id in 0..bone_list.len() {
+            if self.track_for_node(node_id as u8).is_none() && other.track_for_node(node_id as u8).is_none() {
+                pose.push(None);
+                continue;
+            }
+
+            let base = bone_list.get_bone_matrix(node_id).unwrap().to_trs();
+            let trs = self.blend_node(other, node_id as u8, &base, frame, weight)
+                .map_err(|err| AppError::new(&format!("Node {}: {}", node_id, err.message())))?;
+            pose.push(Some(BoneMatrix::from_trs(&trs)));
+        }
+
+        Ok(pose)
+    }
+
+    // Blend towards another animation (see blend_pose) baked into a new one, with the frames of this one. The
+    // model gives the base pose of the nodes animated by only one of them
+    pub fn blend(&self, other: &JntAnimation, model: &Model, weight: f32) -> Result<JntAnimation, AppError> {
+        if self.num_frames == 0 {
+            return Err(AppError::new("Cannot blend an animation with 0 frames"));
+        }
+
+        let bone_list = model.get_bone_list();
+        let weight = weight.clamp(0.0, 1.0);
+
+        let mut node_ids: Vec<u8> = self.tracks.iter().chain(&other.tracks).map(|track| track.node_id).collect();
+        node_ids.sort_unstable();
+        node_ids.dedup();
+
+        let mut pivot_rotations = Vec::new();
+        let mut basis_rotations = Vec::new();
+
+        let mut tracks = Vec::with_capacity(node_ids.len());
+        for node_id in node_ids {
+            let base = bone_list.get_bone_matrix(node_id as usize)
+                .ok_or_else(|| AppError::new(&format!("Node {} is not a bone of the model. Model has {} bones", node_id, bone_list.len())))?
+                .to_trs();
+
+            let mut poses = Vec::with_capacity(self.num_frames as usize);
+            for frame in 0..self.num_frames {
+                poses.push(self.blend_node(other, node_id, &base, frame as f32, weight)?);
+            }
+
+            let (a, b) = (self.track_for_node(node_id), other.track_for_node(node_id));
+
+            let translation = match static_component(a.map(|track| &track.translation), b.map(|track| &track.translation)) {
+                Some(component) => component,
+                None => JointComponent::Animated(try_map_axes(&[0, 1, 2], |&axis| translation_channel(poses.iter().map(|trs| trs.translation[axis]).collect()))?)
+            };
+
+            let rotation = match static_component(a.map(|track| &track.rotation), b.map(|track| &track.rotation)) {
+                Some(component) => component,
+                None => {
+                    let values: Vec<[f32; 9]> = poses.iter().map(|trs| trs.rotation).collect();
+                    JointComponent::Animated(rotation_channel(&values, &mut pivot_rotations, &mut basis_rotations)?)
+                }
+            };
+
+            let scale = match static_component(a.map(|track| &track.scale), b.map(|track| &track.scale)) {
+                Some(component) => component,
+                None => JointComponent::Animated(try_map_axes(&[0, 1, 2], |&axis| scale_channel(poses.iter().map(|trs| trs.scale[axis]).collect()))?)
+            };
+
+            tracks.push(JointTrack {
+                node_id,
+                translation,
+                rotation,
+                scale
+            });
+        }
+
+        let mut animation = JntAnimation {
+            num_frames: self.num_frames,
+            flags: self.flags,
+            pivot_rotations_offset: 0,
+            basis_rotations_offset: 0,
+            tag_offsets: Vec::new(),
+            keyframe_offsets: Vec::new(),
+            size: 0,
+            tracks,
+            pivot_rotations,
+            basis_rotations,
+            _debug_info: self._debug_info.clone()
+        };
+
+        animation.rebase();
+        Ok(animation)
+    }
+
+    // Pose of a node blended towards another animation, which is sampled at the same point of its own frames
+    fn blend_node(&self, other: &JntAnimation, node_id: u8, base: &Trs, frame: f32, weight: f32) -> Result<Trs, AppError> {
+        let other_frame = frame * other.num_frames as f32 / self.num_frames.max(1) as f32;
+
+        let trs = self.sample_node(node_id, base, frame)?;
+        let other_trs = other.sample_node(node_id, base, other_frame)?;
+        Ok(trs.blend(&other_trs, weight))
+    }
+
+    // Row-major 3x3 rotation matrix of a rotation table entry
+    pub fn rotation_matrix(&self, rotation: RotationRef) -> Option<[f32; 9]> {
+        match rotation {
+            RotationRef::Pivot(index) => self.pivot_rotations.get(index as usize).map(PivotRotation::to_matrix),
+            RotationRef::Basis(index) => self.basis_rotations.get(index as usize).map(BasisRotation::to_matrix)
+        }
+    }
+
+    fn resolve_rotation(&self, rotation: RotationRef) -> Result<[f32; 9], AppError> {
+        self.rotation_matrix(rotation)
+            .ok_or_else(|| AppError::new(&format!("Rotation {:?} is not in the rotation tables", rotation)))
+    }
+
+    // Every joint track re-interpolated over num_frames frames (see resampled_frames)
+    pub fn resample(&self, num_frames: u16) -> Result<JntAnimation, AppError> {
+        self.sampled_at(&resampled_frames(self.num_frames, num_frames)?, self.flags)
+    }
+
+    // See frame_rate_frames
+    pub fn resample_frame_rate(&self, fps: f32, new_fps: f32) -> Result<JntAnimation, AppError> {
+        self.sampled_at(&frame_rate_frames(self.num_frames, fps, new_fps)?, self.flags)
+    }
+
+    // Frames start_frame to end_frame (exclusive) as an animation of their own, starting at frame 0 (see sliced_frames)
+    pub fn slice(&self, start_frame: u16, end_frame: u16) -> Result<JntAnimation, AppError> {
+        let (frames, whole) = sliced_frames(self.num_frames, start_frame, end_frame)?;
+        let flags = if whole { self.flags } else { self.flags & !Self::FLAG_END_TO_START_INTERPOLATION };
+
+        self.sampled_at(&frames, flags)
+    }
+
+    // Animation with a frame per sample of this one at the given frames. The rotation tables are rebuilt with the
+    // rotations still in use
+    fn sampled_at(&self, frames: &[f32], flags: u32) -> Result<JntAnimation, AppError> {
+        let end_to_start = self.interpolates_end_to_start();
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let mut pivot_rotations = Vec::new();
+        let mut basis_rotations = Vec::new();
+
+        let mut tracks = Vec::with_capacity(self.tracks.len());
+        for track in &self.tracks {
+            let translation = match &track.translation {
+                JointComponent::Animated(axes) => JointComponent::Animated(try_map_axes(axes, |channel| {
+                    translation_channel(channel.sample_frames(frames, end_to_start, |value| Ok(value.to_f32()), lerp)?)
+                })?),
+                other => other.clone()
+            };
+
+            let rotation = match &track.rotation {
+                JointComponent::Animated(channel) => {
+                    let values = channel.sample_frames(frames, end_to_start, |&rotation| self.resolve_rotation(rotation), |a, b, t| {
+                        lerp_rotation(&a, &b, t)
+                    })?;
+                    JointComponent::Animated(rotation_channel(&values, &mut pivot_rotations, &mut basis_rotations)?)
+                },
+                other => other.clone()
+            };
+
+            let scale = match &track.scale {
+                JointComponent::Animated(axes) => JointComponent::Animated(try_map_axes(axes, |channel| {
+                    scale_channel(channel.sample_frames(frames, end_to_start, |value| Ok(value.scale.to_f32()), lerp)?)
+                })?),
+                other => other.clone()
+            };
+
+            tracks.push(JointTrack {
+                node_id: track.node_id,
+                translation,
+                rotation,
+                scale
+            });
+        }
+
+        let mut animation = JntAnimation {
+            num_frames: frames.len() as u16,
+            flags,
+            pivot_rotations_offset: 0,
+            basis_rotations_offset: 0,
+            tag_offsets: Vec::new(),
+            keyframe_offsets: Vec::new(),
+            size: 0,
+            tracks,
+            pivot_rotations,
+            basis_rotations,
+            _debug_info: self._debug_info.clone()
+        };
+
+        animation.rebase();
+        Ok(animation)
+    }
+}
+
+impl SubfileAnimation for JntAnimation {
+    const STAMP: [u8; 4] = *b"JNT0";
+
+    fn from_bytes(bytes: &[u8], debug_info: DebugInfo) -> Result<JntAnimation, AppError> {
+        JntAnimation::from_bytes(bytes, debug_info)
+    }
+
+    fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
+        JntAnimation::write_bytes(self, buffer)
+    }
+
+    fn rebase(&mut self) {
+        JntAnimation::rebase(self)
+    }
+
+    fn size(&self) -> usize {
+        JntAnimation::size(self)
+    }
+}
+
+impl JointTrack {
+    // The tag and the constants or keyframe infos of its channels
+    fn header_size(&self) -> usize {
+        let mut size = 4;
+
+        if let JointComponent::Animated(axes) = &self.translation {
+            size += axes.iter().map(channel_header_size).sum::<usize>();
+        }
+        if let JointComponent::Animated(channel) = &self.rotation {
+            size += channel_header_size(channel);
+        }
+        if let JointComponent::Animated(axes) = &self.scale {
+            size += axes.iter().map(channel_header_size).sum::<usize>();
+        }
+
+        size
+    }
+
+    // Size of the values of each keyed channel, 4 byte aligned
+    fn keyframe_data_sizes(&self) -> Vec<usize> {
+        let mut sizes = Vec::new();
+
+        if let JointComponent::Animated(axes) = &self.translation {
+            sizes.extend(axes.iter().filter_map(Channel::keyframe_data_size));
+        }
+        if let JointComponent::Animated(channel) = &self.rotation {
+            sizes.extend(channel.keyframe_data_size());
+        }
+        if let JointComponent::Animated(axes) = &self.scale {
+            sizes.extend(axes.iter().filter_map(Channel::keyframe_data_size));
+        }
+
+        sizes
+    }
+}
+
+// The constant or the keyframe info and data offset
+fn channel_header_size<T: ChannelValue>(channel: &Channel<T>) -> usize {
+    match channel {
+        Channel::Constant(_) => T::CONSTANT_SIZE,
+        Channel::Keyed(_) => 8
+    }
+}
+
+impl ChannelValue for ScaleValue {
+    const CONSTANT_SIZE: usize = 8;
+
+    fn keyframe_size(compact: bool) -> usize {
+        Fixed1_19_12::keyframe_size(compact) * 2
+    }
+
+    fn read(bytes: &[u8], offset: usize, compact: bool) -> Result<Self, AppError> {
+        Ok(ScaleValue {
+            scale: Fixed1_19_12::read(bytes, offset, compact)?,
+            inverse: Fixed1_19_12::read(bytes, offset + Fixed1_19_12::keyframe_size(compact), compact)?
+        })
+    }
+
+    fn write(&self, buffer: &mut [u8], offset: usize, compact: bool) {
+        self.scale.write(buffer, offset, compact);
+        self.inverse.write(buffer, offset + Fixed1_19_12::keyframe_size(compact), compact);
+    }
+}
+
+impl ChannelValue for RotationRef {
+    const CONSTANT_SIZE: usize = 4; // The index, padded
+
+    fn keyframe_size(_compact: bool) -> usize {
+        2
+    }
+
+    fn read(bytes: &[u8], offset: usize, _compact: bool) -> Result<Self, AppError> {
+        Ok(RotationRef::from_u16(read_u16(bytes, offset)?))
+    }
+
+    fn write(&self, buffer: &mut [u8], offset: usize, _compact: bool) {
+        buffer[offset..offset + 2].copy_from_slice(&self.to_u16().to_le_bytes());
+    }
+
+    fn write_constant(&self, buffer: &mut [u8], offset: usize) {
+        buffer[offset..offset + 4].copy_from_slice(&(self.to_u16() as u32).to_le_bytes());
+    }
+}
+
+// Builds a joint animation from scratch. Keyframes are pushed per bone at any frame and resampled to every frame
+// (linear interpolation, holding the first and last keys). Components of a track without keyframes keep the pose of
+// the bone in the model. Rotations with a ±1 element are pivot-compressed, the rest go to the basis table
+#[derive(Debug)]
+pub struct JntAnimationBuilder {
+    num_frames: u16,
+    flags: u32,
+    tracks: Vec<TrackKeys>,
+    error: Option<AppError> // First error found by a setter, returned by build
+}
+
+#[derive(Debug)]
+struct TrackKeys {
+    node_id: u8,
+    translation: Vec<(u16, [f32; 3])>,
+    rotation: Vec<(u16, [f32; 9])>,
+    scale: Vec<(u16, [f32; 3])>
+}
+
+impl JntAnimationBuilder {
+    fn new(num_frames: u16) -> JntAnimationBuilder {
+        JntAnimationBuilder {
+            num_frames,
+            flags: 0,
+            tracks: Vec::new(),
+            error: None
+        }
+    }
+
+    pub fn num_frames(mut self, num_frames: u16) -> Self {
+        self.num_frames = num_frames;
+        self
+    }
+
+    // Interpolate the last frame towards the first one, so the animation loops smoothly
+    pub fn end_to_start_interpolation(mut self, enabled: bool) -> Self {
+        if enabled {
+            self.flags |= JntAnimation::FLAG_END_TO_START_INTERPOLATION;
+        }
+        else {
+            self.flags &= !JntAnimation::FLAG_END_TO_START_INTERPOLATION;
+        }
+
+        self
+    }
+
+    // Adds a track for a node (bone index of the model), with every component in the pose of the bone
+    pub fn track(mut self, node_id: u8) -> Self {
+        if self.tracks.iter().any(|track| track.node_id == node_id) {
+            return self.keep_error(Err(AppError::new(&format!("Node {} already has a track", node_id))));
+        }
+
+        self.tracks.push(TrackKeys {
+            node_id,
+            translation: Vec::new(),
+            rotation: Vec::new(),
+            scale: Vec::new()
+        });
+
+        self
+    }
+
+    pub fn translation_key(mut self, node_id: u8, frame: u16, translation: [f32; 3]) -> Self {
+        let result = self.track_keys(node_id).map(|track| insert_key(&mut track.translation, frame, translation));
+        self.keep_error(result)
+    }
+
+    // Row-major 3x3 rotation matrix
+    pub fn rotation_key(mut self, node_id: u8, frame: u16, rotation: [f32; 9]) -> Self {
+        let result = self.track_keys(node_id).map(|track| insert_key(&mut track.rotation, frame, rotation));
+        self.keep_error(result)
+    }
+
+    pub fn scale_key(mut self, node_id: u8, frame: u16, scale: [f32; 3]) -> Self {
+        let result = if scale.contains(&0.0) {
+            Err(AppError::new(&format!("Scale of node {} at frame {} has a zero component", node_id, frame)))
+        } else {
+            self.track_keys(node_id).map(|track| insert_key(&mut track.scale, frame, scale))
+        };
+        self.keep_error(result)
+    }
+
+    pub fn build(self) -> Result<JntAnimation, AppError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+
+        if self.num_frames == 0 {
+            return Err(AppError::new("Joint animation needs at least one frame"));
+        }
+
+        let num_frames = self.num_frames;
+        let mut pivot_rotations: Vec<PivotRotation> = Vec::new();
+        let mut basis_rotations: Vec<BasisRotation> = Vec::new();
+
+        let mut tracks = Vec::with_capacity(self.tracks.len());
+        for keys in &self.tracks {
+            let out_of_range = keys.translation.iter().map(|key| key.0)
+                .chain(keys.rotation.iter().map(|key| key.0))
+                .chain(keys.scale.iter().map(|key| key.0))
+                .find(|&frame| frame >= num_frames);
+            if let Some(frame) = out_of_range {
+                return Err(AppError::new(&format!("Node {} has a keyframe at frame {}, but the animation has {} frames", keys.node_id, frame, num_frames)));
+            }
+
+            let translation = match resample_keys(&keys.translation, num_frames, lerp_array) {
+                Some(values) => JointComponent::Animated(try_map_axes(&[0, 1, 2], |&axis| {
+                    translation_channel(values.iter().map(|value| value[axis]).collect())
+                })?),
+                None => JointComponent::Base
+            };
+
+            let rotation = match resample_keys(&keys.rotation, num_frames, lerp_rotation) {
+                Some(values) => JointComponent::Animated(rotation_channel(&values, &mut pivot_rotations, &mut basis_rotations)?),
+                None => JointComponent::Base
+            };
+
+            let scale = match resample_keys(&keys.scale, num_frames, lerp_array) {
+                Some(values) => JointComponent::Animated(try_map_axes(&[0, 1, 2], |&axis| {
+                    scale_channel(values.iter().map(|value| value[axis]).collect())
+                })?),
+                None => JointComponent::Base
+            };
+
+            tracks.push(JointTrack {
+                node_id: keys.node_id,
+                translation,
+                rotation,
+                scale
+            });
+        }
+
+        let mut animation = JntAnimation {
+            num_frames,
+            flags: self.flags,
+            pivot_rotations_offset: 0,
+            basis_rotations_offset: 0,
+            tag_offsets: Vec::new(),
+            keyframe_offsets: Vec::new(),
+            size: 0,
+            tracks,
+            pivot_rotations,
+            basis_rotations,
+            _debug_info: DebugInfo { offset: 0 }
+        };
+
+        animation.rebase();
+        Ok(animation)
+    }
+
+    fn track_keys(&mut self, node_id: u8) -> Result<&mut TrackKeys, AppError> {
+        self.tracks.iter_mut()
+            .find(|track| track.node_id == node_id)
+            .ok_or_else(|| AppError::new(&format!("Node {} has no track", node_id)))
+    }
+
+    fn keep_error(mut self, result: Result<(), AppError>) -> Self {
+        if let (Err(error), None) = (result, &self.error) {
+            self.error = Some(error);
+        }
+
+        self
+    }
+}
+
+// Identity or Base if both components are the same of those (a node without a track keeps its base pose), None if
+// the blend of them has to be animated
+fn static_component<T, U>(a: Option<&JointComponent<T>>, b: Option<&JointComponent<T>>) -> Option<JointComponent<U>> {
+    let is_identity = |component: Option<&JointComponent<T>>| match component {
+        None | Some(JointComponent::Base) => Some(false),
+        Some(JointComponent::Identity) => Some(true),
+        Some(JointComponent::Animated(_)) => None
+    };
+
+    match (is_identity(a)?, is_identity(b)?) {
+        (true, true) => Some(JointComponent::Identity),
+        (false, false) => Some(JointComponent::Base),
+        _ => None
+    }
+}
+
+// Channels of the 3 axes of a component mapped one by one, stopping at the first error
+fn try_map_axes<T, U>(axes: &[T; 3], f: impl Fn(&T) -> Result<U, AppError>) -> Result<[U; 3], AppError> {
+    Ok([f(&axes[0])?, f(&axes[1])?, f(&axes[2])?])
+}
+
+// Channels from one value per frame (compact when every value fits in fx16)
+fn translation_channel(values: Vec<f32>) -> Result<Channel<Fixed1_19_12>, AppError> {
+    Channel::from_values(values.into_iter().map(Fixed1_19_12::from_f32).collect(), |value| {
+        i16::try_from(value.to_i32()).is_ok()
+    })
+}
+
+fn scale_channel(values: Vec<f32>) -> Result<Channel<ScaleValue>, AppError> {
+    Channel::from_values(values.into_iter().map(|value| ScaleValue {
+        scale: Fixed1_19_12::from_f32(value),
+        inverse: Fixed1_19_12::from_f32(1.0 / value)
+    }).collect(), |value| {
+        i16::try_from(value.scale.to_i32()).is_ok() && i16::try_from(value.inverse.to_i32()).is_ok()
+    })
+}
+
+fn rotation_channel(values: &[[f32; 9]], pivot_rotations: &mut Vec<PivotRotation>, basis_rotations: &mut Vec<BasisRotation>) -> Result<Channel<RotationRef>, AppError> {
+    let mut refs = Vec::with_capacity(values.len());
+    for value in values {
+        refs.push(add_rotation(value, pivot_rotations, basis_rotations)?);
+    }
+
+    Channel::from_values(refs, |_| false)
+}
+
+// Index of the rotation in the tables, adding it if needed
+fn add_rotation(rotation: &[f32; 9], pivot_rotations: &mut Vec<PivotRotation>, basis_rotations: &mut Vec<BasisRotation>) -> Result<RotationRef, AppError> {
+    if let Some(pivot) = PivotRotation::from_matrix(rotation) {
+        let index = pivot_rotations.iter().position(|other| *other == pivot).unwrap_or_else(|| {
+            pivot_rotations.push(pivot);
+            pivot_rotations.len() - 1
+        });
+
+        return RotationRef::pivot(index);
+    }
+
+    let basis = BasisRotation::from_matrix(rotation);
+    let index = basis_rotations.iter().position(|other| *other == basis).unwrap_or_else(|| {
+        basis_rotations.push(basis);
+        basis_rotations.len() - 1
+    });
+
+    RotationRef::basis(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subfiles::jnt::Jnt;
+
+    fn assert_close(a: &[f32], b: &[f32]) {
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).abs() < 1.0 / 1024.0, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn built_animation_round_trips_through_jnt0() {
+        let quarter_turn = [0.0, -1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0];
+        let animation = JntAnimation::builder(8)
+            .end_to_start_interpolation(true)
+            .track(0)
+            .translation_key(0, 0, [0.0, 1.0, 2.0])
+            .translation_key(0, 7, [4.0, 1.0, -2.0])
+            .rotation_key(0, 0, Trs::IDENTITY_ROTATION)
+            .rotation_key(0, 4, quarter_turn)
+            .track(2)
+            .scale_key(2, 0, [1.0, 2.0, 0.5])
+            .build()
+            .unwrap();
+
+        let mut jnt = Jnt::new();
+        jnt.add_animation("walk", animation).unwrap();
+        let bytes = jnt.to_bytes();
+
+        let parsed = Jnt::from_bytes(&bytes, DebugInfo { offset: 0 }).unwrap();
+        assert_eq!(parsed.to_bytes(), bytes);
+        assert_eq!(parsed.get_animation_name(0).unwrap().to_not_null_string().unwrap(), "walk");
+
+        let (original, parsed) = (jnt.get_animation(0).unwrap(), parsed.get_animation(0).unwrap());
+        assert_eq!(parsed.num_frames(), 8);
+        assert_eq!(parsed.to_bytes().unwrap(), original.to_bytes().unwrap());
+        assert!(parsed.interpolates_end_to_start());
+        assert_eq!(parsed.tracks().iter().map(|track| track.node_id).collect::<Vec<_>>(), vec![0, 2]);
+
+        for node_id in [0, 1, 2] {
+            for frame in 0..8 {
+                let expected = original.sample_node(node_id, &Trs::identity(), frame as f32).unwrap();
+                let trs = parsed.sample_node(node_id, &Trs::identity(), frame as f32).unwrap();
+
+                assert_close(&trs.translation, &expected.translation);
+                assert_close(&trs.rotation, &expected.rotation);
+                assert_close(&trs.scale, &expected.scale);
+            }
+        }
+
+        let trs = parsed.sample_node(0, &Trs::identity(), 4.0).unwrap();
+        assert_close(&trs.rotation, &quarter_turn);
+    }
+}
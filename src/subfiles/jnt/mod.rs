@@ -0,0 +1,7 @@
+use animation::JntAnimation;
+
+use super::animation_subfile::AnimationSubfile;
+
+pub mod animation;
+
+pub type Jnt = AnimationSubfile<JntAnimation>;
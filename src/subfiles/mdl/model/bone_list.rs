@@ -1,4 +1,4 @@
-use crate::{data_structures::{name::Name, name_list::NameList}, debug_info::DebugInfo, error::AppError, util::{math::matrix::Matrix, number::fixed_point::{fixed_1_19_12::Fixed1_19_12, fixed_1_3_12::Fixed1_3_12}}};
+use crate::{data_structures::{name::Name, name_list::NameList}, debug_info::DebugInfo, error::AppError, util::{math::{fixed_matrix::FixedMatrix, matrix::Matrix, pivot_rotation::PivotRotation, trs::Trs}, number::fixed_point::{fixed_1_19_12::Fixed1_19_12, fixed_1_3_12::Fixed1_3_12}}};
 
 
 #[derive(Debug, Clone)]
@@ -13,6 +13,14 @@ pub struct BoneList {
 }
 
 impl BoneList {
+    pub fn new() -> BoneList {
+        BoneList {
+            bones: NameList::new(4),
+            bone_matrices: Vec::new(),
+            _debug_info: DebugInfo { offset: 0 }
+        }
+    }
+
     pub fn from_bytes(bytes: &[u8], debug_info: DebugInfo) -> Result<BoneList, AppError> {
         let bones = NameList::from_bytes(bytes)?;
 
@@ -52,6 +60,10 @@ impl BoneList {
         self.bones.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.bones.len() == 0
+    }
+
     pub fn size(&self) -> usize {
         self.bones.size() + self.bone_matrices.iter().map(|m| m.size()).sum::<usize>()
     }
@@ -64,8 +76,28 @@ impl BoneList {
         self.bone_matrices.get(index)
     }
 
+    pub fn push(&mut self, name: Name, bone_matrix: BoneMatrix) -> Result<(), AppError> {
+        self.bones.push(name, 0)?; // Offset is set on rebase
+        self.bone_matrices.push(bone_matrix);
+
+        Ok(())
+    }
+
     pub fn rebase(&mut self) {
         self.bones.rebase();
+
+        // Bone matrices go right after the name list. Every matrix size is a multiple of 4, so no extra alignment is needed
+        let mut offset = self.bones.size() as u32;
+        for (bone_offset, bone_matrix) in self.bones.data_iter_mut().zip(self.bone_matrices.iter()) {
+            *bone_offset = offset;
+            offset += bone_matrix.size() as u32;
+        }
+    }
+}
+
+impl Default for BoneList {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -80,6 +112,74 @@ pub struct BoneMatrix {
 }
 
 impl BoneMatrix {
+    // Builds a bone matrix from its decomposed components. Missing components are flagged as identity
+    // Rotation is expected as a row-major 3x3 matrix
+    pub fn from_components(translation: Option<[f32; 3]>, rotation: Option<[f32; 9]>, scale: Option<[f32; 3]>) -> BoneMatrix {
+        let mut flags = 0u16;
+
+        let translation = match translation {
+            Some([x, y, z]) => Some(TranslationMatrix {
+                x: Fixed1_19_12::from_f32(x),
+                y: Fixed1_19_12::from_f32(y),
+                z: Fixed1_19_12::from_f32(z)
+            }),
+            None => {
+                flags |= 0x1;
+                None
+            }
+        };
+
+        let (m0, rotation) = match rotation {
+            Some(r) => match PivotRotation::from_matrix(&r) {
+                Some(pivot) => {
+                    // rp form, with the pivot info in the flags
+                    flags |= 0x8 | pivot.info() << 4;
+
+                    let zero = Fixed1_3_12::from(0i16);
+                    (zero, Some(RotationMatrix { data: [pivot.a(), pivot.b(), zero, zero, zero, zero, zero, zero] }))
+                },
+                None => {
+                    // Inverse of the layout used in RotationMatrix::matrix_data
+                    let data = [r[3], r[6], r[1], r[4], r[7], r[2], r[5], r[8]].map(Fixed1_3_12::from_f32);
+                    (Fixed1_3_12::from_f32(r[0]), Some(RotationMatrix { data }))
+                }
+            },
+            None => {
+                flags |= 0x2;
+                (Fixed1_3_12::from(0i16), None)
+            }
+        };
+
+        let scale = match scale {
+            Some([x, y, z]) => Some(ScaleMatrix {
+                x: Fixed1_19_12::from_f32(x),
+                y: Fixed1_19_12::from_f32(y),
+                z: Fixed1_19_12::from_f32(z)
+            }),
+            None => {
+                flags |= 0x4;
+                None
+            }
+        };
+
+        BoneMatrix {
+            flags: BoneMatrixFlags::from_u16(flags),
+            m0,
+            translation,
+            rotation,
+            scale
+        }
+    }
+
+    // Identity components are flagged instead of stored
+    pub fn from_trs(trs: &Trs) -> BoneMatrix {
+        let translation = (trs.translation != [0.0; 3]).then_some(trs.translation);
+        let rotation = (trs.rotation != Trs::IDENTITY_ROTATION).then_some(trs.rotation);
+        let scale = (trs.scale != [1.0; 3]).then_some(trs.scale);
+
+        Self::from_components(translation, rotation, scale)
+    }
+
     pub fn from_bytes(bytes: &[u8]) -> Result<BoneMatrix, AppError> {
         if bytes.len() < 4 {
             return Err(AppError::new("Bone matrix needs at least 4 bytes to start reading"))
@@ -158,6 +258,56 @@ impl BoneMatrix {
     }
 
     pub fn to_matrix(&self) -> Matrix {
+        self.component_matrices()
+            .into_iter()
+            .rev()
+            .fold(Matrix::identity(4), |matrix, component| component * matrix)
+    }
+
+    // Translation, rotation and scale of the bone, identity for the missing ones
+    pub fn to_trs(&self) -> Trs {
+        let mut trs = Trs::identity();
+
+        if let Some(translation) = &self.translation {
+            trs.translation = [translation.x.to_f32(), translation.y.to_f32(), translation.z.to_f32()];
+        }
+
+        if let Some(rotation) = self.rotation.as_ref().and_then(|rotation| rotation.matrix_data(self.flags, self.m0)) {
+            trs.rotation = rotation;
+        }
+
+        if let Some(scale) = &self.scale {
+            trs.scale = [scale.x.to_f32(), scale.y.to_f32(), scale.z.to_f32()];
+        }
+
+        trs
+    }
+
+    // Same as component_matrices, with the raw 20.12 values (rotations are 4.12, which f32 holds exactly)
+    pub fn fixed_component_matrices(&self) -> Vec<FixedMatrix> {
+        let mut components = Vec::with_capacity(3);
+        if let Some(translation) = &self.translation {
+            components.push(FixedMatrix::translation(translation.x, translation.y, translation.z));
+        }
+
+        if let Some(rotation) = self.rotation.as_ref().and_then(|rotation| rotation.matrix_data(self.flags, self.m0)) {
+            let mut rotation_matrix = FixedMatrix::identity();
+            for (i, value) in rotation.iter().enumerate() {
+                rotation_matrix.set(i / 3, i % 3, Fixed1_19_12::from_f32(*value));
+            }
+
+            components.push(rotation_matrix);
+        }
+
+        if let Some(scale) = &self.scale {
+            components.push(FixedMatrix::scale(scale.x, scale.y, scale.z));
+        }
+
+        components
+    }
+
+    // Translation, rotation and scale matrices (only the ones present), in the order they are multiplied
+    pub fn component_matrices(&self) -> Vec<Matrix> {
         let translation_component = if let Some(translation) = &self.translation {
             Some([translation.x.to_f32(), translation.y.to_f32(), translation.z.to_f32()])
         } else { None };
@@ -171,11 +321,14 @@ impl BoneMatrix {
         } else { None };
 
 
-        let mut matrix = Matrix::identity(4);
-        if let Some(scale) = scale_component {
-            matrix.set(0, 0, scale[0]).unwrap();
-            matrix.set(1, 1, scale[1]).unwrap();
-            matrix.set(2, 2, scale[2]).unwrap();
+        let mut components = Vec::with_capacity(3);
+        if let Some(translation) = translation_component {
+            let mut translation_matrix = Matrix::identity(4);
+            translation_matrix.set(0, 3, translation[0]).unwrap();
+            translation_matrix.set(1, 3, translation[1]).unwrap();
+            translation_matrix.set(2, 3, translation[2]).unwrap();
+
+            components.push(translation_matrix);
         }
 
         if let Some(rotation) = rotation_component {
@@ -186,19 +339,19 @@ impl BoneMatrix {
                 0.0, 0.0, 0.0, 1.0
             ]).unwrap();
 
-            matrix = rotation_matrix * matrix;
+            components.push(rotation_matrix);
         }
 
-        if let Some(translation) = translation_component {
-            let mut translation_matrix = Matrix::identity(4);
-            translation_matrix.set(0, 3, translation[0]).unwrap();
-            translation_matrix.set(1, 3, translation[1]).unwrap();
-            translation_matrix.set(2, 3, translation[2]).unwrap();
+        if let Some(scale) = scale_component {
+            let mut scale_matrix = Matrix::identity(4);
+            scale_matrix.set(0, 0, scale[0]).unwrap();
+            scale_matrix.set(1, 1, scale[1]).unwrap();
+            scale_matrix.set(2, 2, scale[2]).unwrap();
 
-            matrix = translation_matrix * matrix;
+            components.push(scale_matrix);
         }
 
-        matrix
+        components
     }
 }
 
@@ -244,6 +397,11 @@ impl BoneMatrixFlags {
     pub fn neg_d(&self) -> bool {
         self.flags & 0x400 != 0
     }
+
+    // Form and signs of an rp rotation, laid out like the info of a PivotRotation
+    pub fn pivot_info(&self) -> u16 {
+        (self.flags >> 4) & PivotRotation::INFO_MASK
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -393,35 +551,7 @@ impl RotationMatrix {
 
     pub fn matrix_data(&self, flags: BoneMatrixFlags, m0: Fixed1_3_12) -> Option<[f32; 9]> {
         if flags.rp() {
-            let a = self.data[0].to_f32();
-            let b = self.data[1].to_f32();
-            let form = flags.form();
-            let neg_one = flags.neg_one();
-            let neg_c = flags.neg_c();
-            let neg_d = flags.neg_d();
-
-            if form >= 9 {
-                return Some([-a, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0])
-            }
-
-            let one = if neg_one { -1.0 } else { 1.0 };
-            let c = if neg_c { -b } else { b };
-            let d = if neg_d { -a } else { a };
-
-            let final_data = match form {
-                0 => [one, 0.0, 0.0, 0.0, a, c, 0.0, b, d],
-                1 => [0.0, a, c, one, 0.0, 0.0, 0.0, b, d],
-                2 => [0.0, a, c, 0.0, b, d, one, 0.0, 0.0],
-                3 => [0.0, one, 0.0, a, 0.0, c, b, 0.0, d],
-                4 => [a, 0.0, c, 0.0, one, 0.0, b, 0.0, d],
-                5 => [a, 0.0, c, b, 0.0, d, 0.0, one, 0.0],
-                6 => [0.0, 0.0, one, a, c, 0.0, b, d, 0.0],
-                7 => [a, c, 0.0, 0.0, 0.0, one, b, d, 0.0],
-                8 => [a, c, 0.0, b, d, 0.0, 0.0, 0.0, one],
-                _ => unreachable!()
-            };
-
-            Some(final_data)
+            Some(PivotRotation::new(flags.pivot_info(), self.data[0], self.data[1]).to_matrix())
         }
         else if !flags.rm() {
             Some([
@@ -1,4 +1,4 @@
-use crate::{debug_info::DebugInfo, error::AppError, util::number::fixed_point::fixed_1_19_12::Fixed1_19_12};
+use crate::{debug_info::DebugInfo, error::AppError, util::{math::{fixed_matrix::FixedMatrix, matrix::Matrix}, number::fixed_point::fixed_1_19_12::Fixed1_19_12}};
 
 #[derive(Debug, Clone)]
 pub struct InvBindMatrices {
@@ -9,6 +9,13 @@ pub struct InvBindMatrices {
 }
 
 impl InvBindMatrices {
+    pub fn new(matrices: Vec<InvBindMatrix>) -> InvBindMatrices {
+        InvBindMatrices {
+            matrices,
+            _debug_info: DebugInfo { offset: 0 }
+        }
+    }
+
     pub fn from_bytes(bytes: &[u8], debug_info: DebugInfo) -> Result<InvBindMatrices, AppError> {
         if bytes.len() %  InvBindMatrix::SIZE != 0 {
             return Err(AppError::new("InvBindMatrices needs a multiple of 84 bytes"))
@@ -43,6 +50,22 @@ impl InvBindMatrices {
     pub fn size(&self) -> usize {
         self.matrices.len() * InvBindMatrix::SIZE
     }
+
+    pub fn len(&self) -> usize {
+        self.matrices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matrices.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&InvBindMatrix> {
+        self.matrices.get(index)
+    }
+
+    pub fn push(&mut self, matrix: InvBindMatrix) {
+        self.matrices.push(matrix);
+    }
 }
 
 
@@ -55,6 +78,59 @@ pub struct InvBindMatrix {
 impl InvBindMatrix {
     const SIZE: usize = 84;
 
+    // Matrices are stored as the DS loads them (MTX_LOAD_4x3 and MTX_LOAD_3x3), so each stored row is a column of our 4x4 matrices
+    pub fn from_matrix(matrix: &Matrix) -> Result<InvBindMatrix, AppError> {
+        if matrix.width() != 4 || matrix.height() != 4 {
+            return Err(AppError::new("InvBindMatrix can only be built from a 4x4 matrix"));
+        }
+
+        let mut position_matrix = [Fixed1_19_12::default(); 12];
+        for column in 0..4 {
+            for row in 0..3 {
+                position_matrix[column as usize * 3 + row as usize] = Fixed1_19_12::from_f32(matrix.get(row, column)?);
+            }
+        }
+
+        // Vector matrix is used for normals, so only the rotation is kept (every axis is normalized to remove the scale)
+        let mut vector_matrix = [Fixed1_19_12::default(); 9];
+        for column in 0..3 {
+            let axis = [matrix.get(0, column)?, matrix.get(1, column)?, matrix.get(2, column)?];
+            let length = (axis[0] * axis[0] + axis[1] * axis[1] + axis[2] * axis[2]).sqrt();
+            let length = if length > 0.0 { length } else { 1.0 };
+
+            for row in 0..3 {
+                vector_matrix[column as usize * 3 + row] = Fixed1_19_12::from_f32(axis[row] / length);
+            }
+        }
+
+        Ok(InvBindMatrix {
+            position_matrix,
+            vector_matrix
+        })
+    }
+
+    pub fn to_matrix(&self) -> Matrix {
+        let mut matrix = Matrix::identity(4);
+        for column in 0..4 {
+            for row in 0..3 {
+                matrix.set(row, column, self.position_matrix[column as usize * 3 + row as usize].to_f32()).unwrap();
+            }
+        }
+
+        matrix
+    }
+
+    pub fn to_fixed_matrix(&self) -> FixedMatrix {
+        let mut matrix = FixedMatrix::identity();
+        for column in 0..4 {
+            for row in 0..3 {
+                matrix.set(row, column, self.position_matrix[column * 3 + row]);
+            }
+        }
+
+        matrix
+    }
+
     pub fn from_bytes(bytes: &[u8]) -> Result<InvBindMatrix, AppError> {
         if bytes.len() < 84 {
             return Err(AppError::new("InvBindMatrix needs at least 84 bytes"))
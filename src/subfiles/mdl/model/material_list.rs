@@ -1,4 +1,6 @@
-use crate::{data_structures::name_list::NameList, debug_info::DebugInfo, error::AppError, traits::BinarySerializable, util::number::alignment::get_4_byte_alignment};
+use std::fmt::Display;
+
+use crate::{data_structures::{name::Name, name_list::NameList}, debug_info::DebugInfo, error::AppError, subfiles::tex::Tex, traits::BinarySerializable, util::{color::Rgb555, math::matrix::Matrix, number::{alignment::get_4_byte_alignment, fixed_point::{fixed_1_19_12::Fixed1_19_12, fixed_1_3_12::Fixed1_3_12}}}};
 
 #[derive(Debug, Clone)]
 pub struct MaterialList {
@@ -11,10 +13,15 @@ pub struct MaterialList {
     palette_pairing_list: PalettePairingList,
     materials_data: Vec<Material>,
 
+    // For each material, the first one it shared its offset with in the file (if any)
+    aliases: Vec<Option<usize>>,
+    share_identical_materials: bool, // Whether rebase makes every identical material share its data, not only the aliased ones
+
     // Debug info
     _debug_info: DebugInfo
 }
 
+// Mutators (adding, removing and pairing materials) leave the offsets stale. The list must be rebased afterwards
 impl MaterialList {
     pub fn from_bytes(bytes: &[u8], debug_info: DebugInfo) -> Result<MaterialList, AppError> {
         if bytes.len() < 44 { // 4 bytes for offsets + 40 bytes for material list
@@ -25,11 +32,20 @@ impl MaterialList {
         let palette_pairings_offset = u16::from_le_bytes([bytes[2], bytes[3]]);
         let materials = NameList::from_bytes(&bytes[4..])?;
 
-        let mut materials_data = Vec::with_capacity(materials.len());
-        for &offset in materials.data_iter() {
-            let offset = offset as usize;
+        let offsets = materials.data_iter().copied().collect::<Vec<u32>>();
+
+        let mut materials_data: Vec<Material> = Vec::with_capacity(materials.len());
+        let mut aliases = Vec::with_capacity(materials.len());
+        for (i, &offset) in offsets.iter().enumerate() {
+            // Several entries may point to the same material data
+            let alias = offsets[..i].iter().position(|&other_offset| other_offset == offset);
+            aliases.push(alias);
+
+            let material = match alias {
+                Some(alias) => materials_data[alias].clone(),
+                None => Material::from_bytes(&bytes[offset as usize..], DebugInfo { offset: debug_info.offset + offset })?
+            };
 
-            let material = Material::from_bytes(&bytes[offset..], DebugInfo { offset: debug_info.offset + offset as u32 })?;
             materials_data.push(material);
         }
 
@@ -47,168 +63,1432 @@ impl MaterialList {
         texture_pairing_list.read_indices(bytes)?;
         palette_pairing_list.read_indices(bytes)?;
 
-        Ok(MaterialList {
-            texture_pairings_offset,
-            palette_pairings_offset,
-            materials,
-            materials_data,
-            texture_pairing_list,
-            palette_pairing_list,
-            _debug_info: debug_info
-        })
+        Ok(MaterialList {
+            texture_pairings_offset,
+            palette_pairings_offset,
+            materials,
+            materials_data,
+            texture_pairing_list,
+            palette_pairing_list,
+            aliases,
+            share_identical_materials: false,
+            _debug_info: debug_info
+        })
+    }
+
+    pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
+        if buffer.len() < 44 { // 4 bytes for offsets + 40 bytes for material list
+            return Err(AppError::new("MaterialList needs at least 44 bytes"));
+        }
+
+        buffer[0..2].copy_from_slice(&self.texture_pairings_offset.to_le_bytes());
+        buffer[2..4].copy_from_slice(&self.palette_pairings_offset.to_le_bytes());
+        self.materials.write_bytes(&mut buffer[4..])?;
+
+        for (i, &offset) in self.materials.data_iter().enumerate() {
+            let offset = offset as usize;
+            let material = &self.materials_data[i];
+            material.write_bytes(&mut buffer[offset..])?;
+        }
+
+        self.texture_pairing_list.write_bytes(&mut buffer[self.texture_pairings_offset as usize..])?;
+        self.palette_pairing_list.write_bytes(&mut buffer[self.palette_pairings_offset as usize..])?;
+        
+        self.texture_pairing_list.write_indices(buffer)?;
+        self.palette_pairing_list.write_indices(buffer)?;
+        Ok(())
+    }
+
+    pub fn size(&self) -> usize {
+        // We get it like this, since there might be empty bytes in the middle of the material list
+        usize::max(usize::max(
+            self.materials.data_iter().zip(self.materials_data.iter())
+                .map(|(&offset, material)| offset as usize + material.size())
+                .max()
+                .unwrap_or(0), // Last material
+            self.texture_pairings_offset as usize + self.texture_pairing_list.size()), // Texture pairing
+            self.palette_pairings_offset as usize + self.palette_pairing_list.size() // Palette pairing
+        )
+    }
+
+    pub fn rebase(&mut self) {
+        self.materials.rebase();
+        self.texture_pairing_list.rebase();
+        self.palette_pairing_list.rebase();
+
+        let mut offset = 4; // texture_pairings_offset (2 bytes) + palette_pairings_offset (2 bytes)
+        offset += self.materials.size();
+
+        self.texture_pairings_offset = offset as u16;
+        offset += self.texture_pairing_list.size();
+
+        self.palette_pairings_offset = offset as u16;
+        offset += self.palette_pairing_list.size();
+
+        // Indices from pairing lists go after all the pairing lists and before the materials. They don't need to be aligned (they are individual bytes)
+        self.texture_pairing_list.set_begin_indices_offset(offset as u16);
+        offset += self.texture_pairing_list.total_indices_count();
+        self.palette_pairing_list.set_begin_indices_offset(offset as u16);
+        offset += self.palette_pairing_list.total_indices_count();
+
+        offset = get_4_byte_alignment(offset); // Material data must be 4-byte aligned
+
+        let shared_with = self.shared_materials();
+        let mut offsets: Vec<u32> = Vec::with_capacity(self.materials_data.len());
+
+        for (i, material) in self.materials_data.iter().enumerate() {
+            match shared_with[i] {
+                Some(first) => offsets.push(offsets[first]),
+                None => {
+                    offsets.push(offset as u32);
+                    offset += material.size();
+                }
+            }
+        }
+
+        for (material_offset, new_offset) in self.materials.data_iter_mut().zip(offsets) {
+            *material_offset = new_offset;
+        }
+    }
+
+    // Index of the first material that shared its offset with this one in the file, if any
+    pub fn aliased_material(&self, index: usize) -> Option<usize> {
+        self.aliases.get(index).copied().flatten()
+    }
+
+    // When enabled, rebase writes identical materials only once. Otherwise only the ones aliased in the original file
+    // are shared, as long as they are still identical
+    pub fn set_share_identical_materials(&mut self, share: bool) {
+        self.share_identical_materials = share;
+    }
+
+    // For each material, the earlier one whose data it reuses on write
+    fn shared_materials(&self) -> Vec<Option<usize>> {
+        let bytes = self.materials_data.iter()
+            .map(|material| material.to_bytes().ok())
+            .collect::<Vec<Option<Vec<u8>>>>();
+
+        let is_identical = |a: usize, b: usize| bytes[a].is_some() && bytes[a] == bytes[b];
+
+        (0..self.materials_data.len())
+            .map(|i| {
+                if self.share_identical_materials {
+                    (0..i).find(|&j| is_identical(i, j))
+                }
+                else {
+                    self.aliases[i].filter(|&j| is_identical(i, j))
+                }
+            })
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.materials_data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.materials_data.is_empty()
+    }
+
+    pub fn get_material(&self, index: usize) -> Option<&Material> {
+        self.materials_data.get(index)
+    }
+
+    pub fn get_material_mut(&mut self, index: usize) -> Option<&mut Material> {
+        self.materials_data.get_mut(index)
+    }
+
+    pub fn get_name(&self, index: usize) -> Option<&Name> {
+        self.materials.get_name(index)
+    }
+
+    pub fn index_of_name(&self, name: &str) -> Option<usize> {
+        self.materials.names_iter()
+            .position(|material_name| material_name.to_not_null_string().is_ok_and(|material_name| material_name == name))
+    }
+
+    pub fn get_by_name(&self, name: &str) -> Option<&Material> {
+        self.index_of_name(name).and_then(|index| self.materials_data.get(index))
+    }
+
+    pub fn get_by_name_mut(&mut self, name: &str) -> Option<&mut Material> {
+        self.index_of_name(name).and_then(|index| self.materials_data.get_mut(index))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Name, &Material)> {
+        self.materials.names_iter().zip(self.materials_data.iter())
+    }
+
+    // Appends a material and returns its index. It is not paired with any texture nor palette
+    pub fn add_material(&mut self, name: Name, material: Material) -> Result<usize, AppError> {
+        self.materials.push(name, 0)?; // Offset is set on rebase
+        self.materials_data.push(material);
+        self.aliases.push(None);
+
+        Ok(self.materials_data.len() - 1)
+    }
+
+    // Updates the texture size of every material to match its paired texture in the given TEX0.
+    // Returns every material whose size did not match (or whose texture could not be found, in which case it is left untouched)
+    pub fn sync_texture_dimensions(&mut self, tex: &Tex) -> Result<Vec<TextureDimensionMismatch>, AppError> {
+        let mut mismatches = Vec::new();
+
+        for (material_index, material) in self.materials_data.iter_mut().enumerate() {
+            let texture_name = match self.texture_pairing_list.name_for_material(material_index as u8) {
+                Some(name) => name.to_not_null_string()?,
+                None => continue // Untextured material
+            };
+
+            let material_size = (material.texture_width, material.texture_height);
+
+            let texture = tex.texture_list().index_of_name(&texture_name)
+                .and_then(|index| tex.texture_list().get_texture(index));
+
+            let texture_size = match texture {
+                Some(texture) => (texture.width(), texture.height()),
+                None => {
+                    mismatches.push(TextureDimensionMismatch {
+                        material_index,
+                        texture_name,
+                        material_size,
+                        texture_size: None
+                    });
+                    continue;
+                }
+            };
+
+            if material_size != texture_size {
+                material.set_texture_size(texture_size.0, texture_size.1)?;
+
+                mismatches.push(TextureDimensionMismatch {
+                    material_index,
+                    texture_name,
+                    material_size,
+                    texture_size: Some(texture_size)
+                });
+            }
+        }
+
+        Ok(mismatches)
+    }
+
+    // Name of the texture paired with the material, if any
+    pub fn texture_name_for_material(&self, material_index: usize) -> Option<&Name> {
+        self.texture_pairing_list.name_for_material(u8::try_from(material_index).ok()?)
+    }
+
+    // Name of the palette paired with the material, if any
+    pub fn palette_name_for_material(&self, material_index: usize) -> Option<&Name> {
+        self.palette_pairing_list.name_for_material(u8::try_from(material_index).ok()?)
+    }
+
+    // Indices of the materials paired with the given texture. Empty if the texture is not used
+    pub fn materials_using_texture(&self, texture_name: &str) -> Vec<usize> {
+        self.texture_pairing_list.materials_for_name(texture_name)
+    }
+
+    // Indices of the materials paired with the given palette. Empty if the palette is not used
+    pub fn materials_using_palette(&self, palette_name: &str) -> Vec<usize> {
+        self.palette_pairing_list.materials_for_name(palette_name)
+    }
+
+    // Pairs the material with the given texture, moving it out of its previous pairing.
+    // A new pairing is created if no other material uses that texture yet
+    pub fn set_texture_for_material(&mut self, material_index: usize, texture_name: &str) -> Result<(), AppError> {
+        if material_index >= self.materials_data.len() {
+            return Err(AppError::new(&format!("Material index out of bounds. Expected 0-{}, got {}", self.materials_data.len() as isize - 1, material_index)));
+        }
+
+        self.texture_pairing_list.set_material_name(material_index as u8, texture_name)?;
+
+        Ok(())
+    }
+
+    // Pairs the material with the given palette, moving it out of its previous pairing.
+    // A new pairing is created if no other material uses that palette yet
+    pub fn set_palette_for_material(&mut self, material_index: usize, palette_name: &str) -> Result<(), AppError> {
+        if material_index >= self.materials_data.len() {
+            return Err(AppError::new(&format!("Material index out of bounds. Expected 0-{}, got {}", self.materials_data.len() as isize - 1, material_index)));
+        }
+
+        self.palette_pairing_list.set_material_name(material_index as u8, palette_name)?;
+
+        Ok(())
+    }
+
+    fn remove_alias(&mut self, index: usize) {
+        let removed_alias = self.aliases.remove(index);
+
+        // Materials aliasing the removed one now alias the first of them, which becomes the shared one
+        let mut new_first = None;
+        for (i, alias) in self.aliases.iter_mut().enumerate() {
+            *alias = match *alias {
+                Some(alias) if alias == index => match (removed_alias, new_first) {
+                    (Some(removed_alias), _) => Some(removed_alias),
+                    (None, Some(new_first)) => Some(new_first),
+                    (None, None) => {
+                        new_first = Some(i);
+                        None
+                    }
+                },
+                Some(alias) if alias > index => Some(alias - 1),
+                alias => alias
+            };
+        }
+    }
+
+    // Points the pairing of a texture to its new name. Does nothing if no material uses the texture
+    pub fn rename_texture_pairing(&mut self, texture_name: &str, new_name: &str) -> Result<(), AppError> {
+        self.texture_pairing_list.rename(texture_name, new_name)?;
+
+        Ok(())
+    }
+
+    // Points the pairing of a palette to its new name. Does nothing if no material uses the palette
+    pub fn rename_palette_pairing(&mut self, palette_name: &str, new_name: &str) -> Result<(), AppError> {
+        self.palette_pairing_list.rename(palette_name, new_name)?;
+
+        Ok(())
+    }
+
+    // Checks that every texture and palette pairing points to an existing material
+    pub fn validate_pairings(&self) -> Result<(), AppError> {
+        self.texture_pairing_list.validate_indices(self.materials_data.len())?;
+        self.palette_pairing_list.validate_indices(self.materials_data.len())
+    }
+
+    // Removes a material, dropping it from the pairing lists and shifting the indices of the following materials.
+    // Render commands binding materials are not updated, so BindMaterial indices must be fixed by the caller
+    pub fn remove_material(&mut self, index: usize) -> Result<(Name, Material), AppError> {
+        if index >= self.materials_data.len() {
+            return Err(AppError::new(&format!("Material index out of bounds. Expected 0-{}, got {}", self.materials_data.len() as isize - 1, index)));
+        }
+
+        let (name, _) = self.materials.remove(index)?;
+        let material = self.materials_data.remove(index);
+        self.remove_alias(index);
+
+        self.texture_pairing_list.remove_material_index(index as u8)?;
+        self.palette_pairing_list.remove_material_index(index as u8)?;
+
+        Ok((name, material))
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct Material {
+    dummy: u16,
+    size: u16,
+
+    dif_amb: DifAmb, // Value for DIFF_AMB register
+    spe_emi: SpeEmi, // Value for SPE_EMI register
+    polygon_attr: PolygonAttr, // Value for POLYGON_ATTR register
+    unknown_0: u32, // Mask for POLYGON_ATTR register??
+    teximage_params: TexImageParams,
+
+    unknown_1: u32,
+    texture_palette_base: u16,
+    flags: MaterialFlags,
+
+    texture_width: u16,
+    texture_height: u16,
+
+    magnification_width: Fixed1_19_12,
+    magnification_height: Fixed1_19_12,
+
+    // Optional data after the 44 base bytes, present depending on the flags
+    texcoord_transform: TexcoordTransform,
+    effect_matrix: Option<[Fixed1_19_12; 16]>,
+
+    // Debug info
+    _debug_info: DebugInfo
+}
+
+impl Material {
+    const SIZE: usize = 44; // Without the optional texcoord transform and effect matrix
+    const EFFECT_MATRIX_SIZE: usize = 64;
+
+    pub fn from_bytes(bytes: &[u8], debug_info: DebugInfo) -> Result<Material, AppError> {
+        if bytes.len() < Material::SIZE {
+            return Err(AppError::new("Material needs at least 44 bytes"));
+        }
+
+        let dummy = u16::from_le_bytes([bytes[0], bytes[1]]);
+        let size = u16::from_le_bytes([bytes[2], bytes[3]]);
+
+        let dif_amb = DifAmb::from_u32(u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]));
+        let spe_emi = SpeEmi::from_u32(u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]));
+        let polygon_attr = PolygonAttr::from_u32(u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]));
+        let unknown_0 = u32::from_le_bytes([bytes[16], bytes[17], bytes[18], bytes[19]]);
+        let teximage_params = TexImageParams::from_u32(u32::from_le_bytes([bytes[20], bytes[21], bytes[22], bytes[23]]));
+
+        let unknown_1 = u32::from_le_bytes([bytes[24], bytes[25], bytes[26], bytes[27]]);
+        let texture_palette_base = u16::from_le_bytes([bytes[28], bytes[29]]);
+        let flags = MaterialFlags::from_u16(u16::from_le_bytes([bytes[30], bytes[31]]));
+
+        let texture_width = u16::from_le_bytes([bytes[32], bytes[33]]);
+        let texture_height = u16::from_le_bytes([bytes[34], bytes[35]]);
+
+        let magnification_width = Fixed1_19_12::from(i32::from_le_bytes([bytes[36], bytes[37], bytes[38], bytes[39]]));
+        let magnification_height = Fixed1_19_12::from(i32::from_le_bytes([bytes[40], bytes[41], bytes[42], bytes[43]]));
+
+        let texcoord_transform = TexcoordTransform::from_bytes(&bytes[Material::SIZE..], &flags)?;
+
+        let effect_matrix = if flags.contains(MaterialFlags::EFFECT_MATRIX) {
+            let offset = Material::SIZE + texcoord_transform.size();
+            if bytes.len() < offset + Material::EFFECT_MATRIX_SIZE {
+                return Err(AppError::new(&format!("Material with effect matrix needs at least {} bytes", offset + Material::EFFECT_MATRIX_SIZE)));
+            }
+
+            let mut effect_matrix = [Fixed1_19_12::from(0); 16];
+            for (i, value) in effect_matrix.iter_mut().enumerate() {
+                let value_offset = offset + i * 4;
+                *value = Fixed1_19_12::from(i32::from_le_bytes([bytes[value_offset], bytes[value_offset + 1], bytes[value_offset + 2], bytes[value_offset + 3]]));
+            }
+
+            Some(effect_matrix)
+        }
+        else {
+            None
+        };
+
+        Ok(Material {
+            dummy,
+            size,
+            dif_amb,
+            spe_emi,
+            polygon_attr,
+            unknown_0,
+            teximage_params,
+            unknown_1,
+            texture_palette_base,
+            flags,
+            texture_width,
+            texture_height,
+            magnification_width,
+            magnification_height,
+            texcoord_transform,
+            effect_matrix,
+            _debug_info: debug_info
+        })
+    }
+
+    pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
+        if buffer.len() < self.size() {
+            return Err(AppError::new(&format!("Material needs at least {} bytes", self.size())));
+        }
+
+        buffer[0..2].copy_from_slice(&self.dummy.to_le_bytes());
+        buffer[2..4].copy_from_slice(&self.size.to_le_bytes());
+
+        self.dif_amb.write_bytes(&mut buffer[4..8])?;
+        self.spe_emi.write_bytes(&mut buffer[8..12])?;
+        self.polygon_attr.write_bytes(&mut buffer[12..16])?;
+        buffer[16..20].copy_from_slice(&self.unknown_0.to_le_bytes());
+        self.teximage_params.write_bytes(&mut buffer[20..24])?;
+
+        buffer[24..28].copy_from_slice(&self.unknown_1.to_le_bytes());
+        buffer[28..30].copy_from_slice(&self.texture_palette_base.to_le_bytes());
+        buffer[30..32].copy_from_slice(&self.flags.to_u16().to_le_bytes());
+
+        buffer[32..34].copy_from_slice(&self.texture_width.to_le_bytes());
+        buffer[34..36].copy_from_slice(&self.texture_height.to_le_bytes());
+
+        buffer[36..40].copy_from_slice(&self.magnification_width.to_le_bytes());
+        buffer[40..44].copy_from_slice(&self.magnification_height.to_le_bytes());
+
+        self.texcoord_transform.write_bytes(&mut buffer[Material::SIZE..])?;
+
+        if let Some(effect_matrix) = &self.effect_matrix {
+            let offset = Material::SIZE + self.texcoord_transform.size();
+            for (i, value) in effect_matrix.iter().enumerate() {
+                buffer[offset + i * 4..offset + i * 4 + 4].copy_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, AppError> {
+        let mut bytes = vec![0u8; self.size()];
+        self.write_bytes(&mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    pub fn size(&self) -> usize {
+        let effect_matrix_size = if self.effect_matrix.is_some() { Material::EFFECT_MATRIX_SIZE } else { 0 };
+        Material::SIZE + self.texcoord_transform.size() + effect_matrix_size
+    }
+
+    pub fn flags(&self) -> &MaterialFlags {
+        &self.flags
+    }
+
+    pub fn texture_palette_base(&self) -> u16 {
+        self.texture_palette_base
+    }
+
+    pub fn set_texture_palette_base(&mut self, palette_base: u16) {
+        self.texture_palette_base = palette_base;
+    }
+
+    pub fn magnification(&self) -> (f32, f32) {
+        (self.magnification_width.to_f32(), self.magnification_height.to_f32())
+    }
+
+    pub fn set_magnification(&mut self, width: f32, height: f32) {
+        self.magnification_width = Fixed1_19_12::from_f32(width);
+        self.magnification_height = Fixed1_19_12::from_f32(height);
+    }
+
+    pub fn texcoord_transform(&self) -> &TexcoordTransform {
+        &self.texcoord_transform
+    }
+
+    // Replaces the texcoord transform, keeping the flags and size in sync
+    pub fn set_texcoord_transform(&mut self, texcoord_transform: TexcoordTransform) {
+        self.texcoord_transform = texcoord_transform;
+        self.texcoord_transform.update_flags(&mut self.flags);
+        self.size = self.size() as u16;
+    }
+
+    // 3x3 matrix applied to (s, t, 1) texcoords in texel units, as the hardware texture matrix does.
+    // The transform is scale, then rotation, then translation, all around the texture origin
+    pub fn texcoord_matrix(&self) -> Result<Matrix, AppError> {
+        if self.teximage_params.texcoords_transform_mode() == TexImageParams::TRANSFORM_MODE_NONE {
+            return Ok(Matrix::identity(3));
+        }
+
+        // Untextured materials have no size, so their matrix stays in normalized units
+        let (magnification_width, magnification_height) = self.magnification();
+        let width = if self.texture_width == 0 { 1.0 } else { self.texture_width as f32 * magnification_width };
+        let height = if self.texture_height == 0 { 1.0 } else { self.texture_height as f32 * magnification_height };
+
+        let (scale_s, scale_t) = self.texcoord_transform.scale();
+        let (sin, cos) = self.texcoord_transform.rotation();
+        let (translation_s, translation_t) = self.texcoord_transform.translation();
+
+        let to_texels = Matrix::new(3, 3, vec![
+            width, 0.0, 0.0,
+            0.0, height, 0.0,
+            0.0, 0.0, 1.0
+        ])?;
+
+        let to_normalized = Matrix::new(3, 3, vec![
+            1.0 / width, 0.0, 0.0,
+            0.0, 1.0 / height, 0.0,
+            0.0, 0.0, 1.0
+        ])?;
+
+        let srt = Matrix::new(3, 3, vec![
+            scale_s * cos, -scale_t * sin, translation_s,
+            scale_s * sin, scale_t * cos, translation_t,
+            0.0, 0.0, 1.0
+        ])?;
+
+        Ok(to_texels * srt * to_normalized)
+    }
+
+    pub fn effect_matrix(&self) -> Option<[f32; 16]> {
+        self.effect_matrix.map(|effect_matrix| effect_matrix.map(|value| value.to_f32()))
+    }
+
+    pub fn set_effect_matrix(&mut self, effect_matrix: Option<[f32; 16]>) {
+        self.effect_matrix = effect_matrix.map(|effect_matrix| effect_matrix.map(Fixed1_19_12::from_f32));
+        self.flags.set(MaterialFlags::EFFECT_MATRIX, self.effect_matrix.is_some());
+        self.size = self.size() as u16;
+    }
+
+    pub fn texture_width(&self) -> u16 {
+        self.texture_width
+    }
+
+    pub fn texture_height(&self) -> u16 {
+        self.texture_height
+    }
+
+    // Sets the texture size, keeping the size bits of the teximage params in sync
+    pub fn set_texture_size(&mut self, width: u16, height: u16) -> Result<(), AppError> {
+        let s_size = TexImageParams::size_to_bits(width)?;
+        let t_size = TexImageParams::size_to_bits(height)?;
+
+        self.teximage_params.set_texture_s_size(s_size)?;
+        self.teximage_params.set_texture_t_size(t_size)?;
+
+        self.texture_width = width;
+        self.texture_height = height;
+
+        Ok(())
+    }
+
+    pub fn teximage_params(&self) -> &TexImageParams {
+        &self.teximage_params
+    }
+
+    pub fn teximage_params_mut(&mut self) -> &mut TexImageParams {
+        &mut self.teximage_params
+    }
+
+    // Color setters taking 0.0-1.0 channels, rounded to the nearest RGB15 value. They also flag the color as set by the material
+
+    // Unlit materials use the diffuse as vertex color, so the vertex color flag is set when every light is off
+    pub fn set_diffuse(&mut self, r: f32, g: f32, b: f32) {
+        self.dif_amb.set_diffuse_f32([r, g, b]);
+        self.flags.set(MaterialFlags::DIFFUSE, true);
+
+        let unlit = (0..4).all(|light| !self.polygon_attr.light_enabled(light));
+        self.dif_amb.set_vertex_color(unlit);
+        self.flags.set(MaterialFlags::VERTEX_COLOR, unlit);
+    }
+
+    pub fn set_ambient(&mut self, r: f32, g: f32, b: f32) {
+        self.dif_amb.set_ambient_f32([r, g, b]);
+        self.flags.set(MaterialFlags::AMBIENT, true);
+    }
+
+    pub fn set_specular(&mut self, r: f32, g: f32, b: f32) {
+        self.spe_emi.set_specular_f32([r, g, b]);
+        self.flags.set(MaterialFlags::SPECULAR, true);
+    }
+
+    pub fn set_emission(&mut self, r: f32, g: f32, b: f32) {
+        self.spe_emi.set_emission_f32([r, g, b]);
+        self.flags.set(MaterialFlags::EMISSION, true);
+    }
+
+    pub fn dif_amb(&self) -> &DifAmb {
+        &self.dif_amb
+    }
+
+    pub fn dif_amb_mut(&mut self) -> &mut DifAmb {
+        &mut self.dif_amb
+    }
+
+    pub fn spe_emi(&self) -> &SpeEmi {
+        &self.spe_emi
+    }
+
+    pub fn spe_emi_mut(&mut self) -> &mut SpeEmi {
+        &mut self.spe_emi
+    }
+
+    pub fn polygon_attr(&self) -> &PolygonAttr {
+        &self.polygon_attr
+    }
+
+    pub fn polygon_attr_mut(&mut self) -> &mut PolygonAttr {
+        &mut self.polygon_attr
+    }
+
+    pub fn builder() -> MaterialBuilder {
+        MaterialBuilder::new()
+    }
+
+    // Appends a copy of this material to the list and returns its index. It is not paired with any texture nor palette
+    pub fn clone_into(&self, material_list: &mut MaterialList, name: &str) -> Result<usize, AppError> {
+        if material_list.index_of_name(name).is_some() {
+            return Err(AppError::new(&format!("Material \"{}\" already exists in the material list", name)));
+        }
+
+        material_list.add_material(Name::from_string(name)?, self.clone())
+    }
+}
+
+
+// Builds a material from scratch. Defaults to a lit (lights 0 and 1), opaque, back-face culled material with white diffuse
+#[derive(Debug)]
+pub struct MaterialBuilder {
+    material: Material,
+    error: Option<AppError> // First error found by a setter, returned by build
+}
+
+impl MaterialBuilder {
+    const DEFAULT_POLYGON_ATTR_MASK: u32 = 0x3F1FF8FF; // Every defined bit of POLYGON_ATTR
+    const DEFAULT_TEXIMAGE_PARAMS_MASK: u32 = 0xC00F0000; // Bits owned by the material (repeat, mirror and texcoords transform mode)
+    // No texture matrix, material sets diffuse, ambient, specular and emission
+    const DEFAULT_FLAGS: u16 = MaterialFlags::TEXMTX_SCALE_ONE | MaterialFlags::TEXMTX_ROTATION_ZERO | MaterialFlags::TEXMTX_TRANSLATION_ZERO
+        | MaterialFlags::DIFFUSE | MaterialFlags::AMBIENT | MaterialFlags::SPECULAR | MaterialFlags::EMISSION;
+
+    fn new() -> MaterialBuilder {
+        let mut dif_amb = DifAmb::from_u32(0);
+        dif_amb.set_diffuse(Rgb555::new(31, 31, 31));
+        dif_amb.set_ambient(Rgb555::new(10, 10, 10));
+
+        let mut polygon_attr = PolygonAttr::from_u32(0);
+        polygon_attr.set_light_enabled(0, true).unwrap();
+        polygon_attr.set_light_enabled(1, true).unwrap();
+        polygon_attr.set_culling_mode(PolygonAttr::CULL_BACK).unwrap();
+        polygon_attr.set_alpha(31).unwrap();
+
+        let mut teximage_params = TexImageParams::from_u32(0);
+        teximage_params.set_repeat_s(true);
+        teximage_params.set_repeat_t(true);
+
+        MaterialBuilder {
+            material: Material {
+                dummy: 0,
+                size: Material::SIZE as u16,
+                dif_amb,
+                spe_emi: SpeEmi::from_u32(0),
+                polygon_attr,
+                unknown_0: Self::DEFAULT_POLYGON_ATTR_MASK,
+                teximage_params,
+                unknown_1: Self::DEFAULT_TEXIMAGE_PARAMS_MASK,
+                texture_palette_base: 0,
+                flags: MaterialFlags::from_u16(Self::DEFAULT_FLAGS),
+                texture_width: 0,
+                texture_height: 0,
+                magnification_width: Fixed1_19_12::from_f32(1.0),
+                magnification_height: Fixed1_19_12::from_f32(1.0),
+                texcoord_transform: TexcoordTransform::default(),
+                effect_matrix: None,
+                _debug_info: DebugInfo { offset: 0 }
+            },
+            error: None
+        }
+    }
+
+    pub fn diffuse(mut self, color: Rgb555) -> Self {
+        self.material.dif_amb.set_diffuse(color);
+        self
+    }
+
+    pub fn ambient(mut self, color: Rgb555) -> Self {
+        self.material.dif_amb.set_ambient(color);
+        self
+    }
+
+    pub fn vertex_color(mut self, vertex_color: bool) -> Self {
+        self.material.dif_amb.set_vertex_color(vertex_color);
+        self
+    }
+
+    pub fn specular(mut self, color: Rgb555) -> Self {
+        self.material.spe_emi.set_specular(color);
+        self
+    }
+
+    pub fn emission(mut self, color: Rgb555) -> Self {
+        self.material.spe_emi.set_emission(color);
+        self
+    }
+
+    pub fn light(mut self, light: u8, enabled: bool) -> Self {
+        let result = self.material.polygon_attr.set_light_enabled(light, enabled);
+        self.keep_error(result)
+    }
+
+    pub fn polygon_mode(mut self, mode: u8) -> Self {
+        let result = self.material.polygon_attr.set_polygon_mode(mode);
+        self.keep_error(result)
+    }
+
+    pub fn culling_mode(mut self, mode: u8) -> Self {
+        let result = self.material.polygon_attr.set_culling_mode(mode);
+        self.keep_error(result)
+    }
+
+    pub fn alpha(mut self, alpha: u8) -> Self {
+        let result = self.material.polygon_attr.set_alpha(alpha);
+        self.keep_error(result)
+    }
+
+    pub fn polygon_id(mut self, id: u8) -> Self {
+        let result = self.material.polygon_attr.set_polygon_id(id);
+        self.keep_error(result)
+    }
+
+    pub fn fog(mut self, enabled: bool) -> Self {
+        self.material.polygon_attr.set_fog_enabled(enabled);
+        self
+    }
+
+    pub fn repeat(mut self, repeat_s: bool, repeat_t: bool) -> Self {
+        self.material.teximage_params.set_repeat_s(repeat_s);
+        self.material.teximage_params.set_repeat_t(repeat_t);
+        self
+    }
+
+    pub fn mirror(mut self, mirror_s: bool, mirror_t: bool) -> Self {
+        self.material.teximage_params.set_mirror_s(mirror_s);
+        self.material.teximage_params.set_mirror_t(mirror_t);
+        self
+    }
+
+    pub fn texture_size(mut self, width: u16, height: u16) -> Self {
+        let result = self.material.set_texture_size(width, height);
+        self.keep_error(result)
+    }
+
+    pub fn texcoord_transform(mut self, texcoord_transform: TexcoordTransform) -> Self {
+        self.material.set_texcoord_transform(texcoord_transform);
+        self
+    }
+
+    pub fn depth_equal(mut self, equal: bool) -> Self {
+        self.material.polygon_attr.set_depth_equal(equal);
+        self
+    }
+
+    pub fn translucent_depth_update(mut self, update: bool) -> Self {
+        self.material.polygon_attr.set_translucent_depth_update(update);
+        self
+    }
+
+    // Alpha 0 renders only the edges of the polygons
+    pub fn wireframe(mut self) -> Self {
+        self.material.flags.set(MaterialFlags::WIREFRAME, true);
+        self.alpha(0)
+    }
+
+    pub fn build(self) -> Result<Material, AppError> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.material)
+        }
+    }
+
+    fn keep_error(mut self, result: Result<(), AppError>) -> Self {
+        if let (Err(error), None) = (result, &self.error) {
+            self.error = Some(error);
+        }
+
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MaterialFlags {
+    data: u16
+}
+
+impl MaterialFlags {
+    pub const TEXMTX_USE: u16 = 0x0001;
+    pub const TEXMTX_SCALE_ONE: u16 = 0x0002;
+    pub const TEXMTX_ROTATION_ZERO: u16 = 0x0004;
+    pub const TEXMTX_TRANSLATION_ZERO: u16 = 0x0008;
+    pub const ORIGINAL_SIZE_SAME: u16 = 0x0010;
+    pub const WIREFRAME: u16 = 0x0020;
+    pub const DIFFUSE: u16 = 0x0040;
+    pub const AMBIENT: u16 = 0x0080;
+    pub const VERTEX_COLOR: u16 = 0x0100;
+    pub const SPECULAR: u16 = 0x0200;
+    pub const EMISSION: u16 = 0x0400;
+    pub const SHININESS: u16 = 0x0800;
+    pub const TEXTURE_PALETTE_BASE: u16 = 0x1000;
+    pub const EFFECT_MATRIX: u16 = 0x2000;
+
+    pub fn from_u16(data: u16) -> MaterialFlags {
+        MaterialFlags {
+            data
+        }
+    }
+
+    pub fn to_u16(&self) -> u16 {
+        self.data
+    }
+
+    pub fn contains(&self, flag: u16) -> bool {
+        (self.data & flag) == flag
+    }
+
+    pub fn set(&mut self, flag: u16, enabled: bool) {
+        if enabled {
+            self.data |= flag;
+        }
+        else {
+            self.data &= !flag;
+        }
+    }
+}
+
+
+// Texture coordinates scale, rotation and translation. Each part is omitted from the file when it is the identity
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TexcoordTransform {
+    scale: Option<[Fixed1_19_12; 2]>,
+    rotation: Option<[Fixed1_3_12; 2]>, // Sine and cosine
+    translation: Option<[Fixed1_19_12; 2]>
+}
+
+impl TexcoordTransform {
+    fn from_bytes(bytes: &[u8], flags: &MaterialFlags) -> Result<TexcoordTransform, AppError> {
+        let mut texcoord_transform = TexcoordTransform::default();
+
+        if !flags.contains(MaterialFlags::TEXMTX_USE) {
+            return Ok(texcoord_transform);
+        }
+
+        let has_scale = !flags.contains(MaterialFlags::TEXMTX_SCALE_ONE);
+        let has_rotation = !flags.contains(MaterialFlags::TEXMTX_ROTATION_ZERO);
+        let has_translation = !flags.contains(MaterialFlags::TEXMTX_TRANSLATION_ZERO);
+
+        let size = if has_scale { 8 } else { 0 } + if has_rotation { 4 } else { 0 } + if has_translation { 8 } else { 0 };
+        if bytes.len() < size {
+            return Err(AppError::new(&format!("TexcoordTransform needs at least {} bytes", size)));
+        }
+
+        let mut offset = 0;
+
+        if has_scale {
+            texcoord_transform.scale = Some([
+                Fixed1_19_12::from(i32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])),
+                Fixed1_19_12::from(i32::from_le_bytes([bytes[offset + 4], bytes[offset + 5], bytes[offset + 6], bytes[offset + 7]]))
+            ]);
+            offset += 8;
+        }
+
+        if has_rotation {
+            texcoord_transform.rotation = Some([
+                Fixed1_3_12::from(i16::from_le_bytes([bytes[offset], bytes[offset + 1]])),
+                Fixed1_3_12::from(i16::from_le_bytes([bytes[offset + 2], bytes[offset + 3]]))
+            ]);
+            offset += 4;
+        }
+
+        if has_translation {
+            texcoord_transform.translation = Some([
+                Fixed1_19_12::from(i32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])),
+                Fixed1_19_12::from(i32::from_le_bytes([bytes[offset + 4], bytes[offset + 5], bytes[offset + 6], bytes[offset + 7]]))
+            ]);
+        }
+
+        Ok(texcoord_transform)
+    }
+
+    fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
+        if buffer.len() < self.size() {
+            return Err(AppError::new(&format!("TexcoordTransform needs at least {} bytes", self.size())));
+        }
+
+        let mut offset = 0;
+
+        if let Some([s, t]) = self.scale {
+            buffer[offset..offset + 4].copy_from_slice(&s.to_le_bytes());
+            buffer[offset + 4..offset + 8].copy_from_slice(&t.to_le_bytes());
+            offset += 8;
+        }
+
+        if let Some([sin, cos]) = self.rotation {
+            buffer[offset..offset + 2].copy_from_slice(&sin.to_le_bytes());
+            buffer[offset + 2..offset + 4].copy_from_slice(&cos.to_le_bytes());
+            offset += 4;
+        }
+
+        if let Some([s, t]) = self.translation {
+            buffer[offset..offset + 4].copy_from_slice(&s.to_le_bytes());
+            buffer[offset + 4..offset + 8].copy_from_slice(&t.to_le_bytes());
+        }
+
+        Ok(())
+    }
+
+    pub fn size(&self) -> usize {
+        self.scale.map_or(0, |_| 8) + self.rotation.map_or(0, |_| 4) + self.translation.map_or(0, |_| 8)
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.scale.is_none() && self.rotation.is_none() && self.translation.is_none()
+    }
+
+    pub fn scale(&self) -> (f32, f32) {
+        self.scale.map_or((1.0, 1.0), |[s, t]| (s.to_f32(), t.to_f32()))
+    }
+
+    pub fn set_scale(&mut self, s: f32, t: f32) {
+        self.scale = if s == 1.0 && t == 1.0 {
+            None
+        }
+        else {
+            Some([Fixed1_19_12::from_f32(s), Fixed1_19_12::from_f32(t)])
+        };
+    }
+
+    // Returns (sine, cosine) of the rotation angle
+    pub fn rotation(&self) -> (f32, f32) {
+        self.rotation.map_or((0.0, 1.0), |[sin, cos]| (sin.to_f32(), cos.to_f32()))
+    }
+
+    // Angle in radians
+    pub fn set_rotation(&mut self, angle: f32) {
+        self.rotation = if angle == 0.0 {
+            None
+        }
+        else {
+            Some([Fixed1_3_12::from_f32(angle.sin()), Fixed1_3_12::from_f32(angle.cos())])
+        };
+    }
+
+    pub fn translation(&self) -> (f32, f32) {
+        self.translation.map_or((0.0, 0.0), |[s, t]| (s.to_f32(), t.to_f32()))
+    }
+
+    pub fn set_translation(&mut self, s: f32, t: f32) {
+        self.translation = if s == 0.0 && t == 0.0 {
+            None
+        }
+        else {
+            Some([Fixed1_19_12::from_f32(s), Fixed1_19_12::from_f32(t)])
+        };
+    }
+
+    fn update_flags(&self, flags: &mut MaterialFlags) {
+        flags.set(MaterialFlags::TEXMTX_USE, !self.is_identity());
+        flags.set(MaterialFlags::TEXMTX_SCALE_ONE, self.scale.is_none());
+        flags.set(MaterialFlags::TEXMTX_ROTATION_ZERO, self.rotation.is_none());
+        flags.set(MaterialFlags::TEXMTX_TRANSLATION_ZERO, self.translation.is_none());
+    }
+}
+
+
+// Render state summary, e.g. "128x128, repeat ST, cull back, alpha 31, lights 0+1, modulation"
+impl Display for Material {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+
+        if self.texture_width != 0 || self.texture_height != 0 {
+            parts.push(format!("{}x{}", self.texture_width, self.texture_height));
+
+            let params = &self.teximage_params;
+            for (name, s, t) in [("repeat", params.repeat_s(), params.repeat_t()), ("mirror", params.mirror_s(), params.mirror_t())] {
+                match (s, t) {
+                    (true, true) => parts.push(format!("{} ST", name)),
+                    (true, false) => parts.push(format!("{} S", name)),
+                    (false, true) => parts.push(format!("{} T", name)),
+                    (false, false) => {}
+                }
+            }
+        }
+        else {
+            parts.push("untextured".to_string());
+        }
+
+        let culling = match self.polygon_attr.culling_mode() {
+            PolygonAttr::CULL_ALL => "cull all",
+            PolygonAttr::CULL_FRONT => "cull front",
+            PolygonAttr::CULL_BACK => "cull back",
+            _ => "cull none"
+        };
+        parts.push(culling.to_string());
+
+        parts.push(format!("alpha {}", self.polygon_attr.alpha()));
+
+        let lights = (0..4)
+            .filter(|&light| self.polygon_attr.light_enabled(light))
+            .map(|light| light.to_string())
+            .collect::<Vec<String>>();
+
+        match lights.len() {
+            0 => parts.push("unlit".to_string()),
+            1 => parts.push(format!("light {}", lights[0])),
+            _ => parts.push(format!("lights {}", lights.join("+")))
+        }
+
+        let polygon_mode = match self.polygon_attr.polygon_mode() {
+            PolygonAttr::POLYGON_MODE_MODULATION => "modulation",
+            PolygonAttr::POLYGON_MODE_DECAL => "decal",
+            PolygonAttr::POLYGON_MODE_TOON_HIGHLIGHT => "toon/highlight",
+            _ => "shadow"
+        };
+        parts.push(polygon_mode.to_string());
+
+        if self.dif_amb.vertex_color() {
+            parts.push("vertex color".to_string());
+        }
+
+        if self.polygon_attr.fog_enabled() {
+            parts.push("fog".to_string());
+        }
+
+        if !self.texcoord_transform.is_identity() {
+            parts.push("texcoord transform".to_string());
+        }
+
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+
+// Common material setups
+impl Material {
+    // Texture shown as is. Lighting is off, so the white diffuse is used as vertex color
+    pub fn unlit_textured(texture_width: u16, texture_height: u16) -> Result<Material, AppError> {
+        Material::builder()
+            .light(0, false)
+            .light(1, false)
+            .diffuse(Rgb555::new(31, 31, 31))
+            .vertex_color(true)
+            .polygon_mode(PolygonAttr::POLYGON_MODE_MODULATION)
+            .texture_size(texture_width, texture_height)
+            .build()
+    }
+
+    // Untextured and unlit, colored by the Color commands of the mesh
+    pub fn vertex_color_only() -> Result<Material, AppError> {
+        Material::builder()
+            .light(0, false)
+            .light(1, false)
+            .vertex_color(false)
+            .polygon_mode(PolygonAttr::POLYGON_MODE_MODULATION)
+            .repeat(false, false)
+            .build()
+    }
+
+    // Lit and translucent. Alpha goes from 1 (almost transparent) to 30, as 0 is wireframe and 31 is opaque.
+    // Depth is not updated, so geometry behind is still drawn
+    pub fn alpha_blended(alpha: u8, texture_size: Option<(u16, u16)>) -> Result<Material, AppError> {
+        if !(1..=30).contains(&alpha) {
+            return Err(AppError::new(&format!("Invalid translucent alpha. Expected 1-30, got {}", alpha)));
+        }
+
+        let builder = Material::builder()
+            .alpha(alpha)
+            .translucent_depth_update(false);
+
+        match texture_size {
+            Some((width, height)) => builder.texture_size(width, height),
+            None => builder
+        }.build()
+    }
+
+    // Unlit edges drawn over coplanar geometry, e.g. to highlight a mesh
+    pub fn wireframe_decal(color: Rgb555) -> Result<Material, AppError> {
+        Material::builder()
+            .light(0, false)
+            .light(1, false)
+            .diffuse(color)
+            .vertex_color(true)
+            .polygon_mode(PolygonAttr::POLYGON_MODE_DECAL)
+            .culling_mode(PolygonAttr::CULL_NONE)
+            .depth_equal(true)
+            .wireframe()
+            .build()
+    }
+}
+
+
+// Visit https://problemkaputt.de/gbatek.htm#ds3dpolygonlightparameters (DIF_AMB) for more info
+#[derive(Debug, Clone, Copy)]
+pub struct DifAmb {
+    data: u32
+}
+
+impl DifAmb {
+    pub fn from_u32(data: u32) -> DifAmb {
+        DifAmb {
+            data
+        }
+    }
+
+    pub fn to_u32(&self) -> u32 {
+        self.data
+    }
+
+    pub fn diffuse(&self) -> Rgb555 {
+        Rgb555::from_u16((self.data & 0x7FFF) as u16)
+    }
+
+    pub fn set_diffuse(&mut self, color: Rgb555) {
+        self.data &= !0x00007FFF;
+        self.data |= color.to_u16() as u32;
+    }
+
+    pub fn diffuse_f32(&self) -> [f32; 3] {
+        self.diffuse().to_f32()
+    }
+
+    pub fn set_diffuse_f32(&mut self, rgb: [f32; 3]) {
+        self.set_diffuse(Rgb555::from_f32(rgb));
+    }
+
+    pub fn vertex_color(&self) -> bool {
+        // Diffuse color is also set as the vertex color
+        (self.data & 0x00008000) != 0
+    }
+
+    pub fn set_vertex_color(&mut self, vertex_color: bool) {
+        if vertex_color {
+            self.data |= 0x00008000;
+        } else {
+            self.data &= !0x00008000;
+        }
+    }
+
+    pub fn ambient(&self) -> Rgb555 {
+        Rgb555::from_u16(((self.data >> 16) & 0x7FFF) as u16)
+    }
+
+    pub fn set_ambient(&mut self, color: Rgb555) {
+        self.data &= !0x7FFF0000;
+        self.data |= (color.to_u16() as u32) << 16;
+    }
+
+    pub fn ambient_f32(&self) -> [f32; 3] {
+        self.ambient().to_f32()
+    }
+
+    pub fn set_ambient_f32(&mut self, rgb: [f32; 3]) {
+        self.set_ambient(Rgb555::from_f32(rgb));
+    }
+
+    pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
+        if buffer.len() < 4 {
+            return Err(AppError::new("DifAmb needs at least 4 bytes"));
+        }
+
+        buffer[0..4].copy_from_slice(&self.data.to_le_bytes());
+
+        Ok(())
+    }
+}
+
+// Visit https://problemkaputt.de/gbatek.htm#ds3dpolygonlightparameters (SPE_EMI) for more info
+#[derive(Debug, Clone, Copy)]
+pub struct SpeEmi {
+    data: u32
+}
+
+impl SpeEmi {
+    pub fn from_u32(data: u32) -> SpeEmi {
+        SpeEmi {
+            data
+        }
+    }
+
+    pub fn to_u32(&self) -> u32 {
+        self.data
+    }
+
+    pub fn specular(&self) -> Rgb555 {
+        Rgb555::from_u16((self.data & 0x7FFF) as u16)
+    }
+
+    pub fn set_specular(&mut self, color: Rgb555) {
+        self.data &= !0x00007FFF;
+        self.data |= color.to_u16() as u32;
+    }
+
+    pub fn specular_f32(&self) -> [f32; 3] {
+        self.specular().to_f32()
+    }
+
+    pub fn set_specular_f32(&mut self, rgb: [f32; 3]) {
+        self.set_specular(Rgb555::from_f32(rgb));
+    }
+
+    pub fn shininess_table(&self) -> bool {
+        // Specular reflection uses the shininess table
+        (self.data & 0x00008000) != 0
+    }
+
+    pub fn set_shininess_table(&mut self, enabled: bool) {
+        if enabled {
+            self.data |= 0x00008000;
+        } else {
+            self.data &= !0x00008000;
+        }
+    }
+
+    pub fn emission(&self) -> Rgb555 {
+        Rgb555::from_u16(((self.data >> 16) & 0x7FFF) as u16)
+    }
+
+    pub fn set_emission(&mut self, color: Rgb555) {
+        self.data &= !0x7FFF0000;
+        self.data |= (color.to_u16() as u32) << 16;
+    }
+
+    pub fn emission_f32(&self) -> [f32; 3] {
+        self.emission().to_f32()
+    }
+
+    pub fn set_emission_f32(&mut self, rgb: [f32; 3]) {
+        self.set_emission(Rgb555::from_f32(rgb));
+    }
+
+    pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
+        if buffer.len() < 4 {
+            return Err(AppError::new("SpeEmi needs at least 4 bytes"));
+        }
+
+        buffer[0..4].copy_from_slice(&self.data.to_le_bytes());
+
+        Ok(())
+    }
+}
+
+// Visit https://problemkaputt.de/gbatek.htm#ds3dpolygonattributes (POLYGON_ATTR) for more info
+#[derive(Debug, Clone, Copy)]
+pub struct PolygonAttr {
+    data: u32
+}
+
+impl PolygonAttr {
+    pub const POLYGON_MODE_MODULATION: u8 = 0x00;
+    pub const POLYGON_MODE_DECAL: u8 = 0x01;
+    pub const POLYGON_MODE_TOON_HIGHLIGHT: u8 = 0x02;
+    pub const POLYGON_MODE_SHADOW: u8 = 0x03;
+
+    // Which faces are rendered (bit 6: back, bit 7: front)
+    pub const CULL_ALL: u8 = 0x00;
+    pub const CULL_FRONT: u8 = 0x01;
+    pub const CULL_BACK: u8 = 0x02;
+    pub const CULL_NONE: u8 = 0x03;
+
+    pub fn from_u32(data: u32) -> PolygonAttr {
+        PolygonAttr {
+            data
+        }
+    }
+
+    pub fn to_u32(&self) -> u32 {
+        self.data
+    }
+
+    pub fn light_enabled(&self, light: u8) -> bool {
+        light < 4 && (self.data & (1 << light)) != 0
     }
 
-    pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
-        if buffer.len() < 44 { // 4 bytes for offsets + 40 bytes for material list
-            return Err(AppError::new("MaterialList needs at least 44 bytes"));
+    pub fn set_light_enabled(&mut self, light: u8, enabled: bool) -> Result<(), AppError> {
+        if light > 3 {
+            return Err(AppError::new(&format!("Invalid light index. Expected 0-3, got {}", light)));
         }
 
-        buffer[0..2].copy_from_slice(&self.texture_pairings_offset.to_le_bytes());
-        buffer[2..4].copy_from_slice(&self.palette_pairings_offset.to_le_bytes());
-        self.materials.write_bytes(&mut buffer[4..])?;
+        self.set_flag(1 << light, enabled);
 
-        for (i, &offset) in self.materials.data_iter().enumerate() {
-            let offset = offset as usize;
-            let material = &self.materials_data[i];
-            material.write_bytes(&mut buffer[offset..])?;
+        Ok(())
+    }
+
+    pub fn polygon_mode(&self) -> u8 {
+        ((self.data >> 4) & 0x03) as u8
+    }
+
+    pub fn set_polygon_mode(&mut self, mode: u8) -> Result<(), AppError> {
+        if mode > 3 {
+            return Err(AppError::new("Invalid polygon mode. Expected two bits"));
         }
 
-        self.texture_pairing_list.write_bytes(&mut buffer[self.texture_pairings_offset as usize..])?;
-        self.palette_pairing_list.write_bytes(&mut buffer[self.palette_pairings_offset as usize..])?;
-        
-        self.texture_pairing_list.write_indices(buffer)?;
-        self.palette_pairing_list.write_indices(buffer)?;
+        self.data &= !0x00000030;
+        self.data |= (mode as u32) << 4;
+
         Ok(())
     }
 
-    pub fn size(&self) -> usize {
-        // We get it like this, since there might be empty bytes in the middle of the material list
-        usize::max(usize::max(
-            *(self.materials.data_iter().max().unwrap_or(&0)) as usize + Material::SIZE, // Last material
-            self.texture_pairings_offset as usize + self.texture_pairing_list.size()), // Texture pairing
-            self.palette_pairings_offset as usize + self.palette_pairing_list.size() // Palette pairing
-        )
+    pub fn culling_mode(&self) -> u8 {
+        ((self.data >> 6) & 0x03) as u8
     }
 
-    pub fn rebase(&mut self) {
-        self.materials.rebase();
-        self.texture_pairing_list.rebase();
-        self.palette_pairing_list.rebase();
+    pub fn set_culling_mode(&mut self, mode: u8) -> Result<(), AppError> {
+        if mode > 3 {
+            return Err(AppError::new("Invalid culling mode. Expected two bits"));
+        }
 
-        let mut offset = 4; // texture_pairings_offset (2 bytes) + palette_pairings_offset (2 bytes)
-        offset += self.materials.size();
+        self.data &= !0x000000C0;
+        self.data |= (mode as u32) << 6;
 
-        self.texture_pairings_offset = offset as u16;
-        offset += self.texture_pairing_list.size();
+        Ok(())
+    }
 
-        self.palette_pairings_offset = offset as u16;
-        offset += self.palette_pairing_list.size();
+    pub fn render_back(&self) -> bool {
+        (self.data & 0x00000040) != 0
+    }
 
-        // Indices from pairing lists go after all the pairing lists and before the materials. They don't need to be aligned (they are individual bytes)
-        self.texture_pairing_list.set_begin_indices_offset(offset as u16);
-        offset += self.texture_pairing_list.total_indices_count();
-        self.palette_pairing_list.set_begin_indices_offset(offset as u16);
-        offset += self.palette_pairing_list.total_indices_count();
+    pub fn set_render_back(&mut self, render: bool) {
+        self.set_flag(0x00000040, render);
+    }
 
-        offset = get_4_byte_alignment(offset); // Material data must be 4-byte aligned
+    pub fn render_front(&self) -> bool {
+        (self.data & 0x00000080) != 0
+    }
 
-        for material_offset in self.materials.data_iter_mut() {
-            *material_offset = offset as u32;
-            offset += Material::SIZE;
-        }
+    pub fn set_render_front(&mut self, render: bool) {
+        self.set_flag(0x00000080, render);
     }
-}
 
+    pub fn translucent_depth_update(&self) -> bool {
+        (self.data & 0x00000800) != 0
+    }
 
-#[derive(Debug, Clone)]
-pub struct Material {
-    dummy: u16,
-    size: u16,
+    pub fn set_translucent_depth_update(&mut self, update: bool) {
+        self.set_flag(0x00000800, update);
+    }
 
-    dif_amb: u32, // Value for DIFF_AMB register
-    spe_emi: u32, // Value for SPE_EMI register
-    polygon_attr: u32, // Value for POLYGON_ATTR register
-    unknown_0: u32, // Mask for POLYGON_ATTR register??
-    teximage_params: TexImageParams,
+    pub fn far_plane_clipping(&self) -> bool {
+        // Polygons intersecting the far plane are clipped (1) or hidden (0)
+        (self.data & 0x00001000) != 0
+    }
 
-    unknown_1: u32,
-    unknown_2: u32,
+    pub fn set_far_plane_clipping(&mut self, clip: bool) {
+        self.set_flag(0x00001000, clip);
+    }
 
-    texture_width: u16,
-    texture_height: u16,
+    pub fn depth_equal(&self) -> bool {
+        // Depth test passes on equal values instead of less
+        (self.data & 0x00004000) != 0
+    }
 
-    remaining_fields: [u8; 8],
+    pub fn set_depth_equal(&mut self, equal: bool) {
+        self.set_flag(0x00004000, equal);
+    }
 
-    // Debug info
-    _debug_info: DebugInfo
-}
+    pub fn fog_enabled(&self) -> bool {
+        (self.data & 0x00008000) != 0
+    }
 
-impl Material {
-    const SIZE: usize = 44;
+    pub fn set_fog_enabled(&mut self, enabled: bool) {
+        self.set_flag(0x00008000, enabled);
+    }
 
-    pub fn from_bytes(bytes: &[u8], debug_info: DebugInfo) -> Result<Material, AppError> {
-        if bytes.len() < Material::SIZE {
-            return Err(AppError::new("Material needs at least 44 bytes"));
+    pub fn alpha(&self) -> u8 {
+        // 0 = wireframe, 31 = opaque
+        ((self.data >> 16) & 0x1F) as u8
+    }
+
+    pub fn set_alpha(&mut self, alpha: u8) -> Result<(), AppError> {
+        if alpha > 31 {
+            return Err(AppError::new("Invalid alpha. Expected five bits"));
         }
 
-        let dummy = u16::from_le_bytes([bytes[0], bytes[1]]);
-        let size = u16::from_le_bytes([bytes[2], bytes[3]]);
+        self.data &= !0x001F0000;
+        self.data |= (alpha as u32) << 16;
 
-        let dif_amb = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
-        let spe_emi = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
-        let polygon_attr = u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]);
-        let unknown_0 = u32::from_le_bytes([bytes[16], bytes[17], bytes[18], bytes[19]]);
-        let teximage_params = TexImageParams::from_u32(u32::from_le_bytes([bytes[20], bytes[21], bytes[22], bytes[23]]));
+        Ok(())
+    }
 
-        let unknown_1 = u32::from_le_bytes([bytes[24], bytes[25], bytes[26], bytes[27]]);
-        let unknown_2 = u32::from_le_bytes([bytes[28], bytes[29], bytes[30], bytes[31]]);
+    pub fn polygon_id(&self) -> u8 {
+        ((self.data >> 24) & 0x3F) as u8
+    }
 
-        let texture_width = u16::from_le_bytes([bytes[32], bytes[33]]);
-        let texture_height = u16::from_le_bytes([bytes[34], bytes[35]]);
+    pub fn set_polygon_id(&mut self, id: u8) -> Result<(), AppError> {
+        if id > 63 {
+            return Err(AppError::new("Invalid polygon ID. Expected six bits"));
+        }
 
-        let remaining_fields = [bytes[36], bytes[37], bytes[38], bytes[39], bytes[40], bytes[41], bytes[42], bytes[43]];
+        self.data &= !0x3F000000;
+        self.data |= (id as u32) << 24;
 
-        Ok(Material {
-            dummy,
-            size,
-            dif_amb,
-            spe_emi,
-            polygon_attr,
-            unknown_0,
-            teximage_params,
-            unknown_1,
-            unknown_2,
-            texture_width,
-            texture_height,
-            remaining_fields,
-            _debug_info: debug_info
-        })
+        Ok(())
     }
 
     pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
-        if buffer.len() < Material::SIZE {
-            return Err(AppError::new("Material needs at least 44 bytes"));
+        if buffer.len() < 4 {
+            return Err(AppError::new("PolygonAttr needs at least 4 bytes"));
         }
 
-        buffer[0..2].copy_from_slice(&self.dummy.to_le_bytes());
-        buffer[2..4].copy_from_slice(&self.size.to_le_bytes());
-
-        buffer[4..8].copy_from_slice(&self.dif_amb.to_le_bytes());
-        buffer[8..12].copy_from_slice(&self.spe_emi.to_le_bytes());
-        buffer[12..16].copy_from_slice(&self.polygon_attr.to_le_bytes());
-        buffer[16..20].copy_from_slice(&self.unknown_0.to_le_bytes());
-        self.teximage_params.write_bytes(&mut buffer[20..24])?;
-
-        buffer[24..28].copy_from_slice(&self.unknown_1.to_le_bytes());
-        buffer[28..32].copy_from_slice(&self.unknown_2.to_le_bytes());
-
-        buffer[32..34].copy_from_slice(&self.texture_width.to_le_bytes());
-        buffer[34..36].copy_from_slice(&self.texture_height.to_le_bytes());
-
-        buffer[36..44].copy_from_slice(&self.remaining_fields);
+        buffer[0..4].copy_from_slice(&self.data.to_le_bytes());
 
         Ok(())
     }
+
+    fn set_flag(&mut self, mask: u32, value: bool) {
+        if value {
+            self.data |= mask;
+        } else {
+            self.data &= !mask;
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -223,6 +1503,20 @@ impl TexImageParams {
         }
     }
 
+    pub fn to_u32(&self) -> u32 {
+        self.data
+    }
+
+    // Texture VRAM offset in 8 byte units. Masked out in materials, but used by TEXIMAGE_PARAM commands
+    pub fn texture_data(&self) -> u16 {
+        (self.data & 0xFFFF) as u16
+    }
+
+    pub fn set_texture_data(&mut self, offset: u16) {
+        self.data &= !0x0000FFFF;
+        self.data |= offset as u32;
+    }
+
     pub fn repeat_s(&self) -> bool {
         (self.data & 0x00010000) != 0
     }
@@ -271,6 +1565,80 @@ impl TexImageParams {
         }
     }
 
+    pub fn texture_s_size(&self) -> u8 {
+        // 8 << this = texture width
+        ((self.data >> 20) & 0x07) as u8
+    }
+
+    pub fn set_texture_s_size(&mut self, size: u8) -> Result<(), AppError> {
+        if size > 7 {
+            return Err(AppError::new("Invalid texture S size. Expected three bits"));
+        }
+
+        self.data &= !0x00700000;
+        self.data |= (size as u32) << 20;
+
+        Ok(())
+    }
+
+    pub fn texture_t_size(&self) -> u8 {
+        // 8 << this = texture height
+        ((self.data >> 23) & 0x07) as u8
+    }
+
+    pub fn set_texture_t_size(&mut self, size: u8) -> Result<(), AppError> {
+        if size > 7 {
+            return Err(AppError::new("Invalid texture T size. Expected three bits"));
+        }
+
+        self.data &= !0x03800000;
+        self.data |= (size as u32) << 23;
+
+        Ok(())
+    }
+
+    // Converts a texture dimension (8, 16, ..., 1024) to its size bits
+    pub fn size_to_bits(size: u16) -> Result<u8, AppError> {
+        if !size.is_power_of_two() || !(8..=1024).contains(&size) {
+            return Err(AppError::new(&format!("Invalid texture dimension {}. Expected a power of two between 8 and 1024", size)));
+        }
+
+        Ok((size.trailing_zeros() - 3) as u8)
+    }
+
+    // Same values as TEX0 TeximageParams
+    pub fn texture_format(&self) -> u8 {
+        ((self.data >> 26) & 0x07) as u8
+    }
+
+    pub fn set_texture_format(&mut self, format: u8) -> Result<(), AppError> {
+        if format > 7 {
+            return Err(AppError::new("Invalid texture format. Expected three bits"));
+        }
+
+        self.data &= !0x1C000000;
+        self.data |= (format as u32) << 26;
+
+        Ok(())
+    }
+
+    pub fn palette_color_0_transparent(&self) -> bool {
+        (self.data & 0x20000000) != 0
+    }
+
+    pub fn set_palette_color_0_transparent(&mut self, transparent: bool) {
+        if transparent {
+            self.data |= 0x20000000;
+        } else {
+            self.data &= !0x20000000;
+        }
+    }
+
+    pub const TRANSFORM_MODE_NONE: u8 = 0x00;
+    pub const TRANSFORM_MODE_TEXCOORD: u8 = 0x01;
+    pub const TRANSFORM_MODE_NORMAL: u8 = 0x02;
+    pub const TRANSFORM_MODE_VERTEX: u8 = 0x03;
+
     pub fn texcoords_transform_mode(&self) -> u8 {
         ((self.data >> 30) & 0x03) as u8
     }
@@ -298,6 +1666,15 @@ impl TexImageParams {
 }
 
 
+#[derive(Debug, Clone)]
+pub struct TextureDimensionMismatch {
+    pub material_index: usize,
+    pub texture_name: String,
+    pub material_size: (u16, u16), // Size stored in the material before syncing
+    pub texture_size: Option<(u16, u16)> // None if the texture was not found in the TEX0
+}
+
+
 #[derive(Debug, Clone)]
 pub struct TexturePairingList {
     texture_pairings: NameList<MaterialIdxList>,
@@ -365,6 +1742,81 @@ impl TexturePairingList {
             offset += pairing.count as u16;
         }
     }
+
+    pub fn name_for_material(&self, material_index: u8) -> Option<&Name> {
+        let pairing_index = self.texture_pairings.data_iter()
+            .position(|pairing| pairing.indices.contains(&material_index))?;
+
+        self.texture_pairings.get_name(pairing_index)
+    }
+
+    pub fn validate_indices(&self, material_count: usize) -> Result<(), AppError> {
+        for (pairing_name, pairing) in self.texture_pairings.names_iter().zip(self.texture_pairings.data_iter()) {
+            if let Some(&index) = pairing.indices.iter().find(|&&index| index as usize >= material_count) {
+                return Err(AppError::new(&format!(
+                    "Texture pairing \"{}\" references material {}, but there are only {} materials",
+                    pairing_name.to_not_null_string()?, index, material_count
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn materials_for_name(&self, texture_name: &str) -> Vec<usize> {
+        self.texture_pairings.names_iter()
+            .zip(self.texture_pairings.data_iter())
+            .filter(|(pairing_name, _)| pairing_name.to_not_null_string().is_ok_and(|pairing_name| pairing_name == texture_name))
+            .flat_map(|(_, pairing)| pairing.indices.iter().map(|&index| index as usize))
+            .collect()
+    }
+
+    fn set_material_name(&mut self, material_index: u8, texture_name: &str) -> Result<(), AppError> {
+        let name = Name::from_string(texture_name)?;
+
+        for pairing in self.texture_pairings.data_iter_mut() {
+            pairing.unpair_material_index(material_index);
+        }
+
+        let pairing_index = self.texture_pairings.names_iter()
+            .position(|pairing_name| pairing_name.to_not_null_string().is_ok_and(|pairing_name| pairing_name == texture_name));
+
+        match pairing_index.and_then(|pairing_index| self.texture_pairings.get_mut(pairing_index)) {
+            Some(pairing) => pairing.pair_material_index(material_index),
+            None => {
+                let mut pairing = MaterialIdxList::new();
+                pairing.pair_material_index(material_index);
+                self.texture_pairings.push(name, pairing)?;
+            }
+        }
+
+        self.remove_empty_pairings()
+    }
+
+    fn rename(&mut self, texture_name: &str, new_name: &str) -> Result<(), AppError> {
+        rename_pairing(&mut self.texture_pairings, texture_name, new_name)
+    }
+
+    fn remove_material_index(&mut self, material_index: u8) -> Result<(), AppError> {
+        for pairing in self.texture_pairings.data_iter_mut() {
+            pairing.remove_material_index(material_index);
+        }
+
+        self.remove_empty_pairings()
+    }
+
+    // Textures no longer used by any material are dropped from the pairing list
+    fn remove_empty_pairings(&mut self) -> Result<(), AppError> {
+        loop {
+            let empty_index = self.texture_pairings.data_iter().position(|pairing| pairing.indices.is_empty());
+            match empty_index {
+                Some(empty_index) => { self.texture_pairings.remove(empty_index)?; },
+                None => break
+            }
+        }
+
+        Ok(())
+    }
 }
 
 
@@ -433,6 +1885,81 @@ impl PalettePairingList {
             offset += pairing.count as u16;
         }
     }
+
+    pub fn name_for_material(&self, material_index: u8) -> Option<&Name> {
+        let pairing_index = self.palette_pairings.data_iter()
+            .position(|pairing| pairing.indices.contains(&material_index))?;
+
+        self.palette_pairings.get_name(pairing_index)
+    }
+
+    pub fn validate_indices(&self, material_count: usize) -> Result<(), AppError> {
+        for (pairing_name, pairing) in self.palette_pairings.names_iter().zip(self.palette_pairings.data_iter()) {
+            if let Some(&index) = pairing.indices.iter().find(|&&index| index as usize >= material_count) {
+                return Err(AppError::new(&format!(
+                    "Palette pairing \"{}\" references material {}, but there are only {} materials",
+                    pairing_name.to_not_null_string()?, index, material_count
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn materials_for_name(&self, palette_name: &str) -> Vec<usize> {
+        self.palette_pairings.names_iter()
+            .zip(self.palette_pairings.data_iter())
+            .filter(|(pairing_name, _)| pairing_name.to_not_null_string().is_ok_and(|pairing_name| pairing_name == palette_name))
+            .flat_map(|(_, pairing)| pairing.indices.iter().map(|&index| index as usize))
+            .collect()
+    }
+
+    fn set_material_name(&mut self, material_index: u8, palette_name: &str) -> Result<(), AppError> {
+        let name = Name::from_string(palette_name)?;
+
+        for pairing in self.palette_pairings.data_iter_mut() {
+            pairing.unpair_material_index(material_index);
+        }
+
+        let pairing_index = self.palette_pairings.names_iter()
+            .position(|pairing_name| pairing_name.to_not_null_string().is_ok_and(|pairing_name| pairing_name == palette_name));
+
+        match pairing_index.and_then(|pairing_index| self.palette_pairings.get_mut(pairing_index)) {
+            Some(pairing) => pairing.pair_material_index(material_index),
+            None => {
+                let mut pairing = MaterialIdxList::new();
+                pairing.pair_material_index(material_index);
+                self.palette_pairings.push(name, pairing)?;
+            }
+        }
+
+        self.remove_empty_pairings()
+    }
+
+    fn rename(&mut self, palette_name: &str, new_name: &str) -> Result<(), AppError> {
+        rename_pairing(&mut self.palette_pairings, palette_name, new_name)
+    }
+
+    fn remove_material_index(&mut self, material_index: u8) -> Result<(), AppError> {
+        for pairing in self.palette_pairings.data_iter_mut() {
+            pairing.remove_material_index(material_index);
+        }
+
+        self.remove_empty_pairings()
+    }
+
+    // Palettes no longer used by any material are dropped from the pairing list
+    fn remove_empty_pairings(&mut self) -> Result<(), AppError> {
+        loop {
+            let empty_index = self.palette_pairings.data_iter().position(|pairing| pairing.indices.is_empty());
+            match empty_index {
+                Some(empty_index) => { self.palette_pairings.remove(empty_index)?; },
+                None => break
+            }
+        }
+
+        Ok(())
+    }
 }
 
 
@@ -448,9 +1975,44 @@ pub struct MaterialIdxList {
 
 }
 
+// Materials already paired with new_name keep their pairing, and the ones paired with name join them
+fn rename_pairing(pairings: &mut NameList<MaterialIdxList>, name: &str, new_name: &str) -> Result<(), AppError> {
+    let new_name_value = Name::from_string(new_name)?;
+
+    let position = |pairings: &NameList<MaterialIdxList>, name: &str| pairings.names_iter()
+        .position(|pairing_name| pairing_name.to_not_null_string().is_ok_and(|pairing_name| pairing_name == name));
+
+    let Some(pairing_index) = position(pairings, name) else {
+        return Ok(());
+    };
+
+    match position(pairings, new_name) {
+        Some(existing_index) if existing_index != pairing_index => {
+            let (_, pairing) = pairings.remove(pairing_index)?;
+            let existing_index = if existing_index > pairing_index { existing_index - 1 } else { existing_index };
+            let existing = pairings.get_mut(existing_index).unwrap();
+            for &material_index in &pairing.indices {
+                existing.pair_material_index(material_index);
+            }
+        },
+        _ => *pairings.get_name_mut(pairing_index).unwrap() = new_name_value
+    }
+
+    Ok(())
+}
+
 impl MaterialIdxList {
     const SIZE: usize = 4; // Offset (2 bytes) + Count (1 byte) + Dummy (1 byte)
 
+    fn new() -> MaterialIdxList {
+        MaterialIdxList {
+            offset: 0, // Set on rebase
+            count: 0,
+            dummy: 0,
+            indices: Vec::new()
+        }
+    }
+
     fn read_indices(&mut self, material_list_bytes: &[u8]) -> Result<(), AppError> {
         if material_list_bytes.len() < (self.offset + self.count as u16) as usize {
             return Err(AppError::new(&format!("MaterialIdxList needs at least {} bytes from the MaterialList to read indices", self.offset + self.count as u16)));
@@ -483,6 +2045,34 @@ impl MaterialIdxList {
     pub fn rebase(&mut self) {
         self.count = self.indices.len() as u8;
     }
+
+    fn pair_material_index(&mut self, material_index: u8) {
+        if !self.indices.contains(&material_index) {
+            self.indices.push(material_index);
+            self.indices.sort_unstable();
+        }
+
+        self.rebase();
+    }
+
+    // Drops the given material index without touching the other ones
+    fn unpair_material_index(&mut self, material_index: u8) {
+        self.indices.retain(|&index| index != material_index);
+        self.rebase();
+    }
+
+    // Drops the given material index and shifts down the ones after it
+    fn remove_material_index(&mut self, material_index: u8) {
+        self.indices.retain(|&index| index != material_index);
+
+        for index in self.indices.iter_mut() {
+            if *index > material_index {
+                *index -= 1;
+            }
+        }
+
+        self.rebase();
+    }
 }
 
 impl BinarySerializable for MaterialIdxList {
@@ -1,125 +1,364 @@
-use crate::{error::AppError, util::number::{alignment::get_4_byte_alignment, fixed_point::{fixed_1_0_9::Fixed1_0_9, fixed_1_11_4::Fixed1_11_4, fixed_1_19_12::Fixed1_19_12, fixed_1_3_12::Fixed1_3_12, fixed_1_3_6::Fixed1_3_6}}};
+use std::{fmt::Display, ops::AddAssign};
+
+use crate::{error::AppError, subfiles::{mdl::model::material_list::{DifAmb, PolygonAttr, SpeEmi, TexImageParams}, tex::texture::TeximageParams}, util::{color::Rgb555, number::{alignment::get_4_byte_alignment, fixed_point::{fixed_1_0_9::Fixed1_0_9, fixed_1_11_4::Fixed1_11_4, fixed_1_19_12::Fixed1_19_12, fixed_1_3_12::Fixed1_3_12, fixed_1_3_6::Fixed1_3_6}}}};
 
 static SIZES: [i8; 66] = [
     0, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
     1,  0,  1,  1,  1,  0, 16, 12, 16, 12,  9,  3,  3, -1, -1, -1,
     1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  1, -1, -1, -1, -1,
-    1,  1,  1,  1,  1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
+    1,  1,  1,  1, 32, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
     1,  0
 ];
 
-#[derive(Debug, Clone)]
+// Commands are stored flat (op codes plus a packed param buffer) so big lists do not allocate per command.
+// The GpuCommand enum is a decoded view, built on access
+#[derive(Debug, Clone, Default)]
 pub struct GpuCommandList {
-    render_cmds: Vec<GpuCommand>
+    op_codes: Vec<u8>,
+    param_offsets: Vec<u32>, // Offset of the params of each command in params
+    params: Vec<u8>
 }
 
 impl GpuCommandList {
+    pub fn new() -> GpuCommandList {
+        GpuCommandList {
+            op_codes: Vec::new(),
+            param_offsets: Vec::new(),
+            params: Vec::new()
+        }
+    }
+
     pub fn from_bytes(bytes: &[u8]) -> Result<GpuCommandList, AppError> {
         if bytes.len() < 4 {
             return Err(AppError::new("GpuCommandList needs at least 4 bytes"));
         }
 
-        let mut render_cmds = Vec::new();
+        let mut list = GpuCommandList::new();
 
         let mut pos = 0;
         while pos < bytes.len() {
+            if bytes.len() < pos + 4 {
+                return Err(AppError::new(&format!("GpuCommandList has a partial op code pack of {} bytes at offset {}", bytes.len() - pos, pos)));
+            }
+
             let ops = [bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]];
             pos += 4;
     
             for &op in ops.iter() {
                 let param_count = num_params(op)? << 2;
-    
-                let params = &bytes[pos..pos + param_count];
-                pos += param_count;
 
-                let command = GpuCommand::from_bytes(op, params)?;
+                if bytes.len() < pos + param_count {
+                    return Err(AppError::new(&format!("GPU command 0x{:02X} needs {} bytes of params, only {} left", op, param_count, bytes.len() - pos)));
+                }
 
-                render_cmds.push(command);
+                list.push_raw(op, &bytes[pos..pos + param_count]);
+                pos += param_count;
             }
         }
 
-        Ok(GpuCommandList {
-            render_cmds
-        })
+        Ok(list)
     }
 
     pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
-        let extension_nops = vec![GpuCommand::Nop; self.nop_padding_ammount()];
-
-        let mut padded_cmds_iter = self.render_cmds.iter().chain(extension_nops.iter());
+        let size = self.size();
+        if buffer.len() < size {
+            return Err(AppError::new(&format!("GpuCommandList needs at least {} bytes to write", size)));
+        }
 
         let mut offset = 0;
-        while let (
-            Some(cmd_0),
-            Some(cmd_1),
-            Some(cmd_2),
-            Some(cmd_3)
-        ) = (
-            padded_cmds_iter.next(),
-            padded_cmds_iter.next(),
-            padded_cmds_iter.next(),
-            padded_cmds_iter.next()
-        ) {
-            let commands = [cmd_0, cmd_1, cmd_2, cmd_3];
-
-            buffer[offset..offset + 4].copy_from_slice(
-                &commands.iter()
-                    .map(|cmd| cmd.op_code())
-                    .collect::<Result<Vec<u8>, AppError>>()?
-            );
-
+        for (i, ops) in self.op_codes.chunks(4).enumerate() {
+            // The last pack is padded with NOPs, which have no params
+            buffer[offset..offset + 4].fill(0);
+            buffer[offset..offset + ops.len()].copy_from_slice(ops);
             offset += 4;
 
-            for command in commands {
-                let param_count = num_params(command.op_code()?)?;
-                let param_bytes_amount = param_count << 2;
+            let first = i * 4;
+            let params_start = self.param_offsets[first] as usize;
+            let params_end = self.param_offsets.get(first + 4).map_or(self.params.len(), |&end| end as usize);
 
-                let params_buffer = &mut buffer[offset..offset + param_bytes_amount];
-                
-                command.write_params_bytes(params_buffer)?;
-                offset += param_bytes_amount;
-            }
+            let params = &self.params[params_start..params_end];
+            buffer[offset..offset + params.len()].copy_from_slice(params);
+            offset += params.len();
         }
 
         Ok(())
     }
 
     pub fn size(&self) -> usize {
-        self.render_cmds.len() + self.nop_padding_ammount() + // 1 byte for each command code
-        self.render_cmds.iter()
-            .map(|cmd| num_params(cmd.op_code().unwrap()).unwrap() << 2) // 4 bytes for each parameter
-            .sum::<usize>()
+        self.op_codes.len() + self.nop_padding_ammount() + // 1 byte for each command code
+        self.params.len() // 4 bytes for each parameter
+    }
+
+    pub fn len(&self) -> usize {
+        self.op_codes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.op_codes.is_empty()
     }
 
     pub fn clear(&mut self) {
-        self.render_cmds.clear();
+        self.op_codes.clear();
+        self.param_offsets.clear();
+        self.params.clear();
+    }
+
+    pub fn push(&mut self, command: GpuCommand) -> Result<(), AppError> {
+        let op_code = command.op_code();
+        let start = self.params.len();
+
+        self.params.resize(start + (command.num_params() << 2), 0);
+        if let Err(err) = command.write_params_bytes(&mut self.params[start..]) {
+            self.params.truncate(start);
+            return Err(err);
+        }
+
+        self.op_codes.push(op_code);
+        self.param_offsets.push(start as u32);
+
+        Ok(())
+    }
+
+    pub fn extend(&mut self, commands: Vec<GpuCommand>) -> Result<(), AppError> {
+        for command in commands {
+            self.push(command)?;
+        }
+
+        Ok(())
+    }
+
+    // Decoded view of a command
+    pub fn get(&self, index: usize) -> Option<GpuCommand> {
+        let op_code = *self.op_codes.get(index)?;
+
+        // Params were sized from the op code when the command was added, so decoding cannot fail
+        GpuCommand::from_bytes(op_code, self.params_at(index)).ok()
     }
 
-    pub fn push(&mut self, command: GpuCommand) {
-        self.render_cmds.push(command);
+    pub fn get_all(&self) -> Vec<GpuCommand> {
+        self.iter().collect()
     }
 
-    pub fn extend(&mut self, commands: Vec<GpuCommand>) {
-        self.render_cmds.extend(commands);
+    pub fn iter(&self) -> impl Iterator<Item = GpuCommand> + '_ {
+        (0..self.op_codes.len()).filter_map(|index| self.get(index))
     }
 
-    pub fn get(&self, index: usize) -> Option<&GpuCommand> {
-        self.render_cmds.get(index)
+    // Vertex commands (VTX_*) with their index
+    pub fn vertices(&self) -> impl Iterator<Item = (usize, GpuCommand)> + '_ {
+        self.iter_op_codes(|op_code| (0x23..=0x28).contains(&op_code))
     }
 
-    pub fn get_all(&self) -> &[GpuCommand] {
-        &self.render_cmds
+    // Matrix commands (MTX_*) with their index
+    pub fn matrix_ops(&self) -> impl Iterator<Item = (usize, GpuCommand)> + '_ {
+        self.iter_op_codes(|op_code| (0x10..=0x1C).contains(&op_code))
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = &GpuCommand> {
-        self.render_cmds.iter()
+    pub fn texcoords(&self) -> impl Iterator<Item = (usize, TexCoordParams)> + '_ {
+        self.iter_op_codes(|op_code| op_code == 0x22)
+            .filter_map(|(index, cmd)| match cmd {
+                GpuCommand::TexCoord(params) => Some((index, *params)),
+                _ => None
+            })
+    }
+
+    pub fn normals(&self) -> impl Iterator<Item = (usize, NormalParams)> + '_ {
+        self.iter_op_codes(|op_code| op_code == 0x21)
+            .filter_map(|(index, cmd)| match cmd {
+                GpuCommand::Normal(params) => Some((index, *params)),
+                _ => None
+            })
+    }
+
+    pub fn colors(&self) -> impl Iterator<Item = (usize, ColorParams)> + '_ {
+        self.iter_op_codes(|op_code| op_code == 0x20)
+            .filter_map(|(index, cmd)| match cmd {
+                GpuCommand::Color(params) => Some((index, *params)),
+                _ => None
+            })
+    }
+
+    // Only the commands passing the op code filter get decoded
+    fn iter_op_codes(&self, filter: impl Fn(u8) -> bool + 'static) -> impl Iterator<Item = (usize, GpuCommand)> + '_ {
+        self.op_codes.iter()
+            .enumerate()
+            .filter(move |&(_, &op_code)| filter(op_code))
+            .filter_map(|(index, _)| self.get(index).map(|cmd| (index, cmd)))
+    }
+
+    // Raw access, without decoding the commands
+    pub fn op_codes(&self) -> &[u8] {
+        &self.op_codes
+    }
+
+    pub fn params_at(&self, index: usize) -> &[u8] {
+        let start = self.param_offsets[index] as usize;
+        let end = self.param_offsets.get(index + 1).map_or(self.params.len(), |&end| end as usize);
+
+        &self.params[start..end]
+    }
+
+    fn push_raw(&mut self, op_code: u8, params: &[u8]) {
+        self.op_codes.push(op_code);
+        self.param_offsets.push(self.params.len() as u32);
+        self.params.extend_from_slice(params);
+    }
+
+    // Checks that every vertex group is opened and closed, and that its vertex count fits its primitive type.
+    // Returns one issue per problem found, so an empty list means the commands are valid
+    pub fn validate(&self) -> Vec<GpuCommandIssue> {
+        let mut issues = Vec::new();
+        let mut issue = |command_index: usize, message: String| issues.push(GpuCommandIssue { command_index, message });
+
+        // (command index, primitive type, vertex count) of the open group
+        let mut open_group: Option<(usize, u8, usize)> = None;
+
+        for (i, cmd) in self.iter().enumerate() {
+            match cmd {
+                GpuCommand::BeginVtxs(params) => {
+                    if let Some((begin_index, _, _)) = open_group {
+                        issue(i, format!("BeginVtxs command {} opens a group while the one from command {} is still open", i, begin_index));
+                    }
+
+                    open_group = Some((i, params.primitive_type, 0));
+                },
+                GpuCommand::EndVtxs => {
+                    match open_group.take() {
+                        Some((begin_index, primitive_type, count)) => {
+                            if let Some(problem) = vertex_count_problem(primitive_type, count) {
+                                issue(i, format!("Vertex group from command {} to {}: {}", begin_index, i, problem));
+                            }
+                        },
+                        None => issue(i, format!("EndVtxs command {} closes no group", i))
+                    }
+                },
+                _ if cmd.is_vertex() => {
+                    match open_group.as_mut() {
+                        Some((_, _, count)) => *count += 1,
+                        None => issue(i, format!("Vertex command {} is outside of a vertex group", i))
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        if let Some((begin_index, _, _)) = open_group {
+            issue(begin_index, format!("Vertex group from command {} is never closed", begin_index));
+        }
+
+        issues
+    }
+
+    // Readable listing, one command per line with its decoded params.
+    // Vertex commands also show the running vertex count of the list and of their group
+    pub fn disassemble(&self) -> String {
+        let mut lines = Vec::with_capacity(self.op_codes.len());
+
+        let mut total_vertices = 0;
+        let mut group_vertices = 0;
+        for (i, cmd) in self.iter().enumerate() {
+            match cmd {
+                GpuCommand::BeginVtxs(_) => group_vertices = 0,
+                _ if cmd.is_vertex() => {
+                    total_vertices += 1;
+                    group_vertices += 1;
+                },
+                _ => {}
+            }
+
+            if cmd.is_vertex() {
+                lines.push(format!("{:04} {:<48} ; vertex {} (group vertex {})", i, cmd.to_string(), total_vertices, group_vertices));
+            }
+            else {
+                lines.push(format!("{:04} {}", i, cmd));
+            }
+        }
+
+        lines.join("\n")
+    }
+
+    // Counts the drawn vertices and primitives. Strips are split into their triangles/quads
+    pub fn count_primitives(&self) -> PrimitiveCounts {
+        let mut counts = PrimitiveCounts::default();
+
+        // Groups end with EndVtxs, the next BeginVtxs or the end of the list
+        let mut group: Option<(u8, usize)> = None;
+        for cmd in self.iter() {
+            match cmd {
+                GpuCommand::BeginVtxs(params) => {
+                    if let Some((primitive_type, count)) = group {
+                        counts.add_group(primitive_type, count);
+                    }
+
+                    group = Some((params.primitive_type, 0));
+                },
+                GpuCommand::EndVtxs => {
+                    if let Some((primitive_type, count)) = group.take() {
+                        counts.add_group(primitive_type, count);
+                    }
+                },
+                _ if cmd.is_vertex() => {
+                    counts.vertices += 1;
+
+                    if let Some((_, count)) = group.as_mut() {
+                        *count += 1;
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        if let Some((primitive_type, count)) = group {
+            counts.add_group(primitive_type, count);
+        }
+
+        counts
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PrimitiveCounts {
+    pub vertices: usize,
+    pub triangles: usize,
+    pub quads: usize
+}
+
+impl PrimitiveCounts {
+    pub fn polygons(&self) -> usize {
+        self.triangles + self.quads
+    }
+
+    fn add_group(&mut self, primitive_type: u8, count: usize) {
+        match primitive_type {
+            BeginVtxsParams::TRIANGLE => self.triangles += count / 3,
+            BeginVtxsParams::QUAD => self.quads += count / 4,
+            BeginVtxsParams::TRIANGLE_STRIP => self.triangles += count.saturating_sub(2),
+            _ => self.quads += count.saturating_sub(2) / 2
+        }
+    }
+}
+
+impl AddAssign for PrimitiveCounts {
+    fn add_assign(&mut self, other: PrimitiveCounts) {
+        self.vertices += other.vertices;
+        self.triangles += other.triangles;
+        self.quads += other.quads;
+    }
+}
+
+fn vertex_count_problem(primitive_type: u8, count: usize) -> Option<String> {
+    match primitive_type {
+        BeginVtxsParams::TRIANGLE if !count.is_multiple_of(3) => Some(format!("{} vertices is not a multiple of 3 for triangles", count)),
+        BeginVtxsParams::QUAD if !count.is_multiple_of(4) => Some(format!("{} vertices is not a multiple of 4 for quads", count)),
+        BeginVtxsParams::TRIANGLE_STRIP if count < 3 => Some(format!("{} vertices is not enough for a triangle strip", count)),
+        BeginVtxsParams::QUAD_STRIP if count < 4 || !count.is_multiple_of(2) => Some(format!("{} vertices is not a valid quad strip (at least 4 and even)", count)),
+        _ => None
     }
 }
 
 // Index and size management helpers
 impl GpuCommandList {
     fn nop_padding_ammount(&self) -> usize {
-        let length = self.render_cmds.len();
+        let length = self.op_codes.len();
 
         let next_multiple_of_4 = get_4_byte_alignment(length);
         let padding = next_multiple_of_4 - length;
@@ -137,22 +376,29 @@ fn num_params(opcode: u8) -> Result<usize, AppError> {
     Ok(SIZES[opcode] as usize)
 }
 
+// Problem found by GpuCommandList::validate
+#[derive(Debug, Clone)]
+pub struct GpuCommandIssue {
+    pub command_index: usize,
+    pub message: String
+}
+
 #[derive(Debug, Clone)]
 pub enum GpuCommand {
     Nop, // 0x00
-    Unknown0x10(Box<Unknown0x10Params>), // 0x10
-    Unknown0x11, // 0x11
-    Unknown0x12(Box<Unknown0x12Params>), // 0x12
-    Unknown0x13(Box<Unknown0x13Params>), // 0x13
+    MtxMode(Box<MtxModeParams>), // 0x10
+    MtxPush, // 0x11
+    MtxPop(Box<MtxPopParams>), // 0x12
+    MtxStore(Box<MtxStoreParams>), // 0x13
     MtxRestore(Box<MtxRestoreParams>), // 0x14
-    Unknown0x15, // 0x15
-    Unknown0x16(Box<Unknown0x16Params>), // 0x16
-    Unknown0x17(Box<Unknown0x17Params>), // 0x17
-    Unknown0x18(Box<Unknown0x18Params>), // 0x18
-    Unknown0x19(Box<Unknown0x19Params>), // 0x19
-    Unknown0x1A(Box<Unknown0x1AParams>), // 0x1A
+    MtxIdentity, // 0x15
+    MtxLoad4x4(Box<Mtx4x4Params>), // 0x16
+    MtxLoad4x3(Box<Mtx4x3Params>), // 0x17
+    MtxMult4x4(Box<Mtx4x4Params>), // 0x18
+    MtxMult4x3(Box<Mtx4x3Params>), // 0x19
+    MtxMult3x3(Box<Mtx3x3Params>), // 0x1A
     MtxScale(Box<MtxScaleParams>), // 0x1B
-    Unknown0x1C(Box<Unknown0x1CParams>), // 0x1C
+    MtxTrans(Box<MtxTransParams>), // 0x1C
     Color(Box<ColorParams>), // 0x20
     Normal(Box<NormalParams>), // 0x21
     TexCoord(Box<TexCoordParams>), // 0x22
@@ -162,14 +408,14 @@ pub enum GpuCommand {
     VtxXZ(Box<VtxXZParams>), // 0x26
     VtxYZ(Box<VtxYZParams>), // 0x27
     VtxDiff(Box<VtxDiffParams>), // 0x28
-    Unknown0x29(Box<Unknown0x29Params>), // 0x29
-    Unknown0x2A(Box<Unknown0x2AParams>), // 0x2A
-    Unknown0x2B(Box<Unknown0x2BParams>), // 0x2B
-    Unknown0x30(Box<Unknown0x30Params>), // 0x30
-    Unknown0x31(Box<Unknown0x31Params>), // 0x31
-    Unknown0x32(Box<Unknown0x32Params>), // 0x32
-    Unknown0x33(Box<Unknown0x33Params>), // 0x33
-    Unknown0x34(Box<Unknown0x34Params>), // 0x34
+    PolygonAttr(Box<PolygonAttrParams>), // 0x29
+    TexImageParam(Box<TexImageParamParams>), // 0x2A
+    PlttBase(Box<PlttBaseParams>), // 0x2B
+    DifAmb(Box<DifAmbParams>), // 0x30
+    SpeEmi(Box<SpeEmiParams>), // 0x31
+    LightVector(Box<LightVectorParams>), // 0x32
+    LightColor(Box<LightColorParams>), // 0x33
+    Shininess(Box<ShininessParams>), // 0x34
     BeginVtxs(Box<BeginVtxsParams>), // 0x40
     EndVtxs // 0x41
 }
@@ -179,50 +425,50 @@ impl GpuCommand {
         let command = match op_code {
             0x00 => GpuCommand::Nop,
             0x10 => {
-                let params = Unknown0x10Params::from_bytes(params)?;
-                GpuCommand::Unknown0x10(Box::new(params))
+                let params = MtxModeParams::from_bytes(params)?;
+                GpuCommand::MtxMode(Box::new(params))
             },
-            0x11 => GpuCommand::Unknown0x11,
+            0x11 => GpuCommand::MtxPush,
             0x12 => {
-                let params = Unknown0x12Params::from_bytes(params)?;
-                GpuCommand::Unknown0x12(Box::new(params))
+                let params = MtxPopParams::from_bytes(params)?;
+                GpuCommand::MtxPop(Box::new(params))
             },
             0x13 => {
-                let params = Unknown0x13Params::from_bytes(params)?;
-                GpuCommand::Unknown0x13(Box::new(params))
+                let params = MtxStoreParams::from_bytes(params)?;
+                GpuCommand::MtxStore(Box::new(params))
             },
             0x14 => {
                 let params = MtxRestoreParams::from_bytes(params)?;
                 GpuCommand::MtxRestore(Box::new(params))
             },
-            0x15 => GpuCommand::Unknown0x15,
+            0x15 => GpuCommand::MtxIdentity,
             0x16 => {
-                let params = Unknown0x16Params::from_bytes(params)?;
-                GpuCommand::Unknown0x16(Box::new(params))
+                let params = Mtx4x4Params::from_bytes(params)?;
+                GpuCommand::MtxLoad4x4(Box::new(params))
             },
             0x17 => {
-                let params = Unknown0x17Params::from_bytes(params)?;
-                GpuCommand::Unknown0x17(Box::new(params))
+                let params = Mtx4x3Params::from_bytes(params)?;
+                GpuCommand::MtxLoad4x3(Box::new(params))
             },
             0x18 => {
-                let params = Unknown0x18Params::from_bytes(params)?;
-                GpuCommand::Unknown0x18(Box::new(params))
+                let params = Mtx4x4Params::from_bytes(params)?;
+                GpuCommand::MtxMult4x4(Box::new(params))
             },
             0x19 => {
-                let params = Unknown0x19Params::from_bytes(params)?;
-                GpuCommand::Unknown0x19(Box::new(params))
+                let params = Mtx4x3Params::from_bytes(params)?;
+                GpuCommand::MtxMult4x3(Box::new(params))
             },
             0x1A => {
-                let params = Unknown0x1AParams::from_bytes(params)?;
-                GpuCommand::Unknown0x1A(Box::new(params))
+                let params = Mtx3x3Params::from_bytes(params)?;
+                GpuCommand::MtxMult3x3(Box::new(params))
             },
             0x1B => {
                 let params = MtxScaleParams::from_bytes(params)?;
                 GpuCommand::MtxScale(Box::new(params))
             },
             0x1C => {
-                let params = Unknown0x1CParams::from_bytes(params)?;
-                GpuCommand::Unknown0x1C(Box::new(params))
+                let params = MtxTransParams::from_bytes(params)?;
+                GpuCommand::MtxTrans(Box::new(params))
             },
             0x20 => {
                 let params = ColorParams::from_bytes(params)?;
@@ -261,36 +507,36 @@ impl GpuCommand {
                 GpuCommand::VtxDiff(Box::new(params))
             },
             0x29 => {
-                let params = Unknown0x29Params::from_bytes(params)?;
-                GpuCommand::Unknown0x29(Box::new(params))
+                let params = PolygonAttrParams::from_bytes(params)?;
+                GpuCommand::PolygonAttr(Box::new(params))
             },
             0x2A => {
-                let params = Unknown0x2AParams::from_bytes(params)?;
-                GpuCommand::Unknown0x2A(Box::new(params))
+                let params = TexImageParamParams::from_bytes(params)?;
+                GpuCommand::TexImageParam(Box::new(params))
             },
             0x2B => {
-                let params = Unknown0x2BParams::from_bytes(params)?;
-                GpuCommand::Unknown0x2B(Box::new(params))
+                let params = PlttBaseParams::from_bytes(params)?;
+                GpuCommand::PlttBase(Box::new(params))
             },
             0x30 => {
-                let params = Unknown0x30Params::from_bytes(params)?;
-                GpuCommand::Unknown0x30(Box::new(params))
+                let params = DifAmbParams::from_bytes(params)?;
+                GpuCommand::DifAmb(Box::new(params))
             },
             0x31 => {
-                let params = Unknown0x31Params::from_bytes(params)?;
-                GpuCommand::Unknown0x31(Box::new(params))
+                let params = SpeEmiParams::from_bytes(params)?;
+                GpuCommand::SpeEmi(Box::new(params))
             },
             0x32 => {
-                let params = Unknown0x32Params::from_bytes(params)?;
-                GpuCommand::Unknown0x32(Box::new(params))
+                let params = LightVectorParams::from_bytes(params)?;
+                GpuCommand::LightVector(Box::new(params))
             },
             0x33 => {
-                let params = Unknown0x33Params::from_bytes(params)?;
-                GpuCommand::Unknown0x33(Box::new(params))
+                let params = LightColorParams::from_bytes(params)?;
+                GpuCommand::LightColor(Box::new(params))
             },
             0x34 => {
-                let params = Unknown0x34Params::from_bytes(params)?;
-                GpuCommand::Unknown0x34(Box::new(params))
+                let params = ShininessParams::from_bytes(params)?;
+                GpuCommand::Shininess(Box::new(params))
             },
             0x40 => {
                 let params = BeginVtxsParams::from_bytes(params)?;
@@ -303,22 +549,34 @@ impl GpuCommand {
         Ok(command)
     }
 
-    pub fn op_code(&self) -> Result<u8, AppError> {
-        let op_code = match self {
+    // Commands that emit a vertex
+    pub fn is_vertex(&self) -> bool {
+        matches!(self,
+            GpuCommand::Vtx16(_) |
+            GpuCommand::Vtx10(_) |
+            GpuCommand::VtxXY(_) |
+            GpuCommand::VtxXZ(_) |
+            GpuCommand::VtxYZ(_) |
+            GpuCommand::VtxDiff(_)
+        )
+    }
+
+    pub fn op_code(&self) -> u8 {
+        match self {
             GpuCommand::Nop => 0x00,
-            GpuCommand::Unknown0x10(_) => 0x10,
-            GpuCommand::Unknown0x11 => 0x11,
-            GpuCommand::Unknown0x12(_) => 0x12,
-            GpuCommand::Unknown0x13(_) => 0x13,
+            GpuCommand::MtxMode(_) => 0x10,
+            GpuCommand::MtxPush => 0x11,
+            GpuCommand::MtxPop(_) => 0x12,
+            GpuCommand::MtxStore(_) => 0x13,
             GpuCommand::MtxRestore(_) => 0x14,
-            GpuCommand::Unknown0x15 => 0x15,
-            GpuCommand::Unknown0x16(_) => 0x16,
-            GpuCommand::Unknown0x17(_) => 0x17,
-            GpuCommand::Unknown0x18(_) => 0x18,
-            GpuCommand::Unknown0x19(_) => 0x19,
-            GpuCommand::Unknown0x1A(_) => 0x1A,
+            GpuCommand::MtxIdentity => 0x15,
+            GpuCommand::MtxLoad4x4(_) => 0x16,
+            GpuCommand::MtxLoad4x3(_) => 0x17,
+            GpuCommand::MtxMult4x4(_) => 0x18,
+            GpuCommand::MtxMult4x3(_) => 0x19,
+            GpuCommand::MtxMult3x3(_) => 0x1A,
             GpuCommand::MtxScale(_) => 0x1B,
-            GpuCommand::Unknown0x1C(_) => 0x1C,
+            GpuCommand::MtxTrans(_) => 0x1C,
             GpuCommand::Color(_) => 0x20,
             GpuCommand::Normal(_) => 0x21,
             GpuCommand::TexCoord(_) => 0x22,
@@ -328,58 +586,70 @@ impl GpuCommand {
             GpuCommand::VtxXZ(_) => 0x26,
             GpuCommand::VtxYZ(_) => 0x27,
             GpuCommand::VtxDiff(_) => 0x28,
-            GpuCommand::Unknown0x29(_) => 0x29,
-            GpuCommand::Unknown0x2A(_) => 0x2A,
-            GpuCommand::Unknown0x2B(_) => 0x2B,
-            GpuCommand::Unknown0x30(_) => 0x30,
-            GpuCommand::Unknown0x31(_) => 0x31,
-            GpuCommand::Unknown0x32(_) => 0x32,
-            GpuCommand::Unknown0x33(_) => 0x33,
-            GpuCommand::Unknown0x34(_) => 0x34,
+            GpuCommand::PolygonAttr(_) => 0x29,
+            GpuCommand::TexImageParam(_) => 0x2A,
+            GpuCommand::PlttBase(_) => 0x2B,
+            GpuCommand::DifAmb(_) => 0x30,
+            GpuCommand::SpeEmi(_) => 0x31,
+            GpuCommand::LightVector(_) => 0x32,
+            GpuCommand::LightColor(_) => 0x33,
+            GpuCommand::Shininess(_) => 0x34,
             GpuCommand::BeginVtxs(_) => 0x40,
             GpuCommand::EndVtxs => 0x41
-        };
+        }
+    }
 
-        Ok(op_code)
+    // Number of 32-bit parameters of the command (see SIZES)
+    pub fn num_params(&self) -> usize {
+        match self {
+            GpuCommand::Nop | GpuCommand::MtxPush | GpuCommand::MtxIdentity | GpuCommand::EndVtxs => 0,
+            GpuCommand::MtxLoad4x4(_) | GpuCommand::MtxMult4x4(_) => 16,
+            GpuCommand::MtxLoad4x3(_) | GpuCommand::MtxMult4x3(_) => 12,
+            GpuCommand::MtxMult3x3(_) => 9,
+            GpuCommand::MtxScale(_) | GpuCommand::MtxTrans(_) => 3,
+            GpuCommand::Vtx16(_) => 2,
+            GpuCommand::Shininess(_) => 32,
+            _ => 1
+        }
     }
 
     pub fn write_params_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
         match self {
             GpuCommand::Nop => {},
-            GpuCommand::Unknown0x10(unknown0x10_params) => {
-                unknown0x10_params.write_bytes(buffer)?;
+            GpuCommand::MtxMode(mtx_mode_params) => {
+                mtx_mode_params.write_bytes(buffer)?;
             },
-            GpuCommand::Unknown0x11 => {},
-            GpuCommand::Unknown0x12(unknown0x12_params) => {
-                unknown0x12_params.write_bytes(buffer)?;
+            GpuCommand::MtxPush => {},
+            GpuCommand::MtxPop(mtx_pop_params) => {
+                mtx_pop_params.write_bytes(buffer)?;
             },
-            GpuCommand::Unknown0x13(unknown0x13_params) => {
-                unknown0x13_params.write_bytes(buffer)?;
+            GpuCommand::MtxStore(mtx_store_params) => {
+                mtx_store_params.write_bytes(buffer)?;
             },
             GpuCommand::MtxRestore(mtx_restore_params) => {
                 mtx_restore_params.write_bytes(buffer)?;
             },
-            GpuCommand::Unknown0x15 => {},
-            GpuCommand::Unknown0x16(unknown0x16_params) => {
-                unknown0x16_params.write_bytes(buffer)?;
+            GpuCommand::MtxIdentity => {},
+            GpuCommand::MtxLoad4x4(mtx_load4x4_params) => {
+                mtx_load4x4_params.write_bytes(buffer)?;
             },
-            GpuCommand::Unknown0x17(unknown0x17_params) => {
-                unknown0x17_params.write_bytes(buffer)?;
+            GpuCommand::MtxLoad4x3(mtx_load4x3_params) => {
+                mtx_load4x3_params.write_bytes(buffer)?;
             },
-            GpuCommand::Unknown0x18(unknown0x18_params) => {
-                unknown0x18_params.write_bytes(buffer)?;
+            GpuCommand::MtxMult4x4(mtx_mult4x4_params) => {
+                mtx_mult4x4_params.write_bytes(buffer)?;
             },
-            GpuCommand::Unknown0x19(unknown0x19_params) => {
-                unknown0x19_params.write_bytes(buffer)?;
+            GpuCommand::MtxMult4x3(mtx_mult4x3_params) => {
+                mtx_mult4x3_params.write_bytes(buffer)?;
             },
-            GpuCommand::Unknown0x1A(unknown0x1a_params) => {
-                unknown0x1a_params.write_bytes(buffer)?;
+            GpuCommand::MtxMult3x3(mtx_mult3x3_params) => {
+                mtx_mult3x3_params.write_bytes(buffer)?;
             },
             GpuCommand::MtxScale(mtx_scale_params) => {
                 mtx_scale_params.write_bytes(buffer)?;
             },
-            GpuCommand::Unknown0x1C(unknown0x1c_params) => {
-                unknown0x1c_params.write_bytes(buffer)?;
+            GpuCommand::MtxTrans(mtx_trans_params) => {
+                mtx_trans_params.write_bytes(buffer)?;
             },
             GpuCommand::Color(color_params) => {
                 color_params.write_bytes(buffer)?;
@@ -408,29 +678,29 @@ impl GpuCommand {
             GpuCommand::VtxDiff(vtx_diff_params) => {
                 vtx_diff_params.write_bytes(buffer)?;
             },
-            GpuCommand::Unknown0x29(unknown0x29_params) => {
-                unknown0x29_params.write_bytes(buffer)?;
+            GpuCommand::PolygonAttr(polygon_attr_params) => {
+                polygon_attr_params.write_bytes(buffer)?;
             },
-            GpuCommand::Unknown0x2A(unknown0x2a_params) => {
-                unknown0x2a_params.write_bytes(buffer)?;
+            GpuCommand::TexImageParam(tex_image_param_params) => {
+                tex_image_param_params.write_bytes(buffer)?;
             },
-            GpuCommand::Unknown0x2B(unknown0x2b_params) => {
-                unknown0x2b_params.write_bytes(buffer)?;
+            GpuCommand::PlttBase(pltt_base_params) => {
+                pltt_base_params.write_bytes(buffer)?;
             },
-            GpuCommand::Unknown0x30(unknown0x30_params) => {
-                unknown0x30_params.write_bytes(buffer)?;
+            GpuCommand::DifAmb(dif_amb_params) => {
+                dif_amb_params.write_bytes(buffer)?;
             },
-            GpuCommand::Unknown0x31(unknown0x31_params) => {
-                unknown0x31_params.write_bytes(buffer)?;
+            GpuCommand::SpeEmi(spe_emi_params) => {
+                spe_emi_params.write_bytes(buffer)?;
             },
-            GpuCommand::Unknown0x32(unknown0x32_params) => {
-                unknown0x32_params.write_bytes(buffer)?;
+            GpuCommand::LightVector(light_vector_params) => {
+                light_vector_params.write_bytes(buffer)?;
             },
-            GpuCommand::Unknown0x33(unknown0x33_params) => {
-                unknown0x33_params.write_bytes(buffer)?;
+            GpuCommand::LightColor(light_color_params) => {
+                light_color_params.write_bytes(buffer)?;
             },
-            GpuCommand::Unknown0x34(unknown0x34_params) => {
-                unknown0x34_params.write_bytes(buffer)?;
+            GpuCommand::Shininess(shininess_params) => {
+                shininess_params.write_bytes(buffer)?;
             },
             GpuCommand::BeginVtxs(begin_vtxs_params) => {
                 begin_vtxs_params.write_bytes(buffer)?;
@@ -442,59 +712,113 @@ impl GpuCommand {
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct Unknown0x10Params {
-    pub unknown: u32
-}
-
-impl Unknown0x10Params {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Unknown0x10Params, AppError> {
-        if bytes.len() < 4 {
-            return Err(AppError::new("Unknown0x10Params needs at least 4 bytes"));
+// GBATEK mnemonic followed by the decoded params, e.g. "VTX_16 x=0.5 y=0 z=-0.25"
+impl Display for GpuCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GpuCommand::Nop => write!(f, "NOP"),
+            GpuCommand::MtxMode(params) => {
+                let mode = match params.mode {
+                    MtxModeParams::PROJECTION => "projection",
+                    MtxModeParams::POSITION => "position",
+                    MtxModeParams::POSITION_VECTOR => "position & vector",
+                    _ => "texture"
+                };
+                write!(f, "MTX_MODE {}", mode)
+            },
+            GpuCommand::MtxPush => write!(f, "MTX_PUSH"),
+            GpuCommand::MtxPop(params) => write!(f, "MTX_POP {}", params.offset),
+            GpuCommand::MtxStore(params) => write!(f, "MTX_STORE {}", params.index),
+            GpuCommand::MtxRestore(params) => write!(f, "MTX_RESTORE {}", params.index),
+            GpuCommand::MtxIdentity => write!(f, "MTX_IDENTITY"),
+            GpuCommand::MtxLoad4x4(params) => write!(f, "MTX_LOAD_4x4 {}", format_matrix(&params.m, 4)),
+            GpuCommand::MtxLoad4x3(params) => write!(f, "MTX_LOAD_4x3 {}", format_matrix(&params.m, 3)),
+            GpuCommand::MtxMult4x4(params) => write!(f, "MTX_MULT_4x4 {}", format_matrix(&params.m, 4)),
+            GpuCommand::MtxMult4x3(params) => write!(f, "MTX_MULT_4x3 {}", format_matrix(&params.m, 3)),
+            GpuCommand::MtxMult3x3(params) => write!(f, "MTX_MULT_3x3 {}", format_matrix(&params.m, 3)),
+            GpuCommand::MtxScale(params) => write!(f, "MTX_SCALE x={} y={} z={}", params.x.to_f32(), params.y.to_f32(), params.z.to_f32()),
+            GpuCommand::MtxTrans(params) => write!(f, "MTX_TRANS x={} y={} z={}", params.x.to_f32(), params.y.to_f32(), params.z.to_f32()),
+            GpuCommand::Color(params) => write!(f, "COLOR r={} g={} b={}", params.r, params.g, params.b),
+            GpuCommand::Normal(params) => write!(f, "NORMAL x={} y={} z={}", params.x.to_f32(), params.y.to_f32(), params.z.to_f32()),
+            GpuCommand::TexCoord(params) => write!(f, "TEXCOORD s={} t={}", params.s.to_f32(), params.t.to_f32()),
+            GpuCommand::Vtx16(params) => write!(f, "VTX_16 x={} y={} z={}", params.x.to_f32(), params.y.to_f32(), params.z.to_f32()),
+            GpuCommand::Vtx10(params) => write!(f, "VTX_10 x={} y={} z={}", params.x.to_f32(), params.y.to_f32(), params.z.to_f32()),
+            GpuCommand::VtxXY(params) => write!(f, "VTX_XY x={} y={}", params.x.to_f32(), params.y.to_f32()),
+            GpuCommand::VtxXZ(params) => write!(f, "VTX_XZ x={} z={}", params.x.to_f32(), params.z.to_f32()),
+            GpuCommand::VtxYZ(params) => write!(f, "VTX_YZ y={} z={}", params.y.to_f32(), params.z.to_f32()),
+            GpuCommand::VtxDiff(params) => write!(f, "VTX_DIFF x={} y={} z={}", params.x.to_f32(), params.y.to_f32(), params.z.to_f32()),
+            GpuCommand::PolygonAttr(params) => write!(f, "POLYGON_ATTR 0x{:08X}", params.polygon_attr.to_u32()),
+            GpuCommand::TexImageParam(params) => write!(f, "TEXIMAGE_PARAM 0x{:08X} (offset 0x{:X}, format {})", params.teximage_params.to_u32(), params.texture_offset(), params.teximage_params.texture_format()),
+            GpuCommand::PlttBase(params) => write!(f, "PLTT_BASE 0x{:04X}", params.palette_base),
+            GpuCommand::DifAmb(params) => {
+                let (diffuse, ambient) = (params.dif_amb.diffuse(), params.dif_amb.ambient());
+                write!(f, "DIF_AMB diffuse=({}, {}, {}) ambient=({}, {}, {})", diffuse.r, diffuse.g, diffuse.b, ambient.r, ambient.g, ambient.b)
+            },
+            GpuCommand::SpeEmi(params) => {
+                let (specular, emission) = (params.spe_emi.specular(), params.spe_emi.emission());
+                write!(f, "SPE_EMI specular=({}, {}, {}) emission=({}, {}, {})", specular.r, specular.g, specular.b, emission.r, emission.g, emission.b)
+            },
+            GpuCommand::LightVector(params) => write!(f, "LIGHT_VECTOR light={} x={} y={} z={}", params.light, params.x.to_f32(), params.y.to_f32(), params.z.to_f32()),
+            GpuCommand::LightColor(params) => write!(f, "LIGHT_COLOR light={} color=({}, {}, {})", params.light, params.color.r, params.color.g, params.color.b),
+            GpuCommand::Shininess(params) => {
+                if params.table == ShininessParams::linear().table {
+                    write!(f, "SHININESS linear")
+                }
+                else {
+                    write!(f, "SHININESS {}", params.table.iter().map(|entry| entry.to_string()).collect::<Vec<String>>().join(" "))
+                }
+            },
+            GpuCommand::BeginVtxs(params) => {
+                let primitive_type = match params.primitive_type {
+                    BeginVtxsParams::TRIANGLE => "triangles",
+                    BeginVtxsParams::QUAD => "quads",
+                    BeginVtxsParams::TRIANGLE_STRIP => "triangle strip",
+                    _ => "quad strip"
+                };
+                write!(f, "BEGIN_VTXS {}", primitive_type)
+            },
+            GpuCommand::EndVtxs => write!(f, "END_VTXS")
         }
-
-        let unknown = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
-
-        Ok(Unknown0x10Params {
-            unknown
-        })
     }
+}
 
-    pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
-        if buffer.len() < 4 {
-            return Err(AppError::new("Buffer too small for Unknown0x10Params"));
-        }
-
-        buffer[0..4].copy_from_slice(&self.unknown.to_le_bytes());
-
-        Ok(())
-    }
+// Rows between brackets, e.g. "[1 0 0] [0 1 0] [0 0 1]"
+fn format_matrix(m: &[Fixed1_19_12], columns: usize) -> String {
+    m.chunks(columns)
+        .map(|row| format!("[{}]", row.iter().map(|value| value.to_f32().to_string()).collect::<Vec<String>>().join(" ")))
+        .collect::<Vec<String>>()
+        .join(" ")
 }
 
 #[derive(Debug, Clone)]
-pub struct Unknown0x12Params {
-    pub unknown: u32
+pub struct MtxModeParams {
+    pub mode: u8
 }
 
-impl Unknown0x12Params {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Unknown0x12Params, AppError> {
+impl MtxModeParams {
+    pub const PROJECTION: u8 = 0x00;
+    pub const POSITION: u8 = 0x01;
+    pub const POSITION_VECTOR: u8 = 0x02; // Position and direction (normals) matrices at the same time
+    pub const TEXTURE: u8 = 0x03;
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<MtxModeParams, AppError> {
         if bytes.len() < 4 {
-            return Err(AppError::new("Unknown0x12Params needs at least 4 bytes"));
+            return Err(AppError::new("MtxModeParams needs at least 4 bytes"));
         }
 
-        let unknown = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let mode = bytes[0] & 0x03;
 
-        Ok(Unknown0x12Params {
-            unknown
+        Ok(MtxModeParams {
+            mode
         })
     }
 
     pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
         if buffer.len() < 4 {
-            return Err(AppError::new("Buffer too small for Unknown0x12Params"));
+            return Err(AppError::new("Buffer too small for MtxModeParams"));
         }
 
-        buffer[0..4].copy_from_slice(&self.unknown.to_le_bytes());
+        buffer[0..4].copy_from_slice(&((self.mode & 0x03) as u32).to_le_bytes());
 
         Ok(())
     }
@@ -502,29 +826,30 @@ impl Unknown0x12Params {
 
 
 #[derive(Debug, Clone)]
-pub struct Unknown0x13Params {
-    pub unknown: u32
+pub struct MtxPopParams {
+    pub offset: i8 // 6 bits signed. Number of matrices to pop
 }
 
-impl Unknown0x13Params {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Unknown0x13Params, AppError> {
+impl MtxPopParams {
+    pub fn from_bytes(bytes: &[u8]) -> Result<MtxPopParams, AppError> {
         if bytes.len() < 4 {
-            return Err(AppError::new("Unknown0x13Params needs at least 4 bytes"));
+            return Err(AppError::new("MtxPopParams needs at least 4 bytes"));
         }
 
-        let unknown = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        // Sign extend from 6 bits
+        let offset = ((bytes[0] << 2) as i8) >> 2;
 
-        Ok(Unknown0x13Params {
-            unknown
+        Ok(MtxPopParams {
+            offset
         })
     }
 
     pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
         if buffer.len() < 4 {
-            return Err(AppError::new("Buffer too small for Unknown0x13Params"));
+            return Err(AppError::new("Buffer too small for MtxPopParams"));
         }
 
-        buffer[0..4].copy_from_slice(&self.unknown.to_le_bytes());
+        buffer[0..4].copy_from_slice(&((self.offset as u32) & 0x3F).to_le_bytes());
 
         Ok(())
     }
@@ -532,26 +857,26 @@ impl Unknown0x13Params {
 
 
 #[derive(Debug, Clone)]
-pub struct MtxRestoreParams {
+pub struct MtxStoreParams {
     pub index: u32
 }
 
-impl MtxRestoreParams {
-    pub fn from_bytes(bytes: &[u8]) -> Result<MtxRestoreParams, AppError> {
+impl MtxStoreParams {
+    pub fn from_bytes(bytes: &[u8]) -> Result<MtxStoreParams, AppError> {
         if bytes.len() < 4 {
-            return Err(AppError::new("MtxRestoreParams needs at least 4 bytes"));
+            return Err(AppError::new("MtxStoreParams needs at least 4 bytes"));
         }
 
         let index = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
 
-        Ok(MtxRestoreParams {
+        Ok(MtxStoreParams {
             index
         })
     }
 
     pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
         if buffer.len() < 4 {
-            return Err(AppError::new("Buffer too small for MtxRestoreParams"));
+            return Err(AppError::new("Buffer too small for MtxStoreParams"));
         }
 
         buffer[0..4].copy_from_slice(&self.index.to_le_bytes());
@@ -561,164 +886,58 @@ impl MtxRestoreParams {
 }
 
 
+// Used by MtxLoad4x4 and MtxMult4x4. Values are sent column by column
 #[derive(Debug, Clone)]
-pub struct Unknown0x16Params {
-    pub unknown_0: u32,
-    pub unknown_1: u32,
-    pub unknown_2: u32,
-    pub unknown_3: u32,
-    pub unknown_4: u32,
-    pub unknown_5: u32,
-    pub unknown_6: u32,
-    pub unknown_7: u32,
-    pub unknown_8: u32,
-    pub unknown_9: u32,
-    pub unknown_10: u32,
-    pub unknown_11: u32,
-    pub unknown_12: u32,
-    pub unknown_13: u32,
-    pub unknown_14: u32,
-    pub unknown_15: u32
-}
-
-impl Unknown0x16Params {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Unknown0x16Params, AppError> {
+pub struct Mtx4x4Params {
+    pub m: [Fixed1_19_12; 16]
+}
+
+impl Mtx4x4Params {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Mtx4x4Params, AppError> {
         if bytes.len() < 64 {
-            return Err(AppError::new("Unknown0x16Params needs at least 64 bytes"));
-        }
-
-        let unknown_0 = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
-        let unknown_1 = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
-        let unknown_2 = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
-        let unknown_3 = u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]);
-        let unknown_4 = u32::from_le_bytes([bytes[16], bytes[17], bytes[18], bytes[19]]);
-        let unknown_5 = u32::from_le_bytes([bytes[20], bytes[21], bytes[22], bytes[23]]);
-        let unknown_6 = u32::from_le_bytes([bytes[24], bytes[25], bytes[26], bytes[27]]);
-        let unknown_7 = u32::from_le_bytes([bytes[28], bytes[29], bytes[30], bytes[31]]);
-        let unknown_8 = u32::from_le_bytes([bytes[32], bytes[33], bytes[34], bytes[35]]);
-        let unknown_9 = u32::from_le_bytes([bytes[36], bytes[37], bytes[38], bytes[39]]);
-        let unknown_10 = u32::from_le_bytes([bytes[40], bytes[41], bytes[42], bytes[43]]);
-        let unknown_11 = u32::from_le_bytes([bytes[44], bytes[45], bytes[46], bytes[47]]);
-        let unknown_12 = u32::from_le_bytes([bytes[48], bytes[49], bytes[50], bytes[51]]);
-        let unknown_13 = u32::from_le_bytes([bytes[52], bytes[53], bytes[54], bytes[55]]);
-        let unknown_14 = u32::from_le_bytes([bytes[56], bytes[57], bytes[58], bytes[59]]);
-        let unknown_15 = u32::from_le_bytes([bytes[60], bytes[61], bytes[62], bytes[63]]);
-
-        Ok(Unknown0x16Params {
-            unknown_0,
-            unknown_1,
-            unknown_2,
-            unknown_3,
-            unknown_4,
-            unknown_5,
-            unknown_6,
-            unknown_7,
-            unknown_8,
-            unknown_9,
-            unknown_10,
-            unknown_11,
-            unknown_12,
-            unknown_13,
-            unknown_14,
-            unknown_15
+            return Err(AppError::new("Mtx4x4Params needs at least 64 bytes"));
+        }
+
+        Ok(Mtx4x4Params {
+            m: read_fixed_1_19_12_array(bytes)
         })
     }
 
     pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
         if buffer.len() < 64 {
-            return Err(AppError::new("Buffer too small for Unknown0x16Params"));
-        }
-
-        buffer[0..4].copy_from_slice(&self.unknown_0.to_le_bytes());
-        buffer[4..8].copy_from_slice(&self.unknown_1.to_le_bytes());
-        buffer[8..12].copy_from_slice(&self.unknown_2.to_le_bytes());
-        buffer[12..16].copy_from_slice(&self.unknown_3.to_le_bytes());
-        buffer[16..20].copy_from_slice(&self.unknown_4.to_le_bytes());
-        buffer[20..24].copy_from_slice(&self.unknown_5.to_le_bytes());
-        buffer[24..28].copy_from_slice(&self.unknown_6.to_le_bytes());
-        buffer[28..32].copy_from_slice(&self.unknown_7.to_le_bytes());
-        buffer[32..36].copy_from_slice(&self.unknown_8.to_le_bytes());
-        buffer[36..40].copy_from_slice(&self.unknown_9.to_le_bytes());
-        buffer[40..44].copy_from_slice(&self.unknown_10.to_le_bytes());
-        buffer[44..48].copy_from_slice(&self.unknown_11.to_le_bytes());
-        buffer[48..52].copy_from_slice(&self.unknown_12.to_le_bytes());
-        buffer[52..56].copy_from_slice(&self.unknown_13.to_le_bytes());
-        buffer[56..60].copy_from_slice(&self.unknown_14.to_le_bytes());
-        buffer[60..64].copy_from_slice(&self.unknown_15.to_le_bytes());
+            return Err(AppError::new("Buffer too small for Mtx4x4Params"));
+        }
+
+        write_fixed_1_19_12_array(&self.m, buffer);
 
         Ok(())
     }
 }
 
 
+// Used by MtxLoad4x3 and MtxMult4x3. The fourth column is implied as (0, 0, 0, 1)
 #[derive(Debug, Clone)]
-pub struct Unknown0x17Params {
-    pub unknown_0: u32,
-    pub unknown_1: u32,
-    pub unknown_2: u32,
-    pub unknown_3: u32,
-    pub unknown_4: u32,
-    pub unknown_5: u32,
-    pub unknown_6: u32,
-    pub unknown_7: u32,
-    pub unknown_8: u32,
-    pub unknown_9: u32,
-    pub unknown_10: u32,
-    pub unknown_11: u32
-}
-
-impl Unknown0x17Params {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Unknown0x17Params, AppError> {
+pub struct Mtx4x3Params {
+    pub m: [Fixed1_19_12; 12]
+}
+
+impl Mtx4x3Params {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Mtx4x3Params, AppError> {
         if bytes.len() < 48 {
-            return Err(AppError::new("Unknown0x17Params needs at least 48 bytes"));
-        }
-
-        let unknown_0 = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
-        let unknown_1 = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
-        let unknown_2 = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
-        let unknown_3 = u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]);
-        let unknown_4 = u32::from_le_bytes([bytes[16], bytes[17], bytes[18], bytes[19]]);
-        let unknown_5 = u32::from_le_bytes([bytes[20], bytes[21], bytes[22], bytes[23]]);
-        let unknown_6 = u32::from_le_bytes([bytes[24], bytes[25], bytes[26], bytes[27]]);
-        let unknown_7 = u32::from_le_bytes([bytes[28], bytes[29], bytes[30], bytes[31]]);
-        let unknown_8 = u32::from_le_bytes([bytes[32], bytes[33], bytes[34], bytes[35]]);
-        let unknown_9 = u32::from_le_bytes([bytes[36], bytes[37], bytes[38], bytes[39]]);
-        let unknown_10 = u32::from_le_bytes([bytes[40], bytes[41], bytes[42], bytes[43]]);
-        let unknown_11 = u32::from_le_bytes([bytes[44], bytes[45], bytes[46], bytes[47]]);
-
-        Ok(Unknown0x17Params {
-            unknown_0,
-            unknown_1,
-            unknown_2,
-            unknown_3,
-            unknown_4,
-            unknown_5,
-            unknown_6,
-            unknown_7,
-            unknown_8,
-            unknown_9,
-            unknown_10,
-            unknown_11
+            return Err(AppError::new("Mtx4x3Params needs at least 48 bytes"));
+        }
+
+        Ok(Mtx4x3Params {
+            m: read_fixed_1_19_12_array(bytes)
         })
     }
 
     pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
         if buffer.len() < 48 {
-            return Err(AppError::new("Buffer too small for Unknown0x17Params"));
-        }
-
-        buffer[0..4].copy_from_slice(&self.unknown_0.to_le_bytes());
-        buffer[4..8].copy_from_slice(&self.unknown_1.to_le_bytes());
-        buffer[8..12].copy_from_slice(&self.unknown_2.to_le_bytes());
-        buffer[12..16].copy_from_slice(&self.unknown_3.to_le_bytes());
-        buffer[16..20].copy_from_slice(&self.unknown_4.to_le_bytes());
-        buffer[20..24].copy_from_slice(&self.unknown_5.to_le_bytes());
-        buffer[24..28].copy_from_slice(&self.unknown_6.to_le_bytes());
-        buffer[28..32].copy_from_slice(&self.unknown_7.to_le_bytes());
-        buffer[32..36].copy_from_slice(&self.unknown_8.to_le_bytes());
-        buffer[36..40].copy_from_slice(&self.unknown_9.to_le_bytes());
-        buffer[40..44].copy_from_slice(&self.unknown_10.to_le_bytes());
-        buffer[44..48].copy_from_slice(&self.unknown_11.to_le_bytes());
+            return Err(AppError::new("Buffer too small for Mtx4x3Params"));
+        }
+
+        write_fixed_1_19_12_array(&self.m, buffer);
 
         Ok(())
     }
@@ -726,225 +945,74 @@ impl Unknown0x17Params {
 
 
 #[derive(Debug, Clone)]
-pub struct Unknown0x18Params {
-    pub unknown_0: u32,
-    pub unknown_1: u32,
-    pub unknown_2: u32,
-    pub unknown_3: u32,
-    pub unknown_4: u32,
-    pub unknown_5: u32,
-    pub unknown_6: u32,
-    pub unknown_7: u32,
-    pub unknown_8: u32,
-    pub unknown_9: u32,
-    pub unknown_10: u32,
-    pub unknown_11: u32,
-    pub unknown_12: u32,
-    pub unknown_13: u32,
-    pub unknown_14: u32,
-    pub unknown_15: u32
-}
-
-impl Unknown0x18Params {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Unknown0x18Params, AppError> {
-        if bytes.len() < 64 {
-            return Err(AppError::new("Unknown0x18Params needs at least 64 bytes"));
-        }
-
-        let unknown_0 = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
-        let unknown_1 = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
-        let unknown_2 = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
-        let unknown_3 = u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]);
-        let unknown_4 = u32::from_le_bytes([bytes[16], bytes[17], bytes[18], bytes[19]]);
-        let unknown_5 = u32::from_le_bytes([bytes[20], bytes[21], bytes[22], bytes[23]]);
-        let unknown_6 = u32::from_le_bytes([bytes[24], bytes[25], bytes[26], bytes[27]]);
-        let unknown_7 = u32::from_le_bytes([bytes[28], bytes[29], bytes[30], bytes[31]]);
-        let unknown_8 = u32::from_le_bytes([bytes[32], bytes[33], bytes[34], bytes[35]]);
-        let unknown_9 = u32::from_le_bytes([bytes[36], bytes[37], bytes[38], bytes[39]]);
-        let unknown_10 = u32::from_le_bytes([bytes[40], bytes[41], bytes[42], bytes[43]]);
-        let unknown_11 = u32::from_le_bytes([bytes[44], bytes[45], bytes[46], bytes[47]]);
-        let unknown_12 = u32::from_le_bytes([bytes[48], bytes[49], bytes[50], bytes[51]]);
-        let unknown_13 = u32::from_le_bytes([bytes[52], bytes[53], bytes[54], bytes[55]]);
-        let unknown_14 = u32::from_le_bytes([bytes[56], bytes[57], bytes[58], bytes[59]]);
-        let unknown_15 = u32::from_le_bytes([bytes[60], bytes[61], bytes[62], bytes[63]]);
-
-        Ok(Unknown0x18Params {
-            unknown_0,
-            unknown_1,
-            unknown_2,
-            unknown_3,
-            unknown_4,
-            unknown_5,
-            unknown_6,
-            unknown_7,
-            unknown_8,
-            unknown_9,
-            unknown_10,
-            unknown_11,
-            unknown_12,
-            unknown_13,
-            unknown_14,
-            unknown_15
+pub struct Mtx3x3Params {
+    pub m: [Fixed1_19_12; 9]
+}
+
+impl Mtx3x3Params {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Mtx3x3Params, AppError> {
+        if bytes.len() < 36 {
+            return Err(AppError::new("Mtx3x3Params needs at least 36 bytes"));
+        }
+
+        Ok(Mtx3x3Params {
+            m: read_fixed_1_19_12_array(bytes)
         })
     }
 
     pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
-        if buffer.len() < 64 {
-            return Err(AppError::new("Buffer too small for Unknown0x18Params"));
-        }
-
-        buffer[0..4].copy_from_slice(&self.unknown_0.to_le_bytes());
-        buffer[4..8].copy_from_slice(&self.unknown_1.to_le_bytes());
-        buffer[8..12].copy_from_slice(&self.unknown_2.to_le_bytes());
-        buffer[12..16].copy_from_slice(&self.unknown_3.to_le_bytes());
-        buffer[16..20].copy_from_slice(&self.unknown_4.to_le_bytes());
-        buffer[20..24].copy_from_slice(&self.unknown_5.to_le_bytes());
-        buffer[24..28].copy_from_slice(&self.unknown_6.to_le_bytes());
-        buffer[28..32].copy_from_slice(&self.unknown_7.to_le_bytes());
-        buffer[32..36].copy_from_slice(&self.unknown_8.to_le_bytes());
-        buffer[36..40].copy_from_slice(&self.unknown_9.to_le_bytes());
-        buffer[40..44].copy_from_slice(&self.unknown_10.to_le_bytes());
-        buffer[44..48].copy_from_slice(&self.unknown_11.to_le_bytes());
-        buffer[48..52].copy_from_slice(&self.unknown_12.to_le_bytes());
-        buffer[52..56].copy_from_slice(&self.unknown_13.to_le_bytes());
-        buffer[56..60].copy_from_slice(&self.unknown_14.to_le_bytes());
-        buffer[60..64].copy_from_slice(&self.unknown_15.to_le_bytes());
+        if buffer.len() < 36 {
+            return Err(AppError::new("Buffer too small for Mtx3x3Params"));
+        }
+
+        write_fixed_1_19_12_array(&self.m, buffer);
 
         Ok(())
     }
 }
 
-
-#[derive(Debug, Clone)]
-pub struct Unknown0x19Params {
-    pub unknown_0: u32,
-    pub unknown_1: u32,
-    pub unknown_2: u32,
-    pub unknown_3: u32,
-    pub unknown_4: u32,
-    pub unknown_5: u32,
-    pub unknown_6: u32,
-    pub unknown_7: u32,
-    pub unknown_8: u32,
-    pub unknown_9: u32,
-    pub unknown_10: u32,
-    pub unknown_11: u32
-}
-
-impl Unknown0x19Params {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Unknown0x19Params, AppError> {
-        if bytes.len() < 48 {
-            return Err(AppError::new("Unknown0x19Params needs at least 48 bytes"));
-        }
-
-        let unknown_0 = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
-        let unknown_1 = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
-        let unknown_2 = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
-        let unknown_3 = u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]);
-        let unknown_4 = u32::from_le_bytes([bytes[16], bytes[17], bytes[18], bytes[19]]);
-        let unknown_5 = u32::from_le_bytes([bytes[20], bytes[21], bytes[22], bytes[23]]);
-        let unknown_6 = u32::from_le_bytes([bytes[24], bytes[25], bytes[26], bytes[27]]);
-        let unknown_7 = u32::from_le_bytes([bytes[28], bytes[29], bytes[30], bytes[31]]);
-        let unknown_8 = u32::from_le_bytes([bytes[32], bytes[33], bytes[34], bytes[35]]);
-        let unknown_9 = u32::from_le_bytes([bytes[36], bytes[37], bytes[38], bytes[39]]);
-        let unknown_10 = u32::from_le_bytes([bytes[40], bytes[41], bytes[42], bytes[43]]);
-        let unknown_11 = u32::from_le_bytes([bytes[44], bytes[45], bytes[46], bytes[47]]);
-
-        Ok(Unknown0x19Params {
-            unknown_0,
-            unknown_1,
-            unknown_2,
-            unknown_3,
-            unknown_4,
-            unknown_5,
-            unknown_6,
-            unknown_7,
-            unknown_8,
-            unknown_9,
-            unknown_10,
-            unknown_11
-        })
+// Bounds must be checked by the caller
+fn read_fixed_1_19_12_array<const N: usize>(bytes: &[u8]) -> [Fixed1_19_12; N] {
+    let mut values = [Fixed1_19_12::from(0); N];
+    for (i, value) in values.iter_mut().enumerate() {
+        let offset = i * 4;
+        *value = Fixed1_19_12::from(i32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]));
     }
 
-    pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
-        if buffer.len() < 48 {
-            return Err(AppError::new("Buffer too small for Unknown0x19Params"));
-        }
-
-        buffer[0..4].copy_from_slice(&self.unknown_0.to_le_bytes());
-        buffer[4..8].copy_from_slice(&self.unknown_1.to_le_bytes());
-        buffer[8..12].copy_from_slice(&self.unknown_2.to_le_bytes());
-        buffer[12..16].copy_from_slice(&self.unknown_3.to_le_bytes());
-        buffer[16..20].copy_from_slice(&self.unknown_4.to_le_bytes());
-        buffer[20..24].copy_from_slice(&self.unknown_5.to_le_bytes());
-        buffer[24..28].copy_from_slice(&self.unknown_6.to_le_bytes());
-        buffer[28..32].copy_from_slice(&self.unknown_7.to_le_bytes());
-        buffer[32..36].copy_from_slice(&self.unknown_8.to_le_bytes());
-        buffer[36..40].copy_from_slice(&self.unknown_9.to_le_bytes());
-        buffer[40..44].copy_from_slice(&self.unknown_10.to_le_bytes());
-        buffer[44..48].copy_from_slice(&self.unknown_11.to_le_bytes());
+    values
+}
 
-        Ok(())
+fn write_fixed_1_19_12_array(values: &[Fixed1_19_12], buffer: &mut [u8]) {
+    for (i, value) in values.iter().enumerate() {
+        buffer[i * 4..i * 4 + 4].copy_from_slice(&value.to_le_bytes());
     }
 }
 
 
 #[derive(Debug, Clone)]
-pub struct Unknown0x1AParams {
-    pub unknown_0: u32,
-    pub unknown_1: u32,
-    pub unknown_2: u32,
-    pub unknown_3: u32,
-    pub unknown_4: u32,
-    pub unknown_5: u32,
-    pub unknown_6: u32,
-    pub unknown_7: u32,
-    pub unknown_8: u32
-}
-
-impl Unknown0x1AParams {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Unknown0x1AParams, AppError> {
-        if bytes.len() < 36 {
-            return Err(AppError::new("Unknown0x1AParams needs at least 36 bytes"));
-        }
-
-        let unknown_0 = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
-        let unknown_1 = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
-        let unknown_2 = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
-        let unknown_3 = u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]);
-        let unknown_4 = u32::from_le_bytes([bytes[16], bytes[17], bytes[18], bytes[19]]);
-        let unknown_5 = u32::from_le_bytes([bytes[20], bytes[21], bytes[22], bytes[23]]);
-        let unknown_6 = u32::from_le_bytes([bytes[24], bytes[25], bytes[26], bytes[27]]);
-        let unknown_7 = u32::from_le_bytes([bytes[28], bytes[29], bytes[30], bytes[31]]);
-        let unknown_8 = u32::from_le_bytes([bytes[32], bytes[33], bytes[34], bytes[35]]);
-
-        Ok(Unknown0x1AParams {
-            unknown_0,
-            unknown_1,
-            unknown_2,
-            unknown_3,
-            unknown_4,
-            unknown_5,
-            unknown_6,
-            unknown_7,
-            unknown_8
+pub struct MtxRestoreParams {
+    pub index: u32
+}
+
+impl MtxRestoreParams {
+    pub fn from_bytes(bytes: &[u8]) -> Result<MtxRestoreParams, AppError> {
+        if bytes.len() < 4 {
+            return Err(AppError::new("MtxRestoreParams needs at least 4 bytes"));
+        }
+
+        let index = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+
+        Ok(MtxRestoreParams {
+            index
         })
     }
 
     pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
-        if buffer.len() < 36 {
-            return Err(AppError::new("Buffer too small for Unknown0x1AParams"));
+        if buffer.len() < 4 {
+            return Err(AppError::new("Buffer too small for MtxRestoreParams"));
         }
 
-        buffer[0..4].copy_from_slice(&self.unknown_0.to_le_bytes());
-        buffer[4..8].copy_from_slice(&self.unknown_1.to_le_bytes());
-        buffer[8..12].copy_from_slice(&self.unknown_2.to_le_bytes());
-        buffer[12..16].copy_from_slice(&self.unknown_3.to_le_bytes());
-        buffer[16..20].copy_from_slice(&self.unknown_4.to_le_bytes());
-        buffer[20..24].copy_from_slice(&self.unknown_5.to_le_bytes());
-        buffer[24..28].copy_from_slice(&self.unknown_6.to_le_bytes());
-        buffer[28..32].copy_from_slice(&self.unknown_7.to_le_bytes());
-        buffer[32..36].copy_from_slice(&self.unknown_8.to_le_bytes());
+        buffer[0..4].copy_from_slice(&self.index.to_le_bytes());
 
         Ok(())
     }
@@ -994,44 +1062,41 @@ impl MtxScaleParams {
 }
 
 
+
 #[derive(Debug, Clone)]
-pub struct Unknown0x1CParams {
-    pub unknown_0: u32,
-    pub unknown_1: u32,
-    pub unknown_2: u32
+pub struct MtxTransParams {
+    // Translation in each axis
+    pub x: Fixed1_19_12,
+    pub y: Fixed1_19_12,
+    pub z: Fixed1_19_12
 }
 
-impl Unknown0x1CParams {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Unknown0x1CParams, AppError> {
+impl MtxTransParams {
+    pub fn from_bytes(bytes: &[u8]) -> Result<MtxTransParams, AppError> {
         if bytes.len() < 12 {
-            return Err(AppError::new("Unknown0x1CParams needs at least 12 bytes"));
+            return Err(AppError::new("MtxTransParams needs at least 12 bytes"));
         }
 
-        let unknown_0 = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
-        let unknown_1 = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
-        let unknown_2 = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
+        let [x, y, z] = read_fixed_1_19_12_array(bytes);
 
-        Ok(Unknown0x1CParams {
-            unknown_0,
-            unknown_1,
-            unknown_2
+        Ok(MtxTransParams {
+            x,
+            y,
+            z
         })
     }
 
     pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
         if buffer.len() < 12 {
-            return Err(AppError::new("Buffer too small for Unknown0x1CParams"));
+            return Err(AppError::new("Buffer too small for MtxTransParams"));
         }
 
-        buffer[0..4].copy_from_slice(&self.unknown_0.to_le_bytes());
-        buffer[4..8].copy_from_slice(&self.unknown_1.to_le_bytes());
-        buffer[8..12].copy_from_slice(&self.unknown_2.to_le_bytes());
+        write_fixed_1_19_12_array(&[self.x, self.y, self.z], buffer);
 
         Ok(())
     }
 }
 
-
 #[derive(Debug, Clone)]
 pub struct ColorParams {
     pub r: u8, // 5 bits [0, 5)
@@ -1442,59 +1507,70 @@ impl VtxDiffParams {
 
 
 #[derive(Debug, Clone)]
-pub struct Unknown0x29Params {
-    pub unknown: u32
+pub struct PolygonAttrParams {
+    pub polygon_attr: PolygonAttr
 }
 
-impl Unknown0x29Params {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Unknown0x29Params, AppError> {
+impl PolygonAttrParams {
+    pub fn from_bytes(bytes: &[u8]) -> Result<PolygonAttrParams, AppError> {
         if bytes.len() < 4 {
-            return Err(AppError::new("Unknown0x29Params needs at least 4 bytes"));
+            return Err(AppError::new("PolygonAttrParams needs at least 4 bytes"));
         }
 
-        let unknown = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let polygon_attr = PolygonAttr::from_u32(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
 
-        Ok(Unknown0x29Params {
-            unknown
+        Ok(PolygonAttrParams {
+            polygon_attr
         })
     }
 
     pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
         if buffer.len() < 4 {
-            return Err(AppError::new("Buffer too small for Unknown0x29Params"));
+            return Err(AppError::new("Buffer too small for PolygonAttrParams"));
         }
 
-        buffer[0..4].copy_from_slice(&self.unknown.to_le_bytes());
-
-        Ok(())
+        self.polygon_attr.write_bytes(&mut buffer[0..4])
     }
 }
 
 
 #[derive(Debug, Clone)]
-pub struct Unknown0x2AParams {
-    pub unknown: u32
+pub struct TexImageParamParams {
+    pub teximage_params: TexImageParams
 }
 
-impl Unknown0x2AParams {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Unknown0x2AParams, AppError> {
+impl TexImageParamParams {
+    pub fn from_bytes(bytes: &[u8]) -> Result<TexImageParamParams, AppError> {
         if bytes.len() < 4 {
-            return Err(AppError::new("Unknown0x2AParams needs at least 4 bytes"));
+            return Err(AppError::new("TexImageParamParams needs at least 4 bytes"));
         }
 
-        let unknown = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let teximage_params = TexImageParams::from_u32(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
 
-        Ok(Unknown0x2AParams {
-            unknown
+        Ok(TexImageParamParams {
+            teximage_params
         })
     }
 
     pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
         if buffer.len() < 4 {
-            return Err(AppError::new("Buffer too small for Unknown0x2AParams"));
+            return Err(AppError::new("Buffer too small for TexImageParamParams"));
         }
 
-        buffer[0..4].copy_from_slice(&self.unknown.to_le_bytes());
+        self.teximage_params.write_bytes(&mut buffer[0..4])
+    }
+
+    // Texture VRAM offset in bytes
+    pub fn texture_offset(&self) -> u32 {
+        (self.teximage_params.texture_data() as u32) << 3
+    }
+
+    pub fn set_texture_offset(&mut self, offset: u32) -> Result<(), AppError> {
+        if !offset.is_multiple_of(8) || offset > 0x7FFF8 {
+            return Err(AppError::new(&format!("Invalid texture offset 0x{:X}. Expected a multiple of 8 below 0x80000", offset)));
+        }
+
+        self.teximage_params.set_texture_data((offset >> 3) as u16);
 
         Ok(())
     }
@@ -1502,119 +1578,174 @@ impl Unknown0x2AParams {
 
 
 #[derive(Debug, Clone)]
-pub struct Unknown0x2BParams {
-    pub unknown: u32
+pub struct PlttBaseParams {
+    pub palette_base: u16 // 13 bits [0, 13)
 }
 
-impl Unknown0x2BParams {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Unknown0x2BParams, AppError> {
+impl PlttBaseParams {
+    pub fn from_bytes(bytes: &[u8]) -> Result<PlttBaseParams, AppError> {
         if bytes.len() < 4 {
-            return Err(AppError::new("Unknown0x2BParams needs at least 4 bytes"));
+            return Err(AppError::new("PlttBaseParams needs at least 4 bytes"));
         }
 
-        let unknown = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let data = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
 
-        Ok(Unknown0x2BParams {
-            unknown
+        Ok(PlttBaseParams {
+            palette_base: (data & 0x1FFF) as u16
         })
     }
 
     pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
         if buffer.len() < 4 {
-            return Err(AppError::new("Buffer too small for Unknown0x2BParams"));
+            return Err(AppError::new("Buffer too small for PlttBaseParams"));
+        }
+
+        let data = (self.palette_base & 0x1FFF) as u32;
+
+        buffer[0..4].copy_from_slice(&data.to_le_bytes());
+
+        Ok(())
+    }
+
+    pub fn set_palette_base(&mut self, palette_base: u16) -> Result<(), AppError> {
+        if palette_base > 0x1FFF {
+            return Err(AppError::new("Invalid palette base. Expected 13 bits"));
         }
 
-        buffer[0..4].copy_from_slice(&self.unknown.to_le_bytes());
+        self.palette_base = palette_base;
 
         Ok(())
     }
+
+    // Palette VRAM offset in bytes. 4 color palettes use 8 byte units, the rest 16 byte units
+    pub fn palette_offset(&self, texture_format: u8) -> u32 {
+        if texture_format == TeximageParams::FORMAT_PALETTE_4 {
+            (self.palette_base as u32) << 3
+        } else {
+            (self.palette_base as u32) << 4
+        }
+    }
 }
 
 
 #[derive(Debug, Clone)]
-pub struct Unknown0x30Params {
-    pub unknown: u32
+pub struct DifAmbParams {
+    pub dif_amb: DifAmb
 }
 
-impl Unknown0x30Params {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Unknown0x30Params, AppError> {
+impl DifAmbParams {
+    pub fn from_bytes(bytes: &[u8]) -> Result<DifAmbParams, AppError> {
         if bytes.len() < 4 {
-            return Err(AppError::new("Unknown0x30Params needs at least 4 bytes"));
+            return Err(AppError::new("DifAmbParams needs at least 4 bytes"));
         }
 
-        let unknown = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let dif_amb = DifAmb::from_u32(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
 
-        Ok(Unknown0x30Params {
-            unknown
+        Ok(DifAmbParams {
+            dif_amb
         })
     }
 
     pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
         if buffer.len() < 4 {
-            return Err(AppError::new("Buffer too small for Unknown0x30Params"));
+            return Err(AppError::new("Buffer too small for DifAmbParams"));
         }
 
-        buffer[0..4].copy_from_slice(&self.unknown.to_le_bytes());
-
-        Ok(())
+        self.dif_amb.write_bytes(&mut buffer[0..4])
     }
 }
 
 
 #[derive(Debug, Clone)]
-pub struct Unknown0x31Params {
-    pub unknown: u32
+pub struct SpeEmiParams {
+    pub spe_emi: SpeEmi
 }
 
-impl Unknown0x31Params {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Unknown0x31Params, AppError> {
+impl SpeEmiParams {
+    pub fn from_bytes(bytes: &[u8]) -> Result<SpeEmiParams, AppError> {
         if bytes.len() < 4 {
-            return Err(AppError::new("Unknown0x31Params needs at least 4 bytes"));
+            return Err(AppError::new("SpeEmiParams needs at least 4 bytes"));
         }
 
-        let unknown = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let spe_emi = SpeEmi::from_u32(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
 
-        Ok(Unknown0x31Params {
-            unknown
+        Ok(SpeEmiParams {
+            spe_emi
         })
     }
 
     pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
         if buffer.len() < 4 {
-            return Err(AppError::new("Buffer too small for Unknown0x31Params"));
+            return Err(AppError::new("Buffer too small for SpeEmiParams"));
         }
 
-        buffer[0..4].copy_from_slice(&self.unknown.to_le_bytes());
-
-        Ok(())
+        self.spe_emi.write_bytes(&mut buffer[0..4])
     }
 }
 
 
 #[derive(Debug, Clone)]
-pub struct Unknown0x32Params {
-    pub unknown: u32
+pub struct LightVectorParams {
+    pub light: u8, // 2 bits [30, 32)
+    // Direction, same format as NormalParams
+    pub x: Fixed1_0_9,
+    pub y: Fixed1_0_9,
+    pub z: Fixed1_0_9
 }
 
-impl Unknown0x32Params {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Unknown0x32Params, AppError> {
+impl LightVectorParams {
+    pub fn from_bytes(bytes: &[u8]) -> Result<LightVectorParams, AppError> {
         if bytes.len() < 4 {
-            return Err(AppError::new("Unknown0x32Params needs at least 4 bytes"));
+            return Err(AppError::new("LightVectorParams needs at least 4 bytes"));
         }
 
-        let unknown = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let full = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
 
-        Ok(Unknown0x32Params {
-            unknown
+        let x_i16 = (full & 0x3FF) as i16;
+        let y_i16 = ((full >> 10) & 0x3FF) as i16;
+        let z_i16 = ((full >> 20) & 0x3FF) as i16;
+
+        Ok(LightVectorParams {
+            light: ((full >> 30) & 0x03) as u8,
+            x: Fixed1_0_9::from(x_i16),
+            y: Fixed1_0_9::from(y_i16),
+            z: Fixed1_0_9::from(z_i16)
         })
     }
 
     pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
         if buffer.len() < 4 {
-            return Err(AppError::new("Buffer too small for Unknown0x32Params"));
+            return Err(AppError::new("Buffer too small for LightVectorParams"));
         }
 
-        buffer[0..4].copy_from_slice(&self.unknown.to_le_bytes());
+        let x_i16 = self.x.to_i16() as u32;
+        let y_i16 = self.y.to_i16() as u32;
+        let z_i16 = self.z.to_i16() as u32;
+
+        let full = (x_i16 & 0x3FF) | ((y_i16 & 0x3FF) << 10) | ((z_i16 & 0x3FF) << 20) | (((self.light & 0x03) as u32) << 30);
+
+        buffer[0..4].copy_from_slice(&full.to_le_bytes());
+
+        Ok(())
+    }
+
+    pub fn direction(&self) -> [f32; 3] {
+        [self.x.to_f32(), self.y.to_f32(), self.z.to_f32()]
+    }
+
+    // The direction is expected to be normalized
+    pub fn set_direction(&mut self, direction: [f32; 3]) {
+        self.x = Fixed1_0_9::from_f32(direction[0]);
+        self.y = Fixed1_0_9::from_f32(direction[1]);
+        self.z = Fixed1_0_9::from_f32(direction[2]);
+    }
+
+    pub fn set_light(&mut self, light: u8) -> Result<(), AppError> {
+        if light > 3 {
+            return Err(AppError::new(&format!("Invalid light index. Expected 0-3, got {}", light)));
+        }
+
+        self.light = light;
 
         Ok(())
     }
@@ -1622,29 +1753,51 @@ impl Unknown0x32Params {
 
 
 #[derive(Debug, Clone)]
-pub struct Unknown0x33Params {
-    pub unknown: u32
+pub struct LightColorParams {
+    pub light: u8, // 2 bits [30, 32)
+    pub color: Rgb555 // 15 bits [0, 15)
 }
 
-impl Unknown0x33Params {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Unknown0x33Params, AppError> {
+impl LightColorParams {
+    pub fn from_bytes(bytes: &[u8]) -> Result<LightColorParams, AppError> {
         if bytes.len() < 4 {
-            return Err(AppError::new("Unknown0x33Params needs at least 4 bytes"));
+            return Err(AppError::new("LightColorParams needs at least 4 bytes"));
         }
 
-        let unknown = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let full = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
 
-        Ok(Unknown0x33Params {
-            unknown
+        Ok(LightColorParams {
+            light: ((full >> 30) & 0x03) as u8,
+            color: Rgb555::from_u16((full & 0x7FFF) as u16)
         })
     }
 
     pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
         if buffer.len() < 4 {
-            return Err(AppError::new("Buffer too small for Unknown0x33Params"));
+            return Err(AppError::new("Buffer too small for LightColorParams"));
+        }
+
+        let full = (self.color.to_u16() as u32) | (((self.light & 0x03) as u32) << 30);
+
+        buffer[0..4].copy_from_slice(&full.to_le_bytes());
+
+        Ok(())
+    }
+
+    pub fn color_f32(&self) -> [f32; 3] {
+        self.color.to_f32()
+    }
+
+    pub fn set_color_f32(&mut self, rgb: [f32; 3]) {
+        self.color = Rgb555::from_f32(rgb);
+    }
+
+    pub fn set_light(&mut self, light: u8) -> Result<(), AppError> {
+        if light > 3 {
+            return Err(AppError::new(&format!("Invalid light index. Expected 0-3, got {}", light)));
         }
 
-        buffer[0..4].copy_from_slice(&self.unknown.to_le_bytes());
+        self.light = light;
 
         Ok(())
     }
@@ -1652,32 +1805,59 @@ impl Unknown0x33Params {
 
 
 #[derive(Debug, Clone)]
-pub struct Unknown0x34Params {
-    pub unknown: u32
+pub struct ShininessParams {
+    pub table: [u8; 128]
 }
 
-impl Unknown0x34Params {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Unknown0x34Params, AppError> {
-        if bytes.len() < 4 {
-            return Err(AppError::new("Unknown0x34Params needs at least 4 bytes"));
+impl ShininessParams {
+    pub fn from_bytes(bytes: &[u8]) -> Result<ShininessParams, AppError> {
+        if bytes.len() < 128 {
+            return Err(AppError::new("ShininessParams needs at least 128 bytes"));
         }
 
-        let unknown = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let mut table = [0u8; 128];
+        table.copy_from_slice(&bytes[0..128]);
 
-        Ok(Unknown0x34Params {
-            unknown
+        Ok(ShininessParams {
+            table
         })
     }
 
     pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
-        if buffer.len() < 4 {
-            return Err(AppError::new("Buffer too small for Unknown0x34Params"));
+        if buffer.len() < 128 {
+            return Err(AppError::new("Buffer too small for ShininessParams"));
         }
 
-        buffer[0..4].copy_from_slice(&self.unknown.to_le_bytes());
+        buffer[0..128].copy_from_slice(&self.table);
 
         Ok(())
     }
+
+    // Linear ramp from 0 to 255, a common default for the specular table
+    pub fn linear() -> ShininessParams {
+        let mut table = [0u8; 128];
+        for (i, entry) in table.iter_mut().enumerate() {
+            *entry = (i * 2 + i / 64) as u8;
+        }
+
+        ShininessParams {
+            table
+        }
+    }
+
+    pub fn entry(&self, index: usize) -> Option<u8> {
+        self.table.get(index).copied()
+    }
+
+    pub fn set_entry(&mut self, index: usize, value: u8) -> Result<(), AppError> {
+        match self.table.get_mut(index) {
+            Some(entry) => {
+                *entry = value;
+                Ok(())
+            },
+            None => Err(AppError::new(&format!("Invalid shininess table index. Expected 0-127, got {}", index)))
+        }
+    }
 }
 
 
@@ -1714,3 +1894,40 @@ impl BeginVtxsParams {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One pack of four Vtx10 commands, each with a single param
+    fn vtx10_packs(count: usize) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(count * 20);
+        for i in 0..count {
+            bytes.extend_from_slice(&[0x24; 4]);
+            for j in 0..4u32 {
+                bytes.extend_from_slice(&((i as u32) * 4 + j).to_le_bytes());
+            }
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn trailing_partial_pack_is_an_error() {
+        let mut bytes = vtx10_packs(2);
+        assert_eq!(GpuCommandList::from_bytes(&bytes).unwrap().len(), 8);
+
+        bytes.extend_from_slice(&[0x41, 0x00]);
+        assert!(GpuCommandList::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn write_bytes_round_trips() {
+        let bytes = vtx10_packs(3);
+        let list = GpuCommandList::from_bytes(&bytes).unwrap();
+
+        let mut buffer = vec![0; list.size()];
+        list.write_bytes(&mut buffer).unwrap();
+        assert_eq!(buffer, bytes);
+    }
+}
@@ -1,6 +1,10 @@
-use gpu_command_list::GpuCommandList;
+use std::collections::BTreeSet;
 
-use crate::{data_structures::name_list::NameList, debug_info::DebugInfo, error::AppError};
+use gpu_command_list::{GpuCommand, GpuCommandList, PrimitiveCounts};
+
+use crate::{data_structures::{name::Name, name_list::NameList}, debug_info::DebugInfo, error::AppError, tools::{mesh_command_gen::MeshCommandGenerator, models::primitive::Primitive}};
+
+use super::material_list::Material;
 
 pub mod gpu_command_list;
 
@@ -48,11 +52,34 @@ impl MeshList {
     }
 
     pub fn rebase(&mut self) {
-        for mesh in self.mesh_data.iter_mut() {
+        self.meshes.rebase();
+
+        // Mesh headers go right after the dictionary, followed by every command list
+        let mut header_offset = self.meshes.size();
+        let mut cmds_offset = header_offset + Mesh::SIZE * self.mesh_data.len();
+        for (offset, mesh) in self.meshes.data_iter_mut().zip(self.mesh_data.iter_mut()) {
             mesh.rebase();
+
+            *offset = header_offset as u32;
+            mesh.cmds_offset = (cmds_offset - header_offset) as u32;
+
+            header_offset += Mesh::SIZE;
+            cmds_offset += mesh.cmds_len as usize;
         }
     }
 
+    // Returns the index of the new mesh. The list must be rebased afterwards
+    pub fn push(&mut self, name: Name, mesh: Mesh) -> Result<usize, AppError> {
+        self.meshes.push(name, 0)?;
+        self.mesh_data.push(mesh);
+
+        Ok(self.mesh_data.len() - 1)
+    }
+
+    pub fn get_name(&self, index: usize) -> Option<&Name> {
+        self.meshes.get_name(index)
+    }
+
     pub fn get_mesh(&self, index: usize) -> Option<&Mesh> {
         self.mesh_data.get(index)
     }
@@ -60,13 +87,34 @@ impl MeshList {
     pub fn get_mesh_mut(&mut self, index: usize) -> Option<&mut Mesh> {
         self.mesh_data.get_mut(index)
     }
+
+    pub fn len(&self) -> usize {
+        self.mesh_data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mesh_data.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Mesh> {
+        self.mesh_data.iter()
+    }
+
+    pub fn count_primitives(&self) -> PrimitiveCounts {
+        let mut counts = PrimitiveCounts::default();
+        for mesh in self.mesh_data.iter() {
+            counts += mesh.get_render_cmds_list().count_primitives();
+        }
+
+        counts
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Mesh {
     dummy: u16,
     size: u16, // Always 0x10 (size of this struct?)
-    unknown: u32,
+    flags: u32, // See FLAG_* constants
     cmds_offset: u32,
     cmds_len: u32,
 
@@ -76,6 +124,27 @@ pub struct Mesh {
 impl Mesh {
     const SIZE: usize = 16; // Size of the Mesh struct (without render_cmds_list)
 
+    pub const FLAG_USE_NORMAL: u32 = 0x1;
+    pub const FLAG_USE_COLOR: u32 = 0x2;
+    pub const FLAG_USE_TEXCOORD: u32 = 0x4;
+    pub const FLAG_USE_RESTORE_MTX: u32 = 0x8;
+
+    pub fn new(render_cmds_list: GpuCommandList) -> Mesh {
+        let mut mesh = Mesh {
+            dummy: 0,
+            size: Mesh::SIZE as u16,
+            flags: 0,
+            cmds_offset: Mesh::SIZE as u32,
+            cmds_len: 0,
+            render_cmds_list
+        };
+
+        mesh.update_flags();
+        mesh.rebase();
+
+        mesh
+    }
+
     pub fn from_bytes(bytes: &[u8]) -> Result<Mesh, AppError> {
         if bytes.len() < Mesh::SIZE {
             return Err(AppError::new("Mesh needs at least 16 bytes"));
@@ -83,7 +152,7 @@ impl Mesh {
 
         let dummy = u16::from_le_bytes([bytes[0], bytes[1]]);
         let size = u16::from_le_bytes([bytes[2], bytes[3]]);
-        let unknown = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        let flags = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
         let cmds_offset = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
         let cmds_len = u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]);
 
@@ -101,7 +170,7 @@ impl Mesh {
         Ok(Mesh {
             dummy,
             size,
-            unknown,
+            flags,
             cmds_offset,
             cmds_len,
             render_cmds_list
@@ -115,7 +184,7 @@ impl Mesh {
 
         buffer[0..2].copy_from_slice(&self.dummy.to_le_bytes());
         buffer[2..4].copy_from_slice(&self.size.to_le_bytes());
-        buffer[4..8].copy_from_slice(&self.unknown.to_le_bytes());
+        buffer[4..8].copy_from_slice(&self.flags.to_le_bytes());
         buffer[8..12].copy_from_slice(&self.cmds_offset.to_le_bytes());
         buffer[12..16].copy_from_slice(&self.cmds_len.to_le_bytes());
 
@@ -141,6 +210,54 @@ impl Mesh {
         Mesh::SIZE + self.render_cmds_list.size()
     }
 
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    // Recomputes the flags from the commands in the list
+    pub fn update_flags(&mut self) {
+        let mut flags = 0;
+        for cmd in self.render_cmds_list.iter() {
+            flags |= match cmd {
+                GpuCommand::Normal(_) => Mesh::FLAG_USE_NORMAL,
+                GpuCommand::Color(_) => Mesh::FLAG_USE_COLOR,
+                GpuCommand::TexCoord(_) => Mesh::FLAG_USE_TEXCOORD,
+                GpuCommand::MtxRestore(_) => Mesh::FLAG_USE_RESTORE_MTX,
+                _ => 0
+            };
+        }
+
+        self.flags = flags;
+    }
+
+    // Regenerates the GPU commands from the given primitives, configured for the material (see MeshCommandGenerator).
+    // The parent model must be rebased afterwards
+    pub fn replace_geometry(&mut self, primitives: &Vec<Primitive>, vertex_bones: &Vec<String>, command_bones: &Vec<Option<String>>, material: &Material) -> Result<(), AppError> {
+        let texture_size = (material.texture_width() as f32, material.texture_height() as f32);
+
+        let mut generator = MeshCommandGenerator::new(primitives, vertex_bones, command_bones, texture_size)?;
+        generator.configure_for_material(material)?;
+
+        let mut render_cmds_list = GpuCommandList::new();
+        render_cmds_list.extend(generator.generate_commands()?)?;
+
+        self.render_cmds_list = render_cmds_list;
+        self.update_flags();
+        self.rebase();
+
+        Ok(())
+    }
+
+    // Matrix stack slots loaded by the MtxRestore commands of the mesh, sorted
+    pub fn bones_used(&self) -> BTreeSet<u32> {
+        self.render_cmds_list.matrix_ops()
+            .filter_map(|(_, cmd)| match cmd {
+                GpuCommand::MtxRestore(params) => Some(params.index),
+                _ => None
+            })
+            .collect()
+    }
+
     pub fn get_render_cmds_list(&self) -> &GpuCommandList {
         &self.render_cmds_list
     }
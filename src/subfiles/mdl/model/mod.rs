@@ -2,10 +2,10 @@ use bone_list::BoneList;
 use bounding_box::BoundingBox;
 use inv_bind_matrices::InvBindMatrices;
 use material_list::MaterialList;
-use mesh_list::MeshList;
-use render_command_list::RenderCommandList;
+use mesh_list::{gpu_command_list::GpuCommandList, Mesh, MeshList};
+use render_command_list::{issues_to_result, DrawMeshData, RenderCommand, RenderCommandIssueKind, RenderCommandList};
 
-use crate::{debug_info::DebugInfo, error::AppError, executors::model_render_cmd_executor::ModelRenderCmdExecutor, util::number::{alignment::get_4_byte_alignment, fixed_point::fixed_1_19_12::Fixed1_19_12}};
+use crate::{data_structures::name::Name, debug_info::DebugInfo, error::AppError, executors::model_render_cmd_executor::ModelRenderCmdExecutor, tools::models::primitive::Primitive, util::number::{alignment::get_4_byte_alignment, fixed_point::fixed_1_19_12::Fixed1_19_12}};
 
 pub mod bounding_box;
 pub mod bone_list;
@@ -179,7 +179,9 @@ impl Model {
         // No need to rebase render commands, every size is dynamically calculated and not stored
         self.materials.rebase();
         self.meshes.rebase();
-        
+
+        self.num_materials = self.materials.len() as u8;
+        self.num_meshes = self.meshes.len() as u8;
 
         let render_command_list_offset = 64 + get_4_byte_alignment(self.bone_list.size());
         let material_list_offset = render_command_list_offset + get_4_byte_alignment(self.render_commands.size());
@@ -213,6 +215,19 @@ impl Model {
         &mut self.bounding_box
     }
 
+    // Replaces the skeleton of the model. Render commands are not modified, so they must still reference valid bones
+    pub fn set_skeleton(&mut self, bone_list: BoneList, inv_binds_matrices: InvBindMatrices) -> Result<(), AppError> {
+        if bone_list.len() != inv_binds_matrices.len() {
+            return Err(AppError::new(&format!("Skeleton mismatch: {} bones but {} inverse bind matrices", bone_list.len(), inv_binds_matrices.len())));
+        }
+
+        self.num_bone_matrices = bone_list.len() as u8;
+        self.bone_list = bone_list;
+        self.inv_binds_matrices = inv_binds_matrices;
+
+        Ok(())
+    }
+
     pub fn get_inv_bind_matrices(&self) -> &InvBindMatrices {
         &self.inv_binds_matrices
     }
@@ -221,6 +236,14 @@ impl Model {
         &mut self.inv_binds_matrices
     }
 
+    pub fn get_material_list(&self) -> &MaterialList {
+        &self.materials
+    }
+
+    pub fn get_material_list_mut(&mut self) -> &mut MaterialList {
+        &mut self.materials
+    }
+
     pub fn get_mesh_list(&self) -> &MeshList {
         &self.meshes
     }
@@ -238,6 +261,119 @@ impl Model {
     }
 
     pub fn get_render_command_executor(&self) -> ModelRenderCmdExecutor {
-        ModelRenderCmdExecutor::new(&self.render_commands, &self.bone_list)
+        ModelRenderCmdExecutor::new(&self.render_commands, &self.bone_list, &self.inv_binds_matrices, self.upscale.to_f32(), self.downscale.to_f32())
     }
+
+    // Updates the vertex/polygon counts in the header from the meshes' GPU commands
+    pub fn recompute_counts(&mut self) {
+        let counts = self.meshes.count_primitives();
+
+        self.num_verts = counts.vertices.min(u16::MAX as usize) as u16;
+        self.num_polys = counts.polygons().min(u16::MAX as usize) as u16;
+        self.num_tris = counts.triangles.min(u16::MAX as usize) as u16;
+        self.num_quads = counts.quads.min(u16::MAX as usize) as u16;
+    }
+
+    // Replaces the geometry of a mesh, drawn with the given material. Vertex bones are matched by name with the
+    // bones the render commands leave in the matrix stack. The container must be rebased afterwards
+    pub fn replace_mesh_geometry(&mut self, mesh_index: usize, primitives: &Vec<Primitive>, vertex_bones: &Vec<String>, material_index: usize) -> Result<(), AppError> {
+        let material = self.materials.get_material(material_index)
+            .ok_or_else(|| AppError::new(&format!("Material index {} out of bounds. Model has {} materials", material_index, self.materials.len())))?
+            .clone();
+
+        let mut executor = self.get_render_command_executor();
+        executor.execute()?;
+        let command_bones = executor.loaded_bones_in_matrix().clone();
+
+        let mesh_count = self.meshes.len();
+        let mesh = self.meshes.get_mesh_mut(mesh_index)
+            .ok_or_else(|| AppError::new(&format!("Mesh index {} out of bounds. Model has {} meshes", mesh_index, mesh_count)))?;
+        mesh.replace_geometry(primitives, vertex_bones, &command_bones, &material)?;
+
+        self.recompute_counts();
+
+        Ok(())
+    }
+
+    // Replaces the commands of a mesh with several command lists (see MeshCommandGenerator::generate_split_commands).
+    // The first list stays in the mesh, the rest become new meshes named "<name>_<n>", drawn right after it.
+    // Returns the indices of the meshes holding the lists. The model must be rebased afterwards
+    pub fn split_mesh(&mut self, mesh_index: usize, command_lists: Vec<GpuCommandList>) -> Result<Vec<usize>, AppError> {
+        if command_lists.is_empty() {
+            return Err(AppError::new("At least one command list is needed to split a mesh."));
+        }
+
+        let base_name = self.meshes.get_name(mesh_index)
+            .ok_or_else(|| AppError::new(&format!("Mesh index {} out of bounds. Model has {} meshes", mesh_index, self.meshes.len())))?
+            .to_not_null_string()?;
+
+        // The mesh count is a u8, like the count of the mesh NameList
+        if self.meshes.len() + command_lists.len() - 1 > u8::MAX as usize {
+            return Err(AppError::new(&format!("Too many meshes. Splitting mesh {} would need {} meshes", mesh_index, self.meshes.len() + command_lists.len() - 1)));
+        }
+
+        let mut command_lists = command_lists.into_iter();
+        let mesh = self.meshes.get_mesh_mut(mesh_index).unwrap();
+        *mesh.get_render_cmds_list_mut() = command_lists.next().unwrap();
+        mesh.update_flags();
+        mesh.rebase();
+
+        let mut mesh_indices = vec![mesh_index];
+        for (i, command_list) in command_lists.enumerate() {
+            let suffix = format!("_{}", i + 1);
+            let name = Name::from_string(&format!("{}{}", truncate_name(&base_name, Name::SIZE - suffix.len()), suffix))?;
+
+            mesh_indices.push(self.meshes.push(name, Mesh::new(command_list))?);
+        }
+
+        // Every draw of the mesh now draws its parts
+        let mut index = 0;
+        while index < self.render_commands.len() {
+            let draws_mesh = matches!(self.render_commands.get(index), Some(RenderCommand::DrawMesh(data)) if data.mesh_index as usize == mesh_index);
+            index += 1;
+
+            if draws_mesh {
+                for &extra_mesh_index in mesh_indices[1..].iter() {
+                    self.render_commands.insert(index, RenderCommand::DrawMesh(Box::new(DrawMeshData { mesh_index: extra_mesh_index as u8 })))?;
+                    index += 1;
+                }
+            }
+        }
+
+        Ok(mesh_indices)
+    }
+
+    // Material bound when the mesh is first drawn, without executing the matrix commands.
+    // None if the mesh is never drawn or no material is bound before it
+    pub fn material_for_mesh(&self, mesh_index: usize) -> Option<usize> {
+        let mut bound_material = None;
+        for cmd in self.render_commands.iter() {
+            match cmd {
+                RenderCommand::BindMaterial(data) => bound_material = Some(data.material_index as usize),
+                RenderCommand::DrawMesh(data) if data.mesh_index as usize == mesh_index => return bound_material,
+                _ => {}
+            }
+        }
+
+        None
+    }
+
+    // Checks that render commands and texture/palette pairings only reference existing materials
+    pub fn validate_material_indices(&self) -> Result<(), AppError> {
+        self.materials.validate_pairings()?;
+
+        // Only material issues are kept, so the other counts do not matter
+        let issues = self.render_commands.validate_counts(usize::MAX, self.materials.len(), usize::MAX, usize::MAX);
+        issues_to_result(issues.into_iter().filter(|issue| issue.kind == RenderCommandIssueKind::Material))
+    }
+}
+
+// Longest prefix of the name that fits in max_len bytes
+fn truncate_name(name: &str, max_len: usize) -> &str {
+    let mut end = name.len().min(max_len);
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    &name[..end]
 }
@@ -1,7 +1,10 @@
 use crate::{debug_info::DebugInfo, error::AppError};
 
+use super::Model;
+
 const COMMAND_CODE_MASK: u8 = 0x1F;
 const COMMAND_SUBTYPE_MASK: u8 = !COMMAND_CODE_MASK;
+const MATRIX_STACK_SIZE: usize = 31; // 0..30 (31 entries)
 
 #[derive(Debug, Clone)]
 pub struct RenderCommandList {
@@ -76,6 +79,86 @@ impl RenderCommandList {
         self.render_commands.extend(commands);
     }
 
+    // Commands cannot be inserted after the End command that terminates the list
+    pub fn insert(&mut self, index: usize, command: RenderCommand) -> Result<(), AppError> {
+        let max_index = self.render_commands.len() - self.ends_with_end() as usize;
+        if index > max_index {
+            return Err(AppError::new(&format!("RenderCommandList insert index out of bounds. Expected 0-{}, got {}", max_index, index)));
+        }
+
+        if let RenderCommand::End = command {
+            return Err(AppError::new("RenderCommandList cannot insert an End command, the list already ends with one"));
+        }
+
+        self.render_commands.insert(index, command);
+
+        Ok(())
+    }
+
+    // The End command that terminates the list cannot be removed
+    pub fn remove(&mut self, index: usize) -> Result<RenderCommand, AppError> {
+        let removable = self.render_commands.len() - self.ends_with_end() as usize;
+        if index >= removable {
+            return Err(AppError::new(&format!("RenderCommandList remove index {} out of bounds, there are {} removable commands", index, removable)));
+        }
+
+        Ok(self.render_commands.remove(index))
+    }
+
+    // new_indices holds the new index of every mesh, None for removed meshes. Their DrawMesh commands are removed.
+    // The list is left untouched on error
+    pub fn remap_mesh_indices(&mut self, new_indices: &[Option<usize>]) -> Result<(), AppError> {
+        let mut remapped = Vec::with_capacity(self.render_commands.len());
+        for render_command in self.render_commands.iter() {
+            let mut render_command = render_command.clone();
+            if let RenderCommand::DrawMesh(data) = &mut render_command {
+                match remap_index(new_indices, data.mesh_index, "DrawMesh", "mesh")? {
+                    Some(index) => data.mesh_index = index,
+                    None => continue
+                }
+            }
+
+            remapped.push(render_command);
+        }
+
+        self.render_commands = remapped;
+
+        Ok(())
+    }
+
+    // new_indices holds the new index of every material, None for removed materials, which must not be referenced anymore.
+    // The list is left untouched on error
+    pub fn remap_material_indices(&mut self, new_indices: &[Option<usize>]) -> Result<(), AppError> {
+        let mut remapped = self.render_commands.clone();
+        for render_command in remapped.iter_mut() {
+            let (material_index, command_name) = match render_command {
+                RenderCommand::BindMaterial(data) => (&mut data.material_index, "BindMaterial"),
+                RenderCommand::EnvironmentMap(data) => (&mut data.material_index, "EnvironmentMap"),
+                RenderCommand::ProjectionMap(data) => (&mut data.material_index, "ProjectionMap"),
+                _ => continue
+            };
+
+            *material_index = remap_index(new_indices, *material_index, command_name, "material")?
+                .ok_or_else(|| AppError::new(&format!("{} references material {}, which was removed", command_name, material_index)))?;
+        }
+
+        self.render_commands = remapped;
+
+        Ok(())
+    }
+
+    fn ends_with_end(&self) -> bool {
+        matches!(self.render_commands.last(), Some(RenderCommand::End))
+    }
+
+    pub fn len(&self) -> usize {
+        self.render_commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.render_commands.is_empty()
+    }
+
     pub fn get(&self, index: usize) -> Option<&RenderCommand> {
         self.render_commands.get(index)
     }
@@ -87,24 +170,165 @@ impl RenderCommandList {
     pub fn iter(&self) -> impl Iterator<Item = &RenderCommand> {
         self.render_commands.iter()
     }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut RenderCommand> {
+        self.render_commands.iter_mut()
+    }
+
+    // Checks every index referenced by the commands against the model and that the list ends with End.
+    // Returns one issue per problem found, so an empty list means the commands are valid
+    pub fn validate(&self, model: &Model) -> Vec<RenderCommandIssue> {
+        self.validate_counts(
+            model.get_bone_list().len(),
+            model.get_material_list().len(),
+            model.get_mesh_list().len(),
+            model.get_inv_bind_matrices().len()
+        )
+    }
+
+    pub(crate) fn validate_counts(&self, num_bones: usize, num_materials: usize, num_meshes: usize, num_inv_binds: usize) -> Vec<RenderCommandIssue> {
+        let mut issues = Vec::new();
+
+        let mut pos = 0;
+        for (i, render_command) in self.render_commands.iter().enumerate() {
+            let mut messages = Vec::new();
+            let mut check = |kind: RenderCommandIssueKind, index: u8, count: usize| {
+                if index as usize >= count {
+                    messages.push((kind, format!("{} index {} out of range (there are {})", kind.name(), index, count)));
+                }
+            };
+
+            match render_command {
+                RenderCommand::End => {
+                    if i != self.render_commands.len() - 1 {
+                        messages.push((RenderCommandIssueKind::End, String::from("End command before the end of the list")));
+                    }
+                },
+                RenderCommand::NodeVisibility(data) => check(RenderCommandIssueKind::Bone, data.node_index, num_bones),
+                RenderCommand::LoadMatrixFromStack(data) => check(RenderCommandIssueKind::MatrixStack, data.stack_index, MATRIX_STACK_SIZE),
+                RenderCommand::BindMaterial(data) => check(RenderCommandIssueKind::Material, data.material_index, num_materials),
+                RenderCommand::DrawMesh(data) => check(RenderCommandIssueKind::Mesh, data.mesh_index, num_meshes),
+                RenderCommand::MulCurrentMatrixWithBoneMatrix(data) => {
+                    check(RenderCommandIssueKind::Bone, data.bone_index, num_bones);
+                    check(RenderCommandIssueKind::Bone, data.parent_index, num_bones);
+                    for stack_index in [data.param_3, data.param_4].into_iter().flatten() {
+                        check(RenderCommandIssueKind::MatrixStack, stack_index, MATRIX_STACK_SIZE);
+                    }
+                },
+                RenderCommand::Billboard(data) | RenderCommand::BillboardY(data) => {
+                    check(RenderCommandIssueKind::Bone, data.node_index, num_bones);
+                    for stack_index in [data.store_index, data.load_index].into_iter().flatten() {
+                        check(RenderCommandIssueKind::MatrixStack, stack_index, MATRIX_STACK_SIZE);
+                    }
+                },
+                RenderCommand::CalculateSkinningEquation(data) => {
+                    check(RenderCommandIssueKind::MatrixStack, data.store_index, MATRIX_STACK_SIZE);
+                    for term in data.terms.iter() {
+                        check(RenderCommandIssueKind::MatrixStack, term.matrix_index, MATRIX_STACK_SIZE);
+                        check(RenderCommandIssueKind::InvBindMatrix, term.inv_bind_index, num_inv_binds);
+                    }
+                },
+                RenderCommand::EnvironmentMap(data) | RenderCommand::ProjectionMap(data) => check(RenderCommandIssueKind::Material, data.material_index, num_materials),
+                RenderCommand::Nop(_) | RenderCommand::Scale(_) => {}
+            }
+
+            issues.extend(messages.into_iter().map(|(kind, message)| RenderCommandIssue {
+                command_index: i,
+                offset: pos,
+                kind,
+                message
+            }));
+
+            pos += render_command.size();
+        }
+
+        if !self.ends_with_end() {
+            issues.push(RenderCommandIssue {
+                command_index: self.render_commands.len(),
+                offset: pos,
+                kind: RenderCommandIssueKind::End,
+                message: String::from("List does not end with an End command")
+            });
+        }
+
+        issues
+    }
 }
 
+// Error listing the issues, Ok if there are none
+pub(crate) fn issues_to_result(issues: impl Iterator<Item = RenderCommandIssue>) -> Result<(), AppError> {
+    let messages: Vec<String> = issues
+        .map(|issue| format!("command {} (offset 0x{:X}): {}", issue.command_index, issue.offset, issue.message))
+        .collect();
+
+    if !messages.is_empty() {
+        return Err(AppError::new(&format!("Invalid render command list: {}", messages.join("; "))));
+    }
+
+    Ok(())
+}
+
+
+fn remap_index(new_indices: &[Option<usize>], index: u8, command_name: &str, kind: &str) -> Result<Option<u8>, AppError> {
+    let new_index = new_indices.get(index as usize)
+        .ok_or_else(|| AppError::new(&format!("{} references {} {}, but there are only {}", command_name, kind, index, new_indices.len())))?;
+
+    match *new_index {
+        Some(new_index) if new_index > u8::MAX as usize => {
+            Err(AppError::new(&format!("{} cannot reference {} {}, the maximum is {}", command_name, kind, new_index, u8::MAX)))
+        },
+        new_index => Ok(new_index.map(|new_index| new_index as u8))
+    }
+}
+
+// Problem found by RenderCommandList::validate
+#[derive(Debug, Clone)]
+pub struct RenderCommandIssue {
+    pub command_index: usize,
+    pub offset: usize, // Byte offset of the command in the list
+    pub kind: RenderCommandIssueKind,
+    pub message: String
+}
+
+// What a RenderCommandIssue is about: the kind of index out of range, or a misplaced or missing End
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderCommandIssueKind {
+    Bone,
+    MatrixStack,
+    Material,
+    Mesh,
+    InvBindMatrix,
+    End
+}
+
+impl RenderCommandIssueKind {
+    fn name(&self) -> &'static str {
+        match self {
+            RenderCommandIssueKind::Bone => "Bone",
+            RenderCommandIssueKind::MatrixStack => "Matrix stack",
+            RenderCommandIssueKind::Material => "Material",
+            RenderCommandIssueKind::Mesh => "Mesh",
+            RenderCommandIssueKind::InvBindMatrix => "Inverse bind matrix",
+            RenderCommandIssueKind::End => "End"
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum RenderCommand {
     Nop(Box<NopData>),
     End,
-    Unknown0x02(Box<Unknown0x02Data>),
+    NodeVisibility(Box<NodeVisibilityData>),
     LoadMatrixFromStack(Box<LoadMatrixFromStackData>),
     BindMaterial(Box<BindMaterialData>),
     DrawMesh(Box<DrawMeshData>),
     MulCurrentMatrixWithBoneMatrix(Box<MulCurrentMatrixWithBoneMatrixData>),
-    Unknown0x07(Box<Unknown0x07Data>),
-    Unknown0x08(Box<Unknown0x08Data>),
+    Billboard(Box<BillboardData>),
+    BillboardY(Box<BillboardData>), // Billboard rotating around the Y axis only
     CalculateSkinningEquation(Box<CalculateSkinningEquationData>),
     Scale(Box<ScaleData>),
-    Unknown0x0C(Box<Unknown0x0CData>),
-    Unknown0x0D(Box<Unknown0x0DData>)
+    EnvironmentMap(Box<TexMapData>),
+    ProjectionMap(Box<TexMapData>)
 }
 
 impl RenderCommand {
@@ -118,8 +342,8 @@ impl RenderCommand {
                 Ok(RenderCommand::End)
             },
             0x02 => {
-                let data = Unknown0x02Data::from_bytes(tail)?;
-                Ok(RenderCommand::Unknown0x02(Box::new(data)))
+                let data = NodeVisibilityData::from_bytes(tail)?;
+                Ok(RenderCommand::NodeVisibility(Box::new(data)))
             },
             0x03 => {
                 let data = LoadMatrixFromStackData::from_bytes(tail)?;
@@ -138,12 +362,12 @@ impl RenderCommand {
                 Ok(RenderCommand::MulCurrentMatrixWithBoneMatrix(Box::new(data)))
             },
             0x07 => {
-                let data = Unknown0x07Data::from_bytes(op_code, tail)?;
-                Ok(RenderCommand::Unknown0x07(Box::new(data)))
+                let data = BillboardData::from_bytes(op_code, tail)?;
+                Ok(RenderCommand::Billboard(Box::new(data)))
             },
             0x08 => {
-                let data = Unknown0x08Data::from_bytes(tail)?;
-                Ok(RenderCommand::Unknown0x08(Box::new(data)))
+                let data = BillboardData::from_bytes(op_code, tail)?;
+                Ok(RenderCommand::BillboardY(Box::new(data)))
             },
             0x09 => {
                 let data = CalculateSkinningEquationData::from_bytes(tail)?;
@@ -154,12 +378,12 @@ impl RenderCommand {
                 Ok(RenderCommand::Scale(Box::new(data)))
             },
             0x0C => {
-                let data = Unknown0x0CData::from_bytes(tail)?;
-                Ok(RenderCommand::Unknown0x0C(Box::new(data)))
+                let data = TexMapData::from_bytes(tail)?;
+                Ok(RenderCommand::EnvironmentMap(Box::new(data)))
             },
             0x0D => {
-                let data = Unknown0x0DData::from_bytes(tail)?;
-                Ok(RenderCommand::Unknown0x0D(Box::new(data)))
+                let data = TexMapData::from_bytes(tail)?;
+                Ok(RenderCommand::ProjectionMap(Box::new(data)))
             },
             _ => {
                 Err(AppError::new(&format!("Unknown RenderCommand: 0x{:2X}", op_code)))
@@ -179,9 +403,9 @@ impl RenderCommand {
             RenderCommand::End => {
                 buffer[0] = self.command_code();
             },
-            RenderCommand::Unknown0x02(unknown0x02_data) => {
+            RenderCommand::NodeVisibility(node_visibility_data) => {
                 buffer[0] = self.command_code();
-                unknown0x02_data.write_bytes(&mut buffer[1..])?;
+                node_visibility_data.write_bytes(&mut buffer[1..])?;
             },
             RenderCommand::LoadMatrixFromStack(load_matrix_from_stack_data) => {
                 buffer[0] = self.command_code();
@@ -199,29 +423,20 @@ impl RenderCommand {
                 buffer[0] = self.command_code();
                 mul_current_matrix_with_bone_matrix_data.write_bytes(&mut buffer[1..])?;
             },
-            RenderCommand::Unknown0x07(unknown0x07_data) => {
+            RenderCommand::Billboard(billboard_data) | RenderCommand::BillboardY(billboard_data) => {
                 buffer[0] = self.command_code();
-                unknown0x07_data.write_bytes(&mut buffer[1..])?;
-            },
-            RenderCommand::Unknown0x08(unknown0x08_data) => {
-                buffer[0] = self.command_code();
-                unknown0x08_data.write_bytes(&mut buffer[1..])?;
+                billboard_data.write_bytes(&mut buffer[1..])?;
             },
             RenderCommand::CalculateSkinningEquation(calculate_skinning_equation_data) => {
                 buffer[0] = self.command_code();
                 calculate_skinning_equation_data.write_bytes(&mut buffer[1..])?;
             },
-            RenderCommand::Scale(scale_data) => {
+            RenderCommand::Scale(_) => {
                 buffer[0] = self.command_code();
-                scale_data.write_bytes(&mut buffer[1..])?;
             },
-            RenderCommand::Unknown0x0C(unknown0x0_cdata) => {
+            RenderCommand::EnvironmentMap(tex_map_data) | RenderCommand::ProjectionMap(tex_map_data) => {
                 buffer[0] = self.command_code();
-                unknown0x0_cdata.write_bytes(&mut buffer[1..])?;
-            },
-            RenderCommand::Unknown0x0D(unknown0x0_ddata) => {
-                buffer[0] = self.command_code();
-                unknown0x0_ddata.write_bytes(&mut buffer[1..])?;
+                tex_map_data.write_bytes(&mut buffer[1..])?;
             },
         }
 
@@ -232,17 +447,17 @@ impl RenderCommand {
         match self {
             RenderCommand::Nop(data) => 0x00 | data.subtype,
             RenderCommand::End => 0x01,
-            RenderCommand::Unknown0x02(_) => 0x02,
+            RenderCommand::NodeVisibility(_) => 0x02,
             RenderCommand::LoadMatrixFromStack(_) => 0x03,
             RenderCommand::BindMaterial(data) => 0x04 | data.subtype,
             RenderCommand::DrawMesh(_) => 0x05,
             RenderCommand::MulCurrentMatrixWithBoneMatrix(data) => 0x06 | data.subtype,
-            RenderCommand::Unknown0x07(data) => 0x07 | data.subtype,
-            RenderCommand::Unknown0x08(_) => 0x08,
+            RenderCommand::Billboard(data) => 0x07 | data.subtype,
+            RenderCommand::BillboardY(data) => 0x08 | data.subtype,
             RenderCommand::CalculateSkinningEquation(_) => 0x09,
             RenderCommand::Scale(data) => 0x0B | data.subtype,
-            RenderCommand::Unknown0x0C(_) => 0x0C,
-            RenderCommand::Unknown0x0D(_) => 0x0D
+            RenderCommand::EnvironmentMap(_) => 0x0C,
+            RenderCommand::ProjectionMap(_) => 0x0D
         }
     }
 
@@ -250,17 +465,17 @@ impl RenderCommand {
         match self {
             RenderCommand::Nop(_) => 1,
             RenderCommand::End => 1,
-            RenderCommand::Unknown0x02(_) => 3,
+            RenderCommand::NodeVisibility(_) => 3,
             RenderCommand::LoadMatrixFromStack(_) => 2,
             RenderCommand::BindMaterial(_) => 2,
             RenderCommand::DrawMesh(_) => 2,
             RenderCommand::MulCurrentMatrixWithBoneMatrix(data) => 1 + data.len(),
-            RenderCommand::Unknown0x07(_) => 2,
-            RenderCommand::Unknown0x08(_) => 2,
+            RenderCommand::Billboard(data) => 1 + data.size(),
+            RenderCommand::BillboardY(data) => 1 + data.size(),
             RenderCommand::CalculateSkinningEquation(data) => 1 + data.len(),
             RenderCommand::Scale(_) => 1,
-            RenderCommand::Unknown0x0C(_) => 3,
-            RenderCommand::Unknown0x0D(_) => 3
+            RenderCommand::EnvironmentMap(_) => 3,
+            RenderCommand::ProjectionMap(_) => 3
         }
     }
 }
@@ -287,36 +502,44 @@ impl NopData {
 
 
 #[derive(Debug, Clone)]
-pub struct Unknown0x02Data {
-    pub unknown_0: u8,
-    pub unknown_1: u8
+pub struct NodeVisibilityData {
+    pub node_index: u8, // Bone whose meshes are shown or hidden
+    pub visibility: u8 // Bit 0: visible
 }
 
-impl Unknown0x02Data {
-    pub fn from_bytes(data: &[u8]) -> Result<Unknown0x02Data, AppError> {
+impl NodeVisibilityData {
+    pub fn from_bytes(data: &[u8]) -> Result<NodeVisibilityData, AppError> {
         if data.len() < 2 {
-            return Err(AppError::new("Unknown0x02Data needs at least 2 bytes"));
+            return Err(AppError::new("NodeVisibilityData needs at least 2 bytes"));
         }
 
-        let unknown_0 = data[0];
-        let unknown_1 = data[1];
+        let node_index = data[0];
+        let visibility = data[1];
 
-        Ok(Unknown0x02Data {
-            unknown_0,
-            unknown_1
+        Ok(NodeVisibilityData {
+            node_index,
+            visibility
         })
     }
 
     pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
         if buffer.len() < 2 {
-            return Err(AppError::new("Unknown0x02Data needs at least 2 bytes to write"));
+            return Err(AppError::new("NodeVisibilityData needs at least 2 bytes to write"));
         }
 
-        buffer[0] = self.unknown_0;
-        buffer[1] = self.unknown_1;
+        buffer[0] = self.node_index;
+        buffer[1] = self.visibility;
 
         Ok(())
     }
+
+    pub fn is_visible(&self) -> bool {
+        self.visibility & 0x01 != 0
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visibility = (self.visibility & !0x01) | visible as u8;
+    }
 }
 
 
@@ -534,67 +757,64 @@ impl MulCurrentMatrixWithBoneMatrixData {
 }
 
 
+// Shared by Billboard (0x07) and BillboardY (0x08). Like MulCurrentMatrixWithBoneMatrix, subtype 0x20 stores
+// the resulting matrix in the stack, 0x40 loads the current matrix from it first and 0x60 does both
 #[derive(Debug, Clone)]
-pub struct Unknown0x07Data {
+pub struct BillboardData {
     pub subtype: u8,
 
-    pub unknown: u8,
-    // There might be one more parameter for 0x47?
+    pub node_index: u8,
+    pub store_index: Option<u8>, // Subtypes 0x20 and 0x60
+    pub load_index: Option<u8> // Subtypes 0x40 and 0x60
 }
 
-impl Unknown0x07Data {
-    pub fn from_bytes(op_code: u8, data: &[u8]) -> Result<Unknown0x07Data, AppError> {
-        if data.len() < 1 {
-            return Err(AppError::new("Unknown0x07Data needs at least 1 byte"));
+impl BillboardData {
+    pub fn from_bytes(op_code: u8, data: &[u8]) -> Result<BillboardData, AppError> {
+        let subtype = op_code & COMMAND_SUBTYPE_MASK;
+
+        let len = match subtype {
+            0x00 => 1,
+            0x20 | 0x40 => 2,
+            0x60 => 3,
+            _ => return Err(AppError::new(&format!("Invalid Billboard subtype: 0x{:2X}", subtype)))
+        };
+
+        if data.len() < len {
+            return Err(AppError::new(&format!("BillboardData (subtype 0x{:02X}) needs at least {} bytes", subtype, len)));
         }
 
-        let subtype = op_code & COMMAND_SUBTYPE_MASK;
-        let unknown = data[0];
+        let node_index = data[0];
+        let (store_index, load_index) = match subtype {
+            0x20 => (Some(data[1]), None),
+            0x40 => (None, Some(data[1])),
+            0x60 => (Some(data[1]), Some(data[2])),
+            _ => (None, None)
+        };
 
-        Ok(Unknown0x07Data {
+        Ok(BillboardData {
             subtype,
-            unknown
+            node_index,
+            store_index,
+            load_index
         })
     }
 
     pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
-        if buffer.len() < 1 {
-            return Err(AppError::new("Unknown0x07Data needs at least 1 byte to write"));
+        if buffer.len() < self.size() {
+            return Err(AppError::new(&format!("BillboardData needs at least {} bytes to write", self.size())));
         }
 
-        buffer[0] = self.unknown;
-
-        Ok(())
-    }
-}
-
-
-#[derive(Debug, Clone)]
-pub struct Unknown0x08Data {
-    pub unknown: u8
-}
+        buffer[0] = self.node_index;
 
-impl Unknown0x08Data {
-    pub fn from_bytes(data: &[u8]) -> Result<Unknown0x08Data, AppError> {
-        if data.len() < 1 {
-            return Err(AppError::new("Unknown0x08Data needs at least 1 byte"));
+        for (offset, index) in [self.store_index, self.load_index].into_iter().flatten().enumerate() {
+            buffer[1 + offset] = index;
         }
 
-        let unknown = data[0];
-
-        Ok(Unknown0x08Data {
-            unknown
-        })
+        Ok(())
     }
 
-    pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
-        if buffer.len() < 1 {
-            return Err(AppError::new("Unknown0x08Data needs at least 1 byte to write"));
-        }
-
-        buffer[0] = self.unknown;
-
-        Ok(())
+    pub fn size(&self) -> usize {
+        1 + self.store_index.is_some() as usize + self.load_index.is_some() as usize
     }
 }
 
@@ -690,82 +910,110 @@ impl ScaleData {
             subtype
         })
     }
-
-    pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
-        if buffer.len() < 1 {
-            return Err(AppError::new("ScaleData needs at least 1 byte to write"));
-        }
-
-        buffer[0] = self.subtype;
-
-        Ok(())
-    }
 }
 
 
+// Shared by EnvironmentMap (0x0C) and ProjectionMap (0x0D), which set up the texture matrix of a material
+// from the current matrix
 #[derive(Debug, Clone)]
-pub struct Unknown0x0CData {
-    pub unknown_0: u8,
-    pub unknown_1: u8
+pub struct TexMapData {
+    pub material_index: u8,
+    pub flags: u8
 }
 
-impl Unknown0x0CData {
-    pub fn from_bytes(data: &[u8]) -> Result<Unknown0x0CData, AppError> {
+impl TexMapData {
+    pub fn from_bytes(data: &[u8]) -> Result<TexMapData, AppError> {
         if data.len() < 2 {
-            return Err(AppError::new("Unknown0x0CData needs at least 2 bytes"));
+            return Err(AppError::new("TexMapData needs at least 2 bytes"));
         }
 
-        let unknown_0 = data[0];
-        let unknown_1 = data[1];
+        let material_index = data[0];
+        let flags = data[1];
 
-        Ok(Unknown0x0CData {
-            unknown_0,
-            unknown_1
+        Ok(TexMapData {
+            material_index,
+            flags
         })
     }
 
     pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
         if buffer.len() < 2 {
-            return Err(AppError::new("Unknown0x0CData needs at least 2 bytes to write"));
+            return Err(AppError::new("TexMapData needs at least 2 bytes to write"));
         }
 
-        buffer[0] = self.unknown_0;
-        buffer[1] = self.unknown_1;
+        buffer[0] = self.material_index;
+        buffer[1] = self.flags;
 
         Ok(())
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-#[derive(Debug, Clone)]
-pub struct Unknown0x0DData {
-    pub unknown_0: u8,
-    pub unknown_1: u8
-}
+    // BindMaterial 0, DrawMesh 0, BindMaterial 1, DrawMesh 1, End
+    fn sample_list() -> RenderCommandList {
+        RenderCommandList::from_bytes(&[0x04, 0, 0x05, 0, 0x04, 1, 0x05, 1, 0x01], DebugInfo { offset: 0 }).unwrap()
+    }
 
-impl Unknown0x0DData {
-    pub fn from_bytes(data: &[u8]) -> Result<Unknown0x0DData, AppError> {
-        if data.len() < 2 {
-            return Err(AppError::new("Unknown0x0DData needs at least 2 bytes"));
-        }
+    fn indices(list: &RenderCommandList) -> Vec<(u8, u8)> {
+        list.iter().filter_map(|command| match command {
+            RenderCommand::BindMaterial(data) => Some((0x04, data.material_index)),
+            RenderCommand::DrawMesh(data) => Some((0x05, data.mesh_index)),
+            _ => None
+        }).collect()
+    }
 
-        let unknown_0 = data[0];
-        let unknown_1 = data[1];
+    #[test]
+    fn remap_mesh_indices_drops_removed_meshes() {
+        let mut list = sample_list();
+        list.remap_mesh_indices(&[None, Some(0)]).unwrap();
 
-        Ok(Unknown0x0DData {
-            unknown_0,
-            unknown_1
-        })
+        assert_eq!(indices(&list), vec![(0x04, 0), (0x04, 1), (0x05, 0)]);
+        assert!(matches!(list.get(list.len() - 1), Some(RenderCommand::End)));
     }
 
-    pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
-        if buffer.len() < 2 {
-            return Err(AppError::new("Unknown0x0DData needs at least 2 bytes to write"));
-        }
+    #[test]
+    fn failed_remaps_leave_the_list_untouched() {
+        let mut list = sample_list();
+        let before = indices(&list);
 
-        buffer[0] = self.unknown_0;
-        buffer[1] = self.unknown_1;
+        assert!(list.remap_mesh_indices(&[Some(1)]).is_err());
+        assert_eq!(list.len(), 5);
+        assert_eq!(indices(&list), before);
 
-        Ok(())
+        assert!(list.remap_material_indices(&[Some(1), None]).is_err());
+        assert_eq!(indices(&list), before);
+    }
+
+    #[test]
+    fn material_validation_only_reports_material_issues() {
+        let list = sample_list();
+
+        // Mesh 1 is out of range too, but that is not a material issue
+        let issues = list.validate_counts(0, 2, 1, 0);
+        assert_eq!(issues.iter().map(|issue| issue.kind).collect::<Vec<_>>(), vec![RenderCommandIssueKind::Mesh]);
+
+        let material_issues = |list: &RenderCommandList, count: usize| {
+            issues_to_result(list.validate_counts(usize::MAX, count, usize::MAX, usize::MAX).into_iter().filter(|issue| issue.kind == RenderCommandIssueKind::Material))
+        };
+        assert!(material_issues(&list, 2).is_ok());
+
+        let err = material_issues(&list, 1).unwrap_err();
+        assert!(err.message().contains("command 2 (offset 0x4)"), "{}", err.message());
+    }
+
+    #[test]
+    fn bone_matrix_parents_are_range_checked() {
+        // MulCurrentMatrixWithBoneMatrix of bone 1 with parent 5, End
+        let list = RenderCommandList::from_bytes(&[0x06, 1, 5, 0, 0x01], DebugInfo { offset: 0 }).unwrap();
+
+        let issues = list.validate_counts(2, 0, 0, 0);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].kind, RenderCommandIssueKind::Bone);
+        assert!(issues[0].message.contains("index 5"), "{}", issues[0].message);
+
+        assert!(list.validate_counts(6, 0, 0, 0).is_empty());
     }
 }
@@ -4,7 +4,8 @@ pub mod mdl;
 pub mod tex;
 pub mod jnt;
 pub mod pat;
-pub mod srt;
+pub mod srt;
+pub mod animation_subfile;
 
 #[derive(Debug, Clone, Copy)]
 pub enum Type {
@@ -0,0 +1,581 @@
+use crate::{data_structures::{keyframes::{insert_key, read_u16}, name::Name, name_list::NameList}, debug_info::DebugInfo, error::AppError, traits::{BinarySerializable, SubfileAnimation}, util::number::alignment::get_4_byte_alignment};
+
+// Texture pattern animation of a PAT0 subfile: for each animated material, the frames at which its texture and
+// palette change. Textures and palettes are referenced by index into the name tables of the animation
+#[derive(Debug, Clone)]
+pub struct PatAnimation {
+    num_frames: u16,
+    texture_names_offset: u16, // Name tables, from the start of the animation
+    palette_names_offset: u16,
+    materials: NameList<MaterialPatEntry>, // Keyframe counts and data offsets, from the start of the animation
+    size: u32, // Not stored in the file
+
+    // Actual data
+    texture_names: Vec<Name>,
+    palette_names: Vec<Name>,
+    tracks: Vec<MaterialPatTrack>,
+
+    // Debug info
+    _debug_info: DebugInfo
+}
+
+#[derive(Debug, Clone)]
+pub struct MaterialPatTrack {
+    pub flags: u16,
+    pub keyframes: Vec<PatternKeyframe> // Sorted by frame. Each one lasts until the next
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PatternKeyframe {
+    pub frame: u16,
+    pub texture: u8, // Index into the texture names
+    pub palette: u8 // Index into the palette names
+}
+
+impl PatternKeyframe {
+    pub const SIZE: usize = 4;
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<PatternKeyframe, AppError> {
+        if bytes.len() < Self::SIZE {
+            return Err(AppError::new(&format!("Pattern keyframe needs {} bytes", Self::SIZE)));
+        }
+
+        Ok(PatternKeyframe {
+            frame: u16::from_le_bytes([bytes[0], bytes[1]]),
+            texture: bytes[2],
+            palette: bytes[3]
+        })
+    }
+
+    pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
+        if buffer.len() < Self::SIZE {
+            return Err(AppError::new("Buffer is too small to write pattern keyframe"));
+        }
+
+        buffer[0..2].copy_from_slice(&self.frame.to_le_bytes());
+        buffer[2] = self.texture;
+        buffer[3] = self.palette;
+
+        Ok(())
+    }
+}
+
+impl PatAnimation {
+    const STAMP: &'static [u8; 4] = b"M\0PT";
+    const HEADER_SIZE: usize = 0xC; // Without the material dictionary
+
+    pub fn from_bytes(bytes: &[u8], debug_info: DebugInfo) -> Result<PatAnimation, AppError> {
+        if bytes.len() < Self::HEADER_SIZE {
+            return Err(AppError::new(&format!("PAT animation needs at least (12 | 0xC) bytes to start reading, got {}", bytes.len())));
+        }
+
+        let stamp = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        if &stamp != Self::STAMP {
+            return Err(AppError::new(&format!("Invalid PAT animation stamp: {:?}", stamp)));
+        }
+
+        let num_frames = u16::from_le_bytes([bytes[4], bytes[5]]);
+        let num_textures = bytes[6] as usize;
+        let num_palettes = bytes[7] as usize;
+        let texture_names_offset = u16::from_le_bytes([bytes[8], bytes[9]]);
+        let palette_names_offset = u16::from_le_bytes([bytes[10], bytes[11]]);
+
+        let materials: NameList<MaterialPatEntry> = NameList::from_bytes(&bytes[Self::HEADER_SIZE..])?;
+
+        // The size is not stored, so it is where the last piece of data ends
+        let mut end = Self::HEADER_SIZE + materials.size();
+
+        let texture_names = Self::read_names(bytes, texture_names_offset as usize, num_textures, &mut end)?;
+        let palette_names = Self::read_names(bytes, palette_names_offset as usize, num_palettes, &mut end)?;
+
+        let mut tracks = Vec::with_capacity(materials.len());
+        for (entry, name) in materials.data_iter().zip(materials.names_iter()) {
+            let mut keyframes = Vec::with_capacity(entry.num_keyframes as usize);
+            for index in 0..entry.num_keyframes as usize {
+                let offset = entry.keyframes_offset as usize + index * PatternKeyframe::SIZE;
+                let keyframe = PatternKeyframe::from_bytes(bytes.get(offset..).unwrap_or_default())
+                    .map_err(|err| AppError::new(&format!("Material {}: {}", name.to_not_null_string().unwrap_or_default(), err.message())))?;
+
+                if keyframe.texture as usize >= num_textures || (num_palettes > 0 && keyframe.palette as usize >= num_palettes) {
+                    return Err(AppError::new(&format!("Material {}: keyframe at frame {} references texture {} and palette {}, but there are {} textures and {} palettes",
+                        name.to_not_null_string().unwrap_or_default(), keyframe.frame, keyframe.texture, keyframe.palette, num_textures, num_palettes)));
+                }
+
+                keyframes.push(keyframe);
+            }
+
+            end = end.max(entry.keyframes_offset as usize + keyframes.len() * PatternKeyframe::SIZE);
+            tracks.push(MaterialPatTrack {
+                flags: entry.flags,
+                keyframes
+            });
+        }
+
+        Ok(PatAnimation {
+            num_frames,
+            texture_names_offset,
+            palette_names_offset,
+            materials,
+            size: get_4_byte_alignment(end) as u32,
+            texture_names,
+            palette_names,
+            tracks,
+            _debug_info: debug_info
+        })
+    }
+
+    fn read_names(bytes: &[u8], offset: usize, count: usize, end: &mut usize) -> Result<Vec<Name>, AppError> {
+        let mut names = Vec::with_capacity(count);
+        for index in 0..count {
+            let name_offset = offset + index * Name::SIZE;
+            names.push(Name::from_bytes(bytes.get(name_offset..).unwrap_or_default())?);
+        }
+
+        if count > 0 {
+            *end = (*end).max(offset + count * Name::SIZE);
+        }
+
+        Ok(names)
+    }
+
+    pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
+        if buffer.len() < self.size as usize {
+            return Err(AppError::new("Buffer is too small to write PAT animation"));
+        }
+
+        buffer[0..4].copy_from_slice(Self::STAMP);
+        buffer[4..6].copy_from_slice(&self.num_frames.to_le_bytes());
+        buffer[6] = self.texture_names.len() as u8;
+        buffer[7] = self.palette_names.len() as u8;
+        buffer[8..10].copy_from_slice(&self.texture_names_offset.to_le_bytes());
+        buffer[10..12].copy_from_slice(&self.palette_names_offset.to_le_bytes());
+        self.materials.write_bytes(&mut buffer[Self::HEADER_SIZE..])?;
+
+        for (track, entry) in self.tracks.iter().zip(self.materials.data_iter()) {
+            for (index, keyframe) in track.keyframes.iter().enumerate() {
+                keyframe.write_bytes(&mut buffer[entry.keyframes_offset as usize + index * PatternKeyframe::SIZE..])?;
+            }
+        }
+
+        for (index, name) in self.texture_names.iter().enumerate() {
+            name.write_bytes(&mut buffer[self.texture_names_offset as usize + index * Name::SIZE..])?;
+        }
+
+        for (index, name) in self.palette_names.iter().enumerate() {
+            name.write_bytes(&mut buffer[self.palette_names_offset as usize + index * Name::SIZE..])?;
+        }
+
+        Ok(())
+    }
+
+    // Lays out the header, the material dictionary, the keyframes and the name tables, updating the offsets
+    pub fn rebase(&mut self) {
+        if self.materials.len() != self.tracks.len() {
+            // This should never happen
+            panic!("Unexpected mismatch between PAT materials and tracks");
+        }
+
+        self.materials.rebase();
+        let mut offset = Self::HEADER_SIZE + self.materials.size();
+
+        for (entry, track) in self.materials.data_iter_mut().zip(&self.tracks) {
+            let num_keyframes = track.keyframes.len();
+
+            entry.num_keyframes = num_keyframes as u16;
+            entry.flags = track.flags;
+            entry.ratio = Self::keyframe_ratio(num_keyframes, self.num_frames);
+            entry.keyframes_offset = offset as u16;
+
+            offset += num_keyframes * PatternKeyframe::SIZE;
+        }
+
+        self.texture_names_offset = offset as u16;
+        offset += self.texture_names.len() * Name::SIZE;
+        self.palette_names_offset = offset as u16;
+        offset += self.palette_names.len() * Name::SIZE;
+
+        self.size = get_4_byte_alignment(offset) as u32;
+    }
+
+    // Keyframes per frame (fx16), capped to what fits in the entry
+    fn keyframe_ratio(num_keyframes: usize, num_frames: u16) -> u16 {
+        if num_frames == 0 {
+            return 0;
+        }
+
+        (num_keyframes * 4096 / num_frames as usize).min(i16::MAX as usize) as u16
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, AppError> {
+        let mut bytes = vec![0u8; self.size as usize]; // write buffer
+
+        self.write_bytes(&mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    // Frames start_frame to end_frame (exclusive) as an animation of their own, starting at frame 0. Each material
+    // starts with the texture it has at start_frame
+    pub fn slice(&self, start_frame: u16, end_frame: u16) -> Result<PatAnimation, AppError> {
+        if start_frame >= end_frame || end_frame > self.num_frames {
+            return Err(AppError::new(&format!("Invalid frame range {}..{}. Animation has {} frames", start_frame, end_frame, self.num_frames)));
+        }
+
+        let tracks = self.tracks.iter().map(|track| {
+            // The keyframe active at start_frame moves to frame 0, the ones inside the range keep their distance to it
+            let active = track.keyframes.partition_point(|keyframe| keyframe.frame <= start_frame).saturating_sub(1);
+            let keyframes = track.keyframes.iter()
+                .enumerate()
+                .filter(|&(index, keyframe)| index == active || (keyframe.frame > start_frame && keyframe.frame < end_frame))
+                .map(|(index, keyframe)| PatternKeyframe {
+                    frame: if index == active { 0 } else { keyframe.frame - start_frame },
+                    ..*keyframe
+                })
+                .collect();
+
+            MaterialPatTrack {
+                flags: track.flags,
+                keyframes
+            }
+        }).collect();
+
+        let mut animation = PatAnimation {
+            num_frames: end_frame - start_frame,
+            texture_names_offset: 0,
+            palette_names_offset: 0,
+            materials: self.materials.clone(),
+            size: 0,
+            texture_names: self.texture_names.clone(),
+            palette_names: self.palette_names.clone(),
+            tracks,
+            _debug_info: self._debug_info.clone()
+        };
+
+        animation.rebase();
+        Ok(animation)
+    }
+
+    pub fn builder(num_frames: u16) -> PatAnimationBuilder {
+        PatAnimationBuilder::new(num_frames)
+    }
+
+    pub fn size(&self) -> usize {
+        self.size as usize
+    }
+
+    pub fn num_frames(&self) -> u16 {
+        self.num_frames
+    }
+
+    pub fn texture_names(&self) -> &[Name] {
+        &self.texture_names
+    }
+
+    pub fn palette_names(&self) -> &[Name] {
+        &self.palette_names
+    }
+
+    pub fn tracks(&self) -> &[MaterialPatTrack] {
+        &self.tracks
+    }
+
+    pub fn get_material_name(&self, index: usize) -> Option<&Name> {
+        self.materials.get_name(index)
+    }
+
+    pub fn track_for_material(&self, material_name: &str) -> Option<&MaterialPatTrack> {
+        self.materials.names_iter()
+            .position(|name| name.to_not_null_string().is_ok_and(|name| name == material_name))
+            .map(|index| &self.tracks[index])
+    }
+
+    // Names of the texture and palette bound to a material at a frame (the last keyframe at or before it, or the first
+    // one before any). The palette is None if the animation has no palettes. None if there is no track for the material
+    pub fn texture_at(&self, material_name: &str, frame: f32) -> Result<Option<(String, Option<String>)>, AppError> {
+        let Some(track) = self.track_for_material(material_name) else {
+            return Ok(None);
+        };
+
+        let frame = frame.clamp(0.0, self.num_frames as f32);
+        let next = track.keyframes.partition_point(|keyframe| keyframe.frame as f32 <= frame);
+        let Some(keyframe) = track.keyframes.get(next.saturating_sub(1)) else {
+            return Err(AppError::new(&format!("Material {} has no keyframes", material_name)));
+        };
+
+        let texture = self.texture_names.get(keyframe.texture as usize)
+            .ok_or_else(|| AppError::new(&format!("Invalid texture index {}. PAT animation has {} textures", keyframe.texture, self.texture_names.len())))?
+            .to_not_null_string()?;
+        let palette = match self.palette_names.get(keyframe.palette as usize) {
+            Some(name) => Some(name.to_not_null_string()?),
+            None if self.palette_names.is_empty() => None,
+            None => return Err(AppError::new(&format!("Invalid palette index {}. PAT animation has {} palettes", keyframe.palette, self.palette_names.len())))
+        };
+
+        Ok(Some((texture, palette)))
+    }
+}
+
+impl SubfileAnimation for PatAnimation {
+    const STAMP: [u8; 4] = *b"PAT0";
+
+    fn from_bytes(bytes: &[u8], debug_info: DebugInfo) -> Result<PatAnimation, AppError> {
+        PatAnimation::from_bytes(bytes, debug_info)
+    }
+
+    fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
+        PatAnimation::write_bytes(self, buffer)
+    }
+
+    fn rebase(&mut self) {
+        PatAnimation::rebase(self)
+    }
+
+    fn size(&self) -> usize {
+        PatAnimation::size(self)
+    }
+}
+
+// Builds a texture pattern animation from scratch. Each material gets keyframes at any frame, switching to a texture
+// (and optionally a palette) by name, that hold until the next keyframe. The name tables are built from the names in
+// order of first use
+#[derive(Debug)]
+pub struct PatAnimationBuilder {
+    num_frames: u16,
+    materials: Vec<MaterialKeys>,
+    error: Option<AppError> // First error found by a setter, returned by build
+}
+
+#[derive(Debug)]
+struct MaterialKeys {
+    name: String,
+    keys: Vec<(u16, (String, Option<String>))>
+}
+
+impl PatAnimationBuilder {
+    fn new(num_frames: u16) -> PatAnimationBuilder {
+        PatAnimationBuilder {
+            num_frames,
+            materials: Vec::new(),
+            error: None
+        }
+    }
+
+    pub fn num_frames(mut self, num_frames: u16) -> Self {
+        self.num_frames = num_frames;
+        self
+    }
+
+    pub fn material(mut self, name: &str) -> Self {
+        if self.materials.iter().any(|material| material.name == name) {
+            return self.keep_error(Err(AppError::new(&format!("Material {} already has a track", name))));
+        }
+
+        self.materials.push(MaterialKeys {
+            name: name.to_string(),
+            keys: Vec::new()
+        });
+
+        self
+    }
+
+    // Palette is None for textures that don't use one (direct color)
+    pub fn key(mut self, material: &str, frame: u16, texture: &str, palette: Option<&str>) -> Self {
+        let value = (texture.to_string(), palette.map(str::to_string));
+        let result = self.materials.iter_mut()
+            .find(|keys| keys.name == material)
+            .ok_or_else(|| AppError::new(&format!("Material {} has no track", material)))
+            .map(|keys| insert_key(&mut keys.keys, frame, value));
+
+        self.keep_error(result)
+    }
+
+    pub fn build(self) -> Result<PatAnimation, AppError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+
+        if self.num_frames == 0 {
+            return Err(AppError::new("PAT animation needs at least one frame"));
+        }
+
+        let num_frames = self.num_frames;
+        let mut texture_names = Vec::new();
+        let mut palette_names = Vec::new();
+
+        let mut materials = NameList::new(MaterialPatEntry::SIZE as u16);
+        let mut tracks = Vec::with_capacity(self.materials.len());
+        for keys in &self.materials {
+            if keys.keys.is_empty() {
+                return Err(AppError::new(&format!("Material {} has no keyframes", keys.name)));
+            }
+
+            let mut keyframes = Vec::with_capacity(keys.keys.len());
+            for (frame, (texture, palette)) in &keys.keys {
+                if *frame >= num_frames {
+                    return Err(AppError::new(&format!("Material {} has a keyframe at frame {}, but the animation has {} frames", keys.name, frame, num_frames)));
+                }
+
+                let palette = match palette {
+                    Some(palette) => Self::name_index(&mut palette_names, palette)?,
+                    None => 0
+                };
+
+                keyframes.push(PatternKeyframe {
+                    frame: *frame,
+                    texture: Self::name_index(&mut texture_names, texture)?,
+                    palette
+                });
+            }
+
+            tracks.push(MaterialPatTrack {
+                flags: 0,
+                keyframes
+            });
+            materials.push(Name::from_string(&keys.name)?, MaterialPatEntry { num_keyframes: 0, flags: 0, ratio: 0, keyframes_offset: 0 })?;
+        }
+
+        let mut animation = PatAnimation {
+            num_frames,
+            texture_names_offset: 0,
+            palette_names_offset: 0,
+            materials,
+            size: 0,
+            texture_names: texture_names.iter().map(|name| Name::from_string(name)).collect::<Result<_, _>>()?,
+            palette_names: palette_names.iter().map(|name| Name::from_string(name)).collect::<Result<_, _>>()?,
+            tracks,
+            _debug_info: DebugInfo { offset: 0 }
+        };
+
+        animation.rebase();
+        Ok(animation)
+    }
+
+    // Index of a name in a table, adding it if missing
+    fn name_index(names: &mut Vec<String>, name: &str) -> Result<u8, AppError> {
+        if let Some(index) = names.iter().position(|existing| existing == name) {
+            return Ok(index as u8);
+        }
+
+        // The table sizes are stored in a byte
+        if names.len() >= u8::MAX as usize {
+            return Err(AppError::new(&format!("PAT animation cannot reference more than {} textures or palettes", u8::MAX)));
+        }
+
+        names.push(name.to_string());
+        Ok((names.len() - 1) as u8)
+    }
+
+    fn keep_error(mut self, result: Result<(), AppError>) -> Self {
+        if let (Err(error), None) = (result, &self.error) {
+            self.error = Some(error);
+        }
+
+        self
+    }
+}
+
+// Dictionary entry of a material
+#[derive(Debug, Clone, Copy)]
+struct MaterialPatEntry {
+    num_keyframes: u16,
+    flags: u16,
+    ratio: u16, // Keyframes per frame (fx16), to guess where to start looking for a frame
+    keyframes_offset: u16
+}
+
+impl MaterialPatEntry {
+    const SIZE: usize = 8;
+}
+
+impl BinarySerializable for MaterialPatEntry {
+    fn from_bytes(bytes: &[u8]) -> Result<Self, AppError> {
+        if bytes.len() < Self::SIZE {
+            return Err(AppError::new(&format!("PAT material entry needs {} bytes", Self::SIZE)));
+        }
+
+        Ok(MaterialPatEntry {
+            num_keyframes: read_u16(bytes, 0)?,
+            flags: read_u16(bytes, 2)?,
+            ratio: read_u16(bytes, 4)?,
+            keyframes_offset: read_u16(bytes, 6)?
+        })
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>, AppError> {
+        let mut buffer = vec![0; Self::SIZE];
+        self.write_bytes(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
+        if buffer.len() < Self::SIZE {
+            return Err(AppError::new("Buffer is too small to write PAT material entry"));
+        }
+
+        buffer[0..2].copy_from_slice(&self.num_keyframes.to_le_bytes());
+        buffer[2..4].copy_from_slice(&self.flags.to_le_bytes());
+        buffer[4..6].copy_from_slice(&self.ratio.to_le_bytes());
+        buffer[6..8].copy_from_slice(&self.keyframes_offset.to_le_bytes());
+
+        Ok(())
+    }
+
+    fn size(&self) -> usize {
+        Self::SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subfiles::pat::Pat;
+
+    fn texture(name: &str, palette: Option<&str>) -> Option<(String, Option<String>)> {
+        Some((name.to_string(), palette.map(str::to_string)))
+    }
+
+    #[test]
+    fn built_animation_round_trips_through_pat0() {
+        let animation = PatAnimation::builder(30)
+            .material("eyes")
+            .key("eyes", 0, "eyes_open", Some("eyes_pl"))
+            .key("eyes", 20, "eyes_closed", Some("eyes_pl"))
+            .key("eyes", 24, "eyes_open", Some("eyes_pl"))
+            .material("mouth")
+            .key("mouth", 0, "mouth_a", Some("mouth_pl"))
+            .build()
+            .unwrap();
+
+        // Without palettes, keyframes have none
+        let direct_color = PatAnimation::builder(4)
+            .material("mouth")
+            .key("mouth", 0, "mouth_a", None)
+            .key("mouth", 2, "mouth_o", None)
+            .build()
+            .unwrap();
+
+        let mut pat = Pat::new();
+        pat.add_animation("blink", animation).unwrap();
+        pat.add_animation("talk", direct_color).unwrap();
+        let bytes = pat.to_bytes();
+
+        let parsed = Pat::from_bytes(&bytes, DebugInfo { offset: 0 }).unwrap();
+        assert_eq!(parsed.to_bytes(), bytes);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed.get_animation_name(1).unwrap().to_not_null_string().unwrap(), "talk");
+
+        let blink = parsed.get_animation(0).unwrap();
+        assert_eq!(blink.num_frames(), 30);
+        assert_eq!((blink.texture_names().len(), blink.palette_names().len()), (3, 2));
+
+        assert_eq!(blink.texture_at("eyes", 0.0).unwrap(), texture("eyes_open", Some("eyes_pl")));
+        assert_eq!(blink.texture_at("eyes", 21.5).unwrap(), texture("eyes_closed", Some("eyes_pl")));
+        assert_eq!(blink.texture_at("eyes", 29.0).unwrap(), texture("eyes_open", Some("eyes_pl")));
+        assert_eq!(blink.texture_at("mouth", 10.0).unwrap(), texture("mouth_a", Some("mouth_pl")));
+        assert_eq!(blink.texture_at("nose", 0.0).unwrap(), None);
+
+        let talk = parsed.get_animation(1).unwrap();
+        assert_eq!(talk.texture_at("mouth", 1.0).unwrap(), texture("mouth_a", None));
+        assert_eq!(talk.texture_at("mouth", 3.0).unwrap(), texture("mouth_o", None));
+    }
+}
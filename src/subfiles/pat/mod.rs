@@ -0,0 +1,7 @@
+use animation::PatAnimation;
+
+use super::animation_subfile::AnimationSubfile;
+
+pub mod animation;
+
+pub type Pat = AnimationSubfile<PatAnimation>;
@@ -0,0 +1,672 @@
+use crate::{data_structures::{keyframes::{frame_rate_frames, insert_key, read_u16, read_u32, resample_keys, resampled_frames, sliced_frames, Channel, ChannelValue, KeyframeStep, Keyframes}, name::Name, name_list::NameList}, debug_info::DebugInfo, error::AppError, subfiles::mdl::model::material_list::{MaterialList, TexcoordTransform}, traits::{BinarySerializable, SubfileAnimation}, util::{math::{matrix::Matrix, trs::lerp_array}, number::{alignment::get_4_byte_alignment, fixed_point::{fixed_1_19_12::Fixed1_19_12, fixed_1_3_12::Fixed1_3_12}}}};
+
+// Texture coordinate animation of an SRT0 subfile: scale, rotation and translation of the texture matrix of each
+// animated material
+#[derive(Debug, Clone)]
+pub struct SrtAnimation {
+    num_frames: u16,
+    flags: u8,
+    texcoord_matrix_mode: u8, // Modeling tool the matrices are built for (see Material texcoord transforms)
+    materials: NameList<MaterialSrtEntry>, // Channel infos and constants or data offsets, from the start of the animation
+    size: u32, // Not stored in the file
+
+    // Actual data
+    tracks: Vec<MaterialSrtTrack>,
+
+    // Debug info
+    _debug_info: DebugInfo
+}
+
+#[derive(Debug, Clone)]
+pub struct MaterialSrtTrack {
+    pub scale_s: Channel<Fixed1_19_12>,
+    pub scale_t: Channel<Fixed1_19_12>,
+    pub rotation: Channel<SinCos>,
+    pub translation_s: Channel<Fixed1_19_12>,
+    pub translation_t: Channel<Fixed1_19_12>
+}
+
+// Rotations are stored as their sine and cosine
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SinCos {
+    pub sin: Fixed1_3_12,
+    pub cos: Fixed1_3_12
+}
+
+impl SinCos {
+    pub fn from_radians(angle: f32) -> SinCos {
+        SinCos {
+            sin: Fixed1_3_12::from_f32(angle.sin()),
+            cos: Fixed1_3_12::from_f32(angle.cos())
+        }
+    }
+
+    pub fn to_radians(&self) -> f32 {
+        self.sin.to_f32().atan2(self.cos.to_f32())
+    }
+}
+
+impl ChannelValue for SinCos {
+    const CONSTANT_SIZE: usize = 4;
+
+    fn keyframe_size(_compact: bool) -> usize {
+        4
+    }
+
+    fn read(bytes: &[u8], offset: usize, _compact: bool) -> Result<Self, AppError> {
+        Ok(SinCos {
+            sin: Fixed1_3_12::from_i16(read_u16(bytes, offset)? as i16),
+            cos: Fixed1_3_12::from_i16(read_u16(bytes, offset + 2)? as i16)
+        })
+    }
+
+    fn write(&self, buffer: &mut [u8], offset: usize, _compact: bool) {
+        buffer[offset..offset + 2].copy_from_slice(&self.sin.to_le_bytes());
+        buffer[offset + 2..offset + 4].copy_from_slice(&self.cos.to_le_bytes());
+    }
+}
+
+impl SrtAnimation {
+    const STAMP: &'static [u8; 4] = b"M\0AT";
+    const HEADER_SIZE: usize = 8; // Without the material dictionary
+
+    // Channel info fields
+    const INFO_LAST_INTERP_MASK: u32 = 0x0000FFFF;
+    const INFO_FX16: u32 = 0x10000000;
+    const INFO_CONST: u32 = 0x20000000;
+    const INFO_STEP_SHIFT: u32 = 30;
+
+    // Option flags
+    const FLAG_END_TO_START_INTERPOLATION: u8 = 0x1;
+
+    pub fn from_bytes(bytes: &[u8], debug_info: DebugInfo) -> Result<SrtAnimation, AppError> {
+        if bytes.len() < Self::HEADER_SIZE {
+            return Err(AppError::new(&format!("SRT animation needs at least {} bytes to start reading, got {}", Self::HEADER_SIZE, bytes.len())));
+        }
+
+        let stamp = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        if &stamp != Self::STAMP {
+            return Err(AppError::new(&format!("Invalid SRT animation stamp: {:?}", stamp)));
+        }
+
+        let num_frames = u16::from_le_bytes([bytes[4], bytes[5]]);
+        let flags = bytes[6];
+        let texcoord_matrix_mode = bytes[7];
+
+        let materials: NameList<MaterialSrtEntry> = NameList::from_bytes(&bytes[Self::HEADER_SIZE..])?;
+
+        // The size is not stored, so it is where the last piece of data ends
+        let mut end = Self::HEADER_SIZE + materials.size();
+
+        let mut tracks = Vec::with_capacity(materials.len());
+        for (entry, name) in materials.data_iter().zip(materials.names_iter()) {
+            let track = Self::read_track(bytes, entry, num_frames, &mut end)
+                .map_err(|err| AppError::new(&format!("Material {}: {}", name.to_not_null_string().unwrap_or_default(), err.message())))?;
+            tracks.push(track);
+        }
+
+        Ok(SrtAnimation {
+            num_frames,
+            flags,
+            texcoord_matrix_mode,
+            materials,
+            size: get_4_byte_alignment(end) as u32,
+            tracks,
+            _debug_info: debug_info
+        })
+    }
+
+    fn read_track(bytes: &[u8], entry: &MaterialSrtEntry, num_frames: u16, end: &mut usize) -> Result<MaterialSrtTrack, AppError> {
+        Ok(MaterialSrtTrack {
+            scale_s: Self::read_channel(bytes, entry.channels[0], num_frames, end)?,
+            scale_t: Self::read_channel(bytes, entry.channels[1], num_frames, end)?,
+            rotation: Self::read_channel(bytes, entry.channels[2], num_frames, end)?,
+            translation_s: Self::read_channel(bytes, entry.channels[3], num_frames, end)?,
+            translation_t: Self::read_channel(bytes, entry.channels[4], num_frames, end)?
+        })
+    }
+
+    // The constant is stored in the entry itself, in place of the data offset
+    fn read_channel<T: ChannelValue>(bytes: &[u8], (info, data): (u32, u32), num_frames: u16, end: &mut usize) -> Result<Channel<T>, AppError> {
+        if info & Self::INFO_CONST != 0 {
+            return Ok(Channel::Constant(T::read_constant(&data.to_le_bytes(), 0)?));
+        }
+
+        let last_interp_frame = (info & Self::INFO_LAST_INTERP_MASK) as u16;
+        let step = KeyframeStep::from_shift(info >> Self::INFO_STEP_SHIFT)?;
+        let compact = info & Self::INFO_FX16 != 0;
+
+        let count = Keyframes::<T>::count(num_frames, 0, last_interp_frame, step);
+        let mut values = Vec::with_capacity(count);
+        for index in 0..count {
+            values.push(T::read(bytes, data as usize + index * T::keyframe_size(compact), compact)?);
+        }
+
+        *end = (*end).max(data as usize + count * T::keyframe_size(compact));
+
+        Ok(Channel::Keyed(Keyframes {
+            start_frame: 0,
+            last_interp_frame,
+            step,
+            compact,
+            values
+        }))
+    }
+
+    pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
+        if buffer.len() < self.size as usize {
+            return Err(AppError::new("Buffer is too small to write SRT animation"));
+        }
+
+        buffer[0..4].copy_from_slice(Self::STAMP);
+        buffer[4..6].copy_from_slice(&self.num_frames.to_le_bytes());
+        buffer[6] = self.flags;
+        buffer[7] = self.texcoord_matrix_mode;
+        self.materials.write_bytes(&mut buffer[Self::HEADER_SIZE..])?;
+
+        for (track, entry) in self.tracks.iter().zip(self.materials.data_iter()) {
+            Self::write_channel(buffer, &track.scale_s, entry.channels[0]);
+            Self::write_channel(buffer, &track.scale_t, entry.channels[1]);
+            Self::write_channel(buffer, &track.rotation, entry.channels[2]);
+            Self::write_channel(buffer, &track.translation_s, entry.channels[3]);
+            Self::write_channel(buffer, &track.translation_t, entry.channels[4]);
+        }
+
+        Ok(())
+    }
+
+    // Constants are already in the dictionary entry
+    fn write_channel<T: ChannelValue>(buffer: &mut [u8], channel: &Channel<T>, (_, data): (u32, u32)) {
+        if let Channel::Keyed(keyframes) = channel {
+            for (index, value) in keyframes.values.iter().enumerate() {
+                value.write(buffer, data as usize + index * T::keyframe_size(keyframes.compact), keyframes.compact);
+            }
+        }
+    }
+
+    // Lays out the header, the material dictionary and the keyframe data, updating the dictionary entries
+    pub fn rebase(&mut self) {
+        if self.materials.len() != self.tracks.len() {
+            // This should never happen
+            panic!("Unexpected mismatch between SRT materials and tracks");
+        }
+
+        self.materials.rebase();
+        let mut offset = get_4_byte_alignment(Self::HEADER_SIZE + self.materials.size());
+
+        for (entry, track) in self.materials.data_iter_mut().zip(&self.tracks) {
+            entry.channels = [
+                Self::channel_entry(&track.scale_s, &mut offset),
+                Self::channel_entry(&track.scale_t, &mut offset),
+                Self::channel_entry(&track.rotation, &mut offset),
+                Self::channel_entry(&track.translation_s, &mut offset),
+                Self::channel_entry(&track.translation_t, &mut offset)
+            ];
+        }
+
+        self.size = offset as u32;
+    }
+
+    // Info and constant (or data offset) of a channel, advancing offset past its data
+    fn channel_entry<T: ChannelValue>(channel: &Channel<T>, offset: &mut usize) -> (u32, u32) {
+        match channel {
+            Channel::Constant(value) => {
+                let mut data = [0u8; 4];
+                value.write_constant(&mut data, 0);
+                (Self::INFO_CONST, u32::from_le_bytes(data))
+            },
+            Channel::Keyed(keyframes) => {
+                let info = (keyframes.last_interp_frame as u32 & Self::INFO_LAST_INTERP_MASK) |
+                    if keyframes.compact { Self::INFO_FX16 } else { 0 } |
+                    (keyframes.step.shift() << Self::INFO_STEP_SHIFT);
+
+                let data = *offset as u32;
+                *offset += channel.keyframe_data_size().unwrap();
+                (info, data)
+            }
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, AppError> {
+        let mut bytes = vec![0u8; self.size as usize]; // write buffer
+
+        self.write_bytes(&mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    pub fn builder(num_frames: u16) -> SrtAnimationBuilder {
+        SrtAnimationBuilder::new(num_frames)
+    }
+
+    pub fn size(&self) -> usize {
+        self.size as usize
+    }
+
+    pub fn num_frames(&self) -> u16 {
+        self.num_frames
+    }
+
+    // Whether the last frame is interpolated towards the first one, so the animation loops smoothly
+    pub fn interpolates_end_to_start(&self) -> bool {
+        self.flags & Self::FLAG_END_TO_START_INTERPOLATION != 0
+    }
+
+    pub fn texcoord_matrix_mode(&self) -> u8 {
+        self.texcoord_matrix_mode
+    }
+
+    pub fn tracks(&self) -> &[MaterialSrtTrack] {
+        &self.tracks
+    }
+
+    pub fn get_material_name(&self, index: usize) -> Option<&Name> {
+        self.materials.get_name(index)
+    }
+
+    pub fn track_for_material(&self, material_name: &str) -> Option<&MaterialSrtTrack> {
+        self.materials.names_iter()
+            .position(|name| name.to_not_null_string().is_ok_and(|name| name == material_name))
+            .map(|index| &self.tracks[index])
+    }
+
+    // Texcoord transform of a material at a frame, interpolating between keyframes (rotations take the shortest way).
+    // None if the animation has no track for the material
+    pub fn sample(&self, material_name: &str, frame: f32) -> Result<Option<TexcoordTransform>, AppError> {
+        let Some(track) = self.track_for_material(material_name) else {
+            return Ok(None);
+        };
+
+        let frame = frame.clamp(0.0, self.num_frames as f32);
+        let end_to_start = self.interpolates_end_to_start();
+        let sample_fixed = |channel: &Channel<Fixed1_19_12>| {
+            channel.sample(frame, end_to_start, |value| Ok(value.to_f32()), |a, b, t| a + (b - a) * t)
+        };
+
+        let angle = track.rotation.sample(frame, end_to_start, |value| Ok(value.to_radians()), lerp_angle)?;
+
+        let mut transform = TexcoordTransform::default();
+        transform.set_scale(sample_fixed(&track.scale_s)?, sample_fixed(&track.scale_t)?);
+        transform.set_rotation(angle);
+        transform.set_translation(sample_fixed(&track.translation_s)?, sample_fixed(&track.translation_t)?);
+
+        Ok(Some(transform))
+    }
+
+    // Every material track re-interpolated over num_frames frames (see resampled_frames)
+    pub fn resample(&self, num_frames: u16) -> Result<SrtAnimation, AppError> {
+        self.sampled_at(&resampled_frames(self.num_frames, num_frames)?, self.flags)
+    }
+
+    // See frame_rate_frames
+    pub fn resample_frame_rate(&self, fps: f32, new_fps: f32) -> Result<SrtAnimation, AppError> {
+        self.sampled_at(&frame_rate_frames(self.num_frames, fps, new_fps)?, self.flags)
+    }
+
+    // Frames start_frame to end_frame (exclusive) as an animation of their own, starting at frame 0 (see sliced_frames)
+    pub fn slice(&self, start_frame: u16, end_frame: u16) -> Result<SrtAnimation, AppError> {
+        let (frames, whole) = sliced_frames(self.num_frames, start_frame, end_frame)?;
+        let flags = if whole { self.flags } else { self.flags & !Self::FLAG_END_TO_START_INTERPOLATION };
+
+        self.sampled_at(&frames, flags)
+    }
+
+    // Animation with a frame per sample of this one at the given frames
+    fn sampled_at(&self, frames: &[f32], flags: u8) -> Result<SrtAnimation, AppError> {
+        let end_to_start = self.interpolates_end_to_start();
+        let resample_fixed = |channel: &Channel<Fixed1_19_12>| -> Result<Channel<Fixed1_19_12>, AppError> {
+            fixed_channel(channel.sample_frames(frames, end_to_start, |value| Ok(value.to_f32()), |a, b, t| a + (b - a) * t)?)
+        };
+
+        let mut tracks = Vec::with_capacity(self.tracks.len());
+        for track in &self.tracks {
+            let angles = track.rotation.sample_frames(frames, end_to_start, |value| Ok(value.to_radians()), lerp_angle)?;
+
+            tracks.push(MaterialSrtTrack {
+                scale_s: resample_fixed(&track.scale_s)?,
+                scale_t: resample_fixed(&track.scale_t)?,
+                rotation: Channel::from_values(angles.into_iter().map(SinCos::from_radians).collect(), |_| false)?,
+                translation_s: resample_fixed(&track.translation_s)?,
+                translation_t: resample_fixed(&track.translation_t)?
+            });
+        }
+
+        let mut animation = SrtAnimation {
+            num_frames: frames.len() as u16,
+            flags,
+            texcoord_matrix_mode: self.texcoord_matrix_mode,
+            materials: self.materials.clone(),
+            size: 0,
+            tracks,
+            _debug_info: self._debug_info.clone()
+        };
+
+        animation.rebase();
+        Ok(animation)
+    }
+
+    // Animated texcoord matrix (see Material::texcoord_matrix) of every material of the list the animation has a track for
+    pub fn texcoord_matrices(&self, material_list: &MaterialList, frame: f32) -> Result<Vec<(usize, Matrix)>, AppError> {
+        let mut matrices = Vec::new();
+
+        for (index, (name, material)) in material_list.iter().enumerate() {
+            if let Some(transform) = self.sample(&name.to_not_null_string()?, frame)? {
+                let mut material = material.clone();
+                material.set_texcoord_transform(transform);
+                matrices.push((index, material.texcoord_matrix()?));
+            }
+        }
+
+        Ok(matrices)
+    }
+
+    // Bakes the transforms at a frame into the materials of the list. The texcoord transform mode of the materials is
+    // left as is, so it must not be "none" for the transform to show. Returns the indices of the changed materials.
+    // The model must be rebased afterwards
+    pub fn apply_to_materials(&self, material_list: &mut MaterialList, frame: f32) -> Result<Vec<usize>, AppError> {
+        let mut changed = Vec::new();
+
+        for index in 0..material_list.len() {
+            let name = material_list.get_name(index).unwrap().to_not_null_string()?;
+            if let Some(transform) = self.sample(&name, frame)? {
+                material_list.get_material_mut(index).unwrap().set_texcoord_transform(transform);
+                changed.push(index);
+            }
+        }
+
+        Ok(changed)
+    }
+}
+
+impl SubfileAnimation for SrtAnimation {
+    const STAMP: [u8; 4] = *b"SRT0";
+
+    fn from_bytes(bytes: &[u8], debug_info: DebugInfo) -> Result<SrtAnimation, AppError> {
+        SrtAnimation::from_bytes(bytes, debug_info)
+    }
+
+    fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
+        SrtAnimation::write_bytes(self, buffer)
+    }
+
+    fn rebase(&mut self) {
+        SrtAnimation::rebase(self)
+    }
+
+    fn size(&self) -> usize {
+        SrtAnimation::size(self)
+    }
+}
+
+// Builds a texture coordinate animation from scratch. Keyframes are pushed per material at any frame and resampled to
+// every frame (linear interpolation, holding the first and last keys). Channels without keyframes are left at
+// scale 1, rotation 0 and translation 0. Rotations are in radians
+#[derive(Debug)]
+pub struct SrtAnimationBuilder {
+    num_frames: u16,
+    flags: u8,
+    texcoord_matrix_mode: u8,
+    materials: Vec<MaterialKeys>,
+    error: Option<AppError> // First error found by a setter, returned by build
+}
+
+#[derive(Debug)]
+struct MaterialKeys {
+    name: String,
+    scale: Vec<(u16, [f32; 2])>,
+    rotation: Vec<(u16, f32)>,
+    translation: Vec<(u16, [f32; 2])>
+}
+
+impl SrtAnimationBuilder {
+    fn new(num_frames: u16) -> SrtAnimationBuilder {
+        SrtAnimationBuilder {
+            num_frames,
+            flags: 0,
+            texcoord_matrix_mode: 0,
+            materials: Vec::new(),
+            error: None
+        }
+    }
+
+    pub fn num_frames(mut self, num_frames: u16) -> Self {
+        self.num_frames = num_frames;
+        self
+    }
+
+    // Interpolate the last frame towards the first one, so the animation loops smoothly
+    pub fn end_to_start_interpolation(mut self, enabled: bool) -> Self {
+        if enabled {
+            self.flags |= SrtAnimation::FLAG_END_TO_START_INTERPOLATION;
+        }
+        else {
+            self.flags &= !SrtAnimation::FLAG_END_TO_START_INTERPOLATION;
+        }
+
+        self
+    }
+
+    // Modeling tool convention of the texture matrices: 0 Maya, 1 Softimage 3D, 2 3ds Max, 3 Softimage XSI
+    pub fn texcoord_matrix_mode(mut self, mode: u8) -> Self {
+        if mode > 3 {
+            return self.keep_error(Err(AppError::new(&format!("Invalid texcoord matrix mode {}", mode))));
+        }
+
+        self.texcoord_matrix_mode = mode;
+        self
+    }
+
+    pub fn material(mut self, name: &str) -> Self {
+        if self.materials.iter().any(|material| material.name == name) {
+            return self.keep_error(Err(AppError::new(&format!("Material {} already has a track", name))));
+        }
+
+        self.materials.push(MaterialKeys {
+            name: name.to_string(),
+            scale: Vec::new(),
+            rotation: Vec::new(),
+            translation: Vec::new()
+        });
+
+        self
+    }
+
+    pub fn scale_key(mut self, material: &str, frame: u16, scale: [f32; 2]) -> Self {
+        let result = self.material_keys(material).map(|keys| insert_key(&mut keys.scale, frame, scale));
+        self.keep_error(result)
+    }
+
+    pub fn rotation_key(mut self, material: &str, frame: u16, radians: f32) -> Self {
+        let result = self.material_keys(material).map(|keys| insert_key(&mut keys.rotation, frame, radians));
+        self.keep_error(result)
+    }
+
+    pub fn translation_key(mut self, material: &str, frame: u16, translation: [f32; 2]) -> Self {
+        let result = self.material_keys(material).map(|keys| insert_key(&mut keys.translation, frame, translation));
+        self.keep_error(result)
+    }
+
+    pub fn build(self) -> Result<SrtAnimation, AppError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+
+        if self.num_frames == 0 {
+            return Err(AppError::new("SRT animation needs at least one frame"));
+        }
+
+        let num_frames = self.num_frames;
+        let axis_channel = |values: Option<Vec<[f32; 2]>>, axis: usize, default: f32| match values {
+            Some(values) => fixed_channel(values.iter().map(|value| value[axis]).collect()),
+            None => Ok(Channel::Constant(Fixed1_19_12::from_f32(default)))
+        };
+
+        let mut materials = NameList::new(MaterialSrtEntry::SIZE as u16);
+        let mut tracks = Vec::with_capacity(self.materials.len());
+        for keys in &self.materials {
+            let out_of_range = keys.scale.iter().map(|key| key.0)
+                .chain(keys.rotation.iter().map(|key| key.0))
+                .chain(keys.translation.iter().map(|key| key.0))
+                .find(|&frame| frame >= num_frames);
+            if let Some(frame) = out_of_range {
+                return Err(AppError::new(&format!("Material {} has a keyframe at frame {}, but the animation has {} frames", keys.name, frame, num_frames)));
+            }
+
+            let scale = resample_keys(&keys.scale, num_frames, lerp_array);
+            let rotation = resample_keys(&keys.rotation, num_frames, |a, b, t| a + (b - a) * t);
+            let translation = resample_keys(&keys.translation, num_frames, lerp_array);
+
+            tracks.push(MaterialSrtTrack {
+                scale_s: axis_channel(scale.clone(), 0, 1.0)?,
+                scale_t: axis_channel(scale, 1, 1.0)?,
+                rotation: match rotation {
+                    Some(values) => Channel::from_values(values.into_iter().map(SinCos::from_radians).collect(), |_| false)?,
+                    None => Channel::Constant(SinCos::from_radians(0.0))
+                },
+                translation_s: axis_channel(translation.clone(), 0, 0.0)?,
+                translation_t: axis_channel(translation, 1, 0.0)?
+            });
+            materials.push(Name::from_string(&keys.name)?, MaterialSrtEntry { channels: [(0, 0); 5] })?;
+        }
+
+        let mut animation = SrtAnimation {
+            num_frames,
+            flags: self.flags,
+            texcoord_matrix_mode: self.texcoord_matrix_mode,
+            materials,
+            size: 0,
+            tracks,
+            _debug_info: DebugInfo { offset: 0 }
+        };
+
+        animation.rebase();
+        Ok(animation)
+    }
+
+    fn material_keys(&mut self, name: &str) -> Result<&mut MaterialKeys, AppError> {
+        self.materials.iter_mut()
+            .find(|material| material.name == name)
+            .ok_or_else(|| AppError::new(&format!("Material {} has no track", name)))
+    }
+
+    fn keep_error(mut self, result: Result<(), AppError>) -> Self {
+        if let (Err(error), None) = (result, &self.error) {
+            self.error = Some(error);
+        }
+
+        self
+    }
+}
+
+// Channel from one value per frame (compact when every value fits in fx16)
+fn fixed_channel(values: Vec<f32>) -> Result<Channel<Fixed1_19_12>, AppError> {
+    Channel::from_values(values.into_iter().map(Fixed1_19_12::from_f32).collect(), |value| {
+        i16::try_from(value.to_i32()).is_ok()
+    })
+}
+
+// Interpolates angles the shortest way around
+fn lerp_angle(a: f32, b: f32, t: f32) -> f32 {
+    let delta = (b - a + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU) - std::f32::consts::PI;
+    a + delta * t
+}
+
+// Dictionary entry of a material: info and constant (or data offset) of scale S and T, rotation and translation S and T
+#[derive(Debug, Clone, Copy)]
+struct MaterialSrtEntry {
+    channels: [(u32, u32); 5]
+}
+
+impl MaterialSrtEntry {
+    const SIZE: usize = 40;
+}
+
+impl BinarySerializable for MaterialSrtEntry {
+    fn from_bytes(bytes: &[u8]) -> Result<Self, AppError> {
+        if bytes.len() < Self::SIZE {
+            return Err(AppError::new(&format!("SRT material entry needs {} bytes", Self::SIZE)));
+        }
+
+        let mut channels = [(0, 0); 5];
+        for (i, channel) in channels.iter_mut().enumerate() {
+            *channel = (read_u32(bytes, i * 8)?, read_u32(bytes, i * 8 + 4)?);
+        }
+
+        Ok(MaterialSrtEntry { channels })
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>, AppError> {
+        let mut buffer = vec![0; Self::SIZE];
+        self.write_bytes(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
+        if buffer.len() < Self::SIZE {
+            return Err(AppError::new("Buffer is too small to write SRT material entry"));
+        }
+
+        for (i, &(info, data)) in self.channels.iter().enumerate() {
+            buffer[i * 8..i * 8 + 4].copy_from_slice(&info.to_le_bytes());
+            buffer[i * 8 + 4..i * 8 + 8].copy_from_slice(&data.to_le_bytes());
+        }
+
+        Ok(())
+    }
+
+    fn size(&self) -> usize {
+        Self::SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subfiles::srt::Srt;
+
+    fn assert_close(a: (f32, f32), b: (f32, f32)) {
+        assert!((a.0 - b.0).abs() < 1.0 / 1024.0 && (a.1 - b.1).abs() < 1.0 / 1024.0, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn built_animation_round_trips_through_srt0() {
+        let animation = SrtAnimation::builder(6)
+            .texcoord_matrix_mode(2)
+            .material("water")
+            .translation_key("water", 0, [0.0, 0.0])
+            .translation_key("water", 5, [1.0, -0.5])
+            .rotation_key("water", 0, 0.0)
+            .rotation_key("water", 5, std::f32::consts::FRAC_PI_2)
+            .material("lava")
+            .scale_key("lava", 0, [2.0, 0.5])
+            .build()
+            .unwrap();
+
+        let mut srt = Srt::new();
+        srt.add_animation("flow", animation).unwrap();
+        let bytes = srt.to_bytes();
+
+        let parsed = Srt::from_bytes(&bytes, DebugInfo { offset: 0 }).unwrap();
+        assert_eq!(parsed.to_bytes(), bytes);
+        assert_eq!(parsed.get_animation_name(0).unwrap().to_not_null_string().unwrap(), "flow");
+
+        let (original, parsed) = (srt.get_animation(0).unwrap(), parsed.get_animation(0).unwrap());
+        assert_eq!(parsed.num_frames(), 6);
+        assert_eq!(parsed.texcoord_matrix_mode(), 2);
+
+        for material in ["water", "lava"] {
+            for frame in 0..6 {
+                let expected = original.sample(material, frame as f32).unwrap().unwrap();
+                let transform = parsed.sample(material, frame as f32).unwrap().unwrap();
+
+                assert_close(transform.scale(), expected.scale());
+                assert_close(transform.rotation(), expected.rotation());
+                assert_close(transform.translation(), expected.translation());
+            }
+        }
+
+        assert_close(parsed.sample("water", 5.0).unwrap().unwrap().translation(), (1.0, -0.5));
+        assert_close(parsed.sample("lava", 3.0).unwrap().unwrap().scale(), (2.0, 0.5));
+        assert!(parsed.sample("stone", 0.0).unwrap().is_none());
+    }
+}
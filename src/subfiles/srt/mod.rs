@@ -0,0 +1,7 @@
+use animation::SrtAnimation;
+
+use super::animation_subfile::AnimationSubfile;
+
+pub mod animation;
+
+pub type Srt = AnimationSubfile<SrtAnimation>;
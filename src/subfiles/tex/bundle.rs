@@ -0,0 +1,132 @@
+use std::{fs, path::Path};
+
+use crate::{error::AppError, tools::{texture_decode::DecodedTexture, texture_export::{encode_png, export_png}, texture_import::{decode_png, replace_texture_image, ImportOptions, ImportPalette}}, util::color::Rgb555};
+
+use super::{texture::TeximageParams, Tex};
+
+// Every texture and palette of a TEX0 dumped as PNG files, listed in a tab separated manifest:
+//   texture <name> <file> <palette name, or - if it has none>
+//   palette <name> <file>
+// Palettes are stored as a row of colors
+impl Tex {
+    const MANIFEST_FILE: &'static str = "manifest.txt";
+
+    pub fn export_all(&self, dir: &Path) -> Result<(), AppError> {
+        fs::create_dir_all(dir).map_err(|err| AppError::new(&format!("Could not create {}: {}", dir.display(), err)))?;
+
+        let mut manifest = String::new();
+
+        for index in 0..self.palette_list().len() {
+            let name = self.palette_list().get_palette_name(index).unwrap().to_not_null_string()?;
+            let colors = self.palette_colors(index).unwrap_or_default();
+            if colors.is_empty() {
+                continue;
+            }
+
+            let image = DecodedTexture {
+                width: colors.len(),
+                height: 1,
+                rgba: colors.iter()
+                    .flat_map(|color| {
+                        let [r, g, b] = color.to_rgb8();
+                        [r, g, b, 0xFF]
+                    })
+                    .collect()
+            };
+
+            let file = format!("palette_{}.png", Self::file_name(&name));
+            Self::write_file(dir, &file, &encode_png(&image)?)?;
+            manifest.push_str(&format!("palette\t{}\t{}\n", name, file));
+        }
+
+        for index in 0..self.texture_list().len() {
+            let name = self.texture_list().get_texture_name(index).unwrap().to_not_null_string()?;
+            let format = self.texture_list().get_texture(index).unwrap().teximage_params().texture_format();
+
+            let palette_index = self.palette_for_texture(index);
+            if palette_index.is_none() && format != TeximageParams::FORMAT_DIRECT {
+                // Cannot be decoded, so it is listed without a file and left as is on import
+                manifest.push_str(&format!("texture\t{}\t-\t-\n", name));
+                continue;
+            }
+
+            let palette_name = match palette_index {
+                Some(palette_index) => self.palette_list().get_palette_name(palette_index).unwrap().to_not_null_string()?,
+                None => "-".to_string()
+            };
+
+            let file = format!("texture_{}.png", Self::file_name(&name));
+            Self::write_file(dir, &file, &export_png(self, index, palette_index)?)?;
+            manifest.push_str(&format!("texture\t{}\t{}\t{}\n", name, file, palette_name));
+        }
+
+        Self::write_file(dir, Self::MANIFEST_FILE, manifest.as_bytes())
+    }
+
+    // Reads back a directory written by export_all. Palettes are replaced first, then textures are re-encoded to their
+    // format with their palette (tex4x4 textures regenerate theirs). Textures and palettes not in the manifest are left as is
+    pub fn import_all(&mut self, dir: &Path) -> Result<(), AppError> {
+        let manifest = fs::read_to_string(dir.join(Self::MANIFEST_FILE))
+            .map_err(|err| AppError::new(&format!("Could not read {}: {}", dir.join(Self::MANIFEST_FILE).display(), err)))?;
+
+        let entries: Vec<Vec<&str>> = manifest.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.split('\t').collect())
+            .collect();
+
+        for (line, entry) in entries.iter().enumerate() {
+            match entry.as_slice() {
+                ["palette", _, _] | ["texture", _, _, _] => {},
+                _ => return Err(AppError::new(&format!("Invalid manifest line {}: {}", line + 1, entry.join("\t"))))
+            }
+        }
+
+        for entry in entries.iter().filter(|entry| entry[0] == "palette") {
+            let (name, file) = (entry[1], entry[2]);
+            let index = self.palette_list().index_of_name(name)
+                .ok_or_else(|| AppError::new(&format!("Tex has no palette named {}", name)))?;
+
+            let image = decode_png(&Self::read_file(dir, file)?)?;
+            let data: Vec<u8> = image.rgba.chunks_exact(4)
+                .flat_map(|texel| Rgb555::from_rgb8([texel[0], texel[1], texel[2]]).to_u16().to_le_bytes())
+                .collect();
+
+            self.replace_palette(index, &data)?;
+        }
+
+        for entry in entries.iter().filter(|entry| entry[0] == "texture" && entry[2] != "-") {
+            let (name, file, palette_name) = (entry[1], entry[2], entry[3]);
+            let index = self.texture_list().index_of_name(name)
+                .ok_or_else(|| AppError::new(&format!("Tex has no texture named {}", name)))?;
+
+            let format = self.texture_list().get_texture(index).unwrap().teximage_params().texture_format();
+            let palette = match (format, palette_name) {
+                (TeximageParams::FORMAT_DIRECT, _) | (_, "-") => ImportPalette::None,
+                (TeximageParams::FORMAT_COMPRESSED_4X4, palette_name) => ImportPalette::Generate(palette_name),
+                (_, palette_name) => ImportPalette::Existing(palette_name)
+            };
+
+            let image = decode_png(&Self::read_file(dir, file)?)?;
+            replace_texture_image(self, name, &image, palette, ImportOptions::default())?;
+        }
+
+        Ok(())
+    }
+
+    // Names can hold any byte, so only the safe ones are kept
+    fn file_name(name: &str) -> String {
+        name.chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.' { c } else { '_' })
+            .collect()
+    }
+
+    fn write_file(dir: &Path, file: &str, bytes: &[u8]) -> Result<(), AppError> {
+        let path = dir.join(file);
+        fs::write(&path, bytes).map_err(|err| AppError::new(&format!("Could not write {}: {}", path.display(), err)))
+    }
+
+    fn read_file(dir: &Path, file: &str) -> Result<Vec<u8>, AppError> {
+        let path = dir.join(file);
+        fs::read(&path).map_err(|err| AppError::new(&format!("Could not read {}: {}", path.display(), err)))
+    }
+}
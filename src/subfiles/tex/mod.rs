@@ -1,10 +1,15 @@
-use palette::PaletteList;
+use std::ops::Range;
+
+use palette::{Palette, PaletteList, PlttBase};
 use texture::TextureList;
 
-use crate::{debug_info::DebugInfo, error::AppError};
+use texture::{TeximageParams, Texture};
+
+use crate::{data_structures::name::Name, debug_info::DebugInfo, error::AppError, tools::texture_decode::{decode_tex4x4, decode_texture_with_color_0, direct_texel, paletted_texel, tex4x4_texel, texel_layout, Color0Mode, DecodedTexture}, util::{color::Rgb555, number::alignment::{get_8_byte_alignment, get_16_byte_alignment}}};
 
 pub mod texture;
 pub mod palette;
+mod bundle;
 
 #[derive(Debug, Clone)]
 pub struct Tex {
@@ -16,7 +21,7 @@ pub struct Tex {
     padding_1: u32, // 0
     texture_data_offset: u32,
     padding_2: u32, // 0
-    compressed_texture_data_size: u16, // length / 12
+    compressed_texture_data_size: u16, // length / 12 (8 bytes of texel data and 4 of attributes per unit)
     compressed_texture_list_offset: u16, // Compressed texture list offset
     padding_3: u32, // 0
     compressed_texture_4x4_data_offset: u32, // Compressed Texture Offset for 4x4-Texel Data
@@ -32,13 +37,59 @@ pub struct Tex {
     palette_list: PaletteList,
 
     texture_data: Vec<u8>,
+    compressed_texture_data: Vec<u8>, // 4x4-texel data
+    compressed_texture_attr_data: Vec<u8>, // 4x4-texel attributes, half the size of the texel data
     palette_data: Vec<u8>,
 
     // Debug info
     _debug_info: DebugInfo
 }
 
+// Bytes taken by a texture or palette in VRAM
+#[derive(Debug, Clone)]
+pub struct VramUsage {
+    pub name: String,
+    pub size: usize // Texel and attribute data for tex4x4 textures
+}
+
+#[derive(Debug, Clone)]
+pub struct VramReport {
+    pub textures: Vec<VramUsage>,
+    pub palettes: Vec<VramUsage>,
+    pub texture_total: usize, // Data blocks, so shared data is only counted once
+    pub palette_total: usize
+}
+
+impl VramReport {
+    pub const TEXTURE_VRAM_SIZE: usize = 512 * 1024; // 4 banks of 128KB
+    pub const PALETTE_VRAM_SIZE: usize = 96 * 1024;
+
+    pub fn texture_overrun(&self) -> bool {
+        self.texture_total > Self::TEXTURE_VRAM_SIZE
+    }
+
+    pub fn palette_overrun(&self) -> bool {
+        self.palette_total > Self::PALETTE_VRAM_SIZE
+    }
+}
+
+// Problem found by Tex::validate
+#[derive(Debug, Clone)]
+pub struct TexIssue {
+    pub location: String, // "header", "texture <name>", "palette <name>"...
+    pub message: String
+}
+
+// Entries of the merged TEX0 that were added under another name, as theirs was taken by different data
+#[derive(Debug, Clone, Default)]
+pub struct TexMergeRenames {
+    pub textures: Vec<(String, String)>, // (name in the merged TEX0, name it was added as)
+    pub palettes: Vec<(String, String)>
+}
+
 impl Tex {
+    const HEADER_SIZE: usize = 0x3C;
+
     pub fn from_bytes(bytes: &[u8], debug_info: DebugInfo) -> Result<Tex, AppError> {
         if bytes.len() < 60 {
             return Err(AppError::new("Tex needs at least 56 bytes to start reading"));
@@ -73,6 +124,8 @@ impl Tex {
         let palette_list = PaletteList::from_bytes(&bytes[palette_list_offset as usize..])?;
 
         let texture_data = bytes[texture_data_offset as usize..texture_data_offset as usize + texture_data_size as usize * 8].to_vec();
+        let compressed_texture_data = bytes[compressed_texture_4x4_data_offset as usize..compressed_texture_4x4_data_offset as usize + compressed_texture_data_size as usize * 8].to_vec();
+        let compressed_texture_attr_data = bytes[compressed_texture_4x4_attr_offset as usize..compressed_texture_4x4_attr_offset as usize + compressed_texture_data_size as usize * 4].to_vec();
         let palette_data = bytes[palette_data_offset as usize..palette_data_offset as usize + palette_data_size as usize * 8].to_vec();
 
         let tex = Tex {
@@ -98,6 +151,8 @@ impl Tex {
             compressed_texture_list,
             palette_list,
             texture_data,
+            compressed_texture_data,
+            compressed_texture_attr_data,
             palette_data,
 
             _debug_info: debug_info
@@ -132,9 +187,10 @@ impl Tex {
         self.compressed_texture_list.write_bytes(&mut buffer[self.compressed_texture_list_offset as usize..])?;
         self.palette_list.write_bytes(&mut buffer[self.palette_list_offset as usize..])?;
         buffer[self.texture_data_offset as usize..self.texture_data_offset as usize + self.texture_data_size as usize * 8].copy_from_slice(&self.texture_data);
+        buffer[self.compressed_texture_4x4_data_offset as usize..self.compressed_texture_4x4_data_offset as usize + self.compressed_texture_data_size as usize * 8].copy_from_slice(&self.compressed_texture_data);
+        buffer[self.compressed_texture_4x4_attr_offset as usize..self.compressed_texture_4x4_attr_offset as usize + self.compressed_texture_data_size as usize * 4].copy_from_slice(&self.compressed_texture_attr_data);
         buffer[self.palette_data_offset as usize..self.palette_data_offset as usize + self.palette_data_size as usize * 8].copy_from_slice(&self.palette_data);
 
-
         Ok(())
     }
 
@@ -149,4 +205,807 @@ impl Tex {
     pub fn texture_list_mut(&mut self) -> &mut TextureList {
         &mut self.texture_list
     }
+
+    pub fn palette_list(&self) -> &PaletteList {
+        &self.palette_list
+    }
+
+    pub fn palette_list_mut(&mut self) -> &mut PaletteList {
+        &mut self.palette_list
+    }
+
+    pub fn texture_data(&self) -> &[u8] {
+        &self.texture_data
+    }
+
+    pub fn compressed_texture_data(&self) -> &[u8] {
+        &self.compressed_texture_data
+    }
+
+    pub fn compressed_texture_attr_data(&self) -> &[u8] {
+        &self.compressed_texture_attr_data
+    }
+
+    pub fn palette_data(&self) -> &[u8] {
+        &self.palette_data
+    }
+
+    // Data of a texture, from its offset and size. For tex4x4 textures these are the texels, see texture_attr_bytes
+    pub fn texture_bytes(&self, index: usize) -> Option<&[u8]> {
+        let params = self.texture_list.get_texture(index)?.teximage_params();
+        let start = (params.texture_data() as usize) << 3;
+        let block = if params.texture_format() == TeximageParams::FORMAT_COMPRESSED_4X4 { &self.compressed_texture_data } else { &self.texture_data };
+
+        block.get(start..start + params.data_size())
+    }
+
+    // 4x4-texel attributes of a tex4x4 texture
+    pub fn texture_attr_bytes(&self, index: usize) -> Option<&[u8]> {
+        let params = self.texture_list.get_texture(index)?.teximage_params();
+        if params.texture_format() != TeximageParams::FORMAT_COMPRESSED_4X4 {
+            return None;
+        }
+
+        let start = (params.texture_data() as usize) << 2;
+        self.compressed_texture_attr_data.get(start..start + params.data_size() / 2)
+    }
+
+    // Colors of a palette, up to the next palette or the end of the palette data block
+    pub fn palette_bytes(&self, index: usize) -> Option<&[u8]> {
+        self.palette_list.get_palette(index)?;
+        Some(&self.palette_data[self.palette_range(index)])
+    }
+
+    pub fn palette_colors(&self, index: usize) -> Option<Vec<Rgb555>> {
+        let bytes = self.palette_bytes(index)?;
+        Some(bytes.chunks_exact(2).map(|color| Rgb555::from_u16(u16::from_le_bytes([color[0], color[1]]))).collect())
+    }
+
+    pub fn palette_color(&self, index: usize, color_index: usize) -> Option<Rgb555> {
+        let bytes = self.palette_bytes(index)?.get(color_index * 2..color_index * 2 + 2)?;
+        Some(Rgb555::from_u16(u16::from_le_bytes([bytes[0], bytes[1]])))
+    }
+
+    pub fn set_palette_color(&mut self, index: usize, color_index: usize, color: Rgb555) -> Result<(), AppError> {
+        self.set_palette_colors(index, color_index, &[color])
+    }
+
+    // Overwrites colors from first_color on. The palette cannot grow, as the next palette starts right after it
+    pub fn set_palette_colors(&mut self, index: usize, first_color: usize, colors: &[Rgb555]) -> Result<(), AppError> {
+        if self.palette_list.get_palette(index).is_none() {
+            return Err(AppError::new(&format!("Invalid palette index {}. Tex has {} palettes", index, self.palette_list.len())));
+        }
+
+        let range = self.palette_range(index);
+        let num_colors = range.len() / 2;
+        if first_color + colors.len() > num_colors {
+            return Err(AppError::new(&format!("Palette {} has {} colors, cannot set colors {}-{}", index, num_colors, first_color, first_color + colors.len())));
+        }
+
+        let start = range.start + first_color * 2;
+        for (i, color) in colors.iter().enumerate() {
+            self.palette_data[start + i * 2..start + i * 2 + 2].copy_from_slice(&color.to_u16().to_le_bytes());
+        }
+
+        Ok(())
+    }
+
+    pub fn vram_report(&self) -> Result<VramReport, AppError> {
+        let mut textures = Vec::with_capacity(self.texture_list.len());
+        for index in 0..self.texture_list.len() {
+            let size = self.texture_bytes(index).map_or(0, |bytes| bytes.len()) + self.texture_attr_bytes(index).map_or(0, |bytes| bytes.len());
+            textures.push(VramUsage {
+                name: self.texture_list.get_texture_name(index).unwrap().to_not_null_string()?,
+                size
+            });
+        }
+
+        let mut palettes = Vec::with_capacity(self.palette_list.len());
+        for index in 0..self.palette_list.len() {
+            palettes.push(VramUsage {
+                name: self.palette_list.get_palette_name(index).unwrap().to_not_null_string()?,
+                size: self.palette_range(index).len()
+            });
+        }
+
+        Ok(VramReport {
+            textures,
+            palettes,
+            texture_total: self.texture_data.len() + self.compressed_texture_data.len() + self.compressed_texture_attr_data.len(),
+            palette_total: self.palette_data.len()
+        })
+    }
+
+    // Checks the header sizes against the data blocks, that every texture and palette lies inside its data block and
+    // that the compressed texture list mirrors the texture list. Returns one issue per problem found, so an empty list
+    // means the TEX0 is consistent
+    pub fn validate(&self) -> Vec<TexIssue> {
+        let mut issues = Vec::new();
+        let mut issue = |location: String, message: String| issues.push(TexIssue { location, message });
+
+        let block_sizes = [
+            ("texture_data_size", self.texture_data_size as usize * 8, "texture data", self.texture_data.len()),
+            ("compressed_texture_data_size", self.compressed_texture_data_size as usize * 8, "4x4-texel data", self.compressed_texture_data.len()),
+            ("compressed_texture_data_size", self.compressed_texture_data_size as usize * 4, "4x4-texel attributes", self.compressed_texture_attr_data.len()),
+            ("palette_data_size", self.palette_data_size as usize * 8, "palette data", self.palette_data.len())
+        ];
+        for (field, expected, block, size) in block_sizes {
+            if expected != size {
+                issue(String::from("header"), format!("{} says 0x{:X} bytes of {}, but there are 0x{:X}", field, expected, block, size));
+            }
+        }
+
+        for index in 0..self.texture_list.len() {
+            let name = self.texture_list.get_texture_name(index).unwrap().to_not_null_string().unwrap_or_else(|_| format!("#{}", index));
+            let location = format!("texture {}", name);
+            let texture = self.texture_list.get_texture(index).unwrap();
+            let params = texture.teximage_params();
+
+            if params.texture_format() == TeximageParams::FORMAT_NONE {
+                issue(location.clone(), String::from("Texture has no format"));
+                continue;
+            }
+
+            if (texture.width(), texture.height()) != (params.width(), params.height()) {
+                issue(location.clone(), format!("Size is {}x{}, but its params say {}x{}", texture.width(), texture.height(), params.width(), params.height()));
+            }
+
+            let is_tex4x4 = params.texture_format() == TeximageParams::FORMAT_COMPRESSED_4X4;
+            let (block, block_size) = if is_tex4x4 { ("4x4-texel data", self.compressed_texture_data.len()) } else { ("texture data", self.texture_data.len()) };
+            let start = (params.texture_data() as usize) << 3;
+            if start + params.data_size() > block_size {
+                issue(location.clone(), format!("Data at 0x{:X}..0x{:X} is outside the {} (0x{:X} bytes)", start, start + params.data_size(), block, block_size));
+            }
+
+            if is_tex4x4 {
+                let attr_start = (params.texture_data() as usize) << 2;
+                let attr_end = attr_start + params.data_size() / 2;
+                if attr_end > self.compressed_texture_attr_data.len() {
+                    issue(location.clone(), format!("Attributes at 0x{:X}..0x{:X} are outside the 4x4-texel attributes (0x{:X} bytes)", attr_start, attr_end, self.compressed_texture_attr_data.len()));
+                }
+            }
+        }
+
+        for index in 0..self.palette_list.len() {
+            let name = self.palette_list.get_palette_name(index).unwrap().to_not_null_string().unwrap_or_else(|_| format!("#{}", index));
+            let start = (self.palette_list.get_palette(index).unwrap().pltt_base().palette_base() as usize) << 3;
+            if start >= self.palette_data.len() {
+                issue(format!("palette {}", name), format!("Colors at 0x{:X} are outside the palette data (0x{:X} bytes)", start, self.palette_data.len()));
+            }
+        }
+
+        if self.compressed_texture_list.len() != self.texture_list.len() {
+            issue(String::from("compressed texture list"), format!("Has {} textures, but the texture list has {}", self.compressed_texture_list.len(), self.texture_list.len()));
+        }
+
+        for index in 0..self.texture_list.len().min(self.compressed_texture_list.len()) {
+            let name = self.texture_list.get_texture_name(index).unwrap();
+            let mirrored_name = self.compressed_texture_list.get_texture_name(index).unwrap();
+            let location = format!("compressed texture list entry {}", index);
+
+            if name.name != mirrored_name.name {
+                issue(location.clone(), format!("Is named {}, but the texture list entry is named {}", mirrored_name.to_not_null_string().unwrap_or_default(), name.to_not_null_string().unwrap_or_default()));
+            }
+
+            let texture = self.texture_list.get_texture(index).unwrap();
+            let mirrored_texture = self.compressed_texture_list.get_texture(index).unwrap();
+            if texture.teximage_params() != mirrored_texture.teximage_params() || (texture.width(), texture.height()) != (mirrored_texture.width(), mirrored_texture.height()) {
+                issue(location, String::from("Params differ from the texture list entry"));
+            }
+        }
+
+        issues
+    }
+
+    // Adds the textures and palettes of another TEX0. Entries with the same name and data are not added again, and
+    // entries whose name is taken by different data get a numbered suffix (so their pairings must be renamed too)
+    pub fn merge(&mut self, other: &Tex) -> Result<TexMergeRenames, AppError> {
+        let mut renames = TexMergeRenames::default();
+
+        for index in 0..other.palette_list.len() {
+            let name = other.palette_list.get_palette_name(index).unwrap().to_not_null_string()?;
+            let data = other.palette_bytes(index).unwrap_or_default();
+            if data.is_empty() {
+                continue;
+            }
+
+            let new_name = match self.palette_list.index_of_name(&name) {
+                Some(existing) if self.palette_bytes(existing) == Some(data) => continue,
+                Some(_) => Self::unique_name(&name, |candidate| self.palette_list.index_of_name(candidate).is_some())?,
+                None => name.clone()
+            };
+
+            self.add_palette(&new_name, data)?;
+            if new_name != name {
+                renames.palettes.push((name, new_name));
+            }
+        }
+
+        for index in 0..other.texture_list.len() {
+            let name = other.texture_list.get_texture_name(index).unwrap().to_not_null_string()?;
+            let params = *other.texture_list.get_texture(index).unwrap().teximage_params();
+            if params.texture_format() == TeximageParams::FORMAT_NONE {
+                continue;
+            }
+
+            let (_, data) = other.texture_entry(index).unwrap();
+
+            let new_name = match self.texture_list.index_of_name(&name) {
+                Some(existing) if self.is_same_texture(existing, &params, &data) => continue,
+                Some(_) => Self::unique_name(&name, |candidate| self.texture_list.index_of_name(candidate).is_some())?,
+                None => name.clone()
+            };
+
+            self.add_texture(&new_name, params, &data)?;
+            if new_name != name {
+                renames.textures.push((name, new_name));
+            }
+        }
+
+        Ok(renames)
+    }
+
+    // Params and data of a texture, laid out like add_texture takes them (texels followed by attributes for tex4x4)
+    pub fn texture_entry(&self, index: usize) -> Option<(TeximageParams, Vec<u8>)> {
+        let params = *self.texture_list.get_texture(index)?.teximage_params();
+        let data = [self.texture_bytes(index).unwrap_or_default(), self.texture_attr_bytes(index).unwrap_or_default()].concat();
+
+        Some((params, data))
+    }
+
+    // Whether a texture has the same format, size, color 0 mode and data (see texture_entry), wherever it is stored
+    pub fn is_same_texture(&self, index: usize, params: &TeximageParams, data: &[u8]) -> bool {
+        let Some((existing_params, existing_data)) = self.texture_entry(index) else {
+            return false;
+        };
+
+        (existing_params.texture_format(), existing_params.width(), existing_params.height(), existing_params.palette_color_0_transparent()) ==
+            (params.texture_format(), params.width(), params.height(), params.palette_color_0_transparent()) && existing_data == data
+    }
+
+    // name_1, name_2... cutting the name so it still fits in 16 bytes
+    pub(crate) fn unique_name(name: &str, taken: impl Fn(&str) -> bool) -> Result<String, AppError> {
+        for number in 1..1000 {
+            let suffix = format!("_{}", number);
+            let mut base = name.to_string();
+            while base.len() + suffix.len() > Name::SIZE {
+                base.pop();
+            }
+
+            let candidate = format!("{}{}", base, suffix);
+            if !taken(&candidate) {
+                return Ok(candidate);
+            }
+        }
+
+        Err(AppError::new(&format!("Could not find a free name for {}", name)))
+    }
+
+    // Adds a texture at the end of its data block. For tex4x4 textures, data is the texel data followed by the attributes.
+    // The texture_data offset of params is overwritten. Returns the index of the new texture
+    pub fn add_texture(&mut self, name: &str, params: TeximageParams, data: &[u8]) -> Result<usize, AppError> {
+        if self.texture_list.index_of_name(name).is_some() {
+            return Err(AppError::new(&format!("Tex already has a texture named {}", name)));
+        }
+
+        Self::check_texture_data(name, &params, data)?;
+        let name = Name::from_string(name)?;
+
+        let params = self.append_texture_data(params, data)?;
+        self.texture_list.push(name, Texture::new(params))?;
+
+        self.rebase();
+
+        Ok(self.texture_list.len() - 1)
+    }
+
+    // Removes a texture and its data, moving back the data of the textures after it
+    pub fn remove_texture(&mut self, name: &str) -> Result<(), AppError> {
+        let index = self.texture_list.index_of_name(name)
+            .ok_or_else(|| AppError::new(&format!("Tex has no texture named {}", name)))?;
+
+        self.release_texture_data(index);
+        self.texture_list.remove(index)?;
+
+        self.rebase();
+
+        Ok(())
+    }
+
+    // Replaces the params (format and size can change) and data of a texture, keeping its name and index
+    pub fn replace_texture(&mut self, index: usize, params: TeximageParams, data: &[u8]) -> Result<(), AppError> {
+        let name = self.texture_list.get_texture_name(index)
+            .ok_or_else(|| AppError::new(&format!("Invalid texture index {}. Tex has {} textures", index, self.texture_list.len())))?
+            .to_not_null_string()?;
+
+        Self::check_texture_data(&name, &params, data)?;
+
+        self.release_texture_data(index);
+        let params = self.append_texture_data(params, data)?;
+        *self.texture_list.get_texture_mut(index).unwrap() = Texture::new(params);
+
+        self.rebase();
+
+        Ok(())
+    }
+
+    // Overwrites the data of a texture, keeping its params. The data must have the size its format and dimensions need
+    // (texels followed by attributes for tex4x4). Data shared with other textures is split off, so they keep theirs
+    pub fn set_texture_data(&mut self, index: usize, data: &[u8]) -> Result<(), AppError> {
+        let texture = self.texture_list.get_texture(index)
+            .ok_or_else(|| AppError::new(&format!("Invalid texture index {}. Tex has {} textures", index, self.texture_list.len())))?;
+        let params = *texture.teximage_params();
+        let name = self.texture_list.get_texture_name(index).unwrap().to_not_null_string()?;
+
+        Self::check_texture_data(&name, &params, data)?;
+
+        let compressed = params.texture_format() == TeximageParams::FORMAT_COMPRESSED_4X4;
+        let start = (params.texture_data() as usize) << 3;
+        let data_size = params.data_size();
+        let block_size = if compressed { self.compressed_texture_data.len() } else { self.texture_data.len() };
+
+        if self.is_texture_data_shared(index) || start + data_size > block_size {
+            return self.replace_texture(index, params, data);
+        }
+
+        if compressed {
+            self.compressed_texture_data[start..start + data_size].copy_from_slice(&data[..data_size]);
+            self.compressed_texture_attr_data[start / 2..start / 2 + data_size / 2].copy_from_slice(&data[data_size..]);
+        }
+        else {
+            self.texture_data[start..start + data_size].copy_from_slice(data);
+        }
+
+        Ok(())
+    }
+
+    // Whether another texture points to the same data
+    fn is_texture_data_shared(&self, index: usize) -> bool {
+        let Some(texture) = self.texture_list.get_texture(index) else {
+            return false;
+        };
+
+        let params = texture.teximage_params();
+        let compressed = params.texture_format() == TeximageParams::FORMAT_COMPRESSED_4X4;
+
+        self.texture_list.textures_iter()
+            .enumerate()
+            .filter(|&(other_index, _)| other_index != index)
+            .any(|(_, other)| {
+                let other = other.teximage_params();
+                (other.texture_format() == TeximageParams::FORMAT_COMPRESSED_4X4) == compressed && other.texture_data() == params.texture_data()
+            })
+    }
+
+    fn check_texture_data(name: &str, params: &TeximageParams, data: &[u8]) -> Result<(), AppError> {
+        if params.texture_format() == TeximageParams::FORMAT_NONE {
+            return Err(AppError::new(&format!("Texture {} has no format", name)));
+        }
+
+        let data_size = params.data_size();
+        let expected_size = if params.texture_format() == TeximageParams::FORMAT_COMPRESSED_4X4 { data_size + data_size / 2 } else { data_size };
+        if data.len() != expected_size {
+            return Err(AppError::new(&format!("Texture {} ({}, {}x{}) needs 0x{:X} bytes of data, got 0x{:X}", name, params.texture_format_name(), params.width(), params.height(), expected_size, data.len())));
+        }
+
+        Ok(())
+    }
+
+    // Appends the data at the end of its block and returns params with the new offset
+    fn append_texture_data(&mut self, params: TeximageParams, data: &[u8]) -> Result<TeximageParams, AppError> {
+        let data_size = params.data_size();
+        let compressed = params.texture_format() == TeximageParams::FORMAT_COMPRESSED_4X4;
+
+        let block = if compressed { &self.compressed_texture_data } else { &self.texture_data };
+        let offset = get_8_byte_alignment(block.len());
+        if (offset + data_size) >> 3 > u16::MAX as usize {
+            return Err(AppError::new(&format!("Texture does not fit in the {} data block", if compressed { "tex4x4" } else { "texture" })));
+        }
+
+        if compressed {
+            self.compressed_texture_data.resize(offset, 0);
+            self.compressed_texture_data.extend_from_slice(&data[..data_size]);
+            self.compressed_texture_attr_data.resize(offset / 2, 0);
+            self.compressed_texture_attr_data.extend_from_slice(&data[data_size..]);
+        }
+        else {
+            self.texture_data.resize(offset, 0);
+            self.texture_data.extend_from_slice(data);
+        }
+
+        let mut params = params;
+        params.set_texture_data((offset >> 3) as u16);
+
+        Ok(params)
+    }
+
+    // Drops the data of a texture (unless another texture uses it too) and moves back the data after it
+    fn release_texture_data(&mut self, index: usize) {
+        let Some(texture) = self.texture_list.get_texture(index) else {
+            return;
+        };
+
+        let params = *texture.teximage_params();
+        let compressed = params.texture_format() == TeximageParams::FORMAT_COMPRESSED_4X4;
+        let start = (params.texture_data() as usize) << 3;
+        let data_size = get_8_byte_alignment(params.data_size());
+        let shared = self.is_texture_data_shared(index);
+
+        let block = if compressed { &mut self.compressed_texture_data } else { &mut self.texture_data };
+        if shared || params.texture_format() == TeximageParams::FORMAT_NONE || start >= block.len() {
+            return;
+        }
+
+        let end = (start + data_size).min(block.len());
+        block.drain(start..end);
+
+        if compressed {
+            let attr_end = (end / 2).min(self.compressed_texture_attr_data.len());
+            self.compressed_texture_attr_data.drain(start / 2..attr_end);
+        }
+
+        let removed_units = ((end - start) >> 3) as u16;
+        for other in self.texture_list.textures_iter_mut() {
+            let other = other.teximage_params_mut();
+            let same_block = (other.texture_format() == TeximageParams::FORMAT_COMPRESSED_4X4) == compressed;
+            if same_block && other.texture_data() > params.texture_data() {
+                other.set_texture_data(other.texture_data() - removed_units);
+            }
+        }
+    }
+
+    // Adds a palette (RGB555 colors) at the end of the palette data block. Returns the index of the new palette
+    pub fn add_palette(&mut self, name: &str, data: &[u8]) -> Result<usize, AppError> {
+        if self.palette_list.index_of_name(name).is_some() {
+            return Err(AppError::new(&format!("Tex already has a palette named {}", name)));
+        }
+
+        Self::check_palette_data(name, data)?;
+        let name = Name::from_string(name)?;
+
+        let palette_base = self.append_palette_data(data)?;
+        self.palette_list.push(name, Palette::new(PlttBase::new(palette_base)))?;
+
+        self.rebase();
+
+        Ok(self.palette_list.len() - 1)
+    }
+
+    // Removes a palette and its colors, moving back the palettes after it
+    pub fn remove_palette(&mut self, name: &str) -> Result<(), AppError> {
+        let index = self.palette_list.index_of_name(name)
+            .ok_or_else(|| AppError::new(&format!("Tex has no palette named {}", name)))?;
+
+        self.release_palette_data(index);
+        self.palette_list.remove(index)?;
+
+        self.rebase();
+
+        Ok(())
+    }
+
+    // Replaces the colors of a palette, which can change its size, keeping its name and index
+    pub fn replace_palette(&mut self, index: usize, data: &[u8]) -> Result<(), AppError> {
+        let name = self.palette_list.get_palette_name(index)
+            .ok_or_else(|| AppError::new(&format!("Invalid palette index {}. Tex has {} palettes", index, self.palette_list.len())))?
+            .to_not_null_string()?;
+
+        Self::check_palette_data(&name, data)?;
+
+        self.release_palette_data(index);
+        let palette_base = self.append_palette_data(data)?;
+        self.palette_list.get_palette_mut(index).unwrap().pltt_base_mut().set_palette_base(palette_base);
+
+        self.rebase();
+
+        Ok(())
+    }
+
+    fn check_palette_data(name: &str, data: &[u8]) -> Result<(), AppError> {
+        if data.is_empty() || !data.len().is_multiple_of(2) {
+            return Err(AppError::new(&format!("Palette {} data must be a non empty list of 2 byte colors, got 0x{:X} bytes", name, data.len())));
+        }
+
+        Ok(())
+    }
+
+    // Appends the colors at the end of the palette data block and returns their palette base.
+    // Only pal4 palettes can start at 8 bytes, the rest are loaded in 16 byte units
+    fn append_palette_data(&mut self, data: &[u8]) -> Result<u16, AppError> {
+        let offset = get_16_byte_alignment(self.palette_data.len());
+        if offset >> 3 > 0x1FFF {
+            return Err(AppError::new("Palette does not fit in the palette data block"));
+        }
+
+        self.palette_data.resize(offset, 0);
+        self.palette_data.extend_from_slice(data);
+
+        Ok((offset >> 3) as u16)
+    }
+
+    // Drops the colors of a palette (unless another palette uses them too) and moves back the palettes after it
+    fn release_palette_data(&mut self, index: usize) {
+        let Some(palette) = self.palette_list.get_palette(index) else {
+            return;
+        };
+
+        let palette_base = palette.pltt_base().palette_base();
+        let shared = self.palette_list.palettes_iter()
+            .enumerate()
+            .any(|(other_index, other)| other_index != index && other.pltt_base().palette_base() == palette_base);
+        if shared {
+            return;
+        }
+
+        let range = self.palette_range(index);
+        let range = get_8_byte_alignment(range.start)..get_8_byte_alignment(range.end).min(self.palette_data.len());
+        let removed_units = (range.len() >> 3) as u16;
+        self.palette_data.drain(range);
+
+        for other in self.palette_list.palettes_iter_mut() {
+            let pltt_base = other.pltt_base_mut();
+            if pltt_base.palette_base() > palette_base {
+                pltt_base.set_palette_base(pltt_base.palette_base() - removed_units);
+            }
+        }
+    }
+
+    // Palettes do not store their size, so each one spans up to the next palette or the end of the block
+    fn palette_range(&self, index: usize) -> Range<usize> {
+        let Some(palette) = self.palette_list.get_palette(index) else {
+            return 0..0;
+        };
+
+        let start = ((palette.pltt_base().palette_base() as usize) << 3).min(self.palette_data.len());
+        let end = self.palette_list.palettes_iter()
+            .map(|other| (other.pltt_base().palette_base() as usize) << 3)
+            .filter(|&other_start| other_start > start)
+            .min()
+            .unwrap_or(self.palette_data.len())
+            .min(self.palette_data.len());
+
+        start..end
+    }
+
+    // Lays out the lists and data blocks in the order the NNS converter writes them and updates the header
+    pub fn rebase(&mut self) {
+        // Both lists point to the same data
+        self.compressed_texture_list = self.texture_list.clone();
+
+        let mut offset = Self::HEADER_SIZE;
+        self.texture_list_offset = offset as u16;
+        self.compressed_texture_list_offset = offset as u16;
+        offset += self.texture_list.size();
+
+        self.palette_list_offset = offset as u32;
+        offset += self.palette_list.size();
+
+        // Data blocks are addressed in 8 byte units
+        self.texture_data.resize(get_8_byte_alignment(self.texture_data.len()), 0);
+        self.compressed_texture_data.resize(get_8_byte_alignment(self.compressed_texture_data.len()), 0);
+        self.compressed_texture_attr_data.resize(self.compressed_texture_data.len() / 2, 0);
+        self.palette_data.resize(get_8_byte_alignment(self.palette_data.len()), 0);
+
+        offset = get_8_byte_alignment(offset);
+        self.texture_data_offset = offset as u32;
+        self.texture_data_size = (self.texture_data.len() >> 3) as u16;
+        offset += self.texture_data.len();
+
+        self.compressed_texture_4x4_data_offset = offset as u32;
+        self.compressed_texture_data_size = (self.compressed_texture_data.len() >> 3) as u16;
+        offset += self.compressed_texture_data.len();
+
+        self.compressed_texture_4x4_attr_offset = offset as u32;
+        offset += self.compressed_texture_attr_data.len();
+
+        self.palette_data_offset = offset as u32;
+        self.palette_data_size = (self.palette_data.len() >> 3) as u32;
+        offset += self.palette_data.len();
+
+        self.chunk_size = offset as u32;
+    }
+
+    // Decodes a tex4x4 texture with the palette at palette_index
+    pub fn decode_compressed_texture(&self, texture_index: usize, palette_index: usize) -> Result<DecodedTexture, AppError> {
+        let texture = self.texture_list.get_texture(texture_index)
+            .ok_or_else(|| AppError::new(&format!("Invalid texture index {}. Tex has {} textures", texture_index, self.texture_list.len())))?;
+        let palette = self.palette_list.get_palette(palette_index)
+            .ok_or_else(|| AppError::new(&format!("Invalid palette index {}. Tex has {} palettes", palette_index, self.palette_list.len())))?;
+
+        // The palette base is stored in 8 byte units
+        let palette_offset = (palette.pltt_base().palette_base() as usize) << 3;
+        decode_tex4x4(texture, &self.compressed_texture_data, &self.compressed_texture_attr_data, &self.palette_data, palette_offset)
+    }
+
+    // Palette a texture is most likely drawn with when there are no material pairings, following the NNS naming rules:
+    // the palette with the same name, then the one with the name plus "_pl", then the only palette of the TEX0.
+    // None for direct textures, which have no palette
+    pub fn palette_for_texture(&self, texture_index: usize) -> Option<usize> {
+        let texture = self.texture_list.get_texture(texture_index)?;
+        if matches!(texture.teximage_params().texture_format(), TeximageParams::FORMAT_NONE | TeximageParams::FORMAT_DIRECT) {
+            return None;
+        }
+
+        let texture_name = self.texture_list.get_texture_name(texture_index)?.to_not_null_string().ok()?;
+
+        self.palette_list.index_of_name(&texture_name)
+            .or_else(|| self.palette_list.index_of_name(&format!("{}_pl", texture_name)))
+            .or_else(|| (self.palette_list.len() == 1).then_some(0))
+    }
+
+    // palette_for_texture for every texture
+    pub fn palette_associations(&self) -> Vec<Option<usize>> {
+        (0..self.texture_list.len())
+            .map(|texture_index| self.palette_for_texture(texture_index))
+            .collect()
+    }
+
+    // Gives a paletted texture with no palette_for_texture a palette of its own, named after it with a "_pl" suffix, so it
+    // can still be decoded. The palette has as many colors as the texture can index: the given ones (padded with black),
+    // or a grayscale ramp. Returns the index of the palette the texture uses, None for direct textures
+    pub fn add_default_palette(&mut self, texture_index: usize, colors: Option<&[Rgb555]>) -> Result<Option<usize>, AppError> {
+        let texture = self.texture_list.get_texture(texture_index)
+            .ok_or_else(|| AppError::new(&format!("Invalid texture index {}. Tex has {} textures", texture_index, self.texture_list.len())))?;
+        let format = texture.teximage_params().texture_format();
+
+        if matches!(format, TeximageParams::FORMAT_NONE | TeximageParams::FORMAT_DIRECT) {
+            return Ok(None);
+        }
+
+        if let Some(palette_index) = self.palette_for_texture(texture_index) {
+            return Ok(Some(palette_index));
+        }
+
+        self.add_palette_for_texture(texture_index, format, colors).map(Some)
+    }
+
+    // add_default_palette with a grayscale ramp for every texture without a palette. Returns the (texture, palette) indices
+    // of the added palettes
+    pub fn add_default_palettes(&mut self) -> Result<Vec<(usize, usize)>, AppError> {
+        // Found before adding any, as a first added palette would otherwise be the only palette for the rest
+        let unpaired: Vec<(usize, u8)> = (0..self.texture_list.len())
+            .map(|texture_index| (texture_index, self.texture_list.get_texture(texture_index).unwrap().teximage_params().texture_format()))
+            .filter(|&(texture_index, format)| {
+                !matches!(format, TeximageParams::FORMAT_NONE | TeximageParams::FORMAT_DIRECT) && self.palette_for_texture(texture_index).is_none()
+            })
+            .collect();
+
+        let mut added = Vec::with_capacity(unpaired.len());
+        for (texture_index, format) in unpaired {
+            added.push((texture_index, self.add_palette_for_texture(texture_index, format, None)?));
+        }
+
+        Ok(added)
+    }
+
+    fn add_palette_for_texture(&mut self, texture_index: usize, format: u8, colors: Option<&[Rgb555]>) -> Result<usize, AppError> {
+        let color_count = if format == TeximageParams::FORMAT_COMPRESSED_4X4 {
+            // Each block attribute points to 2 colors (4 bytes) after the palette base, and reads up to 4 colors from there
+            self.texture_attr_bytes(texture_index).unwrap_or_default()
+                .chunks_exact(2)
+                .map(|attr| ((u16::from_le_bytes([attr[0], attr[1]]) & 0x3FFF) as usize) * 2 + 4)
+                .max()
+                .unwrap_or(4)
+        }
+        else {
+            1 << texel_layout(format)?.0
+        };
+
+        let palette: Vec<Rgb555> = match colors {
+            Some(colors) => (0..color_count)
+                .map(|index| colors.get(index).copied().unwrap_or(Rgb555::new(0, 0, 0)))
+                .collect(),
+            None => (0..color_count)
+                .map(|index| {
+                    let value = (index * 31 / (color_count - 1).max(1)) as u8;
+                    Rgb555::new(value, value, value)
+                })
+                .collect()
+        };
+        let data: Vec<u8> = palette.iter().flat_map(|color| color.to_u16().to_le_bytes()).collect();
+
+        let texture_name = self.texture_list.get_texture_name(texture_index).unwrap().to_not_null_string()?;
+        let name = format!("{}_pl", texture_name);
+        let name = if name.len() <= Name::SIZE && self.palette_list.index_of_name(&name).is_none() {
+            name
+        }
+        else {
+            Self::unique_name(&texture_name, |candidate| self.palette_list.index_of_name(candidate).is_some())?
+        };
+
+        self.add_palette(&name, &data)
+    }
+
+    // Decodes a texture of any format. Every format but direct needs a palette, which defaults to palette_for_texture
+    pub fn decode_texture(&self, texture_index: usize, palette_index: Option<usize>) -> Result<DecodedTexture, AppError> {
+        self.decode_texture_with_color_0(texture_index, palette_index, Color0Mode::FromTexture)
+    }
+
+    // decode_texture deciding the transparency of palette index 0 with color_0 (tex4x4 textures ignore it)
+    pub fn decode_texture_with_color_0(&self, texture_index: usize, palette_index: Option<usize>, color_0: Color0Mode) -> Result<DecodedTexture, AppError> {
+        let texture = self.texture_list.get_texture(texture_index)
+            .ok_or_else(|| AppError::new(&format!("Invalid texture index {}. Tex has {} textures", texture_index, self.texture_list.len())))?;
+        let format = texture.teximage_params().texture_format();
+
+        if format == TeximageParams::FORMAT_DIRECT {
+            return decode_texture_with_color_0(texture, self.texture_bytes(texture_index).unwrap_or_default(), &[], color_0);
+        }
+
+        let palette_index = palette_index
+            .or_else(|| self.palette_for_texture(texture_index))
+            .ok_or_else(|| AppError::new(&format!("{} texture {} needs a palette to be decoded", texture.teximage_params().texture_format_name(), texture_index)))?;
+
+        if format == TeximageParams::FORMAT_COMPRESSED_4X4 {
+            return self.decode_compressed_texture(texture_index, palette_index);
+        }
+
+        let palette = self.palette_colors(palette_index)
+            .ok_or_else(|| AppError::new(&format!("Invalid palette index {}. Tex has {} palettes", palette_index, self.palette_list.len())))?;
+
+        decode_texture_with_color_0(texture, self.texture_bytes(texture_index).unwrap_or_default(), &palette, color_0)
+    }
+
+    // Decodes every step-th texel of every step-th row, for previews that do not need the whole texture decoded.
+    // The palette defaults to palette_for_texture, like decode_texture
+    pub fn decode_thumbnail(&self, texture_index: usize, palette_index: Option<usize>, step: usize) -> Result<DecodedTexture, AppError> {
+        if step == 0 {
+            return Err(AppError::new("Thumbnail step must be at least 1"));
+        }
+
+        let texture = self.texture_list.get_texture(texture_index)
+            .ok_or_else(|| AppError::new(&format!("Invalid texture index {}. Tex has {} textures", texture_index, self.texture_list.len())))?;
+        let params = texture.teximage_params();
+        let format = params.texture_format();
+        let (width, height) = (texture.width() as usize, texture.height() as usize);
+
+        let texture_data = self.texture_bytes(texture_index)
+            .ok_or_else(|| AppError::new(&format!("Data of texture {} is out of bounds", texture_index)))?;
+
+        let palette_index = match format {
+            TeximageParams::FORMAT_DIRECT => None,
+            _ => Some(palette_index
+                .or_else(|| self.palette_for_texture(texture_index))
+                .ok_or_else(|| AppError::new(&format!("{} texture {} needs a palette to be decoded", params.texture_format_name(), texture_index)))?)
+        };
+
+        let (thumbnail_width, thumbnail_height) = (width.div_ceil(step), height.div_ceil(step));
+        let mut rgba = Vec::with_capacity(thumbnail_width * thumbnail_height * 4);
+        let samples = (0..thumbnail_height).flat_map(|y| (0..thumbnail_width).map(move |x| (x * step, y * step)));
+
+        match (format, palette_index) {
+            (TeximageParams::FORMAT_DIRECT, _) => {
+                for (x, y) in samples {
+                    rgba.extend_from_slice(&direct_texel(texture_data, y * width + x));
+                }
+            },
+            (TeximageParams::FORMAT_COMPRESSED_4X4, Some(palette_index)) => {
+                let attrs = self.texture_attr_bytes(texture_index)
+                    .ok_or_else(|| AppError::new(&format!("Attributes of texture {} are out of bounds", texture_index)))?;
+                let palette = self.palette_list.get_palette(palette_index)
+                    .ok_or_else(|| AppError::new(&format!("Invalid palette index {}. Tex has {} palettes", palette_index, self.palette_list.len())))?;
+                let palette_offset = (palette.pltt_base().palette_base() as usize) << 3;
+
+                for (x, y) in samples {
+                    rgba.extend_from_slice(&tex4x4_texel(texture_data, attrs, width, x, y, &self.palette_data, palette_offset)?);
+                }
+            },
+            (_, Some(palette_index)) => {
+                let layout = texel_layout(format)?;
+                let transparent_0 = layout.1 == 0 && params.palette_color_0_transparent();
+                let palette = self.palette_colors(palette_index)
+                    .ok_or_else(|| AppError::new(&format!("Invalid palette index {}. Tex has {} palettes", palette_index, self.palette_list.len())))?;
+
+                for (x, y) in samples {
+                    rgba.extend_from_slice(&paletted_texel(texture_data, y * width + x, layout, &palette, transparent_0)?);
+                }
+            },
+            (_, None) => unreachable!()
+        }
+
+        Ok(DecodedTexture {
+            width: thumbnail_width,
+            height: thumbnail_height,
+            rgba
+        })
+    }
 }
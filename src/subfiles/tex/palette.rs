@@ -1,6 +1,6 @@
 use std::fmt::Debug;
 
-use crate::{data_structures::name_list::NameList, error::AppError, traits::BinarySerializable};
+use crate::{data_structures::{name::Name, name_list::NameList}, error::AppError, traits::BinarySerializable};
 
 #[derive(Debug, Clone)]
 pub struct PaletteList {
@@ -23,6 +23,65 @@ impl PaletteList {
 
         self.palettes.write_bytes(buffer)
     }
+
+    pub fn get_palette(&self, index: usize) -> Option<&Palette> {
+        self.palettes.get(index)
+    }
+
+    pub fn get_palette_mut(&mut self, index: usize) -> Option<&mut Palette> {
+        self.palettes.get_mut(index)
+    }
+
+    pub fn get_palette_name(&self, index: usize) -> Option<&Name> {
+        self.palettes.get_name(index)
+    }
+
+    pub fn push(&mut self, name: Name, palette: Palette) -> Result<(), AppError> {
+        self.palettes.push(name, palette)
+    }
+
+    pub fn remove(&mut self, index: usize) -> Result<(Name, Palette), AppError> {
+        self.palettes.remove(index)
+    }
+
+    pub fn rename(&mut self, index: usize, name: &str) -> Result<(), AppError> {
+        if self.index_of_name(name).is_some_and(|existing| existing != index) {
+            return Err(AppError::new(&format!("PaletteList already has a palette named {}", name)));
+        }
+
+        let new_name = Name::from_string(name)?;
+        let len = self.len();
+        let palette_name = self.palettes.get_name_mut(index)
+            .ok_or_else(|| AppError::new(&format!("Invalid palette index {}. PaletteList has {} palettes", index, len)))?;
+        *palette_name = new_name;
+
+        Ok(())
+    }
+
+    pub fn palettes_iter(&self) -> impl Iterator<Item = &Palette> {
+        self.palettes.data_iter()
+    }
+
+    pub fn palettes_iter_mut(&mut self) -> impl Iterator<Item = &mut Palette> {
+        self.palettes.data_iter_mut()
+    }
+
+    pub fn index_of_name(&self, name: &str) -> Option<usize> {
+        self.palettes.names_iter()
+            .position(|palette_name| palette_name.to_not_null_string().is_ok_and(|palette_name| palette_name == name))
+    }
+
+    pub fn len(&self) -> usize {
+        self.palettes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.palettes.len() == 0
+    }
+
+    pub fn size(&self) -> usize {
+        self.palettes.size()
+    }
 }
 
 
@@ -35,6 +94,21 @@ pub struct Palette {
 
 impl Palette {
     const SIZE: usize = 4;
+
+    pub fn new(pltt_base: PlttBase) -> Palette {
+        Palette {
+            pltt_base,
+            unknown: 0
+        }
+    }
+
+    pub fn pltt_base(&self) -> &PlttBase {
+        &self.pltt_base
+    }
+
+    pub fn pltt_base_mut(&mut self) -> &mut PlttBase {
+        &mut self.pltt_base
+    }
 }
 
 impl BinarySerializable for Palette {
@@ -90,6 +164,11 @@ impl PlttBase {
         self.data &0x1FFF
     }
 
+    // Offset in 8 byte units
+    pub fn set_palette_base(&mut self, palette_base: u16) {
+        self.data = (self.data & !0x1FFF) | (palette_base & 0x1FFF);
+    }
+
     pub fn unused(&self) -> u8 {
         // Always 0?
         ((self.data >> 13) & 0x03) as u8
@@ -36,6 +36,49 @@ impl TextureList {
         self.textures.get_name(index)
     }
 
+    pub fn rename(&mut self, index: usize, name: &str) -> Result<(), AppError> {
+        if self.index_of_name(name).is_some_and(|existing| existing != index) {
+            return Err(AppError::new(&format!("TextureList already has a texture named {}", name)));
+        }
+
+        let new_name = Name::from_string(name)?;
+        let len = self.len();
+        let texture_name = self.textures.get_name_mut(index)
+            .ok_or_else(|| AppError::new(&format!("Invalid texture index {}. TextureList has {} textures", index, len)))?;
+        *texture_name = new_name;
+
+        Ok(())
+    }
+
+    pub fn push(&mut self, name: Name, texture: Texture) -> Result<(), AppError> {
+        self.textures.push(name, texture)
+    }
+
+    pub fn remove(&mut self, index: usize) -> Result<(Name, Texture), AppError> {
+        self.textures.remove(index)
+    }
+
+    pub fn textures_iter(&self) -> impl Iterator<Item = &Texture> {
+        self.textures.data_iter()
+    }
+
+    pub fn textures_iter_mut(&mut self) -> impl Iterator<Item = &mut Texture> {
+        self.textures.data_iter_mut()
+    }
+
+    pub fn index_of_name(&self, name: &str) -> Option<usize> {
+        self.textures.names_iter()
+            .position(|texture_name| texture_name.to_not_null_string().is_ok_and(|texture_name| texture_name == name))
+    }
+
+    pub fn len(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.textures.len() == 0
+    }
+
     pub fn size(&self) -> usize {
         self.textures.size()
     }
@@ -50,6 +93,17 @@ pub struct Texture {
 impl Texture {
     const SIZE: usize = 8;
 
+    // Width and height are taken from the S and T sizes of the params
+    pub fn new(teximage_params: TeximageParams) -> Texture {
+        let width = teximage_params.width() as u32;
+        let height = teximage_params.height() as u32;
+
+        Texture {
+            teximage_params,
+            width_height: WidthHeight::new(width | (height << 11))
+        }
+    }
+
     pub fn width(&self) -> u16 {
         self.width_height.width()
     }
@@ -57,6 +111,24 @@ impl Texture {
     pub fn height(&self) -> u16 {
         self.width_height.height()
     }
+
+    pub fn teximage_params(&self) -> &TeximageParams {
+        &self.teximage_params
+    }
+
+    pub fn teximage_params_mut(&mut self) -> &mut TeximageParams {
+        &mut self.teximage_params
+    }
+
+    // Updates both the params and the stored width and height. The texture data is not resized
+    pub fn set_size(&mut self, width: u16, height: u16) -> Result<(), AppError> {
+        self.teximage_params.set_texture_s_size(TeximageParams::size_to_bits(width)?)?;
+        self.teximage_params.set_texture_t_size(TeximageParams::size_to_bits(height)?)?;
+        self.width_height.set_width(width);
+        self.width_height.set_height(height);
+
+        Ok(())
+    }
 }
 
 impl BinarySerializable for Texture {
@@ -96,7 +168,7 @@ impl BinarySerializable for Texture {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct TeximageParams {
     data: u32
 }
@@ -110,6 +182,11 @@ impl TeximageParams {
         (self.data & 0xFFFF) as u16
     }
 
+    // Offset in 8 byte units
+    pub fn set_texture_data(&mut self, texture_data: u16) {
+        self.data = (self.data & !0xFFFF) | texture_data as u32;
+    }
+
     pub fn unknown_0(&self) -> u8 {
         // Zero in TEX0, derived from Model's Material
         ((self.data >> 16) & 0x0F) as u8
@@ -120,19 +197,112 @@ impl TeximageParams {
         ((self.data >> 20) & 0x07) as u8
     }
 
+    pub fn set_texture_s_size(&mut self, size: u8) -> Result<(), AppError> {
+        if size > 7 {
+            return Err(AppError::new("Invalid texture S size. Expected three bits"));
+        }
+
+        self.data &= !0x00700000;
+        self.data |= (size as u32) << 20;
+
+        Ok(())
+    }
+
     pub fn texture_t_size(&self) -> u8 {
         // 8 << this = texture height
         ((self.data >> 23) & 0x07) as u8
     }
 
+    pub fn set_texture_t_size(&mut self, size: u8) -> Result<(), AppError> {
+        if size > 7 {
+            return Err(AppError::new("Invalid texture T size. Expected three bits"));
+        }
+
+        self.data &= !0x03800000;
+        self.data |= (size as u32) << 23;
+
+        Ok(())
+    }
+
+    // Converts a texture dimension (8, 16, ..., 1024) to its size bits
+    pub fn size_to_bits(size: u16) -> Result<u8, AppError> {
+        if !size.is_power_of_two() || !(8..=1024).contains(&size) {
+            return Err(AppError::new(&format!("Invalid texture dimension {}. Expected a power of two between 8 and 1024", size)));
+        }
+
+        Ok((size.trailing_zeros() - 3) as u8)
+    }
+
+    pub fn width(&self) -> u16 {
+        8 << self.texture_s_size()
+    }
+
+    pub fn height(&self) -> u16 {
+        8 << self.texture_t_size()
+    }
+
+    pub const FORMAT_NONE: u8 = 0;
+    pub const FORMAT_A3I5: u8 = 1;
+    pub const FORMAT_PALETTE_4: u8 = 2;
+    pub const FORMAT_PALETTE_16: u8 = 3;
+    pub const FORMAT_PALETTE_256: u8 = 4;
+    pub const FORMAT_COMPRESSED_4X4: u8 = 5;
+    pub const FORMAT_A5I3: u8 = 6;
+    pub const FORMAT_DIRECT: u8 = 7;
+
     pub fn texture_format(&self) -> u8 {
-        ((self.data >> 26) & 0x03) as u8
+        ((self.data >> 26) & 0x07) as u8
+    }
+
+    pub fn set_texture_format(&mut self, format: u8) -> Result<(), AppError> {
+        if format > 7 {
+            return Err(AppError::new("Invalid texture format. Expected three bits"));
+        }
+
+        self.data &= !0x1C000000;
+        self.data |= (format as u32) << 26;
+
+        Ok(())
+    }
+
+    pub fn texture_format_name(&self) -> &'static str {
+        match self.texture_format() {
+            Self::FORMAT_NONE => "none",
+            Self::FORMAT_A3I5 => "a3i5",
+            Self::FORMAT_PALETTE_4 => "pal4",
+            Self::FORMAT_PALETTE_16 => "pal16",
+            Self::FORMAT_PALETTE_256 => "pal256",
+            Self::FORMAT_COMPRESSED_4X4 => "tex4x4",
+            Self::FORMAT_A5I3 => "a5i3",
+            _ => "direct"
+        }
+    }
+
+    // Bytes the texture takes in its data block. For tex4x4 this is the texel data, the attributes take half of it
+    pub fn data_size(&self) -> usize {
+        let bits_per_texel = match self.texture_format() {
+            Self::FORMAT_NONE => 0,
+            Self::FORMAT_PALETTE_4 | Self::FORMAT_COMPRESSED_4X4 => 2,
+            Self::FORMAT_PALETTE_16 => 4,
+            Self::FORMAT_DIRECT => 16,
+            _ => 8
+        };
+
+        self.width() as usize * self.height() as usize * bits_per_texel / 8
     }
 
     pub fn palette_color_0_transparent(&self) -> bool {
         (self.data & 0x20000000) != 0
     }
 
+    pub fn set_palette_color_0_transparent(&mut self, transparent: bool) {
+        if transparent {
+            self.data |= 0x20000000;
+        } else {
+            self.data &= !0x20000000;
+        }
+    }
+
     pub fn unknown_1(&self) -> u8 {
         // Zero in TEX0, derived from Model's Material
         ((self.data >> 30) & 0x03) as u8
@@ -173,6 +343,14 @@ impl WidthHeight {
     pub fn height(&self) -> u16 {
         ((self.data >> 11) & 0x07FF) as u16
     }
+
+    pub fn set_width(&mut self, width: u16) {
+        self.data = (self.data & !0x07FF) | (width as u32 & 0x07FF);
+    }
+
+    pub fn set_height(&mut self, height: u16) {
+        self.data = (self.data & !(0x07FF << 11)) | ((height as u32 & 0x07FF) << 11);
+    }
 }
 
 impl Debug for WidthHeight {
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use crate::{error::AppError, subfiles::{jnt::animation::JntAnimation, mdl::model::{bone_list::BoneList, render_command_list::{RenderCommand, RenderCommandList}}}};
+
+// Remaps bone references between two skeletons that only differ in bone naming (and order)
+#[derive(Debug, Clone)]
+pub struct BoneRetargeter {
+    source_to_target: Vec<Option<usize>>,
+    source_names: Vec<String>,
+    target_names: Vec<String>
+}
+
+impl BoneRetargeter {
+    // Source bones not present in name_mapping are matched by their own name
+    pub fn new(source: &BoneList, target: &BoneList, name_mapping: &HashMap<String, String>) -> Result<BoneRetargeter, AppError> {
+        let source_names = Self::bone_names(source)?;
+        let target_names = Self::bone_names(target)?;
+
+        let source_to_target = source_names.iter()
+            .map(|source_name| {
+                let target_name = name_mapping.get(source_name).unwrap_or(source_name);
+                target_names.iter().position(|name| name == target_name)
+            })
+            .collect();
+
+        Ok(BoneRetargeter {
+            source_to_target,
+            source_names,
+            target_names
+        })
+    }
+
+    pub fn map_index(&self, source_index: usize) -> Option<usize> {
+        self.source_to_target.get(source_index).copied().flatten()
+    }
+
+    pub fn map_name(&self, source_name: &str) -> Option<&String> {
+        let source_index = self.source_names.iter().position(|name| name == source_name)?;
+        let target_index = self.map_index(source_index)?;
+
+        self.target_names.get(target_index)
+    }
+
+    // Source bones that have no counterpart in the target skeleton
+    pub fn unmapped_bones(&self) -> Vec<&String> {
+        self.source_names.iter()
+            .zip(self.source_to_target.iter())
+            .filter(|(_, target)| target.is_none())
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    // Remaps the node indices of visibility and billboard commands, bone and parent indices of bone matrix commands and
+    // the inverse bind indices of skinning terms. The list is left untouched if any index has no counterpart
+    pub fn retarget_render_commands(&self, render_cmds: &mut RenderCommandList) -> Result<(), AppError> {
+        let mut remapped = render_cmds.clone();
+
+        for cmd in remapped.iter_mut() {
+            match cmd {
+                RenderCommand::NodeVisibility(data) => {
+                    data.node_index = self.map_command_index(data.node_index)?;
+                },
+                RenderCommand::Billboard(data) | RenderCommand::BillboardY(data) => {
+                    data.node_index = self.map_command_index(data.node_index)?;
+                },
+                RenderCommand::MulCurrentMatrixWithBoneMatrix(data) => {
+                    data.bone_index = self.map_command_index(data.bone_index)?;
+                    data.parent_index = self.map_command_index(data.parent_index)?;
+                },
+                RenderCommand::CalculateSkinningEquation(data) => {
+                    for term in data.terms.iter_mut() {
+                        term.inv_bind_index = self.map_command_index(term.inv_bind_index)?;
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        *render_cmds = remapped;
+
+        Ok(())
+    }
+
+    // Remaps the node of every track of a joint animation made for the source skeleton, so it can play on the target
+    // one. The animation is left untouched if any node has no counterpart
+    pub fn retarget_animation(&self, animation: &mut JntAnimation) -> Result<(), AppError> {
+        let node_ids = animation.tracks().iter()
+            .map(|track| self.map_command_index(track.node_id))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (track, node_id) in animation.tracks_mut().iter_mut().zip(node_ids) {
+            track.node_id = node_id;
+        }
+
+        Ok(())
+    }
+
+    // Remaps the bone names loaded in each matrix stack slot (see ModelRenderCmdExecutor::loaded_bones_in_matrix)
+    pub fn retarget_bone_names(&self, bone_names: &[Option<String>]) -> Result<Vec<Option<String>>, AppError> {
+        bone_names.iter()
+            .map(|name| match name {
+                Some(name) => self.map_name(name)
+                    .cloned()
+                    .map(Some)
+                    .ok_or_else(|| AppError::new(&format!("Bone '{}' has no counterpart in the target skeleton", name))),
+                None => Ok(None)
+            })
+            .collect()
+    }
+
+    fn map_command_index(&self, source_index: u8) -> Result<u8, AppError> {
+        match self.map_index(source_index as usize) {
+            Some(target_index) => Ok(target_index as u8),
+            None => {
+                let name = self.source_names.get(source_index as usize)
+                    .map(|name| name.as_str())
+                    .unwrap_or("<out of range>");
+
+                Err(AppError::new(&format!("Bone {} ('{}') has no counterpart in the target skeleton", source_index, name)))
+            }
+        }
+    }
+
+    fn bone_names(bone_list: &BoneList) -> Result<Vec<String>, AppError> {
+        (0..bone_list.len())
+            .map(|index| bone_list.get_name(index).unwrap().to_not_null_string())
+            .collect()
+    }
+}
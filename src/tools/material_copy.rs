@@ -0,0 +1,131 @@
+use crate::{container::Container, data_structures::name::Name, error::AppError, subfiles::tex::Tex};
+
+use super::material_resolve::{MaterialResolver, ResolvedMaterial};
+
+// Copies a material from a model into another one, possibly in another container, keeping its texture and palette pairings
+#[derive(Debug, Clone)]
+pub struct MaterialCopier<'a> {
+    source: &'a Container,
+    resolved: ResolvedMaterial<'a>
+}
+
+impl MaterialCopier<'_> {
+    pub fn new<'a>(source: &'a Container, mdl_index: usize, model_index: usize, material_index: usize) -> Result<MaterialCopier<'a>, AppError> {
+        let resolved = MaterialResolver::new(source, mdl_index, model_index)?
+            .resolve(material_index)?;
+
+        Ok(MaterialCopier {
+            source,
+            resolved
+        })
+    }
+
+    // Copies the material into the target model and returns its new index. The name defaults to the source one.
+    // A texture or palette the target doesn't have (same name and data in one of its TEX0 subfiles) is copied into its
+    // first TEX0, with a numbered suffix if the name is taken. The container must be rebased afterwards.
+    // The target is left untouched if the copy fails
+    pub fn copy_into(&self, target: &mut Container, mdl_index: usize, model_index: usize, name: Option<&str>) -> Result<usize, AppError> {
+        // Textures and palettes are copied before the material, so every change is made on a copy of the target
+        let mut staged = target.clone();
+        let material_index = self.copy_into_staged(&mut staged, mdl_index, model_index, name)?;
+        *target = staged;
+
+        Ok(material_index)
+    }
+
+    fn copy_into_staged(&self, target: &mut Container, mdl_index: usize, model_index: usize, name: Option<&str>) -> Result<usize, AppError> {
+        target.get_mdl(mdl_index)
+            .ok_or_else(|| AppError::new(&format!("MDL subfile {} not found", mdl_index)))?
+            .get_model(model_index)
+            .ok_or_else(|| AppError::new(&format!("Model {} not found in MDL subfile {}", model_index, mdl_index)))?;
+
+        let texture_name = match &self.resolved.texture {
+            Some(texture) => Some(self.copy_texture(target, texture.tex_index, &texture.name)?),
+            None => None
+        };
+
+        let palette_name = match &self.resolved.palette {
+            Some(palette) => {
+                // Keep the NNS pairing by name: the palette of a renamed texture follows it
+                let preferred = match (&self.resolved.texture, &texture_name) {
+                    (Some(texture), Some(texture_name)) if palette.name == format!("{}_pl", texture.name) => {
+                        let renamed = format!("{}_pl", texture_name);
+                        if renamed.len() <= Name::SIZE { renamed } else { palette.name.clone() }
+                    },
+                    _ => palette.name.clone()
+                };
+
+                Some(self.copy_palette(target, palette.tex_index, &palette.name, &preferred)?)
+            },
+            None => None
+        };
+
+        let model = target.get_mdl_mut(mdl_index)
+            .ok_or_else(|| AppError::new(&format!("MDL subfile {} not found", mdl_index)))?
+            .get_model_mut(model_index)
+            .ok_or_else(|| AppError::new(&format!("Model {} not found in MDL subfile {}", model_index, mdl_index)))?;
+
+        let material_list = model.get_material_list_mut();
+        let material_index = self.resolved.material.clone_into(material_list, name.unwrap_or(&self.resolved.name))?;
+
+        if let Some(texture_name) = &texture_name {
+            material_list.set_texture_for_material(material_index, texture_name)?;
+        }
+
+        if let Some(palette_name) = &palette_name {
+            material_list.set_palette_for_material(material_index, palette_name)?;
+        }
+
+        Ok(material_index)
+    }
+
+    // Name of the texture in the target, copying it there if needed
+    fn copy_texture(&self, target: &mut Container, tex_index: usize, name: &str) -> Result<String, AppError> {
+        let source_tex = self.source_tex(tex_index)?;
+        let texture_index = source_tex.texture_list().index_of_name(name).unwrap();
+        let (params, data) = source_tex.texture_entry(texture_index).unwrap();
+
+        let existing = target.tex_iter().any(|tex| {
+            tex.texture_list().index_of_name(name).is_some_and(|index| tex.is_same_texture(index, &params, &data))
+        });
+        if existing {
+            return Ok(name.to_string());
+        }
+
+        let taken = |candidate: &str| target.tex_iter().any(|tex| tex.texture_list().index_of_name(candidate).is_some());
+        let new_name = if taken(name) { Tex::unique_name(name, taken)? } else { name.to_string() };
+
+        Self::target_tex(target)?.add_texture(&new_name, params, &data)?;
+        Ok(new_name)
+    }
+
+    // Name of the palette in the target, copying it there (as preferred_name if free) if needed
+    fn copy_palette(&self, target: &mut Container, tex_index: usize, name: &str, preferred_name: &str) -> Result<String, AppError> {
+        let source_tex = self.source_tex(tex_index)?;
+        let palette_index = source_tex.palette_list().index_of_name(name).unwrap();
+        let data = source_tex.palette_bytes(palette_index).unwrap_or_default();
+
+        let existing = target.tex_iter().any(|tex| {
+            tex.palette_list().index_of_name(name).is_some_and(|index| tex.palette_bytes(index) == Some(data))
+        });
+        if existing {
+            return Ok(name.to_string());
+        }
+
+        let taken = |candidate: &str| target.tex_iter().any(|tex| tex.palette_list().index_of_name(candidate).is_some());
+        let new_name = if taken(preferred_name) { Tex::unique_name(preferred_name, taken)? } else { preferred_name.to_string() };
+
+        Self::target_tex(target)?.add_palette(&new_name, data)?;
+        Ok(new_name)
+    }
+
+    fn source_tex(&self, tex_index: usize) -> Result<&Tex, AppError> {
+        self.source.get_tex(tex_index)
+            .ok_or_else(|| AppError::new(&format!("TEX0 subfile {} not found in the source container", tex_index)))
+    }
+
+    fn target_tex(target: &mut Container) -> Result<&mut Tex, AppError> {
+        target.get_tex_mut(0)
+            .ok_or_else(|| AppError::new("The target container has no TEX0 subfile to copy textures and palettes into"))
+    }
+}
@@ -0,0 +1,96 @@
+use crate::{error::AppError, subfiles::mdl::model::{material_list::MaterialList, Model}};
+
+// Material bytes, texture name and palette name
+type MaterialKey = (Vec<u8>, Option<String>, Option<String>);
+
+// Merges byte-identical materials that are also paired with the same texture and palette
+#[derive(Debug, Clone)]
+pub struct MaterialDeduplicator {
+    canonical: Vec<usize> // For each material, the first identical one (itself if unique)
+}
+
+impl MaterialDeduplicator {
+    pub fn new(material_list: &MaterialList) -> Result<MaterialDeduplicator, AppError> {
+        let mut keys = Vec::with_capacity(material_list.len());
+        let mut canonical = Vec::with_capacity(material_list.len());
+
+        for index in 0..material_list.len() {
+            let key = Self::material_key(material_list, index)?;
+
+            let first = keys.iter().position(|other| *other == key).unwrap_or(index);
+            canonical.push(first);
+            keys.push(key);
+        }
+
+        Ok(MaterialDeduplicator {
+            canonical
+        })
+    }
+
+    // Pairs of (duplicate, kept) material indices, before removing anything
+    pub fn duplicates(&self) -> Vec<(usize, usize)> {
+        self.canonical.iter()
+            .enumerate()
+            .filter(|&(index, &first)| index != first)
+            .map(|(index, &first)| (index, first))
+            .collect()
+    }
+
+    // Removes the duplicated materials and remaps BindMaterial commands. Returns the number of removed materials.
+    // The model must be rebased afterwards
+    pub fn apply(&self, model: &mut Model) -> Result<usize, AppError> {
+        if model.get_material_list().len() != self.canonical.len() {
+            return Err(AppError::new(&format!("MaterialDeduplicator was built for {} materials, but the model has {}", self.canonical.len(), model.get_material_list().len())));
+        }
+
+        let duplicates = self.duplicates();
+        let new_indices = self.new_indices();
+
+        let new_indices: Vec<Option<usize>> = new_indices.into_iter().map(Some).collect();
+        model.get_render_cmds_list_mut().remap_material_indices(&new_indices)?;
+
+        // From the end, so the pending indices are not shifted
+        let material_list = model.get_material_list_mut();
+        for &(duplicate, _) in duplicates.iter().rev() {
+            material_list.remove_material(duplicate)?;
+        }
+
+        Ok(duplicates.len())
+    }
+
+    // Index of each original material after removing the duplicates
+    fn new_indices(&self) -> Vec<usize> {
+        let mut kept_indices = Vec::with_capacity(self.canonical.len());
+        let mut next_index = 0;
+
+        for (index, &first) in self.canonical.iter().enumerate() {
+            if index == first {
+                kept_indices.push(next_index);
+                next_index += 1;
+            }
+            else {
+                kept_indices.push(usize::MAX); // Resolved below, as the first one always comes before
+            }
+        }
+
+        self.canonical.iter()
+            .map(|&first| kept_indices[first])
+            .collect()
+    }
+
+    fn material_key(material_list: &MaterialList, index: usize) -> Result<MaterialKey, AppError> {
+        let material = material_list.get_material(index).unwrap();
+
+        let bytes = material.to_bytes()?;
+
+        let texture_name = material_list.texture_name_for_material(index)
+            .map(|name| name.to_not_null_string())
+            .transpose()?;
+
+        let palette_name = material_list.palette_name_for_material(index)
+            .map(|name| name.to_not_null_string())
+            .transpose()?;
+
+        Ok((bytes, texture_name, palette_name))
+    }
+}
@@ -0,0 +1,149 @@
+use std::fmt::Display;
+
+use crate::{container::Container, data_structures::name::Name, error::AppError, subfiles::{mdl::model::{material_list::{Material, MaterialList}, Model}, tex::{palette::Palette, texture::Texture}}};
+
+// Resolves the materials of a model to the texture and palette entries they are paired with in the TEX0 subfiles of a container
+#[derive(Debug, Clone)]
+pub struct MaterialResolver<'a> {
+    container: &'a Container,
+    model: &'a Model
+}
+
+impl<'a> MaterialResolver<'a> {
+    pub fn new(container: &'a Container, mdl_index: usize, model_index: usize) -> Result<MaterialResolver<'a>, AppError> {
+        let mdl = container.get_mdl(mdl_index)
+            .ok_or_else(|| AppError::new(&format!("MDL subfile {} not found", mdl_index)))?;
+
+        let model = mdl.get_model(model_index)
+            .ok_or_else(|| AppError::new(&format!("Model {} not found in MDL subfile {}", model_index, mdl_index)))?;
+
+        Ok(MaterialResolver {
+            container,
+            model
+        })
+    }
+
+    pub fn resolve(&self, material_index: usize) -> Result<ResolvedMaterial<'a>, AppError> {
+        let material_list = self.material_list();
+
+        let material = material_list.get_material(material_index)
+            .ok_or_else(|| AppError::new(&format!("Material index out of bounds. Expected 0-{}, got {}", material_list.len() as isize - 1, material_index)))?;
+
+        let name = Self::name_to_string(material_list.get_name(material_index))?
+            .unwrap_or_default();
+
+        let texture = match Self::name_to_string(material_list.texture_name_for_material(material_index))? {
+            Some(texture_name) => Some(self.resolve_texture(texture_name)?),
+            None => None // Untextured material
+        };
+
+        let palette = match Self::name_to_string(material_list.palette_name_for_material(material_index))? {
+            Some(palette_name) => Some(self.resolve_palette(palette_name)?),
+            None => None // Direct color texture or untextured material
+        };
+
+        Ok(ResolvedMaterial {
+            index: material_index,
+            name,
+            material,
+            texture,
+            palette
+        })
+    }
+
+    pub fn resolve_all(&self) -> Result<Vec<ResolvedMaterial<'a>>, AppError> {
+        (0..self.material_list().len())
+            .map(|material_index| self.resolve(material_index))
+            .collect()
+    }
+
+    fn material_list(&self) -> &'a MaterialList {
+        self.model.get_material_list()
+    }
+
+    // The first TEX0 subfile holding a texture with that name is used
+    fn resolve_texture(&self, name: String) -> Result<ResolvedTexture<'a>, AppError> {
+        for (tex_index, tex) in self.container.tex_iter().enumerate() {
+            let texture_list = tex.texture_list();
+
+            if let Some(texture) = texture_list.index_of_name(&name).and_then(|index| texture_list.get_texture(index)) {
+                let params = texture.teximage_params();
+
+                return Ok(ResolvedTexture {
+                    tex_index,
+                    width: texture.width(),
+                    height: texture.height(),
+                    format: params.texture_format(),
+                    color_0_transparent: params.palette_color_0_transparent(),
+                    data_offset: (params.texture_data() as u32) << 3, // Stored in 8-byte units
+                    name,
+                    texture
+                });
+            }
+        }
+
+        Err(AppError::new(&format!("Texture \"{}\" not found in any TEX0 subfile", name)))
+    }
+
+    // The first TEX0 subfile holding a palette with that name is used
+    fn resolve_palette(&self, name: String) -> Result<ResolvedPalette<'a>, AppError> {
+        for (tex_index, tex) in self.container.tex_iter().enumerate() {
+            let palette_list = tex.palette_list();
+
+            if let Some(palette) = palette_list.index_of_name(&name).and_then(|index| palette_list.get_palette(index)) {
+                return Ok(ResolvedPalette {
+                    tex_index,
+                    data_offset: (palette.pltt_base().palette_base() as u32) << 3, // Stored in 8-byte units
+                    name,
+                    palette
+                });
+            }
+        }
+
+        Err(AppError::new(&format!("Palette \"{}\" not found in any TEX0 subfile", name)))
+    }
+
+    fn name_to_string(name: Option<&Name>) -> Result<Option<String>, AppError> {
+        name.map(|name| name.to_not_null_string()).transpose()
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct ResolvedMaterial<'a> {
+    pub index: usize,
+    pub name: String,
+    pub material: &'a Material,
+    pub texture: Option<ResolvedTexture<'a>>,
+    pub palette: Option<ResolvedPalette<'a>>
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolvedTexture<'a> {
+    pub name: String,
+    pub tex_index: usize, // Index of the TEX0 subfile in the container
+    pub texture: &'a Texture,
+    pub width: u16,
+    pub height: u16,
+    pub format: u8,
+    pub color_0_transparent: bool,
+    pub data_offset: u32 // Offset in bytes into the texture data of the TEX0
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolvedPalette<'a> {
+    pub name: String,
+    pub tex_index: usize, // Index of the TEX0 subfile in the container
+    pub palette: &'a Palette,
+    pub data_offset: u32 // Offset in bytes into the palette data of the TEX0
+}
+
+impl Display for ResolvedMaterial<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(texture) = &self.texture {
+            write!(f, "format={}, ", texture.texture.teximage_params().texture_format_name())?;
+        }
+
+        write!(f, "{}", self.material)
+    }
+}
@@ -1,17 +1,45 @@
 use std::collections::HashMap;
 
-use crate::{error::AppError, subfiles::mdl::model::mesh_list::gpu_command_list::{BeginVtxsParams, GpuCommand, MtxRestoreParams, TexCoordParams, Vtx16Params}, util::number::fixed_point::{fixed_1_11_4::Fixed1_11_4, fixed_1_3_12::Fixed1_3_12}};
+use crate::{error::AppError, subfiles::mdl::model::{material_list::{Material, TexImageParams}, mesh_list::gpu_command_list::{BeginVtxsParams, ColorParams, GpuCommand, GpuCommandList, MtxRestoreParams, NormalParams, TexCoordParams, Vtx16Params}, render_command_list::{CalculateSkinningEquationData, RenderCommand, SkinningEquationTerm}}, util::{color::Rgb555, math::matrix::Matrix, number::fixed_point::{fixed_1_0_9::Fixed1_0_9, fixed_1_11_4::Fixed1_11_4, fixed_1_3_12::Fixed1_3_12}}};
 
-use super::models::{primitive::Primitive, vertex::Vertex};
+use super::{models::{primitive::Primitive, vertex::Vertex}, quad_strip::QuadStripifier, texture_atlas::UvRemap, triangle_strip::TriangleStripifier};
+
+// How texcoords are brought near the origin. Texcoords only reach ±2048 texels, while the hardware
+// repeats the texture every size (or every two sizes when mirroring)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UvWrap {
+    None,
+    Repeat,
+    Mirror
+}
+
+// Limits for generate_split_commands. None means no limit
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MeshSplitLimits {
+    pub max_command_bytes: Option<usize>, // Size of the GPU command list of each mesh
+    pub max_polygons: Option<usize> // Triangles and quads of each mesh
+}
 
 #[derive(Debug, Clone)]
 pub struct MeshCommandGenerator<'a> {
     primitives: &'a Vec<Primitive>,
+    vertex_bones: &'a Vec<String>,
     vertex_to_command_bone_mapping: HashMap<usize, usize>,
-    texture_size: (f32, f32)
+    free_stack_slots: Vec<u32>, // Matrix stack slots without a bone, usable for blended matrices
+    skinning: Option<Skinning>,
+    texture_size: (f32, f32),
+    flip_v: bool,
+    uv_wrap: UvWrap,
+    texcoord_matrix: Option<[f32; 6]>, // First two rows of a 3x3 matrix applied to (s, t, 1) in texels
+    uv_remap: Option<UvRemap>,
+    stripify: bool,
+    emit_normals: bool,
+    emit_colors: bool
 }
 
 impl MeshCommandGenerator<'_> {
+    const STACK_SIZE: u32 = 31;
+
     pub fn new<'a>(
         primitives: &'a Vec<Primitive>,
         vertex_bones: &'a Vec<String>,
@@ -20,22 +48,218 @@ impl MeshCommandGenerator<'_> {
     ) -> Result<MeshCommandGenerator<'a>, AppError> {
         let vertex_to_command_bone_mapping = Self::generate_vertex_to_command_bone_mapping(primitives, vertex_bones, command_bones)?;
 
+        let free_stack_slots = (0..Self::STACK_SIZE)
+            .filter(|&slot| command_bones.get(slot as usize).is_none_or(|bone| bone.is_none()))
+            .collect::<Vec<u32>>();
+
         Ok(MeshCommandGenerator {
             primitives,
+            vertex_bones,
             vertex_to_command_bone_mapping,
-            texture_size
+            free_stack_slots,
+            skinning: None,
+            texture_size,
+            flip_v: false,
+            uv_wrap: UvWrap::None,
+            texcoord_matrix: None,
+            uv_remap: None,
+            stripify: true,
+            emit_normals: false,
+            emit_colors: false
         })
     }
 
+    // Normals are only useful when the material is lit, and vertex colors when it is not
+    // (lighting overwrites the vertex color). Texcoords wrap like the material repeats its texture, and
+    // get the inverse of its texture matrix, so the hardware transform gives back the imported UVs
+    pub fn configure_for_material(&mut self, material: &Material) -> Result<(), AppError> {
+        let lit = (0..4).any(|light| material.polygon_attr().light_enabled(light));
+
+        self.emit_normals = lit;
+        self.emit_colors = !lit;
+
+        let params = material.teximage_params();
+        self.uv_wrap = if params.mirror_s() || params.mirror_t() {
+            UvWrap::Mirror
+        } else if params.repeat_s() || params.repeat_t() {
+            UvWrap::Repeat
+        } else {
+            UvWrap::None
+        };
+
+        // Normal and vertex modes generate the texcoords from other data, so there is nothing to compensate
+        if params.texcoords_transform_mode() == TexImageParams::TRANSFORM_MODE_TEXCOORD {
+            self.set_texcoord_matrix(Some(&material.texcoord_matrix()?.inverted()?))?;
+        } else {
+            self.set_texcoord_matrix(None)?;
+        }
+
+        Ok(())
+    }
+
+    // Flip V (v = 1 - v) for UVs with the origin at the bottom. Disabled by default
+    pub fn set_flip_v(&mut self, flip_v: bool) {
+        self.flip_v = flip_v;
+    }
+
+    // UvWrap::None by default
+    pub fn set_uv_wrap(&mut self, uv_wrap: UvWrap) {
+        self.uv_wrap = uv_wrap;
+    }
+
+    // 3x3 matrix applied to (s, t, 1) in texels before the texcoords are emitted. None by default
+    pub fn set_texcoord_matrix(&mut self, matrix: Option<&Matrix>) -> Result<(), AppError> {
+        self.texcoord_matrix = match matrix {
+            Some(matrix) => {
+                if matrix.width() != 3 || matrix.height() != 3 {
+                    return Err(AppError::new(&format!("Texcoord matrix must be 3x3, got {}x{}", matrix.height(), matrix.width())));
+                }
+
+                Some([
+                    matrix.get(0, 0)?, matrix.get(0, 1)?, matrix.get(0, 2)?,
+                    matrix.get(1, 0)?, matrix.get(1, 1)?, matrix.get(1, 2)?
+                ])
+            },
+            None => None
+        };
+
+        Ok(())
+    }
+
+    // Remaps the UVs (after flipping and wrapping) to a region of an atlas. texture_size must then be the atlas size. None by default
+    pub fn set_uv_remap(&mut self, uv_remap: Option<UvRemap>) {
+        self.uv_remap = uv_remap;
+    }
+
+    // Emit a Normal command for every vertex with a normal. Disabled by default
+    pub fn set_emit_normals(&mut self, emit_normals: bool) {
+        self.emit_normals = emit_normals;
+    }
+
+    // Emit a Color command for every vertex with a color. Disabled by default
+    pub fn set_emit_colors(&mut self, emit_colors: bool) {
+        self.emit_colors = emit_colors;
+    }
+
+    // Emit TRIANGLE_STRIP/QUAD_STRIP blocks for single-bonned polygons when they save vertices. Enabled by default
+    pub fn set_stripify(&mut self, stripify: bool) {
+        self.stripify = stripify;
+    }
+
+    // Enables blended vertices. Each weight combination gets a free matrix stack slot, filled by the
+    // CalculateSkinningEquation commands from skinning_commands. Blended vertices are expected in model space.
+    // node_bones are the model bone names, in BoneList order (the inverse bind matrices share it).
+    // Without skinning, blended vertices are attached to their dominant bone
+    pub fn enable_skinning(&mut self, node_bones: &[String]) -> Result<(), AppError> {
+        let mut skinning = Skinning {
+            equations: Vec::new(),
+            slots: HashMap::new()
+        };
+
+        let mut free_stack_slots = self.free_stack_slots.iter();
+        for vertex in self.primitives.iter().flat_map(|primitive| primitive.vertices().iter()) {
+            let key = quantize_weights(vertex);
+            if key.len() < 2 || skinning.slots.contains_key(&key) {
+                continue;
+            }
+
+            let store_index = *free_stack_slots.next()
+                .ok_or_else(|| AppError::new(&format!("Not enough free matrix stack slots for the blended vertices. Only {} slots are free", self.free_stack_slots.len())))?;
+
+            let mut terms = Vec::with_capacity(key.len());
+            for &(vertex_bone_index, weight) in key.iter() {
+                let bone_name = &self.vertex_bones[vertex_bone_index as usize];
+                let inv_bind_index = node_bones.iter()
+                    .position(|node_bone| node_bone == bone_name)
+                    .ok_or_else(|| AppError::new(&format!("Bone '{}' not found in model bones.", bone_name)))?;
+
+                terms.push(SkinningEquationTerm {
+                    matrix_index: self.get_vertex_to_cmd_bone_mapped_index(vertex_bone_index as usize)? as u8,
+                    inv_bind_index: inv_bind_index as u8,
+                    weight
+                });
+            }
+
+            skinning.equations.push(CalculateSkinningEquationData {
+                store_index: store_index as u8,
+                num_terms: terms.len() as u8,
+                terms
+            });
+            skinning.slots.insert(key, store_index);
+        }
+
+        self.skinning = Some(skinning);
+
+        Ok(())
+    }
+
+    // Render commands that compute the blended matrices. They must run before the mesh is drawn
+    pub fn skinning_commands(&self) -> Vec<RenderCommand> {
+        match &self.skinning {
+            Some(skinning) => skinning.equations.iter()
+                .map(|equation| RenderCommand::CalculateSkinningEquation(Box::new(equation.clone())))
+                .collect(),
+            None => Vec::new()
+        }
+    }
+
     pub fn generate_commands(&self) -> Result<Vec<GpuCommand>, AppError> {
         let command_groups = self.generate_command_groups()?;
+
+        self.generate_group_commands(&command_groups)
+    }
+
+    // Same as generate_commands, but splits the polygons into several command lists that stay within the limits.
+    // Every list is self-contained (it loads its own matrices), so each one can go to its own mesh
+    pub fn generate_split_commands(&self, limits: MeshSplitLimits) -> Result<Vec<GpuCommandList>, AppError> {
+        if limits.max_polygons == Some(0) {
+            return Err(AppError::new("Mesh split polygon limit must be at least 1."));
+        }
+
+        let command_groups = self.generate_command_groups()?;
+        let polygons = command_groups.into_polygons();
+
+        let chunk_size = limits.max_polygons.unwrap_or(polygons.len()).max(1);
+        let mut command_lists = Vec::new();
+        for chunk in polygons.chunks(chunk_size) {
+            self.generate_split_chunk(chunk, limits.max_command_bytes, &mut command_lists)?;
+        }
+
+        Ok(command_lists)
+    }
+
+    // Halves the chunk until its commands fit in max_command_bytes
+    fn generate_split_chunk(&self, polygons: &[GroupPolygon], max_command_bytes: Option<usize>, command_lists: &mut Vec<GpuCommandList>) -> Result<(), AppError> {
+        let mut command_list = GpuCommandList::new();
+        command_list.extend(self.generate_group_commands(&CommandGroups::from_polygons(polygons))?)?;
+
+        let size = command_list.size();
+        match max_command_bytes {
+            Some(max_command_bytes) if size > max_command_bytes => {
+                if polygons.len() == 1 {
+                    return Err(AppError::new(&format!("A single polygon needs {} bytes of commands, over the {} bytes limit.", size, max_command_bytes)));
+                }
+
+                let (first, second) = polygons.split_at(polygons.len() / 2);
+                self.generate_split_chunk(first, Some(max_command_bytes), command_lists)?;
+                self.generate_split_chunk(second, Some(max_command_bytes), command_lists)?;
+            },
+            _ => {
+                command_lists.push(command_list);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn generate_group_commands(&self, command_groups: &CommandGroups) -> Result<Vec<GpuCommand>, AppError> {
         let mut commands = Vec::new();
 
-        // Generate commands for single-bonned triangles
-        self.generate_single_bonned_triangle_commands(&command_groups.single_bonned_triangles, &mut commands)?;
+        // Generate commands for single-bonned polygons
+        let last_matrix_id = self.generate_single_bonned_commands(&command_groups.single_bonned, &mut commands)?;
 
-        // Generate commands for multi-bonned triangles
-        self.generate_multi_bonned_triangle_commands(&command_groups.multi_bonned_triangles, &mut commands)?;
+        // Generate commands for multi-bonned polygons, starting from the matrix left by the single-bonned ones
+        self.generate_multi_bonned_commands(&command_groups.multi_bonned, last_matrix_id, &mut commands)?;
 
         Ok(commands)
     }
@@ -47,11 +271,32 @@ impl MeshCommandGenerator<'_> {
         }
     }
 
+    // Matrix stack slot holding the matrix of the vertex
+    fn vertex_matrix_index(&self, vertex: &Vertex) -> Result<u32, AppError> {
+        if let Some(skinning) = &self.skinning {
+            let key = quantize_weights(vertex);
+            if let Some(&slot) = skinning.slots.get(&key) {
+                return Ok(slot);
+            }
+
+            // Weights that collapse to a single bone once quantized
+            if key.len() == 1 {
+                return self.get_vertex_to_cmd_bone_mapped_index(key[0].0 as usize);
+            }
+        }
+
+        self.get_vertex_to_cmd_bone_mapped_index(vertex.bone_id as usize)
+    }
+
     fn generate_vertex_to_command_bone_mapping(primitives: &Vec<Primitive>, vertex_bones: &Vec<String>, command_bones: &Vec<Option<String>>) -> Result<HashMap<usize, usize>, AppError> {
         let mut vertex_bone_is_used = vec![false; vertex_bones.len()];
         for primitive in primitives {
             for vertex in primitive.vertices().iter() {
                 vertex_bone_is_used[vertex.bone_id as usize] = true;
+
+                for bone_weight in vertex.weights.iter() {
+                    vertex_bone_is_used[bone_weight.bone_id as usize] = true;
+                }
             }
         }
 
@@ -85,123 +330,508 @@ impl MeshCommandGenerator<'_> {
                     }
 
                     for i in (0..indices.len()).step_by(3) {
-                        let v1 = vertices[indices[i] as usize].clone();
-                        let v2 = vertices[indices[i + 1] as usize].clone();
-                        let v3 = vertices[indices[i + 2] as usize].clone();
-                        let triangle = PolygonTriangle::new(v1, v2, v3);
+                        let mut polygon = [indices[i], indices[i + 1], indices[i + 2]].map(|index| vertices[index as usize].clone());
+                        self.prepare_tex_coords(&mut polygon);
+                        let [v1, v2, v3] = polygon;
+                        let matrix_ids = [self.vertex_matrix_index(&v1)?, self.vertex_matrix_index(&v2)?, self.vertex_matrix_index(&v3)?];
+                        let triangle = PolygonTriangle::new(v1, v2, v3, matrix_ids);
                         command_groups.add_triangle(triangle);
                     }
                 },
+                Primitive::Quad { vertices, indices } => {
+                    if indices.len() % 4 != 0 {
+                        return Err(AppError::new("Indices length must be a multiple of 4 for quads."));
+                    }
+
+                    for i in (0..indices.len()).step_by(4) {
+                        let mut polygon = [indices[i], indices[i + 1], indices[i + 2], indices[i + 3]].map(|index| vertices[index as usize].clone());
+                        self.prepare_tex_coords(&mut polygon);
+                        let [v1, v2, v3, v4] = polygon;
+                        let matrix_ids = [self.vertex_matrix_index(&v1)?, self.vertex_matrix_index(&v2)?, self.vertex_matrix_index(&v3)?, self.vertex_matrix_index(&v4)?];
+                        let quad = PolygonQuad::new(v1, v2, v3, v4, matrix_ids);
+                        command_groups.add_quad(quad);
+                    }
+                }
             }
         }
 
         Ok(command_groups)
     }
 
-    fn generate_single_bonned_triangle_commands(&self, triangles: &HashMap<usize, Vec<PolygonTriangle>>, commands: &mut Vec<GpuCommand>) -> Result<(), AppError> {
-        for (&bone_id, triangles) in triangles {
-            if triangles.is_empty() {
+    // Returns the matrix loaded at the end, if any
+    fn generate_single_bonned_commands(&self, groups: &HashMap<u32, PolygonGroup>, commands: &mut Vec<GpuCommand>) -> Result<Option<u32>, AppError> {
+        let mut matrix_ids = groups.keys().copied().collect::<Vec<u32>>();
+        matrix_ids.sort();
+
+        let mut last_matrix_id = None;
+        for matrix_id in matrix_ids {
+            let group = &groups[&matrix_id];
+            if group.is_empty() {
                 continue;
             }
 
-            let cmd_bone_id = self.get_vertex_to_cmd_bone_mapped_index(bone_id)?;
+            // Loaded once, every block of this matrix uses it
+            commands.push(GpuCommand::MtxRestore(Box::new(MtxRestoreParams { index: matrix_id })));
+            last_matrix_id = Some(matrix_id);
 
-            commands.push(GpuCommand::BeginVtxs(Box::new(BeginVtxsParams { primitive_type: BeginVtxsParams::TRIANGLE })));
-            commands.push(GpuCommand::MtxRestore(Box::new(MtxRestoreParams { index: cmd_bone_id })));
-            for triangle in triangles {
-                let current_triangle_vertices = [&triangle.v1, &triangle.v2, &triangle.v3];
+            self.generate_triangle_commands(&group.triangles, commands);
+            self.generate_quad_commands(&group.quads, commands);
+        }
 
-                for vertex in current_triangle_vertices {
-                    let s = Fixed1_11_4::from_f32(vertex.tex_coord.u * self.texture_size.0);
-                    let t = Fixed1_11_4::from_f32(vertex.tex_coord.v * self.texture_size.1);
-                    commands.push(GpuCommand::TexCoord(Box::new(TexCoordParams { s, t })));
-    
-                    let x = Fixed1_3_12::from(vertex.position.x);
-                    let y = Fixed1_3_12::from(vertex.position.y);
-                    let z = Fixed1_3_12::from(vertex.position.z);
-                    commands.push(GpuCommand::Vtx16(Box::new(Vtx16Params { x, y, z })));
-                }
+        Ok(last_matrix_id)
+    }
+
+    // Polygons must share the same bone, which has to be already loaded
+    fn generate_triangle_commands(&self, triangles: &[PolygonTriangle], commands: &mut Vec<GpuCommand>) {
+        if triangles.is_empty() {
+            return;
+        }
+
+        if !self.stripify {
+            let vertices = triangles.iter().flat_map(|triangle| triangle.vertices()).collect::<Vec<&Vertex>>();
+            self.push_vertex_block(BeginVtxsParams::TRIANGLE, &vertices, commands);
+            return;
+        }
+
+        let (vertices, indexed_triangles) = self.index_polygons(triangles.iter().map(|triangle| triangle.vertices()));
+        let result = TriangleStripifier::new(indexed_triangles).stripify();
+
+        for strip in result.strips.iter() {
+            let strip_vertices = strip.iter().map(|&index| vertices[index as usize]).collect::<Vec<&Vertex>>();
+            self.push_vertex_block(BeginVtxsParams::TRIANGLE_STRIP, &strip_vertices, commands);
+        }
+
+        let remaining_vertices = result.triangles.iter()
+            .flatten()
+            .map(|&index| vertices[index as usize])
+            .collect::<Vec<&Vertex>>();
+        self.push_vertex_block(BeginVtxsParams::TRIANGLE, &remaining_vertices, commands);
+    }
+
+    // Polygons must share the same bone, which has to be already loaded
+    fn generate_quad_commands(&self, quads: &[PolygonQuad], commands: &mut Vec<GpuCommand>) {
+        if quads.is_empty() {
+            return;
+        }
+
+        if !self.stripify {
+            let vertices = quads.iter().flat_map(|quad| quad.vertices()).collect::<Vec<&Vertex>>();
+            self.push_vertex_block(BeginVtxsParams::QUAD, &vertices, commands);
+            return;
+        }
+
+        let (vertices, indexed_quads) = self.index_polygons(quads.iter().map(|quad| quad.vertices()));
+        let result = QuadStripifier::new(indexed_quads).stripify();
+
+        for strip in result.strips.iter() {
+            let strip_vertices = strip.iter().map(|&index| vertices[index as usize]).collect::<Vec<&Vertex>>();
+            self.push_vertex_block(BeginVtxsParams::QUAD_STRIP, &strip_vertices, commands);
+        }
+
+        let remaining_vertices = result.quads.iter()
+            .flatten()
+            .map(|&index| vertices[index as usize])
+            .collect::<Vec<&Vertex>>();
+        self.push_vertex_block(BeginVtxsParams::QUAD, &remaining_vertices, commands);
+    }
+
+    // Merges vertices by the values that end up in the commands, so stripifiers can find shared edges
+    fn index_polygons<'v, const N: usize>(&self, polygons: impl Iterator<Item = [&'v Vertex; N]>) -> (Vec<&'v Vertex>, Vec<[u32; N]>) {
+        let mut vertices: Vec<&Vertex> = Vec::new();
+        let mut vertex_indices: HashMap<VertexKey, u32> = HashMap::new();
+        let mut indexed_polygons = Vec::new();
+
+        for polygon in polygons {
+            let mut indexed_polygon = [0u32; N];
+            for (i, vertex) in polygon.into_iter().enumerate() {
+                indexed_polygon[i] = *vertex_indices.entry(self.vertex_key(vertex)).or_insert_with(|| {
+                    vertices.push(vertex);
+                    (vertices.len() - 1) as u32
+                });
             }
-            commands.push(GpuCommand::EndVtxs);
+
+            indexed_polygons.push(indexed_polygon);
+        }
+
+        (vertices, indexed_polygons)
+    }
+
+    fn push_vertex_block(&self, primitive_type: u8, vertices: &[&Vertex], commands: &mut Vec<GpuCommand>) {
+        if vertices.is_empty() {
+            return;
+        }
+
+        commands.push(GpuCommand::BeginVtxs(Box::new(BeginVtxsParams { primitive_type })));
+        for vertex in vertices {
+            self.push_vertex_commands(vertex, commands);
+        }
+        commands.push(GpuCommand::EndVtxs);
+    }
+
+    fn push_vertex_commands(&self, vertex: &Vertex, commands: &mut Vec<GpuCommand>) {
+        if let Some(color) = self.quantize_color(vertex) {
+            commands.push(GpuCommand::Color(Box::new(ColorParams { r: color.r, g: color.g, b: color.b })));
+        }
+
+        let (s, t) = self.quantize_tex_coord(vertex);
+        commands.push(GpuCommand::TexCoord(Box::new(TexCoordParams { s, t })));
+
+        // After TexCoord, so normal based texture coordinate generation sees the right texture coordinates
+        if let Some((x, y, z)) = self.quantize_normal(vertex) {
+            commands.push(GpuCommand::Normal(Box::new(NormalParams { x, y, z })));
         }
 
+        let x = Fixed1_3_12::from(vertex.position.x);
+        let y = Fixed1_3_12::from(vertex.position.y);
+        let z = Fixed1_3_12::from(vertex.position.z);
+        commands.push(GpuCommand::Vtx16(Box::new(Vtx16Params { x, y, z })));
+    }
+
+    // Flips and wraps the texcoords of a polygon. Wrapping shifts the whole polygon by whole periods,
+    // so polygons crossing a texture border keep their shape
+    fn prepare_tex_coords(&self, polygon: &mut [Vertex]) {
+        if self.flip_v {
+            polygon.iter_mut().for_each(|vertex| vertex.tex_coord.v = 1.0 - vertex.tex_coord.v);
+        }
+
+        let period = match self.uv_wrap {
+            UvWrap::None => return,
+            UvWrap::Repeat => 1.0,
+            UvWrap::Mirror => 2.0 // Mirrored textures repeat every two sizes
+        };
+
+        let min_u = polygon.iter().map(|vertex| vertex.tex_coord.u).fold(f32::INFINITY, f32::min);
+        let min_v = polygon.iter().map(|vertex| vertex.tex_coord.v).fold(f32::INFINITY, f32::min);
+        let shift_u = (min_u / period).floor() * period;
+        let shift_v = (min_v / period).floor() * period;
+
+        for vertex in polygon.iter_mut() {
+            vertex.tex_coord.u -= shift_u;
+            vertex.tex_coord.v -= shift_v;
+        }
+    }
+
+    fn quantize_tex_coord(&self, vertex: &Vertex) -> (Fixed1_11_4, Fixed1_11_4) {
+        let (u, v) = match self.uv_remap {
+            Some(uv_remap) => uv_remap.apply(vertex.tex_coord.u, vertex.tex_coord.v),
+            None => (vertex.tex_coord.u, vertex.tex_coord.v)
+        };
+
+        let mut s = u * self.texture_size.0;
+        let mut t = v * self.texture_size.1;
+
+        if let Some(m) = self.texcoord_matrix {
+            (s, t) = (m[0] * s + m[1] * t + m[2], m[3] * s + m[4] * t + m[5]);
+        }
+
+        (Fixed1_11_4::from_f32(s), Fixed1_11_4::from_f32(t))
+    }
+
+    fn quantize_normal(&self, vertex: &Vertex) -> Option<(Fixed1_0_9, Fixed1_0_9, Fixed1_0_9)> {
+        if !self.emit_normals {
+            return None;
+        }
+
+        vertex.normal.as_ref().map(|normal| {
+            let normal = normal.normalized();
+            (Fixed1_0_9::from_f32(normal.x), Fixed1_0_9::from_f32(normal.y), Fixed1_0_9::from_f32(normal.z))
+        })
+    }
+
+    fn quantize_color(&self, vertex: &Vertex) -> Option<Rgb555> {
+        if !self.emit_colors {
+            return None;
+        }
+
+        vertex.color.as_ref().map(|color| Rgb555::from_f32([color.r, color.g, color.b]))
+    }
+
+    fn vertex_key(&self, vertex: &Vertex) -> VertexKey {
+        let (s, t) = self.quantize_tex_coord(vertex);
+        let normal = self.quantize_normal(vertex)
+            .map(|(x, y, z)| [x.to_i16(), y.to_i16(), z.to_i16()]);
+
+        VertexKey {
+            position: [
+                Fixed1_3_12::from(vertex.position.x).to_i16(),
+                Fixed1_3_12::from(vertex.position.y).to_i16(),
+                Fixed1_3_12::from(vertex.position.z).to_i16()
+            ],
+            tex_coord: [s.to_i16(), t.to_i16()],
+            normal,
+            color: self.quantize_color(vertex).map(|color| color.to_u16()),
+            bone_id: vertex.bone_id
+        }
+    }
+
+    fn generate_multi_bonned_commands(&self, group: &PolygonGroup, mut prev_bone_id: Option<u32>, commands: &mut Vec<GpuCommand>) -> Result<(), AppError> {
+        let triangles = group.triangles.iter()
+            .map(|triangle| (triangle.vertices(), triangle.matrix_ids))
+            .collect::<Vec<([&Vertex; 3], [u32; 3])>>();
+        let triangle_vertices = cluster_by_matrix(&triangles, prev_bone_id);
+        self.push_multi_bonned_vertex_block(BeginVtxsParams::TRIANGLE, &triangle_vertices, &mut prev_bone_id, commands);
+
+        let quads = group.quads.iter()
+            .map(|quad| (quad.vertices(), quad.matrix_ids))
+            .collect::<Vec<([&Vertex; 4], [u32; 4])>>();
+        let quad_vertices = cluster_by_matrix(&quads, prev_bone_id);
+        self.push_multi_bonned_vertex_block(BeginVtxsParams::QUAD, &quad_vertices, &mut prev_bone_id, commands);
+
         Ok(())
     }
 
-    fn generate_multi_bonned_triangle_commands(&self, triangles: &Vec<PolygonTriangle>, commands: &mut Vec<GpuCommand>) -> Result<(), AppError> {
-        if triangles.is_empty() {
-            return Ok(());
+    // Loads the matrix of each vertex when it changes
+    fn push_multi_bonned_vertex_block(&self, primitive_type: u8, vertices: &[(&Vertex, u32)], prev_bone_id: &mut Option<u32>, commands: &mut Vec<GpuCommand>) {
+        if vertices.is_empty() {
+            return;
         }
 
-        let mut prev_bone_id = self.get_vertex_to_cmd_bone_mapped_index(triangles[0].v1.bone_id as usize)?;
+        commands.push(GpuCommand::BeginVtxs(Box::new(BeginVtxsParams { primitive_type })));
+        for &(vertex, current_bone_id) in vertices {
+            if *prev_bone_id != Some(current_bone_id) {
+                commands.push(GpuCommand::MtxRestore(Box::new(MtxRestoreParams { index: current_bone_id })));
+                *prev_bone_id = Some(current_bone_id);
+            }
+
+            self.push_vertex_commands(vertex, commands);
+        }
+        commands.push(GpuCommand::EndVtxs);
+    }
+}
 
-        commands.push(GpuCommand::BeginVtxs(Box::new(BeginVtxsParams { primitive_type: BeginVtxsParams::TRIANGLE })));
-        commands.push(GpuCommand::MtxRestore(Box::new(MtxRestoreParams { index: prev_bone_id })));
-        for triangle in triangles {
-            let current_triangle_vertices = [&triangle.v1, &triangle.v2, &triangle.v3];
-            for vertex in current_triangle_vertices {
-                let current_bone_id = self.get_vertex_to_cmd_bone_mapped_index(vertex.bone_id as usize)?;
+// Greedily orders the polygons so each one starts with the matrix the previous one ended with, and rotates
+// them to minimize the matrix switches inside. Rotating a polygon keeps its winding
+fn cluster_by_matrix<'v, const N: usize>(polygons: &[([&'v Vertex; N], [u32; N])], first_matrix_id: Option<u32>) -> Vec<(&'v Vertex, u32)> {
+    let mut polygons_by_matrix: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (i, (_, matrix_ids)) in polygons.iter().enumerate().rev() {
+        for &matrix_id in matrix_ids.iter() {
+            let matrix_polygons = polygons_by_matrix.entry(matrix_id).or_default();
+            if matrix_polygons.last() != Some(&i) {
+                matrix_polygons.push(i);
+            }
+        }
+    }
 
-                if current_bone_id != prev_bone_id {
-                    commands.push(GpuCommand::MtxRestore(Box::new(MtxRestoreParams { index: current_bone_id })));
-                    prev_bone_id = current_bone_id;
+    let mut used = vec![false; polygons.len()];
+    let mut next_unused = 0;
+    let mut current_matrix_id = first_matrix_id;
+    let mut vertices = Vec::with_capacity(polygons.len() * N);
+
+    for _ in 0..polygons.len() {
+        // Next polygon using the current matrix, in their original order (the lists are reversed)
+        let sharing = current_matrix_id
+            .and_then(|matrix_id| polygons_by_matrix.get_mut(&matrix_id))
+            .and_then(|matrix_polygons| {
+                while let Some(&i) = matrix_polygons.last() {
+                    if !used[i] {
+                        return Some(i);
+                    }
+                    matrix_polygons.pop();
+                }
+                None
+            });
+
+        let index = match sharing {
+            Some(i) => i,
+            None => {
+                while used[next_unused] {
+                    next_unused += 1;
                 }
-    
-                let s = Fixed1_11_4::from_f32(vertex.tex_coord.u * self.texture_size.0);
-                let t = Fixed1_11_4::from_f32(vertex.tex_coord.v * self.texture_size.1);
-                commands.push(GpuCommand::TexCoord(Box::new(TexCoordParams { s, t })));
-    
-                let x = Fixed1_3_12::from(vertex.position.x);
-                let y = Fixed1_3_12::from(vertex.position.y);
-                let z = Fixed1_3_12::from(vertex.position.z);
-                commands.push(GpuCommand::Vtx16(Box::new(Vtx16Params { x, y, z })));
+                next_unused
             }
+        };
+        used[index] = true;
+
+        let (polygon, matrix_ids) = &polygons[index];
+        let rotation = (0..N)
+            .min_by_key(|&rotation| matrix_switches(matrix_ids, rotation, current_matrix_id))
+            .unwrap_or(0);
+
+        for k in 0..N {
+            let j = (rotation + k) % N;
+            vertices.push((polygon[j], matrix_ids[j]));
         }
-        commands.push(GpuCommand::EndVtxs);
-        
-        Ok(())
+
+        current_matrix_id = Some(matrix_ids[(rotation + N - 1) % N]);
+    }
+
+    vertices
+}
+
+fn matrix_switches<const N: usize>(matrix_ids: &[u32; N], rotation: usize, prev_matrix_id: Option<u32>) -> usize {
+    let mut prev_matrix_id = prev_matrix_id;
+    let mut switches = 0;
+    for k in 0..N {
+        let matrix_id = matrix_ids[(rotation + k) % N];
+        if prev_matrix_id != Some(matrix_id) {
+            switches += 1;
+            prev_matrix_id = Some(matrix_id);
+        }
+    }
+
+    switches
+}
+
+// Weights in 1/256 units, largest first. The largest absorbs the rounding error so they add up to 256
+fn quantize_weights(vertex: &Vertex) -> Vec<(u32, u8)> {
+    let mut terms = vertex.weights.iter()
+        .map(|bone_weight| (bone_weight.bone_id, (bone_weight.weight * 256.0).round() as u32))
+        .filter(|&(_, weight)| weight > 0)
+        .collect::<Vec<(u32, u32)>>();
+
+    terms.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    if terms.len() > 1 {
+        let rest = terms[1..].iter().map(|&(_, weight)| weight).sum::<u32>();
+        terms[0].1 = 256u32.saturating_sub(rest);
     }
+
+    terms.into_iter()
+        .map(|(bone_id, weight)| (bone_id, weight.min(u8::MAX as u32) as u8))
+        .collect()
 }
 
+#[derive(Debug, Clone)]
+struct Skinning {
+    equations: Vec<CalculateSkinningEquationData>,
+    slots: HashMap<Vec<(u32, u8)>, u32> // Quantized weights -> matrix stack slot
+}
+
+// Quantized vertex values, as written in the commands
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct VertexKey {
+    position: [i16; 3],
+    tex_coord: [i16; 2],
+    normal: Option<[i16; 3]>,
+    color: Option<u16>,
+    bone_id: u32
+}
+
+#[derive(Clone)]
 struct PolygonTriangle {
     v1: Vertex,
     v2: Vertex,
-    v3: Vertex
+    v3: Vertex,
+    matrix_ids: [u32; 3] // Matrix stack slot of each vertex
 }
 
 impl PolygonTriangle {
-    pub fn new(v1: Vertex, v2: Vertex, v3: Vertex) -> Self {
-        PolygonTriangle { v1, v2, v3 }
+    pub fn new(v1: Vertex, v2: Vertex, v3: Vertex, matrix_ids: [u32; 3]) -> Self {
+        PolygonTriangle { v1, v2, v3, matrix_ids }
+    }
+
+    pub fn vertices(&self) -> [&Vertex; 3] {
+        [&self.v1, &self.v2, &self.v3]
     }
 
     pub fn is_single_bonned(&self) -> bool {
-        self.v1.bone_id == self.v2.bone_id && self.v1.bone_id == self.v3.bone_id
+        self.matrix_ids.iter().all(|&matrix_id| matrix_id == self.matrix_ids[0])
+    }
+}
+
+#[derive(Clone)]
+struct PolygonQuad {
+    v1: Vertex,
+    v2: Vertex,
+    v3: Vertex,
+    v4: Vertex,
+    matrix_ids: [u32; 4] // Matrix stack slot of each vertex
+}
+
+impl PolygonQuad {
+    pub fn new(v1: Vertex, v2: Vertex, v3: Vertex, v4: Vertex, matrix_ids: [u32; 4]) -> Self {
+        PolygonQuad { v1, v2, v3, v4, matrix_ids }
+    }
+
+    pub fn vertices(&self) -> [&Vertex; 4] {
+        [&self.v1, &self.v2, &self.v3, &self.v4]
+    }
+
+    pub fn is_single_bonned(&self) -> bool {
+        self.matrix_ids.iter().all(|&matrix_id| matrix_id == self.matrix_ids[0])
+    }
+}
+
+enum GroupPolygon {
+    Triangle(PolygonTriangle),
+    Quad(PolygonQuad)
+}
+
+#[derive(Default)]
+struct PolygonGroup {
+    triangles: Vec<PolygonTriangle>,
+    quads: Vec<PolygonQuad>
+}
+
+impl PolygonGroup {
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_empty() && self.quads.is_empty()
     }
 }
 
 struct CommandGroups {
-    single_bonned_triangles: HashMap<usize, Vec<PolygonTriangle>>,
-    multi_bonned_triangles: Vec<PolygonTriangle>,
+    single_bonned: HashMap<u32, PolygonGroup>, // Matrix stack slot -> polygons
+    multi_bonned: PolygonGroup,
 }
 
 impl CommandGroups {
     pub fn new() -> Self {
         CommandGroups {
-            single_bonned_triangles: HashMap::new(),
-            multi_bonned_triangles: Vec::new(),
+            single_bonned: HashMap::new(),
+            multi_bonned: PolygonGroup::default(),
         }
     }
 
     pub fn add_triangle(&mut self, triangle: PolygonTriangle) {
         if triangle.is_single_bonned() {
-            let bone_id = triangle.v1.bone_id as usize;
-            self.single_bonned_triangles
-                .entry(bone_id)
+            self.single_bonned
+                .entry(triangle.matrix_ids[0])
                 .or_default()
+                .triangles
                 .push(triangle);
         }
         else {
-            self.multi_bonned_triangles.push(triangle);
+            self.multi_bonned.triangles.push(triangle);
+        }
+    }
+
+    pub fn from_polygons(polygons: &[GroupPolygon]) -> Self {
+        let mut command_groups = CommandGroups::new();
+        for polygon in polygons {
+            match polygon {
+                GroupPolygon::Triangle(triangle) => command_groups.add_triangle(triangle.clone()),
+                GroupPolygon::Quad(quad) => command_groups.add_quad(quad.clone())
+            }
+        }
+
+        command_groups
+    }
+
+    // Polygons in command order, so splitting them keeps polygons of the same matrix together
+    pub fn into_polygons(self) -> Vec<GroupPolygon> {
+        let mut single_bonned = self.single_bonned.into_iter().collect::<Vec<(u32, PolygonGroup)>>();
+        single_bonned.sort_by_key(|(matrix_id, _)| *matrix_id);
+
+        single_bonned.into_iter()
+            .map(|(_, group)| group)
+            .chain(std::iter::once(self.multi_bonned))
+            .flat_map(|group| {
+                group.triangles.into_iter().map(GroupPolygon::Triangle)
+                    .chain(group.quads.into_iter().map(GroupPolygon::Quad))
+            })
+            .collect()
+    }
+
+    pub fn add_quad(&mut self, quad: PolygonQuad) {
+        if quad.is_single_bonned() {
+            self.single_bonned
+                .entry(quad.matrix_ids[0])
+                .or_default()
+                .quads
+                .push(quad);
+        }
+        else {
+            self.multi_bonned.quads.push(quad);
         }
     }
 }
@@ -1,2 +1,20 @@
 pub mod models;
 pub mod mesh_command_gen;
+pub mod skeleton_import;
+pub mod bone_retarget;
+pub mod material_resolve;
+pub mod material_dedup;
+pub mod material_copy;
+pub mod triangle_strip;
+pub mod quad_strip;
+pub mod render_cmd_gen;
+pub mod texture_decode;
+pub mod texture_encode;
+pub mod texture_import;
+pub mod texture_export;
+pub mod texture_dedup;
+pub mod palette_merge;
+pub mod texture_resize;
+pub mod texture_atlas;
+pub mod pattern_preview;
+pub mod texture_depth;
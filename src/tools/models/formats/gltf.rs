@@ -1,16 +1,22 @@
-use crate::{error::AppError, tools::models::{primitive::Primitive, vertex::{Position, TexCoord, Vertex}}};
+use std::collections::HashMap;
+
+use crate::{error::AppError, tools::models::{primitive::Primitive, skeleton::{Joint, Skeleton}, vertex::{BoneWeight, Color, Normal, Position, TexCoord, Vertex}}, util::math::matrix::Matrix};
 
 #[derive(Debug, Clone)]
 pub struct Gltf {
-    meshes: Vec<Mesh>
+    meshes: Vec<Mesh>,
+    skeleton: Option<Skeleton>
 }
 
 impl Gltf {
+    const MIN_WEIGHT: f32 = 1e-4;
+
     pub fn open(path: &str) -> Result<Gltf, AppError> {
         let (document, buffers, _images) = gltf::import(path)
             .map_err(|err| AppError::new(&err.to_string()))?;
 
         let mut meshes: Vec<Mesh> = Vec::new();
+        let mut skeleton = None;
 
         for node in document.nodes() {
             if let Some(mesh) = node.mesh() {
@@ -35,6 +41,12 @@ impl Gltf {
                             .map(|uvs| uvs.into_f32().collect())
                             .unwrap_or(vec![[0.0, 0.0]; positions.len()]);
 
+                        let normals = reader.read_normals()
+                            .map(|normals| normals.collect::<Vec<[f32; 3]>>());
+
+                        let colors = reader.read_colors(0)
+                            .map(|colors| colors.into_rgb_f32().collect::<Vec<[f32; 3]>>());
+
 
                         let joint_indices = if let Some(joints) = reader.read_joints(0) {
                             joints.into_u16().collect::<Vec<[u16; 4]>>()
@@ -63,13 +75,27 @@ impl Gltf {
                             let weights = joint_weights[i];
                             let joints = joint_indices[i];
 
-                            let bone_index_in_vertex = weights.iter()
-                                .position(|&weight| weight == 1.0)
-                                .expect("At least one weight should be 1.0");
+                            let mut bone_weights = weights.iter()
+                                .zip(joints.iter())
+                                .filter(|(&weight, _)| weight > Self::MIN_WEIGHT)
+                                .map(|(&weight, &joint)| BoneWeight { bone_id: joint as u32, weight })
+                                .collect::<Vec<BoneWeight>>();
+
+                            if bone_weights.is_empty() {
+                                return Err(AppError::new(&format!("Vertex {} has no bone weights", i)));
+                            }
+
+                            bone_weights.sort_by(|a, b| b.weight.total_cmp(&a.weight));
+                            let joint_index = bone_weights[0].bone_id as usize;
 
-                            let joint_index = joints[bone_index_in_vertex] as usize;
+                            // Weights are normalized, vertices with a single bone are left rigid
+                            let total_weight = bone_weights.iter().map(|bone_weight| bone_weight.weight).sum::<f32>();
+                            bone_weights.iter_mut().for_each(|bone_weight| bone_weight.weight /= total_weight);
+                            if bone_weights.len() == 1 {
+                                bone_weights.clear();
+                            }
 
-                            let vertex = Vertex::new(
+                            let mut vertex = Vertex::new(
                                 Position {
                                     x: positions[i][0],
                                     y: positions[i][1],
@@ -80,7 +106,23 @@ impl Gltf {
                                     v: tex_coords[i][1]
                                 },
                                 joint_index as u32
-                            );
+                            ).with_weights(bone_weights);
+
+                            if let Some(normal) = normals.as_ref().and_then(|normals| normals.get(i)) {
+                                vertex = vertex.with_normal(Normal {
+                                    x: normal[0],
+                                    y: normal[1],
+                                    z: normal[2]
+                                });
+                            }
+
+                            if let Some(color) = colors.as_ref().and_then(|colors| colors.get(i)) {
+                                vertex = vertex.with_color(Color {
+                                    r: color[0],
+                                    g: color[1],
+                                    b: color[2]
+                                });
+                            }
 
                             vertices.push(vertex);
                         }
@@ -119,12 +161,66 @@ impl Gltf {
 
                     meshes.push(mesh);
 
+                    skeleton = Some(Self::read_skeleton(&skin, &buffers)?);
+
                     break; // We only take the first mesh with a skin
                 }
             }
         }
 
-        Ok(Gltf { meshes }) 
+        Ok(Gltf { meshes, skeleton }) 
+    }
+
+    fn read_skeleton(skin: &gltf::Skin, buffers: &[gltf::buffer::Data]) -> Result<Skeleton, AppError> {
+        let joint_nodes = skin.joints().collect::<Vec<gltf::Node>>();
+
+        // Map node indices to joint indices, so parents can be resolved from the node hierarchy
+        let node_to_joint = joint_nodes.iter()
+            .enumerate()
+            .map(|(joint_index, node)| (node.index(), joint_index))
+            .collect::<HashMap<usize, usize>>();
+
+        let mut parents = vec![None; joint_nodes.len()];
+        for (joint_index, node) in joint_nodes.iter().enumerate() {
+            for child in node.children() {
+                if let Some(&child_joint_index) = node_to_joint.get(&child.index()) {
+                    parents[child_joint_index] = Some(joint_index);
+                }
+            }
+        }
+
+        // If the skin does not provide inverse bind matrices, identity is assumed (glTF spec)
+        let reader = skin.reader(|buffer| Some(&buffers[buffer.index()]));
+        let inverse_bind_matrices = match reader.read_inverse_bind_matrices() {
+            Some(iter) => iter.collect::<Vec<[[f32; 4]; 4]>>(),
+            None => vec![[[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]]; joint_nodes.len()]
+        };
+
+        if inverse_bind_matrices.len() != joint_nodes.len() {
+            return Err(AppError::new("Joints and inverse bind matrices length mismatch"));
+        }
+
+        let mut joints = Vec::with_capacity(joint_nodes.len());
+        for (joint_index, node) in joint_nodes.iter().enumerate() {
+            let (translation, rotation, scale) = node.transform().decomposed();
+
+            // glTF matrices are column-major
+            let columns = inverse_bind_matrices[joint_index];
+            let inverse_bind_matrix = Matrix::from_bidimensional_array(
+                (0..4).map(|row| (0..4).map(|column| columns[column][row]).collect()).collect()
+            )?;
+
+            joints.push(Joint {
+                name: node.name().unwrap_or("unnamed_bone").to_string(),
+                parent: parents[joint_index],
+                translation,
+                rotation,
+                scale,
+                inverse_bind_matrix
+            });
+        }
+
+        Skeleton::new(joints)
     }
 
     pub fn primitives(&self) -> Vec<&Primitive> {
@@ -138,6 +234,10 @@ impl Gltf {
             .flat_map(|mesh| &mesh.bones)
             .collect()
     }
+
+    pub fn skeleton(&self) -> Option<&Skeleton> {
+        self.skeleton.as_ref()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -1,3 +1,4 @@
 pub mod formats;
 pub mod vertex;
 pub mod primitive;
+pub mod skeleton;
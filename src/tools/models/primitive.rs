@@ -5,6 +5,10 @@ pub enum Primitive {
     Triangle {
         vertices: Vec<Vertex>,
         indices: Vec<u32>
+    },
+    Quad {
+        vertices: Vec<Vertex>,
+        indices: Vec<u32> // 4 per quad, in drawing order (not strip order)
     }
 }
 
@@ -12,18 +16,21 @@ impl Primitive {
     pub fn vertices(&self) -> &Vec<Vertex> {
         match self {
             Primitive::Triangle { vertices, .. } => vertices,
+            Primitive::Quad { vertices, .. } => vertices
         }
     }
 
     pub fn vertices_mut(&mut self) -> &mut Vec<Vertex> {
         match self {
-            Primitive::Triangle { vertices, .. } => vertices
+            Primitive::Triangle { vertices, .. } => vertices,
+            Primitive::Quad { vertices, .. } => vertices
         }
     }
 
     pub fn indices(&self) -> &Vec<u32> {
         match self {
             Primitive::Triangle { indices, .. } => indices,
+            Primitive::Quad { indices, .. } => indices
         }
     }
 }
@@ -0,0 +1,66 @@
+use crate::{error::AppError, util::math::matrix::Matrix};
+
+#[derive(Debug, Clone)]
+pub struct Skeleton {
+    joints: Vec<Joint>
+}
+
+impl Skeleton {
+    pub fn new(joints: Vec<Joint>) -> Result<Skeleton, AppError> {
+        for (i, joint) in joints.iter().enumerate() {
+            if let Some(parent) = joint.parent {
+                if parent >= joints.len() {
+                    return Err(AppError::new(&format!("Joint '{}' has an invalid parent index {}", joint.name, parent)));
+                }
+
+                if parent == i {
+                    return Err(AppError::new(&format!("Joint '{}' cannot be its own parent", joint.name)));
+                }
+            }
+        }
+
+        Ok(Skeleton { joints })
+    }
+
+    pub fn joints(&self) -> &Vec<Joint> {
+        &self.joints
+    }
+
+    pub fn len(&self) -> usize {
+        self.joints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.joints.is_empty()
+    }
+
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.joints.iter().position(|joint| joint.name == name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Joint {
+    pub name: String,
+    pub parent: Option<usize>,
+
+    // Local transform, relative to the parent joint
+    pub translation: [f32; 3],
+    pub rotation: [f32; 4], // Quaternion (x, y, z, w)
+    pub scale: [f32; 3],
+
+    pub inverse_bind_matrix: Matrix
+}
+
+impl Joint {
+    // Row-major 3x3 rotation matrix
+    pub fn rotation_matrix(&self) -> [f32; 9] {
+        let [x, y, z, w] = self.rotation;
+
+        [
+            1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - z * w), 2.0 * (x * z + y * w),
+            2.0 * (x * y + z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - x * w),
+            2.0 * (x * z - y * w), 2.0 * (y * z + x * w), 1.0 - 2.0 * (x * x + y * y)
+        ]
+    }
+}
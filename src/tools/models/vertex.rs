@@ -4,7 +4,10 @@ use crate::{error::AppError, util::math::matrix::Matrix};
 pub struct Vertex {
     pub position: Position,
     pub tex_coord: TexCoord,
-    pub bone_id: u32
+    pub normal: Option<Normal>,
+    pub color: Option<Color>,
+    pub bone_id: u32, // Dominant bone when the vertex is blended
+    pub weights: Vec<BoneWeight> // Empty for vertices fully attached to bone_id
 }
 
 impl Vertex {
@@ -12,10 +15,32 @@ impl Vertex {
         Vertex {
             position,
             tex_coord,
-            bone_id
+            normal: None,
+            color: None,
+            bone_id,
+            weights: Vec::new()
         }
     }
 
+    pub fn with_weights(mut self, weights: Vec<BoneWeight>) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    pub fn is_blended(&self) -> bool {
+        self.weights.len() > 1
+    }
+
+    pub fn with_normal(mut self, normal: Normal) -> Self {
+        self.normal = Some(normal);
+        self
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = Some(color);
+        self
+    }
+
     pub fn apply_transform(&mut self, transform: &Matrix) -> Result<(), AppError> {
         if transform.width() != 4 || transform.height() != 4 {
             return Err(AppError::new("Transform matrix must be 4x4."));
@@ -27,6 +52,19 @@ impl Vertex {
         self.position.y = transformed_pos.get(1, 0)?;
         self.position.z = transformed_pos.get(2, 0)?;
 
+        if let Some(normal) = &self.normal {
+            // Directions ignore the translation, so w is 0
+            let dir = Matrix::new(1, 4, vec![normal.x, normal.y, normal.z, 0.0])?;
+            let transformed_dir = transform.clone() * dir;
+            let transformed_normal = Normal {
+                x: transformed_dir.get(0, 0)?,
+                y: transformed_dir.get(1, 0)?,
+                z: transformed_dir.get(2, 0)?
+            };
+
+            self.normal = Some(transformed_normal.normalized());
+        }
+
         Ok(())
     }
 }
@@ -38,6 +76,42 @@ pub struct Position {
     pub z: f32
 }
 
+#[derive(Debug, Clone)]
+pub struct Normal {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32
+}
+
+impl Normal {
+    pub fn normalized(&self) -> Normal {
+        let length = (self.x * self.x + self.y * self.y + self.z * self.z).sqrt();
+        if length == 0.0 {
+            return self.clone();
+        }
+
+        Normal {
+            x: self.x / length,
+            y: self.y / length,
+            z: self.z / length
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BoneWeight {
+    pub bone_id: u32,
+    pub weight: f32
+}
+
+// Channels in [0, 1]
+#[derive(Debug, Clone)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32
+}
+
 #[derive(Debug, Clone)]
 pub struct TexCoord {
     pub u: f32,
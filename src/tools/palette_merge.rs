@@ -0,0 +1,190 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use crate::{container::Container, error::AppError, subfiles::tex::Tex, util::color::Rgb555};
+
+use super::texture_decode::texel_layout;
+
+// A palette whose colors (the ones its textures use) are all found in another palette
+#[derive(Debug, Clone)]
+pub struct PaletteMerge {
+    pub palette: String, // Removed
+    pub into: String, // Kept
+    pub textures: Vec<String> // Re-indexed to the kept palette
+}
+
+// Merges palettes that are subsets or permutations of others. The textures drawn with a merged palette are
+// re-indexed to the kept one, so this is only done when those textures are not drawn with any other palette
+#[derive(Debug, Clone)]
+pub struct PaletteMerger {
+    tex_index: usize,
+    merges: Vec<PaletteMerge>,
+    texture_data: Vec<Vec<Vec<u8>>> // New data of the textures of each merge
+}
+
+impl PaletteMerger {
+    pub fn new(container: &Container, tex_index: usize) -> Result<PaletteMerger, AppError> {
+        let tex = container.get_tex(tex_index)
+            .ok_or_else(|| AppError::new(&format!("TEX0 subfile {} not found", tex_index)))?;
+
+        // The TEX0 does not link textures and palettes, only the material pairings do
+        let mut palette_textures: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        let mut texture_palettes: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for mdl in container.mdl_iter() {
+            for model in mdl.models_iter() {
+                let material_list = model.get_material_list();
+                for material_index in 0..material_list.len() {
+                    let (Some(texture_name), Some(palette_name)) = (material_list.texture_name_for_material(material_index), material_list.palette_name_for_material(material_index)) else {
+                        continue;
+                    };
+
+                    let texture_name = texture_name.to_not_null_string()?;
+                    let palette_name = palette_name.to_not_null_string()?;
+                    palette_textures.entry(palette_name.clone()).or_default().insert(texture_name.clone());
+                    texture_palettes.entry(texture_name).or_default().insert(palette_name);
+                }
+            }
+        }
+
+        let palette_list = tex.palette_list();
+        let mut palettes = Vec::with_capacity(palette_list.len());
+        for index in 0..palette_list.len() {
+            let name = palette_list.get_palette_name(index).unwrap().to_not_null_string()?;
+            palettes.push((name, tex.palette_colors(index).unwrap_or_default()));
+        }
+
+        // Small palettes are merged into big ones first
+        let mut by_size: Vec<usize> = (0..palettes.len()).collect();
+        by_size.sort_by_key(|&index| palettes[index].1.len());
+
+        let mut merges = Vec::new();
+        let mut texture_data = Vec::new();
+        let mut removed = HashSet::new();
+        let mut kept = HashSet::new();
+        for &index in &by_size {
+            let (name, colors) = &palettes[index];
+            if kept.contains(&index) {
+                continue;
+            }
+
+            let Some(textures) = palette_textures.get(name) else {
+                continue;
+            };
+
+            // A texture drawn with several palettes cannot be re-indexed for just one of them
+            if textures.iter().any(|texture| texture_palettes[texture].len() != 1) {
+                continue;
+            }
+
+            for &target in by_size.iter().rev() {
+                if target == index || removed.contains(&target) {
+                    continue;
+                }
+
+                if let Some(data) = Self::remap_textures(tex, textures, colors, &palettes[target].1) {
+                    merges.push(PaletteMerge {
+                        palette: name.clone(),
+                        into: palettes[target].0.clone(),
+                        textures: textures.iter().cloned().collect()
+                    });
+                    texture_data.push(data);
+                    removed.insert(index);
+                    kept.insert(target);
+                    break;
+                }
+            }
+        }
+
+        Ok(PaletteMerger {
+            tex_index,
+            merges,
+            texture_data
+        })
+    }
+
+    pub fn merges(&self) -> &[PaletteMerge] {
+        &self.merges
+    }
+
+    // Re-indexes the textures, pairs their materials with the kept palettes and removes the merged ones.
+    // Returns the number of removed palettes. The container must be rebased afterwards
+    pub fn apply(&self, container: &mut Container) -> Result<usize, AppError> {
+        let tex = container.get_tex_mut(self.tex_index)
+            .ok_or_else(|| AppError::new(&format!("TEX0 subfile {} not found", self.tex_index)))?;
+
+        for (merge, texture_data) in self.merges.iter().zip(&self.texture_data) {
+            for (texture_name, data) in merge.textures.iter().zip(texture_data) {
+                let index = tex.texture_list().index_of_name(texture_name)
+                    .ok_or_else(|| AppError::new(&format!("Texture {} not found in TEX0 subfile {}", texture_name, self.tex_index)))?;
+                let params = *tex.texture_list().get_texture(index).unwrap().teximage_params();
+                tex.replace_texture(index, params, data)?;
+            }
+
+            tex.remove_palette(&merge.palette)?;
+        }
+
+        for mdl in container.mdl_iter_mut() {
+            for model in mdl.models_iter_mut() {
+                for merge in &self.merges {
+                    model.get_material_list_mut().rename_palette_pairing(&merge.palette, &merge.into)?;
+                }
+            }
+        }
+
+        Ok(self.merges.len())
+    }
+
+    // Texture data re-indexed from one palette to another, or None if any texture uses a color the target palette lacks
+    // (within the indices its format can reach)
+    fn remap_textures(tex: &Tex, textures: &BTreeSet<String>, from: &[Rgb555], to: &[Rgb555]) -> Option<Vec<Vec<u8>>> {
+        let mut texture_data = Vec::with_capacity(textures.len());
+
+        for texture_name in textures {
+            let index = tex.texture_list().index_of_name(texture_name)?;
+            let params = tex.texture_list().get_texture(index)?.teximage_params();
+            let (index_bits, alpha_bits) = texel_layout(params.texture_format()).ok()?;
+
+            // Index 0 stays transparent, whatever its color
+            let reserved = params.palette_color_0_transparent() && alpha_bits == 0;
+            let reachable = &to[..to.len().min(1 << index_bits)];
+
+            let mut index_map = vec![None; 1 << index_bits];
+            let mut map_index = |color_index: usize| -> Option<u8> {
+                if index_map[color_index].is_none() {
+                    index_map[color_index] = if reserved && color_index == 0 {
+                        Some(0)
+                    }
+                    else {
+                        let color = from.get(color_index)?;
+                        reachable.iter()
+                            .enumerate()
+                            .skip(reserved as usize)
+                            .find(|&(_, other)| other == color)
+                            .map(|(new_index, _)| new_index as u8)
+                    };
+                }
+
+                index_map[color_index]
+            };
+
+            let bits_per_texel = index_bits + alpha_bits;
+            let texel_mask = ((1u16 << bits_per_texel) - 1) as u8;
+            let index_mask = ((1u16 << index_bits) - 1) as u8;
+
+            let mut data = Vec::new();
+            for &byte in tex.texture_bytes(index)? {
+                let mut new_byte = 0;
+                for shift in (0..8).step_by(bits_per_texel) {
+                    let texel = (byte >> shift) & texel_mask;
+                    let new_index = map_index((texel & index_mask) as usize)?;
+                    new_byte |= (new_index | (texel & !index_mask)) << shift;
+                }
+
+                data.push(new_byte);
+            }
+
+            texture_data.push(data);
+        }
+
+        Some(texture_data)
+    }
+}
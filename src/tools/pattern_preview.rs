@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+
+use crate::{error::AppError, subfiles::tex::Tex};
+
+use super::texture_decode::{Color0Mode, DecodedTexture};
+
+// Decodes the frames of a texture pattern (PAT0) animation, given the texture and palette names bound at each frame.
+// Pattern animations cycle through a few textures, so every pairing is decoded once and cached
+#[derive(Debug, Clone)]
+pub struct PatternFrameDecoder<'a> {
+    tex: &'a Tex,
+    color_0: Color0Mode,
+    frames: HashMap<(String, Option<String>), DecodedTexture>
+}
+
+impl<'a> PatternFrameDecoder<'a> {
+    pub fn new(tex: &'a Tex) -> PatternFrameDecoder<'a> {
+        PatternFrameDecoder {
+            tex,
+            color_0: Color0Mode::FromTexture,
+            frames: HashMap::new()
+        }
+    }
+
+    // Usually the flag of the animated material, which is what the GPU uses. Color0Mode::FromTexture by default
+    pub fn set_color_0(&mut self, color_0: Color0Mode) {
+        if self.color_0 != color_0 {
+            self.color_0 = color_0;
+            self.frames.clear();
+        }
+    }
+
+    // RGBA image of a frame. Without a palette name, the palette is the one Tex::palette_for_texture picks
+    pub fn decode_frame(&mut self, texture_name: &str, palette_name: Option<&str>) -> Result<&DecodedTexture, AppError> {
+        let key = (texture_name.to_string(), palette_name.map(str::to_string));
+
+        if !self.frames.contains_key(&key) {
+            let texture_index = self.tex.texture_list().index_of_name(texture_name)
+                .ok_or_else(|| AppError::new(&format!("Tex has no texture named {}", texture_name)))?;
+            let palette_index = match palette_name {
+                Some(palette_name) => Some(self.tex.palette_list().index_of_name(palette_name)
+                    .ok_or_else(|| AppError::new(&format!("Tex has no palette named {}", palette_name)))?),
+                None => None
+            };
+
+            let frame = self.tex.decode_texture_with_color_0(texture_index, palette_index, self.color_0)?;
+            self.frames.insert(key.clone(), frame);
+        }
+
+        Ok(&self.frames[&key])
+    }
+}
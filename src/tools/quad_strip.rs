@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+// Greedy stripifier working on vertex indices, like TriangleStripifier.
+// Strips follow the GPU order: quad i is (v[2i], v[2i+1], v[2i+3], v[2i+2])
+#[derive(Debug, Clone)]
+pub struct QuadStripifier {
+    quads: Vec<[u32; 4]>,
+    edges: HashMap<(u32, u32), Vec<usize>> // Directed edge -> quads containing it
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct QuadStripifyResult {
+    pub strips: Vec<Vec<u32>>, // Every strip has at least 2 quads
+    pub quads: Vec<[u32; 4]> // Quads that did not fit in any strip
+}
+
+impl QuadStripifier {
+    pub fn new(quads: Vec<[u32; 4]>) -> QuadStripifier {
+        let mut edges: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+        for (i, quad) in quads.iter().enumerate() {
+            if is_degenerate(*quad) {
+                continue;
+            }
+
+            for j in 0..4 {
+                edges.entry((quad[j], quad[(j + 1) % 4])).or_default().push(i);
+            }
+        }
+
+        QuadStripifier {
+            quads,
+            edges
+        }
+    }
+
+    pub fn stripify(&self) -> QuadStripifyResult {
+        let mut result = QuadStripifyResult::default();
+
+        let mut used = vec![false; self.quads.len()];
+        let mut attempt_marks = vec![0usize; self.quads.len()];
+        let mut attempt = 0;
+
+        for start in 0..self.quads.len() {
+            if used[start] {
+                continue;
+            }
+
+            let quad = self.quads[start];
+            if is_degenerate(quad) {
+                used[start] = true;
+                result.quads.push(quad);
+                continue;
+            }
+
+            // Try every rotation of the starting quad and keep the longest strip
+            let mut best: Option<(Vec<u32>, Vec<usize>)> = None;
+            for rotation in 0..4 {
+                let first = [quad[rotation], quad[(rotation + 1) % 4], quad[(rotation + 2) % 4], quad[(rotation + 3) % 4]];
+
+                attempt += 1;
+                let candidate = self.grow_strip(start, first, &used, &mut attempt_marks, attempt);
+
+                if best.as_ref().is_none_or(|(_, best_quads)| candidate.1.len() > best_quads.len()) {
+                    best = Some(candidate);
+                }
+            }
+
+            let (strip, strip_quads) = best.unwrap();
+            for &strip_quad in strip_quads.iter() {
+                used[strip_quad] = true;
+            }
+
+            if strip_quads.len() >= 2 {
+                result.strips.push(strip);
+            } else {
+                result.quads.push(quad);
+            }
+        }
+
+        result
+    }
+
+    fn grow_strip(&self, start: usize, [a, b, c, d]: [u32; 4], used: &[bool], attempt_marks: &mut [usize], attempt: usize) -> (Vec<u32>, Vec<usize>) {
+        let mut strip = vec![a, b, d, c];
+        let mut strip_quads = vec![start];
+        attempt_marks[start] = attempt;
+
+        loop {
+            // The next quad is (p, q, e, f), which adds f and e to the strip
+            let edge = (strip[strip.len() - 2], strip[strip.len() - 1]);
+
+            let next = self.edges.get(&edge).and_then(|candidates| {
+                candidates.iter()
+                    .copied()
+                    .find(|&quad| !used[quad] && attempt_marks[quad] != attempt)
+            });
+
+            let Some(next) = next else {
+                break;
+            };
+
+            let quad = self.quads[next];
+            let first = (0..4).find(|&j| (quad[j], quad[(j + 1) % 4]) == edge).unwrap();
+
+            attempt_marks[next] = attempt;
+            strip_quads.push(next);
+            strip.push(quad[(first + 3) % 4]);
+            strip.push(quad[(first + 2) % 4]);
+        }
+
+        (strip, strip_quads)
+    }
+}
+
+fn is_degenerate(quad: [u32; 4]) -> bool {
+    (0..4).any(|i| ((i + 1)..4).any(|j| quad[i] == quad[j]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Quads of a grid of width x height quads, all wound the same way
+    fn grid(width: u32, height: u32) -> Vec<[u32; 4]> {
+        let vertex = |x: u32, y: u32| y * (width + 1) + x;
+
+        (0..height).flat_map(|y| (0..width).map(move |x| [vertex(x, y), vertex(x + 1, y), vertex(x + 1, y + 1), vertex(x, y + 1)])).collect()
+    }
+
+    // Rotated to start at the smallest index, which keeps the winding
+    fn normalized(quad: [u32; 4]) -> [u32; 4] {
+        let first = (0..4).min_by_key(|&i| quad[i]).unwrap();
+        [0, 1, 2, 3].map(|i| quad[(first + i) % 4])
+    }
+
+    fn expand(result: &QuadStripifyResult) -> Vec<[u32; 4]> {
+        let mut quads: Vec<[u32; 4]> = result.strips.iter()
+            .flat_map(|strip| (0..strip.len() / 2 - 1).map(move |i| [strip[2 * i], strip[2 * i + 1], strip[2 * i + 3], strip[2 * i + 2]]))
+            .chain(result.quads.iter().copied())
+            .map(normalized)
+            .collect();
+
+        quads.sort();
+        quads
+    }
+
+    #[test]
+    fn strips_keep_every_quad_and_its_winding() {
+        let quads = grid(3, 2);
+        let result = QuadStripifier::new(quads.clone()).stripify();
+
+        assert!(!result.strips.is_empty());
+        assert!(result.strips.iter().all(|strip| strip.len() >= 6 && strip.len().is_multiple_of(2)));
+
+        let mut expected: Vec<[u32; 4]> = quads.into_iter().map(normalized).collect();
+        expected.sort();
+        assert_eq!(expand(&result), expected);
+    }
+
+    #[test]
+    fn quads_with_opposite_winding_are_not_joined() {
+        // Both quads share the edge (1, 2), but the second one is wound the other way
+        let quads = vec![[0, 1, 2, 3], [1, 2, 5, 4]];
+        let result = QuadStripifier::new(quads).stripify();
+
+        assert!(result.strips.is_empty());
+        assert_eq!(result.quads, vec![[0, 1, 2, 3], [1, 2, 5, 4]]);
+    }
+
+    #[test]
+    fn degenerate_quads_are_left_alone() {
+        let result = QuadStripifier::new(vec![[0, 1, 1, 2], [0, 1, 4, 3], [1, 2, 5, 4]]).stripify();
+
+        assert_eq!(result.strips.len(), 1);
+        assert_eq!(result.quads, vec![[0, 1, 1, 2]]);
+        assert_eq!(expand(&result), vec![[0, 1, 1, 2], [0, 1, 4, 3], [1, 2, 5, 4]]);
+    }
+}
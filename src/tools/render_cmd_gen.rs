@@ -0,0 +1,213 @@
+use crate::{error::AppError, subfiles::mdl::model::{render_command_list::{BindMaterialData, DrawMeshData, MulCurrentMatrixWithBoneMatrixData, RenderCommand}, Model}};
+
+use super::models::skeleton::Skeleton;
+
+// Mesh drawn with the matrix of a bone
+#[derive(Debug, Clone, Copy)]
+pub struct MeshAssignment {
+    pub bone_index: usize,
+    pub mesh_index: usize,
+    pub material_index: usize
+}
+
+// Generates the render commands of a model from its skeleton, the way the NNS converter lays them out:
+// bones are visited depth first, every bone matrix is stored in the stack slot matching its index
+// (so meshes can MtxRestore it) and the meshes of each bone are drawn right after its matrix is computed
+#[derive(Debug, Clone)]
+pub struct RenderCommandGenerator<'a> {
+    skeleton: &'a Skeleton,
+    meshes: Vec<MeshAssignment>
+}
+
+impl RenderCommandGenerator<'_> {
+    const MATRIX_STACK_SIZE: usize = 31; // 0..30 (31 entries)
+
+    pub fn new<'a>(skeleton: &'a Skeleton) -> Result<RenderCommandGenerator<'a>, AppError> {
+        if skeleton.is_empty() {
+            return Err(AppError::new("Skeleton must have at least one joint"));
+        }
+
+        if skeleton.len() > Self::MATRIX_STACK_SIZE {
+            return Err(AppError::new(&format!("Skeleton cannot have more than {} joints, as every bone matrix is stored in the matrix stack. Found: {}", Self::MATRIX_STACK_SIZE, skeleton.len())));
+        }
+
+        // Roots start from the identity matrix, which can only be the initial current matrix
+        let num_roots = skeleton.joints().iter().filter(|joint| joint.parent.is_none()).count();
+        if num_roots != 1 {
+            return Err(AppError::new(&format!("Skeleton must have a single root joint. Found: {}", num_roots)));
+        }
+
+        let generator = RenderCommandGenerator {
+            skeleton,
+            meshes: Vec::new()
+        };
+
+        if generator.depth_first_order().len() != skeleton.len() {
+            return Err(AppError::new("Skeleton has joints that cannot be reached from the root joint"));
+        }
+
+        Ok(generator)
+    }
+
+    pub fn assign_mesh(&mut self, bone_index: usize, mesh_index: usize, material_index: usize) -> Result<(), AppError> {
+        if bone_index >= self.skeleton.len() {
+            return Err(AppError::new(&format!("Invalid bone index. Expected 0-{}, got {}", self.skeleton.len() - 1, bone_index)));
+        }
+
+        if mesh_index > u8::MAX as usize || material_index > u8::MAX as usize {
+            return Err(AppError::new(&format!("Mesh and material indices must be at most {}. Got mesh {} and material {}", u8::MAX, mesh_index, material_index)));
+        }
+
+        self.meshes.push(MeshAssignment {
+            bone_index,
+            mesh_index,
+            material_index
+        });
+
+        Ok(())
+    }
+
+    pub fn generate(&self) -> Vec<RenderCommand> {
+        let mut commands = Vec::new();
+
+        let mut previous_bone = None;
+        let mut bound_material = None;
+        for bone_index in self.depth_first_order() {
+            let parent = self.skeleton.joints()[bone_index].parent;
+
+            // The current matrix only holds the parent matrix if the parent was the last bone visited
+            let load_index = parent.filter(|&parent| Some(parent) != previous_bone);
+            let store_index = bone_index;
+
+            let (subtype, param_3, param_4) = match load_index {
+                Some(load_index) => (0x60, Some(store_index as u8), Some(load_index as u8)),
+                None => (0x20, Some(store_index as u8), None)
+            };
+
+            commands.push(RenderCommand::MulCurrentMatrixWithBoneMatrix(Box::new(MulCurrentMatrixWithBoneMatrixData {
+                subtype,
+                bone_index: bone_index as u8,
+                parent_index: parent.unwrap_or(bone_index) as u8,
+                unknown: 0,
+                param_3,
+                param_4
+            })));
+
+            for mesh in self.meshes.iter().filter(|mesh| mesh.bone_index == bone_index) {
+                if bound_material != Some(mesh.material_index) {
+                    commands.push(RenderCommand::BindMaterial(Box::new(BindMaterialData {
+                        subtype: 0x00,
+                        material_index: mesh.material_index as u8
+                    })));
+                    bound_material = Some(mesh.material_index);
+                }
+
+                commands.push(RenderCommand::DrawMesh(Box::new(DrawMeshData {
+                    mesh_index: mesh.mesh_index as u8
+                })));
+            }
+
+            previous_bone = Some(bone_index);
+        }
+
+        commands.push(RenderCommand::End);
+
+        commands
+    }
+
+    // Replaces the render commands of the model. The model must be rebased afterwards
+    pub fn apply_to_model(&self, model: &mut Model) {
+        let render_commands = model.get_render_cmds_list_mut();
+        render_commands.clear();
+        render_commands.extend(self.generate());
+    }
+
+    fn depth_first_order(&self) -> Vec<usize> {
+        let joints = self.skeleton.joints();
+
+        let mut order = Vec::with_capacity(joints.len());
+        let mut pending: Vec<usize> = (0..joints.len()).rev()
+            .filter(|&i| joints[i].parent.is_none())
+            .collect();
+
+        while let Some(bone_index) = pending.pop() {
+            order.push(bone_index);
+
+            // Pushed in reverse so children are visited in index order
+            pending.extend((0..joints.len()).rev().filter(|&i| joints[i].parent == Some(bone_index)));
+        }
+
+        order
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{debug_info::DebugInfo, subfiles::mdl::model::render_command_list::RenderCommandList, tools::models::skeleton::Joint, util::math::matrix::Matrix};
+
+    use super::*;
+
+    // (subtype, bone, parent, store slot, load slot)
+    type BoneCommand = (u8, u8, u8, Option<u8>, Option<u8>);
+
+    fn skeleton(parents: &[Option<usize>]) -> Skeleton {
+        let joints = parents.iter().enumerate().map(|(i, &parent)| Joint {
+            name: format!("joint_{}", i),
+            parent,
+            translation: [0.0; 3],
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            scale: [1.0; 3],
+            inverse_bind_matrix: Matrix::identity(4)
+        }).collect();
+
+        Skeleton::new(joints).unwrap()
+    }
+
+    #[test]
+    fn branching_skeleton_stores_every_bone_and_restores_parents() {
+        // 0 -> 1 -> 2, and 0 -> 3, visited 0, 1, 2, 3
+        let skeleton = skeleton(&[None, Some(0), Some(1), Some(0)]);
+        let mut generator = RenderCommandGenerator::new(&skeleton).unwrap();
+        generator.assign_mesh(0, 0, 0).unwrap();
+        generator.assign_mesh(1, 1, 0).unwrap();
+        generator.assign_mesh(2, 2, 1).unwrap();
+        generator.assign_mesh(3, 3, 1).unwrap();
+        generator.assign_mesh(3, 4, 0).unwrap();
+
+        let commands = generator.generate();
+
+        // Every bone is stored, so the load only subtype 0x40 is never needed
+        let bone_commands: Vec<BoneCommand> = commands.iter().filter_map(|command| match command {
+            RenderCommand::MulCurrentMatrixWithBoneMatrix(data) => Some((data.subtype, data.bone_index, data.parent_index, data.param_3, data.param_4)),
+            _ => None
+        }).collect();
+        assert_eq!(bone_commands, vec![
+            (0x20, 0, 0, Some(0), None),
+            (0x20, 1, 0, Some(1), None),
+            (0x20, 2, 1, Some(2), None),
+            // The current matrix holds bone 2, so the parent is restored from its slot
+            (0x60, 3, 0, Some(3), Some(0))
+        ]);
+        assert!(bone_commands.iter().all(|&(subtype, ..)| subtype != 0x40));
+
+        // Materials are only bound when they change
+        let draws: Vec<(u8, u8)> = commands.iter().filter_map(|command| match command {
+            RenderCommand::BindMaterial(data) => Some((0x04, data.material_index)),
+            RenderCommand::DrawMesh(data) => Some((0x05, data.mesh_index)),
+            _ => None
+        }).collect();
+        assert_eq!(draws, vec![(0x04, 0), (0x05, 0), (0x05, 1), (0x04, 1), (0x05, 2), (0x05, 3), (0x04, 0), (0x05, 4)]);
+
+        let mut list = RenderCommandList::from_bytes(&[0x01], DebugInfo { offset: 0 }).unwrap();
+        list.clear();
+        list.extend(commands);
+        let issues = list.validate_counts(4, 2, 5, 0);
+        assert!(issues.is_empty(), "{:?}", issues);
+    }
+
+    #[test]
+    fn skeletons_need_a_single_root() {
+        assert!(RenderCommandGenerator::new(&skeleton(&[None, None])).is_err());
+        assert!(RenderCommandGenerator::new(&skeleton(&[None, Some(2), Some(1)])).is_err());
+    }
+}
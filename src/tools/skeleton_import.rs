@@ -0,0 +1,154 @@
+use crate::{data_structures::name::Name, error::AppError, subfiles::mdl::model::{bone_list::{BoneList, BoneMatrix}, inv_bind_matrices::{InvBindMatrices, InvBindMatrix}, render_command_list::{RenderCommand, RenderCommandList}, Model}};
+
+use super::models::skeleton::{Joint, Skeleton};
+
+// Converts a source skeleton (e.g. a glTF skin) into the BoneList and InvBindMatrices of a model
+#[derive(Debug, Clone)]
+pub struct SkeletonImporter<'a> {
+    skeleton: &'a Skeleton
+}
+
+impl SkeletonImporter<'_> {
+    const IDENTITY_THRESHOLD: f32 = 1e-6;
+
+    pub fn new<'a>(skeleton: &'a Skeleton) -> Result<SkeletonImporter<'a>, AppError> {
+        if skeleton.is_empty() {
+            return Err(AppError::new("Skeleton must have at least one joint"));
+        }
+
+        if skeleton.len() > u8::MAX as usize {
+            return Err(AppError::new(&format!("Skeleton cannot have more than {} joints. Found: {}", u8::MAX, skeleton.len())));
+        }
+
+        Ok(SkeletonImporter {
+            skeleton
+        })
+    }
+
+    pub fn generate_bone_list(&self) -> Result<BoneList, AppError> {
+        let mut bone_list = BoneList::new();
+
+        for joint in self.skeleton.joints() {
+            let name = Name::from_string(&joint.name)?;
+            let bone_matrix = Self::joint_to_bone_matrix(joint);
+
+            bone_list.push(name, bone_matrix)?;
+        }
+
+        bone_list.rebase();
+
+        Ok(bone_list)
+    }
+
+    pub fn generate_inv_bind_matrices(&self) -> Result<InvBindMatrices, AppError> {
+        let matrices = self.skeleton.joints().iter()
+            .map(|joint| InvBindMatrix::from_matrix(&joint.inverse_bind_matrix))
+            .collect::<Result<Vec<InvBindMatrix>, AppError>>()?;
+
+        Ok(InvBindMatrices::new(matrices))
+    }
+
+    // Replaces the skeleton of the model. The model must be rebased afterwards.
+    // The hierarchy lives in the render commands, so they must already match the joint parents
+    // (see check_hierarchy). Regenerate them with RenderCommandGenerator first to change it
+    pub fn apply_to_model(&self, model: &mut Model) -> Result<(), AppError> {
+        self.check_hierarchy(model.get_render_cmds_list())?;
+
+        let bone_list = self.generate_bone_list()?;
+        let inv_bind_matrices = self.generate_inv_bind_matrices()?;
+
+        model.set_skeleton(bone_list, inv_bind_matrices)
+    }
+
+    // Checks that every MulCurrentMatrixWithBoneMatrix command uses the parent of its joint.
+    // Root joints are their own parent, as RenderCommandGenerator writes them
+    pub fn check_hierarchy(&self, render_commands: &RenderCommandList) -> Result<(), AppError> {
+        let joints = self.skeleton.joints();
+
+        for (i, command) in render_commands.iter().enumerate() {
+            let RenderCommand::MulCurrentMatrixWithBoneMatrix(data) = command else {
+                continue;
+            };
+
+            let bone_index = data.bone_index as usize;
+            let joint = joints.get(bone_index)
+                .ok_or_else(|| AppError::new(&format!("Render command {} uses bone {}, but the skeleton has {} joints", i, bone_index, joints.len())))?;
+
+            let parent = joint.parent.unwrap_or(bone_index);
+            if parent != data.parent_index as usize {
+                return Err(AppError::new(&format!(
+                    "Joint '{}' has parent {}, but render command {} uses parent {}. Regenerate the render commands with RenderCommandGenerator",
+                    joint.name, parent, i, data.parent_index
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn joint_to_bone_matrix(joint: &Joint) -> BoneMatrix {
+        let translation = if joint.translation.iter().all(|v| v.abs() < Self::IDENTITY_THRESHOLD) {
+            None
+        } else { Some(joint.translation) };
+
+        let [x, y, z, _] = joint.rotation;
+        let rotation = if x.abs() < Self::IDENTITY_THRESHOLD && y.abs() < Self::IDENTITY_THRESHOLD && z.abs() < Self::IDENTITY_THRESHOLD {
+            None
+        } else { Some(joint.rotation_matrix()) };
+
+        let scale = if joint.scale.iter().all(|v| (v - 1.0).abs() < Self::IDENTITY_THRESHOLD) {
+            None
+        } else { Some(joint.scale) };
+
+        BoneMatrix::from_components(translation, rotation, scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{debug_info::DebugInfo, tools::render_cmd_gen::RenderCommandGenerator, util::math::matrix::Matrix};
+
+    use super::*;
+
+    fn skeleton(parents: &[Option<usize>]) -> Skeleton {
+        let joints = parents.iter().enumerate().map(|(i, &parent)| Joint {
+            name: format!("joint_{}", i),
+            parent,
+            translation: [0.0; 3],
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            scale: [1.0; 3],
+            inverse_bind_matrix: Matrix::identity(4)
+        }).collect();
+
+        Skeleton::new(joints).unwrap()
+    }
+
+    fn render_commands(skeleton: &Skeleton) -> RenderCommandList {
+        let mut list = RenderCommandList::from_bytes(&[0x01], DebugInfo { offset: 0 }).unwrap();
+        list.clear();
+        list.extend(RenderCommandGenerator::new(skeleton).unwrap().generate());
+        list
+    }
+
+    #[test]
+    fn matching_hierarchy_is_accepted() {
+        let skeleton = skeleton(&[None, Some(0), Some(1), Some(0)]);
+        let commands = render_commands(&skeleton);
+
+        assert!(SkeletonImporter::new(&skeleton).unwrap().check_hierarchy(&commands).is_ok());
+    }
+
+    #[test]
+    fn different_parents_are_rejected() {
+        let commands = render_commands(&skeleton(&[None, Some(0), Some(1), Some(0)]));
+
+        // Joint 3 hangs from joint 2 instead of the root
+        let imported = skeleton(&[None, Some(0), Some(1), Some(2)]);
+        let err = SkeletonImporter::new(&imported).unwrap().check_hierarchy(&commands).unwrap_err();
+        assert!(err.message().contains("Joint 'joint_3' has parent 2"), "{}", err.message());
+
+        // Commands for bones the skeleton does not have
+        let smaller = skeleton(&[None, Some(0)]);
+        assert!(SkeletonImporter::new(&smaller).unwrap().check_hierarchy(&commands).is_err());
+    }
+}
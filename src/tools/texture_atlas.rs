@@ -0,0 +1,169 @@
+use crate::{error::AppError, subfiles::tex::{texture::TeximageParams, Tex}};
+
+use super::{texture_decode::DecodedTexture, texture_import::{replace_texture_image, ImportOptions, ImportPalette}};
+
+// Where a texture was placed in an atlas, in texels
+#[derive(Debug, Clone)]
+pub struct AtlasRegion {
+    pub texture: String,
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16
+}
+
+// Maps the UVs of a texture to the UVs of its region in an atlas: u' = offset.0 + u * scale.0 (same for v)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UvRemap {
+    pub offset: (f32, f32),
+    pub scale: (f32, f32)
+}
+
+impl UvRemap {
+    pub fn apply(&self, u: f32, v: f32) -> (f32, f32) {
+        (self.offset.0 + u * self.scale.0, self.offset.1 + v * self.scale.1)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TextureAtlas {
+    pub name: String,
+    pub width: u16,
+    pub height: u16,
+    pub regions: Vec<AtlasRegion>
+}
+
+impl TextureAtlas {
+    pub fn region(&self, texture: &str) -> Option<&AtlasRegion> {
+        self.regions.iter().find(|region| region.texture == texture)
+    }
+
+    // UV remapping for the meshes drawn with a packed texture (see MeshCommandGenerator::set_uv_remap)
+    pub fn uv_remap(&self, texture: &str) -> Option<UvRemap> {
+        let region = self.region(texture)?;
+
+        Some(UvRemap {
+            offset: (region.x as f32 / self.width as f32, region.y as f32 / self.height as f32),
+            scale: (region.width as f32 / self.width as f32, region.height as f32 / self.height as f32)
+        })
+    }
+}
+
+// Packs textures of a TEX0 into a new texture, added with the format and color 0 flag of params and encoded like
+// replace_texture_image does. Each texture is decoded with its palette_for_texture. The packed textures and the
+// materials are left as is, so the caller can re-pair the materials and regenerate their meshes with the UV remaps.
+// The hardware cannot repeat a region of a texture, so only textures drawn without repeat should be packed
+pub fn pack_atlas(tex: &mut Tex, atlas_name: &str, textures: &[&str], params: TeximageParams, palette: ImportPalette, options: ImportOptions) -> Result<TextureAtlas, AppError> {
+    if textures.is_empty() {
+        return Err(AppError::new("No textures to pack"));
+    }
+
+    if tex.texture_list().index_of_name(atlas_name).is_some() {
+        return Err(AppError::new(&format!("Tex already has a texture named {}", atlas_name)));
+    }
+
+    let mut images = Vec::with_capacity(textures.len());
+    for &texture_name in textures {
+        let index = tex.texture_list().index_of_name(texture_name)
+            .ok_or_else(|| AppError::new(&format!("Tex has no texture named {}", texture_name)))?;
+        images.push(tex.decode_texture(index, None)?);
+    }
+
+    let sizes: Vec<(usize, usize)> = images.iter().map(|image| (image.width, image.height)).collect();
+    let (width, height, positions) = pack_rects(&sizes)
+        .ok_or_else(|| AppError::new(&format!("Textures do not fit in a {}x{} atlas", MAX_ATLAS_SIZE, MAX_ATLAS_SIZE)))?;
+
+    let mut atlas = DecodedTexture {
+        width,
+        height,
+        rgba: vec![0; width * height * 4]
+    };
+    for (image, &(x, y)) in images.iter().zip(&positions) {
+        for row in 0..image.height {
+            let src = row * image.width * 4;
+            let dst = ((y + row) * width + x) * 4;
+            atlas.rgba[dst..dst + image.width * 4].copy_from_slice(&image.rgba[src..src + image.width * 4]);
+        }
+    }
+
+    // Added empty, then filled with the image
+    let mut atlas_params = params;
+    atlas_params.set_texture_s_size(TeximageParams::size_to_bits(width as u16)?)?;
+    atlas_params.set_texture_t_size(TeximageParams::size_to_bits(height as u16)?)?;
+    let data_size = atlas_params.data_size();
+    let data_size = if atlas_params.texture_format() == TeximageParams::FORMAT_COMPRESSED_4X4 { data_size + data_size / 2 } else { data_size };
+
+    tex.add_texture(atlas_name, atlas_params, &vec![0; data_size])?;
+    if let Err(err) = replace_texture_image(tex, atlas_name, &atlas, palette, options) {
+        tex.remove_texture(atlas_name)?;
+        return Err(err);
+    }
+
+    Ok(TextureAtlas {
+        name: atlas_name.to_string(),
+        width: width as u16,
+        height: height as u16,
+        regions: textures.iter()
+            .zip(&images)
+            .zip(&positions)
+            .map(|((&texture, image), &(x, y))| AtlasRegion {
+                texture: texture.to_string(),
+                x: x as u16,
+                y: y as u16,
+                width: image.width as u16,
+                height: image.height as u16
+            })
+            .collect()
+    })
+}
+
+const MAX_ATLAS_SIZE: usize = 1024;
+
+// Atlas width and height, and the position of each rect
+type Packing = (usize, usize, Vec<(usize, usize)>);
+
+// Shelf packing of the rects (tallest first) for every valid atlas width, keeping the smallest atlas
+fn pack_rects(sizes: &[(usize, usize)]) -> Option<Packing> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&index| (std::cmp::Reverse(sizes[index].1), std::cmp::Reverse(sizes[index].0)));
+
+    let mut best: Option<Packing> = None;
+    for width in (3..=10).map(|bits| 1usize << bits) {
+        let mut positions = vec![(0, 0); sizes.len()];
+        let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+        let mut fits = true;
+
+        for &index in &order {
+            let (rect_width, rect_height) = sizes[index];
+            if rect_width > width {
+                fits = false;
+                break;
+            }
+
+            if x + rect_width > width {
+                x = 0;
+                y += shelf_height;
+                shelf_height = 0;
+            }
+
+            positions[index] = (x, y);
+            x += rect_width;
+            shelf_height = shelf_height.max(rect_height);
+        }
+
+        let height = (y + shelf_height).max(8).next_power_of_two();
+        if !fits || height > MAX_ATLAS_SIZE {
+            continue;
+        }
+
+        // Squarer atlases win ties
+        let is_better = best.as_ref().is_none_or(|&(best_width, best_height, _)| {
+            (width * height, width.abs_diff(height)) < (best_width * best_height, best_width.abs_diff(best_height))
+        });
+        if is_better {
+            best = Some((width, height, positions));
+        }
+    }
+
+    best
+}
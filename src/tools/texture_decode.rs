@@ -0,0 +1,295 @@
+use crate::{error::AppError, subfiles::tex::texture::{TeximageParams, Texture}, util::color::Rgb555};
+
+// Texture converted to 8-bit RGBA, row by row from the top left texel
+#[derive(Debug, Clone)]
+pub struct DecodedTexture {
+    pub width: usize,
+    pub height: usize,
+    pub rgba: Vec<u8>
+}
+
+impl DecodedTexture {
+    pub fn pixel(&self, x: usize, y: usize) -> Option<[u8; 4]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        let offset = (y * self.width + x) * 4;
+        Some([self.rgba[offset], self.rgba[offset + 1], self.rgba[offset + 2], self.rgba[offset + 3]])
+    }
+}
+
+// Whether palette index 0 of pal4, pal16 and pal256 textures is transparent. The GPU uses the flag of the material
+// drawing the texture, which usually matches the flag of the texture but can override it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Color0Mode {
+    #[default]
+    FromTexture,
+    Transparent,
+    Opaque
+}
+
+impl Color0Mode {
+    pub fn from_flag(transparent: bool) -> Color0Mode {
+        if transparent { Color0Mode::Transparent } else { Color0Mode::Opaque }
+    }
+
+    pub fn is_transparent(&self, params: &TeximageParams) -> bool {
+        match self {
+            Color0Mode::FromTexture => params.palette_color_0_transparent(),
+            Color0Mode::Transparent => true,
+            Color0Mode::Opaque => false
+        }
+    }
+}
+
+// Decodes a direct, paletted or translucent (a3i5/a5i3) texture from its own data (see Tex::texture_bytes).
+// Palette index 0 is transparent if the texture has the color 0 transparency flag. Visit
+// https://problemkaputt.de/gbatek.htm#ds3dtextureformats for more info
+pub fn decode_texture(texture: &Texture, texture_data: &[u8], palette: &[Rgb555]) -> Result<DecodedTexture, AppError> {
+    decode_texture_with_color_0(texture, texture_data, palette, Color0Mode::FromTexture)
+}
+
+// decode_texture deciding the transparency of palette index 0 with color_0
+pub fn decode_texture_with_color_0(texture: &Texture, texture_data: &[u8], palette: &[Rgb555], color_0: Color0Mode) -> Result<DecodedTexture, AppError> {
+    let params = texture.teximage_params();
+    let width = texture.width() as usize;
+    let height = texture.height() as usize;
+
+    if texture_data.len() < params.data_size() {
+        return Err(AppError::new(&format!("{} texture of {}x{} needs 0x{:X} bytes of data, got 0x{:X}", params.texture_format_name(), width, height, params.data_size(), texture_data.len())));
+    }
+
+    let rgba = match params.texture_format() {
+        TeximageParams::FORMAT_DIRECT => (0..width * height)
+            .flat_map(|texel| direct_texel(texture_data, texel))
+            .collect(),
+        TeximageParams::FORMAT_COMPRESSED_4X4 => {
+            return Err(AppError::new("tex4x4 textures are decoded with decode_tex4x4"));
+        },
+        format => {
+            let layout = texel_layout(format)?;
+            let transparent_0 = layout.1 == 0 && color_0.is_transparent(params);
+
+            let mut rgba = Vec::with_capacity(width * height * 4);
+            for texel in 0..width * height {
+                rgba.extend_from_slice(&paletted_texel(texture_data, texel, layout, palette, transparent_0)?);
+            }
+
+            rgba
+        }
+    };
+
+    Ok(DecodedTexture {
+        width,
+        height,
+        rgba
+    })
+}
+
+// Texel of a direct texture, by its index in the texture
+pub(crate) fn direct_texel(texture_data: &[u8], texel: usize) -> [u8; 4] {
+    let value = u16::from_le_bytes([texture_data[texel * 2], texture_data[texel * 2 + 1]]);
+    let [r, g, b] = Rgb555::from_u16(value).to_rgb8();
+    [r, g, b, if value & 0x8000 != 0 { 0xFF } else { 0 }]
+}
+
+// Texel of a paletted or translucent texture, by its index in the texture. layout is the one of texel_layout
+pub(crate) fn paletted_texel(texture_data: &[u8], texel: usize, layout: (usize, usize), palette: &[Rgb555], transparent_0: bool) -> Result<[u8; 4], AppError> {
+    let (index_bits, alpha_bits) = layout;
+    let bits_per_texel = index_bits + alpha_bits;
+    let index_mask = ((1u16 << index_bits) - 1) as u8;
+
+    // Texels are packed from the lowest bits
+    let bit = texel * bits_per_texel;
+    let value = (texture_data[bit / 8] >> (bit % 8)) & ((1u16 << bits_per_texel) - 1) as u8;
+    let index = value & index_mask;
+
+    let color = palette.get(index as usize)
+        .ok_or_else(|| AppError::new(&format!("Texel {} uses color {}, but the palette has {} colors", texel, index, palette.len())))?;
+    let [r, g, b] = color.to_rgb8();
+
+    let alpha = match alpha_bits {
+        0 if transparent_0 && index == 0 => 0,
+        0 => 0xFF,
+        // a3i5 alpha is expanded to 5 bits like the hardware does, then to 8
+        3 => expand_5_bits(((value >> 5) << 2) | (value >> 6)),
+        _ => expand_5_bits(value >> 3)
+    };
+
+    Ok([r, g, b, alpha])
+}
+
+// Texel (x, y) of a tex4x4 texture of the given width. texels and attrs are the data of the texture alone
+pub(crate) fn tex4x4_texel(texels: &[u8], attrs: &[u8], width: usize, x: usize, y: usize, palette_data: &[u8], palette_offset: usize) -> Result<[u8; 4], AppError> {
+    let block = (y / 4) * (width / 4) + x / 4;
+    let attr = u16::from_le_bytes([attrs[block * 2], attrs[block * 2 + 1]]);
+    let color_index = (texels[block * 4 + y % 4] >> ((x % 4) * 2)) & 0x03;
+
+    Ok(tex4x4_block_colors(attr, palette_data, palette_offset)?[color_index as usize])
+}
+
+// Decodes a compressed 4x4-texel texture. texel_block and attr_block are the whole compressed data blocks of the TEX0
+// (the texture offset locates the texture in both) and palette_offset is the palette offset in bytes within palette_data.
+// Visit https://problemkaputt.de/gbatek.htm#ds3dtextureformats for more info
+pub fn decode_tex4x4(texture: &Texture, texel_block: &[u8], attr_block: &[u8], palette_data: &[u8], palette_offset: usize) -> Result<DecodedTexture, AppError> {
+    let params = texture.teximage_params();
+    if params.texture_format() != TeximageParams::FORMAT_COMPRESSED_4X4 {
+        return Err(AppError::new(&format!("Expected a tex4x4 texture, got {}", params.texture_format_name())));
+    }
+
+    let width = texture.width() as usize;
+    let height = texture.height() as usize;
+    if !width.is_multiple_of(4) || !height.is_multiple_of(4) {
+        return Err(AppError::new(&format!("tex4x4 texture size must be a multiple of 4, got {}x{}", width, height)));
+    }
+
+    let blocks_x = width / 4;
+    let num_blocks = blocks_x * (height / 4);
+
+    // Each block has 4 bytes of texels and 2 bytes of attributes, so the attributes sit at half the texel offset
+    let texel_offset = (params.texture_data() as usize) << 3;
+    let attr_offset = texel_offset / 2;
+
+    let texels = texel_block.get(texel_offset..texel_offset + num_blocks * 4)
+        .ok_or_else(|| AppError::new(&format!("tex4x4 texel data out of bounds. Needs 0x{:X} bytes from 0x{:X}, block has 0x{:X}", num_blocks * 4, texel_offset, texel_block.len())))?;
+    let attrs = attr_block.get(attr_offset..attr_offset + num_blocks * 2)
+        .ok_or_else(|| AppError::new(&format!("tex4x4 attribute data out of bounds. Needs 0x{:X} bytes from 0x{:X}, block has 0x{:X}", num_blocks * 2, attr_offset, attr_block.len())))?;
+
+    let mut rgba = vec![0u8; width * height * 4];
+    for block in 0..num_blocks {
+        let attr = u16::from_le_bytes([attrs[block * 2], attrs[block * 2 + 1]]);
+        let colors = tex4x4_block_colors(attr, palette_data, palette_offset)?;
+
+        let block_x = (block % blocks_x) * 4;
+        let block_y = (block / blocks_x) * 4;
+        for row in 0..4 {
+            // One byte per row, 2 bits per texel starting from the left
+            let row_texels = texels[block * 4 + row];
+            for column in 0..4 {
+                let color_index = (row_texels >> (column * 2)) & 0x03;
+                let offset = ((block_y + row) * width + block_x + column) * 4;
+                rgba[offset..offset + 4].copy_from_slice(&colors[color_index as usize]);
+            }
+        }
+    }
+
+    Ok(DecodedTexture {
+        width,
+        height,
+        rgba
+    })
+}
+
+// The 4 colors a block can use. Bits 0-13 of the attribute are the palette offset in 4 byte units
+// and bits 14-15 the mode, which decides how colors 2 and 3 are built
+fn tex4x4_block_colors(attr: u16, palette_data: &[u8], palette_offset: usize) -> Result<[[u8; 4]; 4], AppError> {
+    let mode = attr >> 14;
+    let offset = palette_offset + (attr & 0x3FFF) as usize * 4;
+
+    let num_colors = if mode == 2 { 4 } else if mode == 0 { 3 } else { 2 };
+    let palette = palette_data.get(offset..offset + num_colors * 2)
+        .ok_or_else(|| AppError::new(&format!("tex4x4 palette data out of bounds. Needs {} colors from 0x{:X}, palette data has 0x{:X} bytes", num_colors, offset, palette_data.len())))?;
+    let color = |i: usize| Rgb555::from_u16(u16::from_le_bytes([palette[i * 2], palette[i * 2 + 1]]));
+
+    let transparent = [0, 0, 0, 0];
+    let colors = match mode {
+        0 => [opaque(color(0)), opaque(color(1)), opaque(color(2)), transparent],
+        1 => [opaque(color(0)), opaque(color(1)), opaque(blend(color(0), color(1), 4, 4)), transparent],
+        2 => [opaque(color(0)), opaque(color(1)), opaque(color(2)), opaque(color(3))],
+        _ => [
+            opaque(color(0)),
+            opaque(color(1)),
+            opaque(blend(color(0), color(1), 5, 3)),
+            opaque(blend(color(0), color(1), 3, 5))
+        ]
+    };
+
+    Ok(colors)
+}
+
+// (a * weight_a + b * weight_b) / 8 per channel, as the hardware interpolates
+pub(crate) fn blend(a: Rgb555, b: Rgb555, weight_a: u16, weight_b: u16) -> Rgb555 {
+    let channel = |a: u8, b: u8| ((a as u16 * weight_a + b as u16 * weight_b) / 8) as u8;
+    Rgb555::new(channel(a.r, b.r), channel(a.g, b.g), channel(a.b, b.b))
+}
+
+// Index and alpha bits of each texel of the formats that use a palette (besides tex4x4)
+pub(crate) fn texel_layout(format: u8) -> Result<(usize, usize), AppError> {
+    match format {
+        TeximageParams::FORMAT_PALETTE_4 => Ok((2, 0)),
+        TeximageParams::FORMAT_PALETTE_16 => Ok((4, 0)),
+        TeximageParams::FORMAT_PALETTE_256 => Ok((8, 0)),
+        TeximageParams::FORMAT_A3I5 => Ok((5, 3)),
+        TeximageParams::FORMAT_A5I3 => Ok((3, 5)),
+        _ => Err(AppError::new(&format!("Format {} is not a paletted format (pal4, pal16, pal256, a3i5 or a5i3)", format)))
+    }
+}
+
+fn expand_5_bits(value: u8) -> u8 {
+    (value << 3) | (value >> 2)
+}
+
+fn opaque(color: Rgb555) -> [u8; 4] {
+    let [r, g, b] = color.to_rgb8();
+    [r, g, b, 0xFF]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgba(color: Rgb555) -> [u8; 4] {
+        opaque(color)
+    }
+
+    // One block whose first row uses colors 0 to 3 from left to right
+    fn block_texels() -> [u8; 4] {
+        [0b11_10_01_00, 0, 0, 0]
+    }
+
+    fn palette_data(colors: &[Rgb555]) -> Vec<u8> {
+        colors.iter().flat_map(|color| color.to_u16().to_le_bytes()).collect()
+    }
+
+    fn first_row(mode: u16, palette: &[u8]) -> Vec<[u8; 4]> {
+        let attrs = (mode << 14).to_le_bytes();
+        (0..4).map(|x| tex4x4_texel(&block_texels(), &attrs, 4, x, 0, palette, 0).unwrap()).collect()
+    }
+
+    #[test]
+    fn tex4x4_modes_build_colors_2_and_3() {
+        let (c0, c1, c2, c3) = (Rgb555::new(0, 0, 0), Rgb555::new(31, 31, 31), Rgb555::new(31, 0, 0), Rgb555::new(0, 0, 31));
+        let palette = palette_data(&[c0, c1, c2, c3]);
+        let transparent = [0, 0, 0, 0];
+
+        assert_eq!(first_row(0, &palette), vec![rgba(c0), rgba(c1), rgba(c2), transparent]);
+        assert_eq!(first_row(1, &palette), vec![rgba(c0), rgba(c1), rgba(Rgb555::new(15, 15, 15)), transparent]);
+        assert_eq!(first_row(2, &palette), vec![rgba(c0), rgba(c1), rgba(c2), rgba(c3)]);
+        assert_eq!(first_row(3, &palette), vec![rgba(c0), rgba(c1), rgba(Rgb555::new(11, 11, 11)), rgba(Rgb555::new(19, 19, 19))]);
+    }
+
+    #[test]
+    fn tex4x4_palette_offset_is_in_4_byte_units() {
+        let (c0, c1) = (Rgb555::new(1, 2, 3), Rgb555::new(4, 5, 6));
+        let palette = palette_data(&[Rgb555::default(), Rgb555::default(), c0, c1]);
+
+        let attrs = ((3u16 << 14) | 1).to_le_bytes();
+        assert_eq!(tex4x4_texel(&block_texels(), &attrs, 4, 1, 0, &palette, 0).unwrap(), rgba(c1));
+
+        // Mode 2 needs 4 colors from the offset, which runs past the palette
+        let attrs = ((2u16 << 14) | 1).to_le_bytes();
+        assert!(tex4x4_texel(&block_texels(), &attrs, 4, 0, 0, &palette, 0).is_err());
+    }
+
+    #[test]
+    fn paletted_color_0_is_transparent_only_when_asked() {
+        let palette = [Rgb555::new(31, 0, 0), Rgb555::new(0, 31, 0)];
+        // pal4 texels 0 and 1
+        let texture_data = [0b01_00];
+
+        assert_eq!(paletted_texel(&texture_data, 0, (2, 0), &palette, true).unwrap()[3], 0);
+        assert_eq!(paletted_texel(&texture_data, 0, (2, 0), &palette, false).unwrap(), rgba(palette[0]));
+        assert_eq!(paletted_texel(&texture_data, 1, (2, 0), &palette, true).unwrap(), rgba(palette[1]));
+    }
+}
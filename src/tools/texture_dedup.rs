@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use crate::{container::Container, error::AppError, subfiles::tex::Tex};
+
+// Format, S size, T size and color 0 transparency, followed by the texel and attribute bytes
+type TextureKey = (u8, u8, u8, bool, Vec<u8>, Vec<u8>);
+
+// Merges textures (and palettes) of a TEX0 with identical data, pointing the materials of every model to the kept one
+#[derive(Debug, Clone)]
+pub struct TextureDeduplicator {
+    tex_index: usize,
+    texture_duplicates: Vec<(String, String)>, // (duplicate, kept) names
+    palette_duplicates: Vec<(String, String)>
+}
+
+impl TextureDeduplicator {
+    pub fn new(container: &Container, tex_index: usize) -> Result<TextureDeduplicator, AppError> {
+        let tex = container.get_tex(tex_index)
+            .ok_or_else(|| AppError::new(&format!("TEX0 subfile {} not found", tex_index)))?;
+
+        Ok(TextureDeduplicator {
+            tex_index,
+            texture_duplicates: Self::find_texture_duplicates(tex)?,
+            palette_duplicates: Self::find_palette_duplicates(tex)?
+        })
+    }
+
+    pub fn texture_duplicates(&self) -> &[(String, String)] {
+        &self.texture_duplicates
+    }
+
+    pub fn palette_duplicates(&self) -> &[(String, String)] {
+        &self.palette_duplicates
+    }
+
+    // Removes the duplicates from the TEX0 and renames the pairings that used them. Returns the number of removed textures and palettes.
+    // The container must be rebased afterwards
+    pub fn apply(&self, container: &mut Container) -> Result<(usize, usize), AppError> {
+        for mdl in container.mdl_iter_mut() {
+            for model in mdl.models_iter_mut() {
+                let material_list = model.get_material_list_mut();
+
+                for (duplicate, kept) in &self.texture_duplicates {
+                    material_list.rename_texture_pairing(duplicate, kept)?;
+                }
+
+                for (duplicate, kept) in &self.palette_duplicates {
+                    material_list.rename_palette_pairing(duplicate, kept)?;
+                }
+            }
+        }
+
+        let tex = container.get_tex_mut(self.tex_index)
+            .ok_or_else(|| AppError::new(&format!("TEX0 subfile {} not found", self.tex_index)))?;
+
+        for (duplicate, _) in &self.texture_duplicates {
+            tex.remove_texture(duplicate)?;
+        }
+
+        for (duplicate, _) in &self.palette_duplicates {
+            tex.remove_palette(duplicate)?;
+        }
+
+        Ok((self.texture_duplicates.len(), self.palette_duplicates.len()))
+    }
+
+    fn find_texture_duplicates(tex: &Tex) -> Result<Vec<(String, String)>, AppError> {
+        let texture_list = tex.texture_list();
+
+        let mut first_by_key: HashMap<TextureKey, usize> = HashMap::new();
+        let mut duplicates = Vec::new();
+        for index in 0..texture_list.len() {
+            let params = texture_list.get_texture(index).unwrap().teximage_params();
+            let key = (
+                params.texture_format(),
+                params.texture_s_size(),
+                params.texture_t_size(),
+                params.palette_color_0_transparent(),
+                tex.texture_bytes(index).unwrap_or_default().to_vec(),
+                tex.texture_attr_bytes(index).unwrap_or_default().to_vec()
+            );
+
+            let first = *first_by_key.entry(key).or_insert(index);
+            if first != index {
+                duplicates.push((
+                    texture_list.get_texture_name(index).unwrap().to_not_null_string()?,
+                    texture_list.get_texture_name(first).unwrap().to_not_null_string()?
+                ));
+            }
+        }
+
+        Ok(duplicates)
+    }
+
+    fn find_palette_duplicates(tex: &Tex) -> Result<Vec<(String, String)>, AppError> {
+        let palette_list = tex.palette_list();
+
+        let mut first_by_colors: HashMap<&[u8], usize> = HashMap::new();
+        let mut duplicates = Vec::new();
+        for index in 0..palette_list.len() {
+            let first = *first_by_colors.entry(tex.palette_bytes(index).unwrap_or_default()).or_insert(index);
+            if first != index {
+                duplicates.push((
+                    palette_list.get_palette_name(index).unwrap().to_not_null_string()?,
+                    palette_list.get_palette_name(first).unwrap().to_not_null_string()?
+                ));
+            }
+        }
+
+        Ok(duplicates)
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{container::Container, error::AppError, subfiles::tex::texture::TeximageParams};
+
+use super::texture_import::{replace_texture_image, ImportOptions, ImportPalette};
+
+// Converts a pal256 texture to pal16 or pal4, or a pal16 one to pal4, re-quantizing its palette and re-indexing its
+// texels. The palette is the one paired with the texture's materials, or Tex::palette_for_texture if none is.
+// Without new_palette_name the palette is replaced in place, which fails if materials pair it with other textures. With it, the
+// palette is added (or replaced) under that name and the materials of the texture are paired with it.
+// The format of the materials is updated too. Returns the name of the new palette. The container must be rebased afterwards
+pub fn reduce_texture_depth(container: &mut Container, tex_index: usize, texture_name: &str, format: u8, new_palette_name: Option<&str>, options: ImportOptions) -> Result<String, AppError> {
+    let tex = container.get_tex(tex_index)
+        .ok_or_else(|| AppError::new(&format!("TEX0 subfile {} not found", tex_index)))?;
+    let texture_index = tex.texture_list().index_of_name(texture_name)
+        .ok_or_else(|| AppError::new(&format!("Texture {} not found in TEX0 subfile {}", texture_name, tex_index)))?;
+
+    let current_params = *tex.texture_list().get_texture(texture_index).unwrap().teximage_params();
+    let current_format = current_params.texture_format();
+    match (current_format, format) {
+        (TeximageParams::FORMAT_PALETTE_256, TeximageParams::FORMAT_PALETTE_16 | TeximageParams::FORMAT_PALETTE_4) |
+        (TeximageParams::FORMAT_PALETTE_16, TeximageParams::FORMAT_PALETTE_4) => {},
+        _ => {
+            let mut target_params = current_params;
+            target_params.set_texture_format(format)?;
+            return Err(AppError::new(&format!("Cannot reduce texture {} from {} to {}", texture_name, current_params.texture_format_name(), target_params.texture_format_name())));
+        }
+    }
+
+    // The palette the materials draw the texture with, falling back to the naming rules
+    let mut palette_names = Vec::new();
+    for mdl in container.mdl_iter() {
+        for model in mdl.models_iter() {
+            let material_list = model.get_material_list();
+            for material_index in material_list.materials_using_texture(texture_name) {
+                if let Some(palette_name) = material_list.palette_name_for_material(material_index) {
+                    palette_names.push(palette_name.to_not_null_string()?);
+                }
+            }
+        }
+    }
+    palette_names.sort();
+    palette_names.dedup();
+
+    let palette_name = match palette_names.as_slice() {
+        [palette_name] => palette_name.clone(),
+        [] => {
+            let palette_index = tex.palette_for_texture(texture_index)
+                .ok_or_else(|| AppError::new(&format!("Texture {} has no palette", texture_name)))?;
+            tex.palette_list().get_palette_name(palette_index).unwrap().to_not_null_string()?
+        },
+        _ => return Err(AppError::new(&format!("Texture {} is drawn with several palettes ({})", texture_name, palette_names.join(", "))))
+    };
+    let palette_index = tex.palette_list().index_of_name(&palette_name)
+        .ok_or_else(|| AppError::new(&format!("Palette {} not found in TEX0 subfile {}", palette_name, tex_index)))?;
+
+    if new_palette_name.is_none_or(|new_palette_name| new_palette_name == palette_name) {
+        let mut other_textures = Vec::new();
+        for mdl in container.mdl_iter() {
+            for model in mdl.models_iter() {
+                let material_list = model.get_material_list();
+                for material_index in material_list.materials_using_palette(&palette_name) {
+                    if let Some(other) = material_list.texture_name_for_material(material_index) {
+                        let other = other.to_not_null_string()?;
+                        if other != texture_name {
+                            other_textures.push(other);
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(other) = other_textures.first() {
+            return Err(AppError::new(&format!("Palette {} is also used by texture {}, so a new palette name is needed", palette_name, other)));
+        }
+    }
+
+    let image = tex.decode_texture(texture_index, Some(palette_index))?;
+    let new_palette_name = new_palette_name.unwrap_or(&palette_name).to_string();
+
+    let tex = container.get_tex_mut(tex_index).unwrap();
+    let params = tex.texture_list_mut().get_texture_mut(texture_index).unwrap().teximage_params_mut();
+    params.set_texture_format(format)?;
+    if let Err(err) = replace_texture_image(tex, texture_name, &image, ImportPalette::Generate(&new_palette_name), options) {
+        tex.texture_list_mut().get_texture_mut(texture_index).unwrap().teximage_params_mut().set_texture_format(current_format)?;
+        return Err(err);
+    }
+
+    for mdl in container.mdl_iter_mut() {
+        for model in mdl.models_iter_mut() {
+            let material_list = model.get_material_list_mut();
+            for material_index in material_list.materials_using_texture(texture_name) {
+                material_list.get_material_mut(material_index).unwrap().teximage_params_mut().set_texture_format(format)?;
+
+                if new_palette_name != palette_name {
+                    material_list.set_palette_for_material(material_index, &new_palette_name)?;
+                }
+            }
+        }
+    }
+
+    Ok(new_palette_name)
+}
@@ -0,0 +1,537 @@
+use std::collections::HashMap;
+
+use crate::{error::AppError, subfiles::tex::texture::TeximageParams, util::color::Rgb555};
+
+use super::texture_decode::{blend, texel_layout};
+
+// Texels with a lower alpha are encoded as transparent
+const ALPHA_THRESHOLD: u8 = 0x80;
+
+// Compressed 4x4-texel data, laid out as in the TEX0 blocks (palette offsets in the attributes start at 0)
+#[derive(Debug, Clone)]
+pub struct EncodedTex4x4 {
+    pub texel_data: Vec<u8>,
+    pub attr_data: Vec<u8>,
+    pub palette_data: Vec<u8>
+}
+
+// Encodes 8-bit RGBA (row by row from the top left texel) as a compressed 4x4-texel texture.
+// For every block the mode with the lowest error is picked, and identical block palettes are shared
+pub fn encode_tex4x4(width: usize, height: usize, rgba: &[u8]) -> Result<EncodedTex4x4, AppError> {
+    if !width.is_multiple_of(4) || !height.is_multiple_of(4) {
+        return Err(AppError::new(&format!("tex4x4 texture size must be a multiple of 4, got {}x{}", width, height)));
+    }
+
+    check_rgba_size(width, height, rgba)?;
+
+    let blocks_x = width / 4;
+    let num_blocks = blocks_x * (height / 4);
+
+    let mut texel_data = Vec::with_capacity(num_blocks * 4);
+    let mut attr_data = Vec::with_capacity(num_blocks * 2);
+    let mut palette = PaletteBuilder::default();
+
+    for block in 0..num_blocks {
+        let block_x = (block % blocks_x) * 4;
+        let block_y = (block / blocks_x) * 4;
+
+        let mut texels = [None; 16];
+        for (i, texel) in texels.iter_mut().enumerate() {
+            let offset = ((block_y + i / 4) * width + block_x + i % 4) * 4;
+            if rgba[offset + 3] >= ALPHA_THRESHOLD {
+                *texel = Some(Rgb555::from_rgb8([rgba[offset], rgba[offset + 1], rgba[offset + 2]]));
+            }
+        }
+
+        let encoded = encode_block(&texels);
+
+        let palette_offset = palette.add(&encoded.palette)?;
+        attr_data.extend(((encoded.mode << 14) | palette_offset).to_le_bytes());
+
+        for row in 0..4 {
+            let row_texels = (0..4).fold(0u8, |byte, column| byte | (encoded.indices[row * 4 + column] << (column * 2)));
+            texel_data.push(row_texels);
+        }
+    }
+
+    Ok(EncodedTex4x4 {
+        texel_data,
+        attr_data,
+        palette_data: palette.into_bytes()
+    })
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PalettedEncodeOptions {
+    pub dither: bool, // Floyd-Steinberg error diffusion
+    pub color_0_transparent: bool // Index 0 is reserved for transparent texels
+}
+
+// Paletted texture data and the palette it was quantized to
+#[derive(Debug, Clone)]
+pub struct EncodedPaletted {
+    pub texture_data: Vec<u8>,
+    pub palette_data: Vec<u8> // Used colors, padded to the palette alignment (4 colors for pal4, 8 otherwise)
+}
+
+// Encodes 8-bit RGBA (row by row from the top left texel) as a pal4, pal16, pal256, a3i5 or a5i3 texture.
+// Colors are quantized with median cut when there are more than the format allows
+pub fn encode_paletted(width: usize, height: usize, rgba: &[u8], format: u8, options: PalettedEncodeOptions) -> Result<EncodedPaletted, AppError> {
+    let (index_bits, alpha_bits) = texel_layout(format)?;
+    check_rgba_size(width, height, rgba)?;
+
+    let reserved = (options.color_0_transparent && alpha_bits == 0) as usize;
+    let max_colors = (1 << index_bits) - reserved;
+
+    let mut histogram: HashMap<Rgb555, u32> = HashMap::new();
+    for texel in rgba.chunks_exact(4).filter(|texel| reserved == 0 || texel[3] >= ALPHA_THRESHOLD) {
+        let color = Rgb555::from_rgb8([texel[0], texel[1], texel[2]]);
+        *histogram.entry(color).or_default() += 1;
+    }
+
+    let mut palette = vec![Rgb555::default(); reserved];
+    palette.extend(median_cut(histogram, max_colors));
+
+    let texture_data = encode_with_palette(width, height, rgba, format, &palette, options)?;
+
+    let alignment = if format == TeximageParams::FORMAT_PALETTE_4 { 4 } else { 8 };
+    palette.resize(palette.len().div_ceil(alignment) * alignment, Rgb555::default());
+    let palette_data = palette.iter().flat_map(|color| color.to_u16().to_le_bytes()).collect();
+
+    Ok(EncodedPaletted {
+        texture_data,
+        palette_data
+    })
+}
+
+// Same as encode_paletted, but texels are mapped to the nearest color of an existing palette.
+// Only the colors the format can index are used
+pub fn encode_with_palette(width: usize, height: usize, rgba: &[u8], format: u8, palette: &[Rgb555], options: PalettedEncodeOptions) -> Result<Vec<u8>, AppError> {
+    let (index_bits, alpha_bits) = texel_layout(format)?;
+    check_rgba_size(width, height, rgba)?;
+
+    let bits_per_texel = index_bits + alpha_bits;
+    if !(width * bits_per_texel).is_multiple_of(8) {
+        return Err(AppError::new(&format!("Texture width {} does not fill whole bytes with {} bits per texel", width, bits_per_texel)));
+    }
+
+    let reserved = (options.color_0_transparent && alpha_bits == 0) as usize;
+    let palette = &palette[..palette.len().min(1 << index_bits)];
+    if palette.len() <= reserved {
+        return Err(AppError::new("Palette has no colors to encode the texture with"));
+    }
+
+    let alpha_max = (1u16 << alpha_bits) - 1;
+
+    // Errors are diffused in 8-bit space, so they are kept per texel and channel
+    let mut diffused = vec![[0f32; 3]; width * height];
+    let mut texels = vec![0u8; width * height];
+    for texel in 0..width * height {
+        let alpha = rgba[texel * 4 + 3];
+        if reserved == 1 && alpha < ALPHA_THRESHOLD {
+            continue;
+        }
+
+        let wanted = [0, 1, 2].map(|channel| (rgba[texel * 4 + channel] as f32 + diffused[texel][channel]).clamp(0.0, 255.0));
+        let target = Rgb555::from_rgb8(wanted.map(|channel| channel.round() as u8));
+
+        let (index, color) = palette.iter()
+            .enumerate()
+            .skip(reserved)
+            .min_by_key(|&(_, &color)| distance(target, color))
+            .map(|(index, &color)| (index, color))
+            .unwrap();
+
+        // Translucent formats keep the alpha in the top bits
+        let alpha = ((alpha as u16 * alpha_max + 127) / 255) as u8;
+        texels[texel] = index as u8 | (alpha << index_bits);
+
+        if options.dither {
+            let expanded = color.to_rgb8().map(|channel| channel as f32);
+            let error = [0, 1, 2].map(|channel| wanted[channel] - expanded[channel]);
+
+            let (x, y) = (texel % width, texel / width);
+            let mut spread = |dx: isize, dy: usize, weight: f32| {
+                let (nx, ny) = (x as isize + dx, y + dy);
+                if nx >= 0 && (nx as usize) < width && ny < height {
+                    let neighbour = &mut diffused[ny * width + nx as usize];
+                    for channel in 0..3 {
+                        neighbour[channel] += error[channel] * weight;
+                    }
+                }
+            };
+
+            spread(1, 0, 7.0 / 16.0);
+            spread(-1, 1, 3.0 / 16.0);
+            spread(0, 1, 5.0 / 16.0);
+            spread(1, 1, 1.0 / 16.0);
+        }
+    }
+
+    // Texels are packed from the lowest bits
+    let texels_per_byte = 8 / bits_per_texel;
+    let texture_data = texels.chunks(texels_per_byte)
+        .map(|texels| texels.iter()
+            .enumerate()
+            .fold(0u8, |byte, (i, &texel)| byte | (texel << (i * bits_per_texel))))
+        .collect();
+
+    Ok(texture_data)
+}
+
+// Encodes 8-bit RGBA as a direct color texture. Bit 15 of each texel is set for opaque texels
+pub fn encode_direct(width: usize, height: usize, rgba: &[u8]) -> Result<Vec<u8>, AppError> {
+    check_rgba_size(width, height, rgba)?;
+
+    let texture_data = rgba.chunks_exact(4)
+        .flat_map(|texel| {
+            let opaque = if texel[3] >= ALPHA_THRESHOLD { 0x8000 } else { 0 };
+            (Rgb555::from_rgb8([texel[0], texel[1], texel[2]]).to_u16() | opaque).to_le_bytes()
+        })
+        .collect();
+
+    Ok(texture_data)
+}
+
+fn check_rgba_size(width: usize, height: usize, rgba: &[u8]) -> Result<(), AppError> {
+    if rgba.len() != width * height * 4 {
+        return Err(AppError::new(&format!("Expected {} bytes of RGBA data for a {}x{} texture, got {}", width * height * 4, width, height, rgba.len())));
+    }
+
+    Ok(())
+}
+
+// Splits the color box with the widest channel range at its median (by texel count) until there are
+// max_colors boxes, and returns the average color of each box
+fn median_cut(histogram: HashMap<Rgb555, u32>, max_colors: usize) -> Vec<Rgb555> {
+    let mut colors: Vec<(Rgb555, u32)> = histogram.into_iter().collect();
+    // Sorted so the result does not depend on the hash map order
+    colors.sort_by_key(|&(color, _)| color.to_u16());
+
+    if colors.len() <= max_colors {
+        return colors.into_iter().map(|(color, _)| color).collect();
+    }
+
+    let channel = |color: Rgb555, channel: usize| [color.r, color.g, color.b][channel];
+    let widest_channel = |colors: &[(Rgb555, u32)]| (0..3)
+        .map(|c| {
+            let values = colors.iter().map(|&(color, _)| channel(color, c));
+            (c, values.clone().max().unwrap() - values.min().unwrap())
+        })
+        .max_by_key(|&(_, range)| range)
+        .unwrap();
+
+    let mut boxes = vec![colors];
+    while boxes.len() < max_colors {
+        let Some((box_index, (split_channel, _))) = boxes.iter()
+            .enumerate()
+            .map(|(i, colors)| (i, widest_channel(colors)))
+            .filter(|&(_, (_, range))| range > 0)
+            .max_by_key(|&(_, (_, range))| range) else {
+            break;
+        };
+
+        let mut colors = boxes.swap_remove(box_index);
+        colors.sort_by_key(|&(color, _)| channel(color, split_channel));
+
+        let total: u32 = colors.iter().map(|&(_, count)| count).sum();
+        let mut accumulated = 0;
+        let mut median = 1;
+        for (i, &(_, count)) in colors.iter().enumerate() {
+            accumulated += count;
+            if accumulated * 2 >= total {
+                median = (i + 1).clamp(1, colors.len() - 1);
+                break;
+            }
+        }
+
+        let upper = colors.split_off(median);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+
+    boxes.iter()
+        .map(|colors| {
+            let total: u32 = colors.iter().map(|&(_, count)| count).sum();
+            let average = |c: usize| ((colors.iter().map(|&(color, count)| channel(color, c) as u32 * count).sum::<u32>() + total / 2) / total) as u8;
+            Rgb555::new(average(0), average(1), average(2))
+        })
+        .collect()
+}
+
+struct EncodedBlock {
+    mode: u16,
+    palette: Vec<Rgb555>, // Colors stored in the palette (2, 3 or 4 depending on the mode)
+    indices: [u8; 16],
+    error: u32
+}
+
+fn encode_block(texels: &[Option<Rgb555>; 16]) -> EncodedBlock {
+    let mut distinct: Vec<Rgb555> = Vec::new();
+    for &texel in texels.iter().flatten() {
+        if !distinct.contains(&texel) {
+            distinct.push(texel);
+        }
+    }
+
+    let transparent = texels.iter().any(|texel| texel.is_none());
+
+    // Modes 0 and 2 store the colors directly, 1 and 3 interpolate between 2 colors. Color 3 is transparent in modes 0 and 1
+    let (direct_mode, direct_colors, interpolated_mode) = if transparent { (0, 3, 1) } else { (2, 4, 3) };
+
+    let direct = {
+        // Unused slots repeat the first color, as the hardware reads them anyway
+        let mut colors = pick_colors(&distinct, direct_colors);
+        colors.resize(direct_colors, colors.first().copied().unwrap_or_default());
+        build_block(texels, direct_mode, colors.clone(), &colors)
+    };
+
+    if distinct.len() <= direct_colors {
+        return direct;
+    }
+
+    let endpoints = pick_colors(&distinct, 2);
+    let (c0, c1) = (endpoints[0], endpoints[1]);
+    let colors = if transparent {
+        vec![c0, c1, blend(c0, c1, 4, 4)]
+    } else {
+        vec![c0, c1, blend(c0, c1, 5, 3), blend(c0, c1, 3, 5)]
+    };
+    let interpolated = build_block(texels, interpolated_mode, endpoints, &colors);
+
+    if interpolated.error < direct.error { interpolated } else { direct }
+}
+
+// Maps every texel to its closest color. Transparent texels use index 3
+fn build_block(texels: &[Option<Rgb555>; 16], mode: u16, palette: Vec<Rgb555>, colors: &[Rgb555]) -> EncodedBlock {
+    let mut indices = [3u8; 16];
+    let mut error = 0;
+
+    for (index, texel) in indices.iter_mut().zip(texels.iter()) {
+        if let Some(texel) = texel {
+            let (closest, distance) = colors.iter()
+                .enumerate()
+                .map(|(i, &color)| (i, distance(*texel, color)))
+                .min_by_key(|&(_, distance)| distance)
+                .unwrap();
+
+            *index = closest as u8;
+            error += distance;
+        }
+    }
+
+    EncodedBlock {
+        mode,
+        palette,
+        indices,
+        error
+    }
+}
+
+// Greedy farthest point selection: starts with the pair of colors furthest apart,
+// then adds the color furthest from the ones already picked
+fn pick_colors(colors: &[Rgb555], count: usize) -> Vec<Rgb555> {
+    if colors.len() <= count {
+        return colors.to_vec();
+    }
+
+    let mut first_pair = (colors[0], colors[0]);
+    let mut max_distance = 0;
+    for (i, &a) in colors.iter().enumerate() {
+        for &b in colors[i + 1..].iter() {
+            if distance(a, b) > max_distance {
+                max_distance = distance(a, b);
+                first_pair = (a, b);
+            }
+        }
+    }
+
+    let mut picked = vec![first_pair.0, first_pair.1];
+    while picked.len() < count {
+        let next = colors.iter()
+            .filter(|color| !picked.contains(color))
+            .max_by_key(|&&color| picked.iter().map(|&p| distance(color, p)).min().unwrap())
+            .copied()
+            .unwrap();
+
+        picked.push(next);
+    }
+
+    picked
+}
+
+// Block palettes are stored in 4 byte units (2 colors), so 3 color palettes take 4 slots
+#[derive(Default)]
+struct PaletteBuilder {
+    colors: Vec<u16>,
+    offsets: HashMap<Vec<u16>, u16>
+}
+
+impl PaletteBuilder {
+    const MAX_OFFSET: usize = 0x3FFF;
+
+    fn add(&mut self, palette: &[Rgb555]) -> Result<u16, AppError> {
+        let mut colors: Vec<u16> = palette.iter().map(|color| color.to_u16()).collect();
+        if !colors.len().is_multiple_of(2) {
+            colors.push(0);
+        }
+
+        if let Some(&offset) = self.offsets.get(&colors) {
+            return Ok(offset);
+        }
+
+        let offset = self.colors.len() / 2;
+        if offset > Self::MAX_OFFSET {
+            return Err(AppError::new(&format!("tex4x4 palette is too big. Block palette offsets are limited to 0x{:X}", Self::MAX_OFFSET)));
+        }
+
+        self.colors.extend(colors.iter());
+        self.offsets.insert(colors, offset as u16);
+
+        Ok(offset as u16)
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.colors.iter().flat_map(|color| color.to_le_bytes()).collect()
+    }
+}
+
+fn distance(a: Rgb555, b: Rgb555) -> u32 {
+    let channel = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+    channel(a.r, b.r) + channel(a.g, b.g) + channel(a.b, b.b)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tools::texture_decode::{paletted_texel, tex4x4_texel};
+
+    use super::*;
+
+    fn gray(value: u8) -> Option<Rgb555> {
+        Some(Rgb555::new(value, value, value))
+    }
+
+    fn to_rgba(texels: &[Option<Rgb555>]) -> Vec<u8> {
+        texels.iter()
+            .flat_map(|texel| match texel {
+                Some(color) => {
+                    let [r, g, b] = color.to_rgb8();
+                    [r, g, b, 0xFF]
+                },
+                None => [0xFF, 0, 0xFF, 0]
+            })
+            .collect()
+    }
+
+    // Encodes a single 4x4 block and returns its mode and its decoded texels
+    fn round_trip(texels: &[Option<Rgb555>; 16]) -> (u16, Vec<[u8; 4]>) {
+        let encoded = encode_tex4x4(4, 4, &to_rgba(texels)).unwrap();
+        let mode = u16::from_le_bytes([encoded.attr_data[0], encoded.attr_data[1]]) >> 14;
+
+        let decoded = (0..16)
+            .map(|i| tex4x4_texel(&encoded.texel_data, &encoded.attr_data, 4, i % 4, i / 4, &encoded.palette_data, 0).unwrap())
+            .collect();
+
+        (mode, decoded)
+    }
+
+    fn expected(texels: &[Option<Rgb555>]) -> Vec<[u8; 4]> {
+        to_rgba(texels).chunks_exact(4)
+            .map(|texel| if texel[3] == 0 { [0, 0, 0, 0] } else { [texel[0], texel[1], texel[2], 0xFF] })
+            .collect()
+    }
+
+    #[test]
+    fn tex4x4_opaque_block_with_few_colors_is_stored_directly() {
+        let (a, b, c, d) = (gray(0), gray(31), Some(Rgb555::new(31, 0, 0)), Some(Rgb555::new(0, 0, 31)));
+        let texels = [a, b, c, d, a, a, b, b, c, c, d, d, a, b, c, d];
+
+        let (mode, decoded) = round_trip(&texels);
+        assert_eq!(mode, 2);
+        assert_eq!(decoded, expected(&texels));
+    }
+
+    #[test]
+    fn tex4x4_transparent_block_with_few_colors_is_stored_directly() {
+        let (a, b, c) = (gray(0), gray(31), Some(Rgb555::new(31, 0, 0)));
+        let texels = [a, b, c, None, None, a, b, c, c, None, a, b, a, b, c, None];
+
+        let (mode, decoded) = round_trip(&texels);
+        assert_eq!(mode, 0);
+        assert_eq!(decoded, expected(&texels));
+    }
+
+    #[test]
+    fn tex4x4_transparent_block_interpolates_between_its_extremes() {
+        // Gray 15 is the midpoint of black and white. The single green texel is approximated by it
+        let (black, white, mid, green) = (gray(0), gray(31), gray(15), Some(Rgb555::new(0, 31, 0)));
+        let texels = [black, white, mid, mid, mid, mid, mid, black, white, green, None, None, None, None, None, None];
+
+        let (mode, decoded) = round_trip(&texels);
+        assert_eq!(mode, 1);
+
+        let mut wanted = texels;
+        wanted[9] = mid;
+        assert_eq!(decoded, expected(&wanted));
+    }
+
+    #[test]
+    fn tex4x4_opaque_block_interpolates_between_its_extremes() {
+        // Grays 11 and 19 are 3/8 and 5/8 between black and white. 12 and 18 are approximated by them
+        let texels = [0, 0, 31, 31, 11, 11, 11, 11, 19, 19, 19, 19, 19, 19, 12, 18].map(gray);
+
+        let (mode, decoded) = round_trip(&texels);
+        assert_eq!(mode, 3);
+
+        let mut wanted = texels;
+        wanted[14] = gray(11);
+        wanted[15] = gray(19);
+        assert_eq!(decoded, expected(&wanted));
+    }
+
+    #[test]
+    fn tex4x4_identical_block_palettes_are_shared() {
+        let block = [gray(0), gray(31), gray(5), None];
+        let texels: Vec<Option<Rgb555>> = (0..32).map(|i| block[i % 4]).collect();
+
+        let encoded = encode_tex4x4(8, 4, &to_rgba(&texels)).unwrap();
+        assert_eq!(encoded.attr_data, vec![0, 0, 0, 0]);
+        assert_eq!(encoded.palette_data.len(), 8);
+    }
+
+    #[test]
+    fn pal4_round_trips_with_transparent_color_0() {
+        let (red, green, blue) = (Some(Rgb555::new(31, 0, 0)), Some(Rgb555::new(0, 31, 0)), Some(Rgb555::new(0, 0, 31)));
+        let texels = [red, green, None, blue, blue, None, green, red];
+        let options = PalettedEncodeOptions { color_0_transparent: true, ..Default::default() };
+
+        let encoded = encode_paletted(4, 2, &to_rgba(&texels), TeximageParams::FORMAT_PALETTE_4, options).unwrap();
+        let palette: Vec<Rgb555> = encoded.palette_data.chunks_exact(2)
+            .map(|color| Rgb555::from_u16(u16::from_le_bytes([color[0], color[1]])))
+            .collect();
+        assert_eq!(palette.len(), 4);
+
+        let decoded: Vec<[u8; 4]> = (0..8)
+            .map(|texel| paletted_texel(&encoded.texture_data, texel, (2, 0), &palette, true).unwrap())
+            .collect();
+        assert_eq!(decoded, expected(&texels));
+    }
+
+    #[test]
+    fn paletted_colors_are_quantized_to_the_format() {
+        // 8 grays in a pal4 texture keep only 4 colors, each close to the texels it replaces
+        let texels: Vec<Option<Rgb555>> = (0..8).map(|i| gray(i * 4)).collect();
+
+        let encoded = encode_paletted(8, 1, &to_rgba(&texels), TeximageParams::FORMAT_PALETTE_4, PalettedEncodeOptions::default()).unwrap();
+        let palette: Vec<Rgb555> = encoded.palette_data.chunks_exact(2)
+            .map(|color| Rgb555::from_u16(u16::from_le_bytes([color[0], color[1]])))
+            .collect();
+        assert_eq!(palette.len(), 4);
+
+        for (texel, original) in texels.iter().enumerate() {
+            let [r, _, _, a] = paletted_texel(&encoded.texture_data, texel, (2, 0), &palette, false).unwrap();
+            assert_eq!(a, 0xFF);
+            assert!((r as i32 - original.unwrap().to_rgb8()[0] as i32).abs() <= 20);
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use png::{BitDepth, ColorType, Encoder};
+
+use crate::{error::AppError, subfiles::tex::Tex};
+
+use super::texture_decode::DecodedTexture;
+
+// Encodes 8-bit RGBA as a PNG
+pub fn encode_png(image: &DecodedTexture) -> Result<Vec<u8>, AppError> {
+    let mut bytes = Vec::new();
+
+    let mut encoder = Encoder::new(&mut bytes, image.width as u32, image.height as u32);
+    encoder.set_color(ColorType::Rgba);
+    encoder.set_depth(BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(|err| AppError::new(&format!("Could not write PNG header: {}", err)))?;
+    writer.write_image_data(&image.rgba).map_err(|err| AppError::new(&format!("Could not write PNG data: {}", err)))?;
+    writer.finish().map_err(|err| AppError::new(&format!("Could not finish PNG: {}", err)))?;
+
+    Ok(bytes)
+}
+
+// Decodes a texture (with a palette for every format but direct) and encodes it as a PNG
+pub fn export_png(tex: &Tex, texture_index: usize, palette_index: Option<usize>) -> Result<Vec<u8>, AppError> {
+    encode_png(&tex.decode_texture(texture_index, palette_index)?)
+}
@@ -0,0 +1,115 @@
+use std::io::Cursor;
+
+use png::{ColorType, Decoder, Transformations};
+
+use crate::{error::AppError, subfiles::tex::{texture::TeximageParams, Tex}};
+
+use super::{texture_decode::{Color0Mode, DecodedTexture}, texture_encode::{encode_direct, encode_paletted, encode_tex4x4, encode_with_palette, PalettedEncodeOptions}};
+
+// Palette of the imported texture, for the formats that use one
+#[derive(Debug, Clone, Copy)]
+pub enum ImportPalette<'a> {
+    None, // Direct textures
+    Existing(&'a str), // Texels are mapped to the colors of this palette, which is left as is
+    Generate(&'a str) // A palette is quantized from the image, replacing the palette with this name or adding it
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImportOptions {
+    pub dither: bool,
+    // Transparent reserves palette index 0 for the transparent texels, and both Transparent and Opaque set the
+    // color 0 transparency flag of the texture to match, so the texture decodes back to the image
+    pub color_0: Color0Mode
+}
+
+// Decodes any PNG to 8-bit RGBA
+pub fn decode_png(bytes: &[u8]) -> Result<DecodedTexture, AppError> {
+    let mut decoder = Decoder::new(Cursor::new(bytes));
+    decoder.set_transformations(Transformations::normalize_to_color8());
+
+    let mut reader = decoder.read_info().map_err(|err| AppError::new(&format!("Invalid PNG: {}", err)))?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|err| AppError::new(&format!("Invalid PNG: {}", err)))?;
+    let pixels = &buffer[..info.buffer_size()];
+
+    let rgba = match info.color_type {
+        ColorType::Rgba => pixels.to_vec(),
+        ColorType::Rgb => pixels.chunks_exact(3).flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 0xFF]).collect(),
+        ColorType::GrayscaleAlpha => pixels.chunks_exact(2).flat_map(|ga| [ga[0], ga[0], ga[0], ga[1]]).collect(),
+        ColorType::Grayscale => pixels.iter().flat_map(|&g| [g, g, g, 0xFF]).collect(),
+        ColorType::Indexed => return Err(AppError::new("Indexed PNG was not expanded to RGB"))
+    };
+
+    Ok(DecodedTexture {
+        width: info.width as usize,
+        height: info.height as usize,
+        rgba
+    })
+}
+
+// Replaces a texture with a PNG image, re-encoded to the format of the texture
+pub fn import_png(tex: &mut Tex, texture_name: &str, png: &[u8], palette: ImportPalette, options: ImportOptions) -> Result<(), AppError> {
+    let image = decode_png(png)?;
+    replace_texture_image(tex, texture_name, &image, palette, options)
+}
+
+// Replaces a texture with an RGBA image, re-encoded to the format of the texture. The image size can differ
+// from the texture size (it must still be a valid DS size), and the texture params and data blocks are updated
+pub fn replace_texture_image(tex: &mut Tex, texture_name: &str, image: &DecodedTexture, palette: ImportPalette, options: ImportOptions) -> Result<(), AppError> {
+    let index = tex.texture_list().index_of_name(texture_name)
+        .ok_or_else(|| AppError::new(&format!("Tex has no texture named {}", texture_name)))?;
+
+    let mut params = *tex.texture_list().get_texture(index).unwrap().teximage_params();
+    params.set_texture_s_size(TeximageParams::size_to_bits(image.width as u16)?)?;
+    params.set_texture_t_size(TeximageParams::size_to_bits(image.height as u16)?)?;
+    params.set_palette_color_0_transparent(options.color_0.is_transparent(&params));
+
+    let encode_options = PalettedEncodeOptions {
+        dither: options.dither,
+        color_0_transparent: params.palette_color_0_transparent()
+    };
+
+    let format = params.texture_format();
+    let (data, palette_data) = match (format, palette) {
+        (TeximageParams::FORMAT_NONE, _) => {
+            return Err(AppError::new(&format!("Texture {} has no format", texture_name)));
+        },
+        (TeximageParams::FORMAT_DIRECT, _) => {
+            (encode_direct(image.width, image.height, &image.rgba)?, None)
+        },
+        (TeximageParams::FORMAT_COMPRESSED_4X4, ImportPalette::Generate(palette_name)) => {
+            let encoded = encode_tex4x4(image.width, image.height, &image.rgba)?;
+            ([encoded.texel_data, encoded.attr_data].concat(), Some((palette_name, encoded.palette_data)))
+        },
+        (TeximageParams::FORMAT_COMPRESSED_4X4, _) => {
+            return Err(AppError::new(&format!("Texture {} is tex4x4, which needs a generated palette", texture_name)));
+        },
+        (_, ImportPalette::Existing(palette_name)) => {
+            let palette_index = tex.palette_list().index_of_name(palette_name)
+                .ok_or_else(|| AppError::new(&format!("Tex has no palette named {}", palette_name)))?;
+            let colors = tex.palette_colors(palette_index).unwrap_or_default();
+
+            (encode_with_palette(image.width, image.height, &image.rgba, format, &colors, encode_options)?, None)
+        },
+        (_, ImportPalette::Generate(palette_name)) => {
+            let encoded = encode_paletted(image.width, image.height, &image.rgba, format, encode_options)?;
+            (encoded.texture_data, Some((palette_name, encoded.palette_data)))
+        },
+        (_, ImportPalette::None) => {
+            return Err(AppError::new(&format!("Texture {} is {}, which needs a palette", texture_name, params.texture_format_name())));
+        }
+    };
+
+    tex.replace_texture(index, params, &data)?;
+
+    if let Some((palette_name, palette_data)) = palette_data {
+        match tex.palette_list().index_of_name(palette_name) {
+            Some(palette_index) => tex.replace_palette(palette_index, &palette_data)?,
+            None => {
+                tex.add_palette(palette_name, &palette_data)?;
+            }
+        }
+    }
+
+    Ok(())
+}
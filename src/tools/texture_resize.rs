@@ -0,0 +1,115 @@
+use crate::{container::Container, error::AppError, subfiles::tex::texture::TeximageParams};
+
+use super::{texture_decode::DecodedTexture, texture_import::{replace_texture_image, ImportOptions, ImportPalette}};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResizeFilter {
+    Nearest,
+    Bilinear
+}
+
+// Nearest valid texture dimension (a power of two between 8 and 1024). Ties go to the bigger size
+pub fn nearest_texture_size(size: usize) -> u16 {
+    let mut nearest = 8usize;
+    for candidate in (3..=10).map(|bits| 1usize << bits) {
+        if candidate.abs_diff(size) <= nearest.abs_diff(size) {
+            nearest = candidate;
+        }
+    }
+
+    nearest as u16
+}
+
+pub fn resize_image(image: &DecodedTexture, width: usize, height: usize, filter: ResizeFilter) -> DecodedTexture {
+    let mut rgba = Vec::with_capacity(width * height * 4);
+
+    let scale_x = image.width as f32 / width as f32;
+    let scale_y = image.height as f32 / height as f32;
+    let texel = |x: usize, y: usize| {
+        let offset = (y * image.width + x) * 4;
+        &image.rgba[offset..offset + 4]
+    };
+
+    for y in 0..height {
+        for x in 0..width {
+            // Texel centers are sampled, so the image does not drift when scaling
+            let src_x = (x as f32 + 0.5) * scale_x - 0.5;
+            let src_y = (y as f32 + 0.5) * scale_y - 0.5;
+
+            match filter {
+                ResizeFilter::Nearest => {
+                    let nearest_x = (src_x.round().max(0.0) as usize).min(image.width - 1);
+                    let nearest_y = (src_y.round().max(0.0) as usize).min(image.height - 1);
+                    rgba.extend_from_slice(texel(nearest_x, nearest_y));
+                },
+                ResizeFilter::Bilinear => {
+                    let src_x = src_x.clamp(0.0, (image.width - 1) as f32);
+                    let src_y = src_y.clamp(0.0, (image.height - 1) as f32);
+                    let (x0, y0) = (src_x.floor() as usize, src_y.floor() as usize);
+                    let (x1, y1) = ((x0 + 1).min(image.width - 1), (y0 + 1).min(image.height - 1));
+                    let (fx, fy) = (src_x - x0 as f32, src_y - y0 as f32);
+
+                    for channel in 0..4 {
+                        let top = texel(x0, y0)[channel] as f32 * (1.0 - fx) + texel(x1, y0)[channel] as f32 * fx;
+                        let bottom = texel(x0, y1)[channel] as f32 * (1.0 - fx) + texel(x1, y1)[channel] as f32 * fx;
+                        rgba.push((top * (1.0 - fy) + bottom * fy).round() as u8);
+                    }
+                }
+            }
+        }
+    }
+
+    DecodedTexture {
+        width,
+        height,
+        rgba
+    }
+}
+
+// Resizes a texture of a TEX0 subfile to the nearest valid size of width x height, re-encoding it in its format, and
+// updates the size of the materials paired with it. Paletted textures keep their palette (by default the one
+// Tex::palette_for_texture picks), tex4x4 ones regenerate it.
+// Mesh texcoords are in texels, so they are not scaled along. Returns the new size. The container must be rebased afterwards
+pub fn resize_texture(container: &mut Container, tex_index: usize, texture_name: &str, palette_name: Option<&str>, width: usize, height: usize, filter: ResizeFilter) -> Result<(u16, u16), AppError> {
+    let tex = container.get_tex_mut(tex_index)
+        .ok_or_else(|| AppError::new(&format!("TEX0 subfile {} not found", tex_index)))?;
+
+    let texture_index = tex.texture_list().index_of_name(texture_name)
+        .ok_or_else(|| AppError::new(&format!("Texture {} not found in TEX0 subfile {}", texture_name, tex_index)))?;
+    let palette_index = match palette_name {
+        Some(palette_name) => Some(tex.palette_list().index_of_name(palette_name)
+            .ok_or_else(|| AppError::new(&format!("Palette {} not found in TEX0 subfile {}", palette_name, tex_index)))?),
+        None => tex.palette_for_texture(texture_index)
+    };
+    let palette_name = match palette_index {
+        Some(palette_index) => Some(tex.palette_list().get_palette_name(palette_index).unwrap().to_not_null_string()?),
+        None => None
+    };
+    let palette_name = palette_name.as_deref();
+
+    let image = tex.decode_texture(texture_index, palette_index)?;
+
+    let new_width = nearest_texture_size(width);
+    let new_height = nearest_texture_size(height);
+    let resized = resize_image(&image, new_width as usize, new_height as usize, filter);
+
+    let format = tex.texture_list().get_texture(texture_index).unwrap().teximage_params().texture_format();
+    let palette = match (format, palette_name) {
+        (TeximageParams::FORMAT_DIRECT, _) | (_, None) => ImportPalette::None,
+        (TeximageParams::FORMAT_COMPRESSED_4X4, Some(palette_name)) => ImportPalette::Generate(palette_name),
+        (_, Some(palette_name)) => ImportPalette::Existing(palette_name)
+    };
+
+    replace_texture_image(tex, texture_name, &resized, palette, ImportOptions::default())?;
+
+    for mdl in container.mdl_iter_mut() {
+        for model in mdl.models_iter_mut() {
+            let material_list = model.get_material_list_mut();
+            for material_index in material_list.materials_using_texture(texture_name) {
+                material_list.get_material_mut(material_index).unwrap().set_texture_size(new_width, new_height)?;
+            }
+        }
+    }
+
+    Ok((new_width, new_height))
+}
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+
+// Greedy stripifier working on vertex indices.
+// Strips follow the GPU winding: triangle i is (v[i], v[i+1], v[i+2]) for even i and (v[i+1], v[i], v[i+2]) for odd i
+#[derive(Debug, Clone)]
+pub struct TriangleStripifier {
+    triangles: Vec<[u32; 3]>,
+    edges: HashMap<(u32, u32), Vec<usize>> // Directed edge -> triangles containing it
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StripifyResult {
+    pub strips: Vec<Vec<u32>>, // Every strip has at least 2 triangles
+    pub triangles: Vec<[u32; 3]> // Triangles that did not fit in any strip
+}
+
+impl TriangleStripifier {
+    pub fn new(triangles: Vec<[u32; 3]>) -> TriangleStripifier {
+        let mut edges: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+        for (i, &[a, b, c]) in triangles.iter().enumerate() {
+            if is_degenerate([a, b, c]) {
+                continue;
+            }
+
+            edges.entry((a, b)).or_default().push(i);
+            edges.entry((b, c)).or_default().push(i);
+            edges.entry((c, a)).or_default().push(i);
+        }
+
+        TriangleStripifier {
+            triangles,
+            edges
+        }
+    }
+
+    pub fn stripify(&self) -> StripifyResult {
+        let mut result = StripifyResult::default();
+
+        let mut used = vec![false; self.triangles.len()];
+        // Marks the triangles taken by the strip being tried, without a per-attempt allocation
+        let mut attempt_marks = vec![0usize; self.triangles.len()];
+        let mut attempt = 0;
+
+        for start in 0..self.triangles.len() {
+            if used[start] {
+                continue;
+            }
+
+            let [a, b, c] = self.triangles[start];
+            if is_degenerate([a, b, c]) {
+                used[start] = true;
+                result.triangles.push([a, b, c]);
+                continue;
+            }
+
+            // Try every rotation of the starting triangle and keep the longest strip
+            let mut best: Option<(Vec<u32>, Vec<usize>)> = None;
+            for rotation in [[a, b, c], [b, c, a], [c, a, b]] {
+                attempt += 1;
+                let candidate = self.grow_strip(start, rotation, &used, &mut attempt_marks, attempt);
+
+                if best.as_ref().is_none_or(|(_, best_triangles)| candidate.1.len() > best_triangles.len()) {
+                    best = Some(candidate);
+                }
+            }
+
+            let (strip, strip_triangles) = best.unwrap();
+            for &triangle in strip_triangles.iter() {
+                used[triangle] = true;
+            }
+
+            if strip_triangles.len() >= 2 {
+                result.strips.push(strip);
+            } else {
+                result.triangles.push([a, b, c]);
+            }
+        }
+
+        result
+    }
+
+    fn grow_strip(&self, start: usize, first: [u32; 3], used: &[bool], attempt_marks: &mut [usize], attempt: usize) -> (Vec<u32>, Vec<usize>) {
+        let mut strip = first.to_vec();
+        let mut strip_triangles = vec![start];
+        attempt_marks[start] = attempt;
+
+        loop {
+            let p = strip[strip.len() - 2];
+            let q = strip[strip.len() - 1];
+
+            // The next triangle is odd when the strip already has an odd amount of triangles
+            let edge = if strip_triangles.len().is_multiple_of(2) { (p, q) } else { (q, p) };
+
+            let next = self.edges.get(&edge).and_then(|candidates| {
+                candidates.iter()
+                    .copied()
+                    .find(|&triangle| !used[triangle] && attempt_marks[triangle] != attempt)
+            });
+
+            let Some(next) = next else {
+                break;
+            };
+
+            attempt_marks[next] = attempt;
+            strip_triangles.push(next);
+            strip.push(third_vertex(self.triangles[next], edge));
+        }
+
+        (strip, strip_triangles)
+    }
+}
+
+fn is_degenerate([a, b, c]: [u32; 3]) -> bool {
+    a == b || b == c || a == c
+}
+
+// Vertex that follows the directed edge in the triangle
+fn third_vertex([a, b, c]: [u32; 3], edge: (u32, u32)) -> u32 {
+    if (a, b) == edge {
+        c
+    } else if (b, c) == edge {
+        a
+    } else {
+        b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Triangles of a grid of width x height quads, all wound the same way
+    fn grid(width: u32, height: u32) -> Vec<[u32; 3]> {
+        let vertex = |x: u32, y: u32| y * (width + 1) + x;
+
+        (0..height).flat_map(|y| (0..width).flat_map(move |x| [
+            [vertex(x, y), vertex(x + 1, y), vertex(x + 1, y + 1)],
+            [vertex(x, y), vertex(x + 1, y + 1), vertex(x, y + 1)]
+        ])).collect()
+    }
+
+    // Rotated to start at the smallest index, which keeps the winding
+    fn normalized([a, b, c]: [u32; 3]) -> [u32; 3] {
+        let min = a.min(b).min(c);
+        if min == a { [a, b, c] } else if min == b { [b, c, a] } else { [c, a, b] }
+    }
+
+    fn expand(result: &StripifyResult) -> Vec<[u32; 3]> {
+        let mut triangles: Vec<[u32; 3]> = result.strips.iter()
+            .flat_map(|strip| (0..strip.len() - 2).map(move |i| if i % 2 == 0 {
+                [strip[i], strip[i + 1], strip[i + 2]]
+            } else {
+                [strip[i + 1], strip[i], strip[i + 2]]
+            }))
+            .chain(result.triangles.iter().copied())
+            .map(normalized)
+            .collect();
+
+        triangles.sort();
+        triangles
+    }
+
+    #[test]
+    fn strips_keep_every_triangle_and_its_winding() {
+        let triangles = grid(3, 2);
+        let result = TriangleStripifier::new(triangles.clone()).stripify();
+
+        assert!(!result.strips.is_empty());
+        assert!(result.strips.iter().all(|strip| strip.len() >= 4));
+
+        let mut expected: Vec<[u32; 3]> = triangles.into_iter().map(normalized).collect();
+        expected.sort();
+        assert_eq!(expand(&result), expected);
+    }
+
+    #[test]
+    fn triangles_with_opposite_winding_are_not_joined() {
+        // Both triangles share the edge (1, 2), but the second one is wound the other way
+        let triangles = vec![[0, 1, 2], [1, 2, 3]];
+        let result = TriangleStripifier::new(triangles).stripify();
+
+        assert!(result.strips.is_empty());
+        assert_eq!(result.triangles, vec![[0, 1, 2], [1, 2, 3]]);
+    }
+
+    #[test]
+    fn degenerate_triangles_are_left_alone() {
+        let result = TriangleStripifier::new(vec![[0, 0, 1], [0, 1, 2], [2, 1, 3]]).stripify();
+
+        assert_eq!(result.strips, vec![vec![0, 1, 2, 3]]);
+        assert_eq!(result.triangles, vec![[0, 0, 1]]);
+    }
+}
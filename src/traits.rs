@@ -1,4 +1,4 @@
-use crate::error::AppError;
+use crate::{debug_info::DebugInfo, error::AppError};
 
 pub trait BinarySerializable: Sized {
     fn from_bytes(bytes: &[u8]) -> Result<Self, AppError>;
@@ -6,3 +6,13 @@ pub trait BinarySerializable: Sized {
     fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError>;
     fn size(&self) -> usize;
 }
+
+// Animation stored in a JNT0, PAT0 or SRT0 (see AnimationSubfile), STAMP being the one of its subfile
+pub trait SubfileAnimation: Sized {
+    const STAMP: [u8; 4];
+
+    fn from_bytes(bytes: &[u8], debug_info: DebugInfo) -> Result<Self, AppError>;
+    fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError>;
+    fn rebase(&mut self);
+    fn size(&self) -> usize;
+}
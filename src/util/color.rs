@@ -0,0 +1,116 @@
+// 15-bit color as used by the DS (5 bits per channel)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Rgb555 {
+    pub r: u8, // 5 bits [0, 5)
+    pub g: u8, // 5 bits [5, 10)
+    pub b: u8  // 5 bits [10, 15)
+}
+
+impl Rgb555 {
+    const CHANNEL_MAX: u8 = 0x1F;
+
+    pub fn new(r: u8, g: u8, b: u8) -> Rgb555 {
+        Rgb555 {
+            r: r & Self::CHANNEL_MAX,
+            g: g & Self::CHANNEL_MAX,
+            b: b & Self::CHANNEL_MAX
+        }
+    }
+
+    pub fn from_u16(value: u16) -> Rgb555 {
+        Rgb555 {
+            r: (value & 0x1F) as u8,
+            g: ((value >> 5) & 0x1F) as u8,
+            b: ((value >> 10) & 0x1F) as u8
+        }
+    }
+
+    pub fn to_u16(&self) -> u16 {
+        (self.r as u16 & 0x1F) | ((self.g as u16 & 0x1F) << 5) | ((self.b as u16 & 0x1F) << 10)
+    }
+
+    // Channels are expected in [0, 1]. Values out of range are clamped
+    pub fn from_f32(rgb: [f32; 3]) -> Rgb555 {
+        let quantize = |value: f32| (value.clamp(0.0, 1.0) * Self::CHANNEL_MAX as f32).round() as u8;
+
+        Rgb555 {
+            r: quantize(rgb[0]),
+            g: quantize(rgb[1]),
+            b: quantize(rgb[2])
+        }
+    }
+
+    pub fn to_f32(&self) -> [f32; 3] {
+        let max = Self::CHANNEL_MAX as f32;
+        [self.r as f32 / max, self.g as f32 / max, self.b as f32 / max]
+    }
+
+    // Rounds each 8-bit channel to the nearest 5-bit value
+    pub fn from_rgb8(rgb: [u8; 3]) -> Rgb555 {
+        let quantize = |value: u8| ((value as u16 * Self::CHANNEL_MAX as u16 + 127) / 255) as u8;
+
+        Rgb555 {
+            r: quantize(rgb[0]),
+            g: quantize(rgb[1]),
+            b: quantize(rgb[2])
+        }
+    }
+
+    // Expands each channel by repeating its top bits, as the hardware does (31 becomes 255)
+    pub fn to_rgb8(&self) -> [u8; 3] {
+        let expand = |value: u8| (value << 3) | (value >> 2);
+        [expand(self.r), expand(self.g), expand(self.b)]
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_convert_from_and_to_u16() {
+        let color = Rgb555::from_u16(0x7FFF);
+        assert_eq!(color, Rgb555::new(31, 31, 31));
+
+        let color = Rgb555::from_u16(0b0_10101_00110_00011);
+        assert_eq!(color, Rgb555::new(3, 6, 21));
+        assert_eq!(color.to_u16(), 0b0_10101_00110_00011);
+
+        // Bit 15 is not part of the color
+        assert_eq!(Rgb555::from_u16(0x8000), Rgb555::new(0, 0, 0));
+    }
+
+    #[test]
+    fn can_convert_from_and_to_f32() {
+        assert_eq!(Rgb555::from_f32([1.0, 0.0, 0.5]), Rgb555::new(31, 0, 16));
+        assert_eq!(Rgb555::from_f32([2.0, -1.0, 0.0]), Rgb555::new(31, 0, 0));
+
+        let [r, g, b] = Rgb555::new(31, 0, 31).to_f32();
+        assert_eq!((r, g, b), (1.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn f32_round_trip_is_stable() {
+        for value in 0..=31 {
+            let color = Rgb555::new(value, value, value);
+            assert_eq!(Rgb555::from_f32(color.to_f32()), color);
+        }
+    }
+
+    #[test]
+    fn can_convert_from_and_to_rgb8() {
+        assert_eq!(Rgb555::from_rgb8([255, 0, 128]), Rgb555::new(31, 0, 16));
+        assert_eq!(Rgb555::new(31, 0, 16).to_rgb8(), [255, 0, 132]);
+
+        for value in 0..=31 {
+            let color = Rgb555::new(value, value, value);
+            assert_eq!(Rgb555::from_rgb8(color.to_rgb8()), color);
+        }
+    }
+
+    #[test]
+    fn new_masks_channels() {
+        assert_eq!(Rgb555::new(0xFF, 0x20, 0x1F), Rgb555::new(31, 0, 31));
+    }
+}
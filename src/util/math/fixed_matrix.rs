@@ -0,0 +1,175 @@
+use std::ops::Mul;
+
+use crate::{error::AppError, util::{math::matrix::Matrix, number::fixed_point::fixed_1_19_12::Fixed1_19_12}};
+
+// 4x4 matrix in 20.12 fixed point, the format the DS geometry engine works with. Laid out like Matrix (row-major,
+// column vectors). Values are kept raw so big translations do not lose the precision they would in f32
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FixedMatrix {
+    data: [i32; 16]
+}
+
+impl FixedMatrix {
+    pub fn identity() -> FixedMatrix {
+        let mut data = [0; 16];
+        for i in 0..4 {
+            data[i * 5] = 1 << 12;
+        }
+
+        FixedMatrix { data }
+    }
+
+    pub fn from_raw(data: [i32; 16]) -> FixedMatrix {
+        FixedMatrix { data }
+    }
+
+    // Elements are truncated to 20.12
+    pub fn from_matrix(matrix: &Matrix) -> Result<FixedMatrix, AppError> {
+        if matrix.width() != 4 || matrix.height() != 4 {
+            return Err(AppError::new("Fixed point matrices must be 4x4."));
+        }
+
+        let mut data = [0; 16];
+        for (i, value) in data.iter_mut().enumerate() {
+            *value = Fixed1_19_12::from_f32(matrix.get(i as u32 / 4, i as u32 % 4)?).to_i32();
+        }
+
+        Ok(FixedMatrix { data })
+    }
+
+    pub fn translation(x: Fixed1_19_12, y: Fixed1_19_12, z: Fixed1_19_12) -> FixedMatrix {
+        let mut matrix = Self::identity();
+        matrix.set(0, 3, x);
+        matrix.set(1, 3, y);
+        matrix.set(2, 3, z);
+
+        matrix
+    }
+
+    pub fn scale(x: Fixed1_19_12, y: Fixed1_19_12, z: Fixed1_19_12) -> FixedMatrix {
+        let mut matrix = Self::identity();
+        matrix.set(0, 0, x);
+        matrix.set(1, 1, y);
+        matrix.set(2, 2, z);
+
+        matrix
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> Fixed1_19_12 {
+        Fixed1_19_12::from_i32(self.data[row * 4 + column])
+    }
+
+    pub fn set(&mut self, row: usize, column: usize, value: Fixed1_19_12) {
+        self.data[row * 4 + column] = value.to_i32();
+    }
+
+    pub fn raw(&self) -> &[i32; 16] {
+        &self.data
+    }
+
+    pub fn to_matrix(&self) -> Matrix {
+        Matrix::new(4, 4, self.data.iter().map(|&value| Fixed1_19_12::from_i32(value).to_f32()).collect()).unwrap()
+    }
+
+    // Every element multiplied by a weight in 1/256 units, as skinning does
+    pub fn weighted(&self, weight: u8) -> FixedMatrix {
+        FixedMatrix { data: self.data.map(|value| ((value as i64 * weight as i64) >> 8) as i32) }
+    }
+
+    pub fn wrapping_add(&self, rhs: &FixedMatrix) -> FixedMatrix {
+        let mut data = self.data;
+        for (value, rhs_value) in data.iter_mut().zip(rhs.data) {
+            *value = value.wrapping_add(rhs_value);
+        }
+
+        FixedMatrix { data }
+    }
+
+    // Point with w = 1, products accumulated in 64 bits like in matrix multiplication
+    pub fn transform_point(&self, point: [Fixed1_19_12; 3]) -> [Fixed1_19_12; 3] {
+        let point = [point[0].to_i32() as i64, point[1].to_i32() as i64, point[2].to_i32() as i64, 1 << 12];
+
+        [0, 1, 2].map(|row| {
+            let sum: i64 = (0..4).map(|k| self.data[row * 4 + k] as i64 * point[k]).sum();
+            Fixed1_19_12::from_i32((sum >> 12) as i32)
+        })
+    }
+}
+
+impl Default for FixedMatrix {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+// Multiplication as done by the DS geometry engine: products are accumulated in 64 bits
+// and the sum is shifted back to 12 fractional bits (flooring it)
+impl Mul for FixedMatrix {
+    type Output = FixedMatrix;
+
+    fn mul(self, rhs: FixedMatrix) -> FixedMatrix {
+        let mut data = [0; 16];
+        for (i, value) in data.iter_mut().enumerate() {
+            let (row, column) = (i / 4, i % 4);
+            let sum: i64 = (0..4).map(|k| self.data[row * 4 + k] as i64 * rhs.data[k * 4 + column] as i64).sum();
+            *value = (sum >> 12) as i32;
+        }
+
+        FixedMatrix { data }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed(value: f32) -> Fixed1_19_12 {
+        Fixed1_19_12::from_f32(value)
+    }
+
+    #[test]
+    fn multiplication_truncates_like_hardware() {
+        // 1/3 truncates to 1365/4096, so products lose precision like on hardware
+        let mut lhs = FixedMatrix::identity();
+        lhs.set(0, 0, fixed(1.0 / 3.0));
+        let rhs = FixedMatrix::scale(fixed(3.0), fixed(1.0), fixed(1.0));
+
+        assert_eq!((lhs * rhs).get(0, 0).to_i32(), 1365 * 3);
+    }
+
+    #[test]
+    fn multiplication_floors_the_result() {
+        // (1/4096) * (1/2) = 1/8192, which floors to 0, and its negative floors to -1/4096
+        let mut lhs = FixedMatrix::identity();
+        lhs.set(0, 0, Fixed1_19_12::from_i32(1));
+
+        let positive = FixedMatrix::scale(fixed(0.5), fixed(1.0), fixed(1.0));
+        let negative = FixedMatrix::scale(fixed(-0.5), fixed(1.0), fixed(1.0));
+
+        assert_eq!((lhs * positive).get(0, 0).to_i32(), 0);
+        assert_eq!((lhs * negative).get(0, 0).to_i32(), -1);
+    }
+
+    #[test]
+    fn big_translations_keep_every_fractional_bit() {
+        // Over 4096 an f32 can no longer hold 12 fractional bits
+        let x = Fixed1_19_12::from_i32((100_000 << 12) + 1);
+        let translation = FixedMatrix::translation(x, fixed(0.0), fixed(0.0));
+        let scale = FixedMatrix::scale(fixed(2.0), fixed(2.0), fixed(2.0));
+
+        let point = (translation * scale).transform_point([Fixed1_19_12::from_i32(1), fixed(0.0), fixed(0.0)]);
+        assert_eq!(point[0].to_i32(), (100_000 << 12) + 3);
+    }
+
+    #[test]
+    fn matrix_conversion_truncates() {
+        let mut matrix = Matrix::identity(4);
+        matrix.set(0, 3, 0.1).unwrap();
+        matrix.set(1, 3, -0.1).unwrap();
+
+        let fixed_matrix = FixedMatrix::from_matrix(&matrix).unwrap();
+        assert_eq!(fixed_matrix.get(0, 3).to_i32(), 409);
+        assert_eq!(fixed_matrix.get(1, 3).to_i32(), -409);
+        assert_eq!(fixed_matrix.to_matrix().get(0, 0).unwrap(), 1.0);
+    }
+}
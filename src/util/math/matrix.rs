@@ -2,7 +2,7 @@ use std::{fmt::Debug, ops::Mul};
 
 use crate::error::AppError;
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct Matrix {
     width: u32,
     height: u32,
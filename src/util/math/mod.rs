@@ -1 +1,4 @@
+pub mod fixed_matrix;
 pub mod matrix;
+pub mod pivot_rotation;
+pub mod trs;
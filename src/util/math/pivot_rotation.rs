@@ -0,0 +1,159 @@
+use crate::{error::AppError, util::number::fixed_point::fixed_1_3_12::Fixed1_3_12};
+
+// Pivot-compressed rotation: one element of the matrix is ±1 (its row and column being zero otherwise) and the other
+// four are ±a and ±b. Bone matrices store it in the rp form (info in their flags) and joint animations in their pivot
+// rotation tables
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PivotRotation {
+    info: u16, // Bits 0-3 pivot (form), bit 4 negative one, bit 5 negative c, bit 6 negative d
+    a: Fixed1_3_12,
+    b: Fixed1_3_12
+}
+
+impl PivotRotation {
+    pub const SIZE: usize = 6;
+    pub const INFO_MASK: u16 = 0x7F;
+
+    const FORM_MASK: u16 = 0x0F;
+    const NEG_ONE: u16 = 0x10;
+    const NEG_C: u16 = 0x20;
+    const NEG_D: u16 = 0x40;
+
+    pub fn new(info: u16, a: Fixed1_3_12, b: Fixed1_3_12) -> PivotRotation {
+        PivotRotation {
+            info: info & Self::INFO_MASK,
+            a,
+            b
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<PivotRotation, AppError> {
+        if bytes.len() < Self::SIZE {
+            return Err(AppError::new(&format!("Pivot rotation needs {} bytes", Self::SIZE)));
+        }
+
+        Ok(PivotRotation {
+            info: u16::from_le_bytes([bytes[0], bytes[1]]),
+            a: Fixed1_3_12::from_i16(i16::from_le_bytes([bytes[2], bytes[3]])),
+            b: Fixed1_3_12::from_i16(i16::from_le_bytes([bytes[4], bytes[5]]))
+        })
+    }
+
+    // Row-major rotation matrix with a ±1 element (its row and column being zero otherwise), None for other matrices
+    pub fn from_matrix(matrix: &[f32; 9]) -> Option<PivotRotation> {
+        const TOLERANCE: f32 = 1.0 / 4096.0;
+
+        let pivot = (0..9).max_by(|&i, &j| matrix[i].abs().total_cmp(&matrix[j].abs()))?;
+        let (row, column) = (pivot / 3, pivot % 3);
+        if (matrix[pivot].abs() - 1.0).abs() > TOLERANCE {
+            return None;
+        }
+
+        // The other rows and columns make the 2x2 minor [a c; b d]
+        let rows: Vec<usize> = (0..3).filter(|&r| r != row).collect();
+        let columns: Vec<usize> = (0..3).filter(|&c| c != column).collect();
+        let a = matrix[rows[0] * 3 + columns[0]];
+        let c = matrix[rows[0] * 3 + columns[1]];
+        let b = matrix[rows[1] * 3 + columns[0]];
+        let d = matrix[rows[1] * 3 + columns[1]];
+
+        let form = (column * 3 + row) as u16;
+        let mut info = form;
+        if matrix[pivot] < 0.0 {
+            info |= Self::NEG_ONE;
+        }
+        if (c + b).abs() < (c - b).abs() {
+            info |= Self::NEG_C;
+        }
+        if (d + a).abs() < (d - a).abs() {
+            info |= Self::NEG_D;
+        }
+
+        Some(PivotRotation {
+            info,
+            a: Fixed1_3_12::from_f32(a),
+            b: Fixed1_3_12::from_f32(b)
+        })
+    }
+
+    pub fn write_bytes(&self, buffer: &mut [u8]) -> Result<(), AppError> {
+        if buffer.len() < Self::SIZE {
+            return Err(AppError::new("Buffer is too small to write pivot rotation"));
+        }
+
+        buffer[0..2].copy_from_slice(&self.info.to_le_bytes());
+        buffer[2..4].copy_from_slice(&self.a.to_le_bytes());
+        buffer[4..6].copy_from_slice(&self.b.to_le_bytes());
+
+        Ok(())
+    }
+
+    pub fn info(&self) -> u16 {
+        self.info
+    }
+
+    pub fn a(&self) -> Fixed1_3_12 {
+        self.a
+    }
+
+    pub fn b(&self) -> Fixed1_3_12 {
+        self.b
+    }
+
+    // Row-major 3x3 matrix
+    pub fn to_matrix(&self) -> [f32; 9] {
+        let a = self.a.to_f32();
+        let b = self.b.to_f32();
+        let one = if self.info & Self::NEG_ONE != 0 { -1.0 } else { 1.0 };
+        let c = if self.info & Self::NEG_C != 0 { -b } else { b };
+        let d = if self.info & Self::NEG_D != 0 { -a } else { a };
+
+        match self.info & Self::FORM_MASK {
+            0 => [one, 0.0, 0.0, 0.0, a, c, 0.0, b, d],
+            1 => [0.0, a, c, one, 0.0, 0.0, 0.0, b, d],
+            2 => [0.0, a, c, 0.0, b, d, one, 0.0, 0.0],
+            3 => [0.0, one, 0.0, a, 0.0, c, b, 0.0, d],
+            4 => [a, 0.0, c, 0.0, one, 0.0, b, 0.0, d],
+            5 => [a, 0.0, c, b, 0.0, d, 0.0, one, 0.0],
+            6 => [0.0, 0.0, one, a, c, 0.0, b, d, 0.0],
+            7 => [a, c, 0.0, 0.0, 0.0, one, b, d, 0.0],
+            8 => [a, c, 0.0, b, d, 0.0, 0.0, 0.0, one],
+            _ => [-a, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &[f32], b: &[f32]) {
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).abs() < 1.0 / 1024.0, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn every_form_round_trips() {
+        let (s, c) = (0.6, 0.8);
+
+        for form in 0..9 {
+            for info in [form, form | 0x10, form | 0x20, form | 0x40, form | 0x70] {
+                let rotation = PivotRotation::new(info, Fixed1_3_12::from_f32(c), Fixed1_3_12::from_f32(s));
+                let matrix = rotation.to_matrix();
+
+                let encoded = PivotRotation::from_matrix(&matrix).unwrap();
+                assert_close(&encoded.to_matrix(), &matrix);
+            }
+        }
+    }
+
+    #[test]
+    fn matrices_without_a_unit_element_are_not_pivot() {
+        let c = std::f32::consts::FRAC_1_SQRT_2;
+        let tilted = [c, -c * c, c * c, c, c * c, -c * c, 0.0, c, c];
+
+        assert!(PivotRotation::from_matrix(&tilted).is_none());
+        assert!(PivotRotation::from_matrix(&[0.0, -1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0]).is_some());
+    }
+}
@@ -0,0 +1,199 @@
+use super::matrix::Matrix;
+
+// Decomposed transform of a bone: translation, row-major 3x3 rotation and scale, applied as T * R * S
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Trs {
+    pub translation: [f32; 3],
+    pub rotation: [f32; 9],
+    pub scale: [f32; 3]
+}
+
+impl Trs {
+    pub const IDENTITY_ROTATION: [f32; 9] = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+
+    pub fn identity() -> Trs {
+        Trs {
+            translation: [0.0; 3],
+            rotation: Self::IDENTITY_ROTATION,
+            scale: [1.0; 3]
+        }
+    }
+
+    pub fn to_matrix(&self) -> Matrix {
+        let [tx, ty, tz] = self.translation;
+        let [sx, sy, sz] = self.scale;
+        let r = self.rotation;
+
+        // T * R * S
+        Matrix::new(4, 4, vec![
+            r[0] * sx, r[1] * sy, r[2] * sz, tx,
+            r[3] * sx, r[4] * sy, r[5] * sz, ty,
+            r[6] * sx, r[7] * sy, r[8] * sz, tz,
+            0.0, 0.0, 0.0, 1.0
+        ]).unwrap()
+    }
+
+    // Linear interpolation of translation and scale, spherical of the rotation (see slerp_rotation)
+    pub fn blend(&self, other: &Trs, t: f32) -> Trs {
+        Trs {
+            translation: lerp_array(&self.translation, &other.translation, t),
+            rotation: slerp_rotation(&self.rotation, &other.rotation, t),
+            scale: lerp_array(&self.scale, &other.scale, t)
+        }
+    }
+}
+
+impl Default for Trs {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+pub fn lerp_array<const N: usize>(a: &[f32; N], b: &[f32; N], t: f32) -> [f32; N] {
+    std::array::from_fn(|i| a[i] + (b[i] - a[i]) * t)
+}
+
+// Interpolates two row-major rotation matrices element-wise, then rebuilds an orthonormal basis from the second and
+// third rows (the first one is their cross product, like in the compressed rotations of JNT0)
+pub fn lerp_rotation(a: &[f32; 9], b: &[f32; 9], t: f32) -> [f32; 9] {
+    let m = lerp_array(a, b, t);
+
+    let normalize = |v: [f32; 3]| {
+        let length = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+        if length > f32::EPSILON { [v[0] / length, v[1] / length, v[2] / length] } else { v }
+    };
+    let cross = |a: [f32; 3], b: [f32; 3]| [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0]
+    ];
+
+    let row_2 = normalize([m[6], m[7], m[8]]);
+    let row_0 = normalize(cross([m[3], m[4], m[5]], row_2));
+    let row_1 = cross(row_2, row_0);
+
+    [
+        row_0[0], row_0[1], row_0[2],
+        row_1[0], row_1[1], row_1[2],
+        row_2[0], row_2[1], row_2[2]
+    ]
+}
+
+// Spherical interpolation of two row-major rotation matrices through their quaternions, along the shortest path
+pub fn slerp_rotation(a: &[f32; 9], b: &[f32; 9], t: f32) -> [f32; 9] {
+    let qa = quaternion_from_rotation(a);
+    let mut qb = quaternion_from_rotation(b);
+
+    // q and -q are the same rotation, the one closer to qa takes the short way
+    let mut dot: f32 = (0..4).map(|i| qa[i] * qb[i]).sum();
+    if dot < 0.0 {
+        qb = qb.map(|value| -value);
+        dot = -dot;
+    }
+
+    let (wa, wb) = if dot > 0.9995 {
+        // Almost the same rotation, where slerp is numerically unstable and lerp is just as good
+        (1.0 - t, t)
+    } else {
+        let theta = dot.acos();
+        (((1.0 - t) * theta).sin() / theta.sin(), (t * theta).sin() / theta.sin())
+    };
+
+    rotation_from_quaternion(std::array::from_fn(|i| qa[i] * wa + qb[i] * wb))
+}
+
+// Unit quaternion [w, x, y, z] of a row-major rotation matrix
+fn quaternion_from_rotation(m: &[f32; 9]) -> [f32; 4] {
+    let trace = m[0] + m[4] + m[8];
+
+    let q = if trace > 0.0 {
+        let s = (trace + 1.0).sqrt() * 2.0;
+        [0.25 * s, (m[7] - m[5]) / s, (m[2] - m[6]) / s, (m[3] - m[1]) / s]
+    } else if m[0] > m[4] && m[0] > m[8] {
+        let s = (1.0 + m[0] - m[4] - m[8]).sqrt() * 2.0;
+        [(m[7] - m[5]) / s, 0.25 * s, (m[1] + m[3]) / s, (m[2] + m[6]) / s]
+    } else if m[4] > m[8] {
+        let s = (1.0 + m[4] - m[0] - m[8]).sqrt() * 2.0;
+        [(m[2] - m[6]) / s, (m[1] + m[3]) / s, 0.25 * s, (m[5] + m[7]) / s]
+    } else {
+        let s = (1.0 + m[8] - m[0] - m[4]).sqrt() * 2.0;
+        [(m[3] - m[1]) / s, (m[2] + m[6]) / s, (m[5] + m[7]) / s, 0.25 * s]
+    };
+
+    let length = q.iter().map(|value| value * value).sum::<f32>().sqrt();
+    q.map(|value| value / length)
+}
+
+// Row-major rotation matrix of a quaternion [w, x, y, z], which is normalized first
+fn rotation_from_quaternion(q: [f32; 4]) -> [f32; 9] {
+    let length = q.iter().map(|value| value * value).sum::<f32>().sqrt();
+    let [w, x, y, z] = q.map(|value| value / length);
+
+    [
+        1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - z * w), 2.0 * (x * z + y * w),
+        2.0 * (x * y + z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - x * w),
+        2.0 * (x * z - y * w), 2.0 * (y * z + x * w), 1.0 - 2.0 * (x * x + y * y)
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &[f32], b: &[f32]) {
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).abs() < 1e-5, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn identity_is_identity_matrix() {
+        assert_eq!(Trs::identity().to_matrix().get(0, 0).unwrap(), 1.0);
+        assert_eq!(Trs::identity().to_matrix().get(0, 3).unwrap(), 0.0);
+        assert_eq!(Trs::identity().to_matrix().get(3, 3).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn to_matrix_applies_scale_then_rotation_then_translation() {
+        let trs = Trs {
+            translation: [1.0, 2.0, 3.0],
+            rotation: [0.0, -1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0], // 90 degrees around Z
+            scale: [2.0, 1.0, 1.0]
+        };
+
+        let point = Matrix::new(1, 4, vec![1.0, 0.0, 0.0, 1.0]).unwrap();
+        let result = trs.to_matrix() * point;
+
+        assert_close(&[result.get(0, 0).unwrap(), result.get(1, 0).unwrap(), result.get(2, 0).unwrap()], &[1.0, 4.0, 3.0]);
+    }
+
+    #[test]
+    fn lerp_rotation_stays_orthonormal() {
+        let quarter_turn = [0.0, -1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0];
+        let half = lerp_rotation(&Trs::IDENTITY_ROTATION, &quarter_turn, 0.5);
+        let c = std::f32::consts::FRAC_1_SQRT_2;
+
+        assert_close(&half, &[c, -c, 0.0, c, c, 0.0, 0.0, 0.0, 1.0]);
+        assert_close(&lerp_rotation(&quarter_turn, &quarter_turn, 0.3), &quarter_turn);
+    }
+
+    fn rotation_z(degrees: f32) -> [f32; 9] {
+        let (s, c) = degrees.to_radians().sin_cos();
+        [c, -s, 0.0, s, c, 0.0, 0.0, 0.0, 1.0]
+    }
+
+    #[test]
+    fn slerp_rotation_follows_the_rotation_angle() {
+        // Element-wise, +90 and -90 degrees around Z average to a zero matrix
+        assert_close(&slerp_rotation(&rotation_z(90.0), &rotation_z(-90.0), 0.5), &Trs::IDENTITY_ROTATION);
+        assert_close(&slerp_rotation(&rotation_z(90.0), &rotation_z(-90.0), 0.25), &rotation_z(45.0));
+        assert_close(&slerp_rotation(&Trs::IDENTITY_ROTATION, &rotation_z(120.0), 0.5), &rotation_z(60.0));
+    }
+
+    #[test]
+    fn slerp_rotation_takes_the_shortest_path() {
+        // 170 and -170 degrees are 20 degrees apart through 180, not 340 through 0
+        assert_close(&slerp_rotation(&rotation_z(170.0), &rotation_z(-170.0), 0.5), &rotation_z(180.0));
+        assert_close(&slerp_rotation(&rotation_z(170.0), &rotation_z(-170.0), 0.25), &rotation_z(175.0));
+    }
+}
@@ -1,2 +1,3 @@
 pub mod number;
 pub mod math;
+pub mod color;
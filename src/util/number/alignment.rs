@@ -2,6 +2,10 @@ pub fn get_4_byte_alignment(a: usize) -> usize {
     (a.wrapping_sub(1) & !3).wrapping_add(4)
 }
 
+pub fn get_8_byte_alignment(a: usize) -> usize {
+    (a.wrapping_sub(1) & !7).wrapping_add(8)
+}
+
 pub fn get_16_byte_alignment(a: usize) -> usize {
     (a.wrapping_sub(1) & !15).wrapping_add(16)
 }
@@ -24,6 +28,16 @@ mod tests {
         assert_eq!(get_4_byte_alignment(8), 8);
     }
 
+    #[test]
+    fn test_get_8_byte_alignment() {
+        assert_eq!(get_8_byte_alignment(0), 0);
+        assert_eq!(get_8_byte_alignment(1), 8);
+        assert_eq!(get_8_byte_alignment(4), 8);
+        assert_eq!(get_8_byte_alignment(7), 8);
+        assert_eq!(get_8_byte_alignment(8), 8);
+        assert_eq!(get_8_byte_alignment(9), 16);
+    }
+
     #[test]
     fn test_get_16_byte_alignment() {
         assert_eq!(get_16_byte_alignment(0), 0);